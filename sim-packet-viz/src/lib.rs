@@ -1,5 +1,5 @@
 use std::io::{BufRead, BufReader};
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fmt;
 
 #[macro_use] extern crate failure;
@@ -34,6 +34,7 @@ pub struct EventMatch(Option<Box<LogEvent>>, Option<Box<LogEvent>>);
 pub enum EventMatchSide {
     Tx,
     Rx,
+    Drop,
 }
 
 pub trait LogEvent: std::fmt::Debug {
@@ -43,6 +44,10 @@ pub trait LogEvent: std::fmt::Debug {
     fn event(&self) -> Option<EventMatchSide>;
     fn annotation(&self) -> String;
     fn color(&self) -> EventColor;
+    /// For an rx event, the node the packet arrived from, if the log says.
+    fn from_node(&self) -> Option<usize> {
+        None
+    }
 }
 
 pub struct SlogJSONReader<R: std::io::Read>(R);
@@ -106,6 +111,8 @@ impl LogEvent for JsonLogEvent {
         match self.json_object["msg"].as_str().unwrap() {
             "tx" => Some(EventMatchSide::Tx),
             "rx" => Some(EventMatchSide::Rx),
+            "random drop" | "pre-dropping" => Some(EventMatchSide::Drop),
+            msg if msg.starts_with("dropping") => Some(EventMatchSide::Drop),
             _ => None,
         }
     }
@@ -114,6 +121,10 @@ impl LogEvent for JsonLogEvent {
         format!("{}-{}-{}", self.flow, self.packet_type, self.seq.to_string())
     }
 
+    fn from_node(&self) -> Option<usize> {
+        self.json_object["from"].as_usize()
+    }
+
     fn color(&self) -> EventColor {
         match self.packet_type.as_str() {
             "Data" => EventColor::Black,
@@ -124,11 +135,166 @@ impl LogEvent for JsonLogEvent {
     }
 }
 
+/// A "qlen" log line: the occupancy of the queue at `node` towards `port`.
+#[derive(Debug, PartialEq)]
+pub struct QlenLogEvent {
+    pub time: usize,
+    pub node: usize,
+    pub port: usize,
+    pub occupancy: usize,
+}
+
+impl QlenLogEvent {
+    fn new(json: &json::JsonValue) -> Result<Self, failure::Error> {
+        if json["msg"].as_str() != Some("qlen") {
+            bail!("Not a qlen line")
+        }
+
+        let field = |key: &str| json[key].as_usize().ok_or_else(|| format_err!("Did not match {}", key));
+        Ok(QlenLogEvent{
+            time: field("time")?,
+            node: field("node")?,
+            port: field("port")?,
+            occupancy: field("occupancy")?,
+        })
+    }
+}
+
+/// Queue occupancy over time, as `(time, occupancy)` points, for each `(node, port)`.
+pub type QueueDepthSeries = BTreeMap<(usize, usize), Vec<(usize, usize)>>;
+
+pub fn queue_depth_series(events: impl Iterator<Item=QlenLogEvent>) -> QueueDepthSeries {
+    let mut series = QueueDepthSeries::new();
+    for ev in events {
+        series.entry((ev.node, ev.port)).or_insert_with(Vec::new).push((ev.time, ev.occupancy));
+    }
+
+    series
+}
+
+/// An `IngressPFCSwitch` or `CreditSwitch` "enqueue" or "dequeue" log line: after a packet from
+/// `ingress` entered or left an output queue at `node`, that queue had `headroom` bytes of
+/// headroom and `ingress` had `ingress_occupancy` bytes queued at the switch.
+#[derive(Debug, PartialEq)]
+pub struct OccupancyLogEvent {
+    pub time: usize,
+    pub node: usize,
+    pub ingress: usize,
+    pub headroom: usize,
+    pub ingress_occupancy: usize,
+}
+
+impl OccupancyLogEvent {
+    fn new(json: &json::JsonValue) -> Result<Self, failure::Error> {
+        match json["msg"].as_str() {
+            Some("enqueue") | Some("dequeue") => (),
+            _ => bail!("Not an enqueue or dequeue line"),
+        }
+
+        let field = |key: &str| json[key].as_usize().ok_or_else(|| format_err!("Did not match {}", key));
+        Ok(OccupancyLogEvent{
+            time: field("time")?,
+            node: field("node")?,
+            ingress: field("ingress")?,
+            headroom: field("headroom")?,
+            ingress_occupancy: field("ingress-occupancy")?,
+        })
+    }
+}
+
+/// Bytes queued from each ingress over time, as `(time, occupancy)` points, for each
+/// `(node, ingress)`, to plot with `TikzWriter::dump_queue_depths`.
+pub fn ingress_occupancy_series(events: impl Iterator<Item=OccupancyLogEvent>) -> QueueDepthSeries {
+    let mut series = QueueDepthSeries::new();
+    for ev in events {
+        series.entry((ev.node, ev.ingress)).or_insert_with(Vec::new).push((ev.time, ev.ingress_occupancy));
+    }
+
+    series
+}
+
+/// A "flow completed" log line, logged by both sides of each flow.
+#[derive(Debug, PartialEq)]
+pub struct FlowCompletionLogEvent {
+    pub flow: usize,
+    pub side: String,
+    pub start_time: usize,
+    pub completion_time: usize,
+}
+
+impl FlowCompletionLogEvent {
+    fn new(json: &json::JsonValue) -> Result<Self, failure::Error> {
+        if json["msg"].as_str() != Some("flow completed") {
+            bail!("Not a flow completed line")
+        }
+
+        let field = |key: &str| json[key].as_usize().ok_or_else(|| format_err!("Did not match {}", key));
+        Ok(FlowCompletionLogEvent{
+            flow: field("flow")?,
+            side: json["side"].as_str().ok_or_else(|| format_err!("Did not match side"))?.to_owned(),
+            start_time: field("start_time")?,
+            completion_time: field("completion_time")?,
+        })
+    }
+}
+
 impl<R: std::io::Read> SlogJSONReader<R> {
     pub fn new(r: R) -> Self {
         SlogJSONReader(r)
     }
 
+    /// The "qlen" lines of the log, with times relative to the first one.
+    pub fn get_qlen_events(self) -> impl Iterator<Item=QlenLogEvent> {
+        let f = BufReader::new(self.0);
+        let mut start_time: Option<usize> = None;
+        f.lines()
+            .take_while(|l| l.is_ok())
+            .map(|l| l.unwrap())
+            .filter(|l| !l.trim().is_empty())
+            .filter_map(|line| {
+                let parsed = json::parse(&line).ok()?;
+                QlenLogEvent::new(&parsed).ok()
+            })
+            .map(move |mut ev| {
+                let start = *start_time.get_or_insert(ev.time);
+                ev.time -= start;
+                ev
+            })
+    }
+
+    /// The "enqueue" and "dequeue" lines of the log, with times relative to the first one.
+    pub fn get_occupancy_events(self) -> impl Iterator<Item=OccupancyLogEvent> {
+        let f = BufReader::new(self.0);
+        let mut start_time: Option<usize> = None;
+        f.lines()
+            .take_while(|l| l.is_ok())
+            .map(|l| l.unwrap())
+            .filter(|l| !l.trim().is_empty())
+            .filter_map(|line| {
+                let parsed = json::parse(&line).ok()?;
+                OccupancyLogEvent::new(&parsed).ok()
+            })
+            .map(move |mut ev| {
+                let start = *start_time.get_or_insert(ev.time);
+                ev.time -= start;
+                ev
+            })
+    }
+
+    /// The lines of the log whose `msg` is "flow completed", ignoring the tx, rx, and other
+    /// lines interleaved with them. Times are left as logged.
+    pub fn get_flow_completion_events(self) -> impl Iterator<Item=FlowCompletionLogEvent> {
+        let f = BufReader::new(self.0);
+        f.lines()
+            .take_while(|l| l.is_ok())
+            .map(|l| l.unwrap())
+            .filter(|l| !l.trim().is_empty())
+            .filter_map(|line| {
+                let parsed = json::parse(&line).ok()?;
+                FlowCompletionLogEvent::new(&parsed).ok()
+            })
+    }
+
     pub fn get_events(self) -> impl Iterator<Item=Box<LogEvent + 'static>> {
         let f = BufReader::new(self.0);
         let mut start_time: Option<usize> = None;
@@ -152,6 +318,76 @@ pub trait VizWriter {
     fn dump_events(&mut self, events: impl Iterator<Item=Box<LogEvent>>) -> Result<(), failure::Error>;
 }
 
+/// The horizontal position `node` is drawn at, if it is one of `nodes`' `(node, position)`
+/// pairs.
+fn lookup_position(nodes: &[(usize, usize)], node: usize) -> Option<usize> {
+    nodes
+        .iter()
+        .find(|&(n, _)| *n == node)
+        .map(|&(_, n)| n)
+}
+
+/// Match each rx event with the earliest unmatched tx event with the same annotation from the
+/// node it arrived from (from any node, if the log does not say), calling `edge(tx, rx)` for
+/// each pair. Returns the time of the last event, and the drop events in time order.
+///
+/// A node drops either a packet it logged receiving, or one arriving without an rx line (as
+/// `RandomLossSwitch` drops them), whose tx is matched with the drop as it would be with an rx.
+/// Transmissions still unmatched when the log ends were in flight, and are not drops.
+fn match_edges<F>(events: impl Iterator<Item=Box<LogEvent>>, mut edge: F) -> Result<(usize, Vec<Box<LogEvent>>), failure::Error>
+    where F: FnMut(&Box<LogEvent>, &Box<LogEvent>) -> Result<(), failure::Error>
+{
+    let mut pending_edges: HashMap<String, VecDeque<Box<LogEvent>>> = HashMap::new();
+    // copies of each packet which each node received and has neither sent on nor dropped
+    let mut held: HashMap<(String, usize), usize> = HashMap::new();
+    let mut drops = vec![];
+    let mut end_time = 0;
+    for ev in events {
+        end_time = ev.time();
+        match ev.event() {
+            Some(EventMatchSide::Tx) => {
+                if let Some(n) = held.get_mut(&(ev.annotation(), ev.node())) {
+                    *n = n.saturating_sub(1);
+                }
+
+                let val = pending_edges.entry(ev.annotation()).or_insert_with(|| VecDeque::new());
+                val.push_back(ev);
+            }
+            Some(EventMatchSide::Rx) => {
+                match take_tx(&mut pending_edges, &ev) {
+                    Some(tx) => edge(&tx, &ev)?,
+                    None => bail!("Found unmatched rx: {:?}", ev.annotation()),
+                }
+
+                *held.entry((ev.annotation(), ev.node())).or_insert(0) += 1;
+            }
+            Some(EventMatchSide::Drop) => {
+                let n = held.entry((ev.annotation(), ev.node())).or_insert(0);
+                if *n > 0 {
+                    *n -= 1;
+                } else if let Some(tx) = take_tx(&mut pending_edges, &ev) {
+                    edge(&tx, &ev)?;
+                }
+
+                drops.push(ev);
+            }
+            None => continue,
+        }
+
+    }
+
+    Ok((end_time, drops))
+}
+
+/// Remove the earliest unmatched tx of the packet `ev` is about, from the node it arrived from.
+fn take_tx(pending_edges: &mut HashMap<String, VecDeque<Box<LogEvent>>>, ev: &Box<LogEvent>) -> Option<Box<LogEvent>> {
+    let from = ev.from_node();
+    pending_edges.get_mut(&ev.annotation()).and_then(|txs| {
+        let i = txs.iter().position(|tx| from.map_or(true, |f| tx.node() == f))?;
+        txs.remove(i)
+    })
+}
+
 pub struct TikzWriter<W: std::io::Write> {
     dump: W,
     nodes: Vec<(usize, usize)>,
@@ -167,10 +403,7 @@ impl<W: std::io::Write> TikzWriter<W> {
     }
 
     fn lookup(&self, node: usize) -> Option<usize> {
-        self.nodes
-            .iter()
-            .find(|&(n, _)| *n == node)
-            .map(|&(_, n)| n)
+        lookup_position(&self.nodes, node)
     }
 
     fn prelude(&mut self) -> Result<(), failure::Error> {
@@ -239,35 +472,250 @@ impl<W: std::io::Write> TikzWriter<W> {
     }
 }
 
+impl<W: std::io::Write> TikzWriter<W> {
+    fn dropped_packet(&mut self, drop: &Box<LogEvent>) -> Result<(), failure::Error> {
+        let pos = match self.lookup(drop.node()) {
+            Some(pos) => pos,
+            None => return Ok(()), // skip
+        };
+
+        // an X at the node and time the packet was lost
+        let s = format!(
+            r#"\draw ({0},-{1}) node[red] {{$\times$}} node[right,red] {{{2}}} ;
+            "#,
+            pos,
+            drop.time() as f64 / 1e6, // ms
+            drop.annotation(),
+        );
+        self.dump(&s)
+    }
+
+    /// Plot each port's queue occupancy (in KB) over time (in ms), one line per port.
+    pub fn dump_queue_depths(&mut self, series: &QueueDepthSeries) -> Result<(), failure::Error> {
+        self.prelude()?;
+        for (&(node, port), points) in series {
+            let coords = points.iter()
+                .map(|&(time, occupancy)| format!("({},{})", time as f64 / 1e6, occupancy as f64 / 1e3))
+                .collect::<Vec<String>>()
+                .join(" ");
+            let s = format!(
+                r#"\draw plot coordinates {{{0}}} node[right] {{{1}-{2}}} ;
+                "#,
+                coords,
+                node,
+                port,
+            );
+            self.dump(&s)?;
+        }
+
+        let s = r#"
+        \end{tikzpicture}
+        \end{document}
+        "#;
+        self.dump(s)
+    }
+}
+
 impl<W: std::io::Write> VizWriter for TikzWriter<W> {
     fn dump_events(&mut self, events: impl Iterator<Item=Box<LogEvent>>) -> Result<(), failure::Error> {
         self.prelude()?;
+        let (end_time, drops) = match_edges(events, |tx, rx| self.single_edge(tx, rx))?;
+        for drop in drops {
+            self.dropped_packet(&drop)?;
+        }
+
+        self.postlude(end_time)
+    }
+}
+
+/// Pixels per TikZ unit: node positions are in these units, and so is each millisecond.
+const SVG_SCALE: f64 = 20.0;
+/// Room above the timelines for the node labels, in pixels.
+const SVG_MARGIN: f64 = 20.0;
+
+/// Draws the same picture as `TikzWriter` as an SVG image, which a browser can show without a
+/// TeX install.
+pub struct SvgWriter<W: std::io::Write> {
+    dump: W,
+    nodes: Vec<(usize, usize)>,
+    body: String, // the edges drawn so far; the image size is only known at the end
+}
+
+impl<W: std::io::Write> SvgWriter<W> {
+    pub fn new(w: W, nodes: &[(usize, usize)]) -> Self {
+        SvgWriter { dump: w, nodes: nodes.to_vec(), body: String::new() }
+    }
+
+    fn x(position: usize) -> f64 {
+        SVG_MARGIN + position as f64 * SVG_SCALE
+    }
+
+    fn y(time: usize) -> f64 {
+        SVG_MARGIN + time as f64 / 1e6 * SVG_SCALE
+    }
+
+    fn single_edge(&mut self, tx_edge: &Box<LogEvent>, rx_edge: &Box<LogEvent>) -> Result<(), failure::Error> {
+        let (tx_pos, rx_pos) = match (lookup_position(&self.nodes, tx_edge.node()), lookup_position(&self.nodes, rx_edge.node())) {
+            (Some(tx_pos), Some(rx_pos)) => (tx_pos, rx_pos),
+            _ => return Ok(()), // skip
+        };
+
+        let (x1, y1) = (Self::x(tx_pos), Self::y(tx_edge.time()));
+        let (x2, y2) = (Self::x(rx_pos), Self::y(rx_edge.time()));
+        self.body.push_str(&format!(
+            r#"<line x1="{0}" y1="{1}" x2="{2}" y2="{3}" stroke="{4}" />
+<text x="{5}" y="{6}" fill="{4}" font-size="8">{7}</text>
+"#,
+            x1, y1, x2, y2,
+            tx_edge.color(),
+            (x1 + x2) / 2.0,
+            (y1 + y2) / 2.0,
+            tx_edge.annotation(),
+        ));
+        Ok(())
+    }
+
+    fn dropped_packet(&mut self, drop: &Box<LogEvent>) -> Result<(), failure::Error> {
+        let pos = match lookup_position(&self.nodes, drop.node()) {
+            Some(pos) => pos,
+            None => return Ok(()), // skip
+        };
+
+        // a path rather than a <line>, which is for delivered packets
+        let (x, y) = (Self::x(pos), Self::y(drop.time()));
+        let r = 4.0;
+        self.body.push_str(&format!(
+            r#"<path d="M {0} {1} L {2} {3} M {0} {3} L {2} {1}" stroke="red" stroke-width="2" />
+<text x="{4}" y="{5}" fill="red" font-size="8">{6}</text>
+"#,
+            x - r, y - r, x + r, y + r,
+            x + 2.0 * r,
+            y,
+            drop.annotation(),
+        ));
+        Ok(())
+    }
+
+    fn dump_image(&mut self, end_time: usize) -> Result<(), failure::Error> {
+        let width = self.nodes.iter().map(|&(_, pos)| Self::x(pos)).fold(0.0, f64::max) + SVG_MARGIN;
+        let height = Self::y(end_time) + SVG_MARGIN;
+        writeln!(self.dump, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}">"#, width, height)?;
+        for &(node, pos) in &self.nodes {
+            // timelines are paths, so that every <line> is an edge
+            writeln!(
+                self.dump,
+                r#"<path d="M {0} {1} V {2}" stroke="black" stroke-width="3" />
+<text x="{0}" y="{3}" text-anchor="middle">{4}</text>"#,
+                Self::x(pos),
+                SVG_MARGIN,
+                height - SVG_MARGIN,
+                SVG_MARGIN / 2.0,
+                node,
+            )?;
+        }
+
+        self.dump.write_all(self.body.as_bytes())?;
+        writeln!(self.dump, "</svg>")?;
+        Ok(())
+    }
+}
+
+impl<W: std::io::Write> VizWriter for SvgWriter<W> {
+    fn dump_events(&mut self, events: impl Iterator<Item=Box<LogEvent>>) -> Result<(), failure::Error> {
+        let (end_time, drops) = match_edges(events, |tx, rx| self.single_edge(tx, rx))?;
+        for drop in drops {
+            self.dropped_packet(&drop)?;
+        }
+
+        self.dump_image(end_time)
+    }
+}
+
+/// Writes events in Chrome's trace-event JSON format, for `chrome://tracing`.
+///
+/// Each node is a thread (`tid`) of a single process. A packet's transmission is a duration
+/// event (`"ph": "X"`) on the sending node lasting until the next hop receives it, and its
+/// reception is an instant event (`"ph": "i"`) on the receiving node.
+/// Timestamps are in microseconds.
+pub struct ChromeTraceWriter<W: std::io::Write> {
+    dump: W,
+}
+
+impl<W: std::io::Write> ChromeTraceWriter<W> {
+    pub fn new(w: W) -> Self {
+        ChromeTraceWriter { dump: w }
+    }
+
+    fn trace_event(ev: &Box<LogEvent>, phase: &str) -> json::JsonValue {
+        let mut obj = json::JsonValue::new_object();
+        obj["name"] = ev.annotation().into();
+        obj["ph"] = phase.into();
+        obj["ts"] = (ev.time() as f64 / 1e3).into();
+        obj["pid"] = 0.into();
+        obj["tid"] = ev.node().into();
+        obj
+    }
+}
+
+impl<W: std::io::Write> VizWriter for ChromeTraceWriter<W> {
+    fn dump_events(&mut self, events: impl Iterator<Item=Box<LogEvent>>) -> Result<(), failure::Error> {
         use std::collections::HashMap;
         let mut pending_edges: HashMap<String, VecDeque<Box<LogEvent>>> = HashMap::new();
-        let mut end_time = 0;
+        let mut trace_events = json::JsonValue::new_array();
         for ev in events {
-            end_time = ev.time();
             match ev.event() {
                 Some(EventMatchSide::Tx) => {
                     let val = pending_edges.entry(ev.annotation()).or_insert_with(|| VecDeque::new());
                     val.push_back(ev);
                 }
                 Some(EventMatchSide::Rx) => {
-                    if let Some(tx) = pending_edges.get_mut(&ev.annotation()) {
-                        match tx.pop_front() {
-                            Some(tx) => self.single_edge(&tx, &ev)?,
-                            None => bail!("Found unmatched tx: {:?}", ev.annotation()),
-                        }
-                    } else {
-                        bail!("Found unmatched rx: {:?}", ev.annotation());
-                    }
+                    let tx = pending_edges.get_mut(&ev.annotation())
+                        .and_then(|tx| tx.pop_front())
+                        .ok_or_else(|| format_err!("Found unmatched rx: {:?}", ev.annotation()))?;
+                    let mut span = Self::trace_event(&tx, "X");
+                    span["dur"] = ((ev.time() - tx.time()) as f64 / 1e3).into();
+                    trace_events.push(span)?;
+
+                    let mut arrival = Self::trace_event(&ev, "i");
+                    arrival["s"] = "t".into();
+                    trace_events.push(arrival)?;
                 }
                 _ => continue,
             }
-            
         }
 
-        self.postlude(end_time)
+        // packets still in flight (or dropped) when the log ends were never received
+        for tx in pending_edges.values().flat_map(|txs| txs.iter()) {
+            let mut departure = Self::trace_event(tx, "i");
+            departure["s"] = "t".into();
+            trace_events.push(departure)?;
+        }
+
+        let mut trace = json::JsonValue::new_object();
+        trace["traceEvents"] = trace_events;
+        self.dump.write_all(trace.dump().as_bytes()).map_err(failure::Error::from)
+    }
+}
+
+/// Writes flow completion times as CSV, one row per "flow completed" line, for analysis
+/// outside of LaTeX.
+pub struct CsvWriter<W: std::io::Write> {
+    dump: W,
+}
+
+impl<W: std::io::Write> CsvWriter<W> {
+    pub fn new(w: W) -> Self {
+        CsvWriter { dump: w }
+    }
+
+    /// Write a `flow,side,start_time_ns,completion_time_ns` header and then a row per event.
+    pub fn dump_flow_completions(&mut self, events: impl Iterator<Item=FlowCompletionLogEvent>) -> Result<(), failure::Error> {
+        writeln!(self.dump, "flow,side,start_time_ns,completion_time_ns")?;
+        for ev in events {
+            writeln!(self.dump, "{},{},{},{}", ev.flow, ev.side, ev.start_time, ev.completion_time)?;
+        }
+
+        Ok(())
     }
 }
 
@@ -303,10 +751,59 @@ pub fn plot_log(slug: &str, wanted_flow: usize) -> Result<(), failure::Error> {
     compile_viz(outfilen.as_str())
 }
 
+/// Write the packet events of `{slug}.tr` to `{slug}.json`, to open in `chrome://tracing`.
+pub fn trace_log(slug: &str) -> Result<(), failure::Error> {
+    use std::fs::File;
+    let logfile = File::open(format!("{}.tr", slug))?;
+    let outfile = File::create(format!("{}.json", slug))?;
+    let reader = SlogJSONReader::new(std::io::BufReader::new(logfile));
+    let mut writer = ChromeTraceWriter::new(outfile);
+    writer.dump_events(reader.get_events())
+}
+
+pub fn plot_qlen(slug: &str) -> Result<(), failure::Error> {
+    use std::fs::File;
+    let logfile = format!("{}.tr", slug);
+    let outfilen = format!("{}-qlen.tex", slug);
+    let logfile = File::open(logfile)?;
+    let outfile = File::create(outfilen.as_str())?;
+    let reader = SlogJSONReader::new(std::io::BufReader::new(logfile));
+    let series = queue_depth_series(reader.get_qlen_events());
+    let mut writer = TikzWriter::new(outfile, &[]);
+    writer.dump_queue_depths(&series)?;
+
+    compile_viz(outfilen.as_str())
+}
+
+/// Plot the bytes queued from each ingress of each `IngressPFCSwitch` in `{slug}.tr`, to see
+/// when PFC PAUSEs and RESUMEs them.
+pub fn plot_ingress_occupancy(slug: &str) -> Result<(), failure::Error> {
+    use std::fs::File;
+    let logfile = File::open(format!("{}.tr", slug))?;
+    let outfilen = format!("{}-ingress.tex", slug);
+    let outfile = File::create(outfilen.as_str())?;
+    let reader = SlogJSONReader::new(std::io::BufReader::new(logfile));
+    let series = ingress_occupancy_series(reader.get_occupancy_events());
+    let mut writer = TikzWriter::new(outfile, &[]);
+    writer.dump_queue_depths(&series)?;
+
+    compile_viz(outfilen.as_str())
+}
+
+/// Write the flow completion times in `{slug}.tr` to `{slug}-fct.csv`.
+pub fn fct_csv(slug: &str) -> Result<(), failure::Error> {
+    use std::fs::File;
+    let logfile = File::open(format!("{}.tr", slug))?;
+    let outfile = File::create(format!("{}-fct.csv", slug))?;
+    let reader = SlogJSONReader::new(std::io::BufReader::new(logfile));
+    let mut writer = CsvWriter::new(outfile);
+    writer.dump_flow_completions(reader.get_flow_completion_events())
+}
+
 #[cfg(test)]
 mod tests {
     use std;
-    use super::{SlogJSONReader, EventMatchSide, LogEvent, VizWriter, TikzWriter};
+    use super::{SlogJSONReader, EventMatchSide, LogEvent, VizWriter, TikzWriter, SvgWriter, ChromeTraceWriter, CsvWriter, QlenLogEvent, OccupancyLogEvent, queue_depth_series, ingress_occupancy_series};
     
     #[test]
     fn slog_json_parse() {
@@ -361,4 +858,199 @@ mod tests {
         let res = buf.into_inner().into_iter().take_while(|&b| b != 0).collect::<Vec<u8>>();
         let _output = std::str::from_utf8(&res).unwrap();
     }
+
+    #[test]
+    fn slog_json_svg() {
+        let log_sample = r#"
+        {"msg":"tx","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.844790100-07:00","hostname":"Y4089549","pid":6323,"packet":"Ack { hdr: PacketHeader { flow: 0, from: 1, to: 0 }, cumulative_acked_seq: 32120 }","node":1,"time":1191200000}
+        {"msg":"rx","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.845250600-07:00","hostname":"Y4089549","pid":6323,"packet":"Ack { hdr: PacketHeader { flow: 0, from: 1, to: 0 }, cumulative_acked_seq: 32120 }","node":4,"time":1192520000}
+        {"msg":"tx","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.845318700-07:00","hostname":"Y4089549","pid":6323,"packet":"Ack { hdr: PacketHeader { flow: 0, from: 1, to: 0 }, cumulative_acked_seq: 32120 }","node":4,"time":1192520000}
+        {"msg":"rx","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.845845800-07:00","hostname":"Y4089549","pid":6323,"packet":"Ack { hdr: PacketHeader { flow: 0, from: 1, to: 0 }, cumulative_acked_seq: 32120 }","node":0,"time":1193840000}
+        "#;
+        let reader = std::io::BufReader::new(log_sample.as_bytes());
+        let reader = SlogJSONReader(reader);
+
+        let mut buf = vec![];
+        {
+        let mut writer = SvgWriter::new(&mut buf, &[(0, 0), (4, 5), (1, 10)]);
+        writer.dump_events(reader.get_events()).unwrap();
+        }
+
+        let output = String::from_utf8(buf).unwrap();
+        assert!(output.starts_with("<svg"), "{}", output);
+        assert!(output.trim().ends_with("</svg>"), "{}", output);
+        // 1 -> 4 and 4 -> 0
+        assert_eq!(output.matches("<line").count(), 2, "{}", output);
+        assert!(output.contains(r#"stroke="green""#), "{}", output);
+        assert!(output.contains(">0-Ack-32120</text>"), "{}", output);
+    }
+
+    #[test]
+    fn match_edges_by_previous_hop() {
+        // a three node path 0 -> 4 -> 1, where host 0 retransmits seq 1460 before the switch
+        // has forwarded the original
+        let log_sample = r#"
+        {"msg":"tx","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.844790100-07:00","hostname":"Y4089549","pid":6323,"packet":"Data { hdr: PacketHeader { flow: 0, from: 0, to: 1 }, seq: 1460, length: 1460 }","node":0,"time":1000000000}
+        {"msg":"rx","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.845250600-07:00","hostname":"Y4089549","pid":6323,"packet":"Data { hdr: PacketHeader { flow: 0, from: 0, to: 1 }, seq: 1460, length: 1460 }","node":4,"from":0,"time":1013000000}
+        {"msg":"tx","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.845318700-07:00","hostname":"Y4089549","pid":6323,"packet":"Data { hdr: PacketHeader { flow: 0, from: 0, to: 1 }, seq: 1460, length: 1460 }","node":4,"time":1020000000}
+        {"msg":"tx","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.845318700-07:00","hostname":"Y4089549","pid":6323,"packet":"Data { hdr: PacketHeader { flow: 0, from: 0, to: 1 }, seq: 1460, length: 1460 }","node":0,"time":1025000000}
+        {"msg":"rx","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.845845800-07:00","hostname":"Y4089549","pid":6323,"packet":"Data { hdr: PacketHeader { flow: 0, from: 0, to: 1 }, seq: 1460, length: 1460 }","node":1,"from":4,"time":1033000000}
+        "#;
+        let reader = SlogJSONReader(std::io::BufReader::new(log_sample.as_bytes()));
+
+        let mut buf = vec![];
+        {
+        let mut writer = TikzWriter::new(&mut buf, &[(0, 0), (4, 5), (1, 10)]);
+        writer.dump_events(reader.get_events()).unwrap();
+        }
+
+        // the original's two hops; the retransmission is still on its way to the switch
+        let output = String::from_utf8(buf).unwrap();
+        assert!(output.contains(r"\draw[black] (0,-0) -> (5,-13)"), "{}", output);
+        assert!(output.contains(r"\draw[black] (5,-20) -> (10,-33)"), "{}", output);
+        assert_eq!(output.matches(r"$\times$").count(), 0, "{}", output);
+    }
+
+    #[test]
+    fn drop_lines_are_drops() {
+        // the switch drops the first ACK it received, then loses the second as it arrives (with no
+        // rx line, as `RandomLossSwitch` does). the retransmission of the second gets through.
+        let log_sample = r#"
+        {"msg":"tx","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.844790100-07:00","hostname":"Y4089549","pid":6323,"packet":"Ack { hdr: PacketHeader { flow: 0, from: 1, to: 0 }, cumulative_acked_seq: 32120 }","node":1,"time":1191200000}
+        {"msg":"rx","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.845250600-07:00","hostname":"Y4089549","pid":6323,"packet":"Ack { hdr: PacketHeader { flow: 0, from: 1, to: 0 }, cumulative_acked_seq: 32120 }","node":4,"from":1,"time":1192520000}
+        {"msg":"dropping","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.845250600-07:00","hostname":"Y4089549","pid":6323,"packet":"Ack { hdr: PacketHeader { flow: 0, from: 1, to: 0 }, cumulative_acked_seq: 32120 }","node":4,"from":1,"time":1192520000}
+        {"msg":"tx","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.845318700-07:00","hostname":"Y4089549","pid":6323,"packet":"Ack { hdr: PacketHeader { flow: 0, from: 1, to: 0 }, cumulative_acked_seq: 33580 }","node":1,"time":1193200000}
+        {"msg":"tx","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.845318700-07:00","hostname":"Y4089549","pid":6323,"packet":"Ack { hdr: PacketHeader { flow: 0, from: 1, to: 0 }, cumulative_acked_seq: 33580 }","node":1,"time":1194200000}
+        {"msg":"random drop","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.845318700-07:00","hostname":"Y4089549","pid":6323,"packet":"Ack { hdr: PacketHeader { flow: 0, from: 1, to: 0 }, cumulative_acked_seq: 33580 }","node":4,"from":1,"time":1194520000}
+        {"msg":"rx","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.845318700-07:00","hostname":"Y4089549","pid":6323,"packet":"Ack { hdr: PacketHeader { flow: 0, from: 1, to: 0 }, cumulative_acked_seq: 33580 }","node":4,"from":1,"time":1195520000}
+        "#;
+
+        let mut buf = vec![];
+        {
+        let reader = SlogJSONReader(std::io::BufReader::new(log_sample.as_bytes()));
+        let mut writer = TikzWriter::new(&mut buf, &[(0, 0), (4, 5), (1, 10)]);
+        writer.dump_events(reader.get_events()).unwrap();
+        }
+
+        // each hop is drawn in order, and each drop is an X at the switch
+        let output = String::from_utf8(buf).unwrap();
+        assert!(output.contains(r"\draw[green] (10,-0) -> (5,-1.32)"), "{}", output);
+        assert!(output.contains(r"\draw[green] (10,-2) -> (5,-3.32)"), "{}", output);
+        assert!(output.contains(r"\draw[green] (10,-3) -> (5,-4.32)"), "{}", output);
+        assert_eq!(output.matches(r"$\times$").count(), 2, "{}", output);
+        assert!(output.contains(r"\draw (5,-1.32) node[red] {$\times$} node[right,red] {0-Ack-32120}"), "{}", output);
+        assert!(output.contains(r"\draw (5,-3.32) node[red] {$\times$} node[right,red] {0-Ack-33580}"), "{}", output);
+
+        let mut buf = vec![];
+        {
+        let reader = SlogJSONReader(std::io::BufReader::new(log_sample.as_bytes()));
+        let mut writer = SvgWriter::new(&mut buf, &[(0, 0), (4, 5), (1, 10)]);
+        writer.dump_events(reader.get_events()).unwrap();
+        }
+
+        let output = String::from_utf8(buf).unwrap();
+        assert_eq!(output.matches("<line").count(), 3, "{}", output);
+        assert_eq!(output.matches(r#"stroke="red" stroke-width="2""#).count(), 2, "{}", output);
+    }
+
+    #[test]
+    fn qlen_parse() {
+        let log_sample = r#"
+        {"msg":"qlen","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.848190-07:00","hostname":"Y4089549","pid":6323,"occupancy":3000,"port":0,"node":4,"time":1202560000}
+        {"msg":"rx","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.848190-07:00","hostname":"Y4089549","pid":6323,"packet":"Data { hdr: PacketHeader { flow: 0, from: 0, to: 1 }, seq: 37960, length: 1460 }","node":1,"time":1202560000}
+        {"msg":"qlen","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.848190-07:00","hostname":"Y4089549","pid":6323,"occupancy":1500,"port":0,"node":4,"time":1214560000}
+        {"msg":"qlen","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.848190-07:00","hostname":"Y4089549","pid":6323,"occupancy":0,"port":1,"node":4,"time":1215000000}
+        "#;
+        let reader = std::io::BufReader::new(log_sample.as_bytes());
+        let reader = SlogJSONReader(reader);
+        let evs: Vec<QlenLogEvent> = reader.get_qlen_events().collect();
+        assert_eq!(evs.len(), 3);
+        assert_eq!(evs[0], QlenLogEvent{time: 0, node: 4, port: 0, occupancy: 3000});
+
+        let series = queue_depth_series(evs.into_iter());
+        assert_eq!(series.len(), 2);
+        assert_eq!(series[&(4, 0)], vec![(0, 3000), (12000000, 1500)]);
+        assert_eq!(series[&(4, 1)], vec![(12440000, 0)]);
+
+        use std::io::Cursor;
+        let mut buf = Cursor::new(vec![]);
+        {
+        let mut writer = TikzWriter::new(&mut buf, &[]);
+        writer.dump_queue_depths(&series).unwrap();
+        }
+
+        let output = String::from_utf8(buf.into_inner()).unwrap();
+        assert!(output.contains(r"\draw plot coordinates {(0,3) (12,1.5)} node[right] {4-0} ;"), "{}", output);
+    }
+
+    #[test]
+    fn flow_completion_csv() {
+        let log_sample = r#"
+        {"msg":"rx","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.848190-07:00","hostname":"Y4089549","pid":6323,"packet":"Data { hdr: PacketHeader { flow: 0, from: 0, to: 1 }, seq: 37960, length: 1460 }","node":1,"time":1202560000}
+        {"msg":"flow completed","v":0,"name":"slog-rs","level":30,"time":"2018-07-27T09:37:56.849518700-07:00","hostname":"Y4089549","pid":6323,"end_time":1207280000,"start_time":1126000000,"completion_time":81280000,"side":"Receiver","node":1,"flow":0}
+        {"msg":"tx","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.844790100-07:00","hostname":"Y4089549","pid":6323,"packet":"Ack { hdr: PacketHeader { flow: 0, from: 1, to: 0 }, cumulative_acked_seq: 43800 }","node":1,"time":1207280000}
+        "#;
+        let reader = std::io::BufReader::new(log_sample.as_bytes());
+        let reader = SlogJSONReader(reader);
+
+        let mut buf = vec![];
+        {
+        let mut writer = CsvWriter::new(&mut buf);
+        writer.dump_flow_completions(reader.get_flow_completion_events()).unwrap();
+        }
+
+        let output = String::from_utf8(buf).unwrap();
+        let lines = output.lines().collect::<Vec<&str>>();
+        assert_eq!(lines, vec!["flow,side,start_time_ns,completion_time_ns", "0,Receiver,1126000000,81280000"]);
+    }
+
+    #[test]
+    fn occupancy_parse() {
+        let log_sample = r#"
+        {"msg":"enqueue","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.848190-07:00","hostname":"Y4089549","pid":6323,"out_link":"Link { propagation_delay: 1000000, bandwidth_bps: 1000000, from: 4, to: 0 }","in_link":"Link { propagation_delay: 1000000, bandwidth_bps: 1000000, from: 2, to: 4 }","ingress-occupancy":3000,"headroom":12000,"ingress":2,"node":4,"time":1202560000}
+        {"msg":"rx","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.848190-07:00","hostname":"Y4089549","pid":6323,"packet":"Data { hdr: PacketHeader { flow: 0, from: 0, to: 1 }, seq: 37960, length: 1460 }","node":1,"time":1202560000}
+        {"msg":"dequeue","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.848190-07:00","hostname":"Y4089549","pid":6323,"resume-thresh":4000,"resume-head":3000,"resume":"None","ingress-occupancy":1500,"headroom":13500,"ingress":2,"node":4,"time":1214560000}
+        "#;
+        let reader = SlogJSONReader(std::io::BufReader::new(log_sample.as_bytes()));
+        let evs: Vec<OccupancyLogEvent> = reader.get_occupancy_events().collect();
+        assert_eq!(evs.len(), 2);
+        assert_eq!(evs[0], OccupancyLogEvent{time: 0, node: 4, ingress: 2, headroom: 12000, ingress_occupancy: 3000});
+        assert_eq!(evs[1].ingress_occupancy, 1500);
+
+        let series = ingress_occupancy_series(evs.into_iter());
+        assert_eq!(series[&(4, 2)], vec![(0, 3000), (12000000, 1500)]);
+    }
+
+    #[test]
+    fn slog_json_chrome_trace() {
+        let log_sample = r#"
+        {"msg":"tx","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.844790100-07:00","hostname":"Y4089549","pid":6323,"packet":"Ack { hdr: PacketHeader { flow: 0, from: 1, to: 0 }, cumulative_acked_seq: 32120 }","node":1,"time":1191200000}
+        {"msg":"rx","v":0,"name":"slog-rs","level":20,"time":"2018-07-27T09:37:56.845250600-07:00","hostname":"Y4089549","pid":6323,"packet":"Ack { hdr: PacketHeader { flow: 0, from: 1, to: 0 }, cumulative_acked_seq: 32120 }","node":4,"time":1192520000}
+        "#;
+        let reader = std::io::BufReader::new(log_sample.as_bytes());
+        let reader = SlogJSONReader(reader);
+
+        let mut buf = vec![];
+        {
+        let mut writer = ChromeTraceWriter::new(&mut buf);
+        writer.dump_events(reader.get_events()).unwrap();
+        }
+
+        let trace = ::json::parse(std::str::from_utf8(&buf).unwrap()).unwrap();
+        let evs = &trace["traceEvents"];
+        assert_eq!(evs.len(), 2);
+
+        // the transmission lasts until the next hop receives the ACK 1.32ms later
+        assert_eq!(evs[0]["name"].as_str(), Some("0-Ack-32120"));
+        assert_eq!(evs[0]["ph"].as_str(), Some("X"));
+        assert_eq!(evs[0]["ts"].as_f64(), Some(0.0));
+        assert_eq!(evs[0]["dur"].as_f64(), Some(1320.0));
+        assert_eq!(evs[0]["pid"].as_usize(), Some(0));
+        assert_eq!(evs[0]["tid"].as_usize(), Some(1));
+
+        assert_eq!(evs[1]["ph"].as_str(), Some("i"));
+        assert_eq!(evs[1]["ts"].as_f64(), Some(1320.0));
+        assert_eq!(evs[1]["pid"].as_usize(), Some(0));
+        assert_eq!(evs[1]["tid"].as_usize(), Some(4));
+    }
 }
@@ -1,8 +1,9 @@
 use std::fs::File;
 
 extern crate rdma_sim;
-use rdma_sim::node::switch::{Switch, nack_switch::NackSwitch, pfc_switch::{PFCSwitch, IngressPFCSwitch}, lossy_switch::LossySwitch};
+use rdma_sim::node::switch::{Switch, nack_switch::NackSwitch, pfc_switch::{PFCSwitch, IngressPFCSwitch}, credit_switch::CreditSwitch, lossy_switch::LossySwitch};
 use rdma_sim::flow::FlowSide;
+use rdma_sim::stats::fct_summary;
 use rdma_sim::{Scenario, SharedIngressVictimFlowScenario, IndependentVictimFlowScenario};
 
 extern crate viz;
@@ -44,7 +45,7 @@ fn do_args() -> (String, String) {
             .long("switch-type")
             .short("t")
             .takes_value(true)
-            .possible_values(&["pfc", "ingresspfc", "nacks", "lossy"])
+            .possible_values(&["pfc", "ingresspfc", "credit", "nacks", "lossy"])
             .required(true))
         .arg(Arg::with_name("scenario")
             .help("Name of the scenario to run")
@@ -93,12 +94,24 @@ fn run_scenario_switch<C: Scenario, S: Switch>(logger: slog::Logger) {
             "fct" => f.completion_time().unwrap(),
         );
     }
+
+    let summary = fct_summary(e.components().1);
+    info!(logger, "fct summary";
+        "completed" => summary.completed,
+        "incomplete" => summary.incomplete,
+        "min" => summary.min,
+        "median" => summary.median,
+        "p99" => summary.p99,
+        "max" => summary.max,
+        "mean" => summary.mean,
+    );
 }
 
 fn run_scenario<C: Scenario>(switch: &str, logger: slog::Logger) {
     match switch {
         "pfc" => run_scenario_switch::<C, PFCSwitch>(logger),
         "ingresspfc" => run_scenario_switch::<C, IngressPFCSwitch>(logger),
+        "credit" => run_scenario_switch::<C, CreditSwitch>(logger),
         "nacks" => run_scenario_switch::<C, NackSwitch>(logger),
         "lossy" => run_scenario_switch::<C, LossySwitch>(logger),
         _ => unreachable!(),
@@ -121,4 +134,5 @@ fn main() {
     }
 
     viz::plot_log(slug.as_str(), 0).unwrap();
+    viz::plot_qlen(slug.as_str()).unwrap();
 }
@@ -0,0 +1,262 @@
+//! Saving a running simulation to resume later (see `Executor::checkpoint`).
+//!
+//! Events, flows, queues, and loss models are trait objects, so each is saved as a variant of an
+//! enum of the concrete types this crate provides, e.g. `SavedEvent`. Other implementations
+//! cannot be saved: checkpointing a simulation holding one fails.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error as DeError;
+use serde::ser::Error as SerError;
+
+use congcontrol::{Aimd, ConstCwnd, Dcqcn, Dctcp, NewReno, Timely, Vegas};
+use event::{Event, EventTime};
+use flow::{Flow, FlowArrivalEvent, FlowTimerEvent, WarmStartFlowArrivalEvent};
+use flow::constant_bit_rate::{ConstantBitRateFlowArrivalEvent, ConstantBitRateSender, ConstantBitRateReceiver};
+use flow::go_back_n::{GoBackNSender, GoBackNReceiver};
+use flow::receiver_driven::{ReceiverDrivenFlowArrivalEvent, GrantSender, GrantReceiver};
+use flow::rpc::{RpcFlowArrivalEvent, RpcClient, RpcServer};
+use flow::selective_repeat::{SelectiveRepeatFlowArrivalEvent, SelectiveRepeatSender, SelectiveRepeatReceiver};
+use loss::{AnyLoss, FixedRateLoss, GilbertElliott, LossModel, NoLoss, QueueOverflow};
+use node::{HostWakeEvent, LinkFreeEvent, LinkTransmitEvent, NodeTransmitEvent, PacketDropEvent, ProcessingDoneEvent};
+use node::switch::Queue;
+use node::switch::drop_tail_queue::DropTailQueue;
+use node::switch::edf_queue::EdfQueue;
+use node::switch::fabric_switch::FabricFreeEvent;
+use node::switch::priority_queue::PriorityQueue;
+use node::switch::wfq_queue::WfqQueue;
+
+/// Recovers the concrete type behind an `Event`, `Queue`, or `LossModel`, so it can be saved.
+pub trait AsAny {
+    fn as_any(&self) -> &Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &Any {
+        self
+    }
+}
+
+/// One of a generic type's instantiations with each congestion control algorithm.
+#[derive(Serialize, Deserialize)]
+pub enum ByCongAlg<A, B, C, D, E, F, G> {
+    ConstCwnd(A),
+    NewReno(B),
+    Aimd(C),
+    Dctcp(D),
+    Vegas(E),
+    Timely(F),
+    Dcqcn(G),
+}
+
+/// `checkpoint_enum!(Trait, Saved, Restored { Variant(Type), ... } by cong alg { Variant(Generic), ... })`
+/// declares `Saved`, which saves a `Trait` object of one of the listed types, and `Restored`,
+/// which restores it. Generic types are listed once, and cover every congestion control
+/// algorithm.
+macro_rules! checkpoint_enum {
+    ($tr:ident, $saved:ident, $restored:ident {
+        $($variant:ident($t:ty)),* $(,)*
+    } by cong alg {
+        $($gvariant:ident($g:ident)),* $(,)*
+    }) => {
+        #[derive(Serialize)]
+        pub enum $saved<'a> {
+            $($variant(&'a $t),)*
+            $($gvariant(ByCongAlg<&'a $g<ConstCwnd>, &'a $g<NewReno>, &'a $g<Aimd>, &'a $g<Dctcp>, &'a $g<Vegas>, &'a $g<Timely>, &'a $g<Dcqcn>>),)*
+        }
+
+        #[derive(Deserialize)]
+        pub enum $restored {
+            $($variant($t),)*
+            $($gvariant(ByCongAlg<$g<ConstCwnd>, $g<NewReno>, $g<Aimd>, $g<Dctcp>, $g<Vegas>, $g<Timely>, $g<Dcqcn>>),)*
+        }
+
+        impl<'a> $saved<'a> {
+            /// `None` if `x` is not one of the types listed.
+            pub fn of(x: &'a Any) -> Option<Self> {
+                $(if let Some(x) = x.downcast_ref::<$t>() {
+                    return Some($saved::$variant(x));
+                })*
+                $(
+                if let Some(x) = x.downcast_ref::<$g<ConstCwnd>>() {
+                    return Some($saved::$gvariant(ByCongAlg::ConstCwnd(x)));
+                }
+                if let Some(x) = x.downcast_ref::<$g<NewReno>>() {
+                    return Some($saved::$gvariant(ByCongAlg::NewReno(x)));
+                }
+                if let Some(x) = x.downcast_ref::<$g<Aimd>>() {
+                    return Some($saved::$gvariant(ByCongAlg::Aimd(x)));
+                }
+                if let Some(x) = x.downcast_ref::<$g<Dctcp>>() {
+                    return Some($saved::$gvariant(ByCongAlg::Dctcp(x)));
+                }
+                if let Some(x) = x.downcast_ref::<$g<Vegas>>() {
+                    return Some($saved::$gvariant(ByCongAlg::Vegas(x)));
+                }
+                if let Some(x) = x.downcast_ref::<$g<Timely>>() {
+                    return Some($saved::$gvariant(ByCongAlg::Timely(x)));
+                }
+                if let Some(x) = x.downcast_ref::<$g<Dcqcn>>() {
+                    return Some($saved::$gvariant(ByCongAlg::Dcqcn(x)));
+                }
+                )*
+                None
+            }
+        }
+
+        impl $restored {
+            pub fn into_box(self) -> Box<$tr> {
+                match self {
+                    $($restored::$variant(x) => Box::new(x),)*
+                    $($restored::$gvariant(x) => match x {
+                        ByCongAlg::ConstCwnd(x) => Box::new(x),
+                        ByCongAlg::NewReno(x) => Box::new(x),
+                        ByCongAlg::Aimd(x) => Box::new(x),
+                        ByCongAlg::Dctcp(x) => Box::new(x),
+                        ByCongAlg::Vegas(x) => Box::new(x),
+                        ByCongAlg::Timely(x) => Box::new(x),
+                        ByCongAlg::Dcqcn(x) => Box::new(x),
+                    },)*
+                }
+            }
+        }
+    }
+}
+
+checkpoint_enum!(Event, SavedEvent, RestoredEvent {
+    LinkTransmit(LinkTransmitEvent),
+    HostWake(HostWakeEvent),
+    ProcessingDone(ProcessingDoneEvent),
+    PacketDrop(PacketDropEvent),
+    NodeTransmit(NodeTransmitEvent),
+    LinkFree(LinkFreeEvent),
+    FabricFree(FabricFreeEvent),
+    FlowTimer(FlowTimerEvent),
+    ReceiverDrivenFlowArrival(ReceiverDrivenFlowArrivalEvent),
+    ConstantBitRateFlowArrival(ConstantBitRateFlowArrivalEvent),
+} by cong alg {
+    FlowArrival(FlowArrivalEvent),
+    WarmStartFlowArrival(WarmStartFlowArrivalEvent),
+    RpcFlowArrival(RpcFlowArrivalEvent),
+    SelectiveRepeatFlowArrival(SelectiveRepeatFlowArrivalEvent),
+});
+
+checkpoint_enum!(Flow, SavedFlow, RestoredFlow {
+    GoBackNReceiver(GoBackNReceiver),
+    SelectiveRepeatReceiver(SelectiveRepeatReceiver),
+    GrantSender(GrantSender),
+    GrantReceiver(GrantReceiver),
+    ConstantBitRateSender(ConstantBitRateSender),
+    ConstantBitRateReceiver(ConstantBitRateReceiver),
+} by cong alg {
+    GoBackNSender(GoBackNSender),
+    SelectiveRepeatSender(SelectiveRepeatSender),
+    RpcClient(RpcClient),
+    RpcServer(RpcServer),
+});
+
+checkpoint_enum!(Queue, SavedQueue, RestoredQueue {
+    DropTail(DropTailQueue),
+    Edf(EdfQueue),
+    Priority(PriorityQueue),
+    Wfq(WfqQueue),
+} by cong alg {});
+
+checkpoint_enum!(LossModel, SavedLossModel, RestoredLossModel {
+    NoLoss(NoLoss),
+    QueueOverflow(QueueOverflow),
+    FixedRate(FixedRateLoss),
+    GilbertElliott(GilbertElliott),
+    Any(AnyLoss),
+} by cong alg {});
+
+impl Serialize for Box<Event> {
+    fn serialize<S: Serializer>(&self, s: S) -> ::std::result::Result<S::Ok, S::Error> {
+        match SavedEvent::of((**self).as_any()) {
+            Some(ev) => ev.serialize(s),
+            None => Err(S::Error::custom(match self.time() {
+                EventTime::Absolute(t) => format!("cannot checkpoint the event due at {}", t),
+                EventTime::Delta(_) => format!("cannot checkpoint an event for nodes {:?}", self.affected_node_ids()),
+            })),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<Event> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> ::std::result::Result<Self, D::Error> {
+        RestoredEvent::deserialize(d).map(RestoredEvent::into_box)
+    }
+}
+
+impl Serialize for Box<Flow> {
+    fn serialize<S: Serializer>(&self, s: S) -> ::std::result::Result<S::Ok, S::Error> {
+        match SavedFlow::of((**self).as_any()) {
+            Some(f) => f.serialize(s),
+            None => Err(S::Error::custom(format!("cannot checkpoint flow {}", self.flow_info().flow_id))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<Flow> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> ::std::result::Result<Self, D::Error> {
+        RestoredFlow::deserialize(d).map(RestoredFlow::into_box)
+    }
+}
+
+impl Serialize for Box<Queue> {
+    fn serialize<S: Serializer>(&self, s: S) -> ::std::result::Result<S::Ok, S::Error> {
+        match SavedQueue::of((**self).as_any()) {
+            Some(q) => q.serialize(s),
+            None => Err(S::Error::custom(format!("cannot checkpoint the queue on link {} -> {}", self.link().from, self.link().to))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<Queue> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> ::std::result::Result<Self, D::Error> {
+        RestoredQueue::deserialize(d).map(RestoredQueue::into_box)
+    }
+}
+
+impl Serialize for Box<LossModel> {
+    fn serialize<S: Serializer>(&self, s: S) -> ::std::result::Result<S::Ok, S::Error> {
+        match SavedLossModel::of((**self).as_any()) {
+            Some(m) => m.serialize(s),
+            None => Err(S::Error::custom(format!("cannot checkpoint loss model {:?}", self))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<LossModel> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> ::std::result::Result<Self, D::Error> {
+        RestoredLossModel::deserialize(d).map(RestoredLossModel::into_box)
+    }
+}
+
+/// For `#[serde(with = "::checkpoint::pairs")]` on maps whose keys are not strings or numbers,
+/// e.g. links' `(from, to)`, which JSON objects cannot hold: saves them as `(key, value)` pairs.
+pub mod pairs {
+    use super::*;
+
+    pub fn serialize<K, V, S>(m: &HashMap<K, V>, s: S) -> ::std::result::Result<S::Ok, S::Error>
+        where K: Serialize + Eq + Hash, V: Serialize, S: Serializer
+    {
+        s.collect_seq(m.iter())
+    }
+
+    pub fn deserialize<'de, K, V, D>(d: D) -> ::std::result::Result<HashMap<K, V>, D::Error>
+        where K: Deserialize<'de> + Eq + Hash, V: Deserialize<'de>, D: Deserializer<'de>
+    {
+        let pairs = Vec::<(K, V)>::deserialize(d)?;
+        let len = pairs.len();
+        let m = pairs.into_iter().collect::<HashMap<K, V>>();
+        if m.len() != len {
+            return Err(D::Error::custom("duplicate key"));
+        }
+
+        Ok(m)
+    }
+}
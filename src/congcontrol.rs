@@ -1,5 +1,6 @@
 use std::fmt::Debug;
-use super::Nanos;
+use super::{Nanos, DEFAULT_MTU};
+use super::packet::PACKET_HEADER_SIZE_BYTES;
 
 pub enum ReductionType {
     Drop,
@@ -8,12 +9,29 @@ pub enum ReductionType {
 
 pub trait CongAlg: Clone + Debug {
     fn new() -> Self;
+    /// The congestion window, in packets.
+    /// Senders always allow at least one packet in flight, even if this is 0.
     fn cwnd(&self) -> u32;
     fn on_packet(&mut self, acked: u32, rtt: Nanos) -> u32;
     fn reduction(&mut self, reduction: ReductionType) -> u32;
+    /// A duplicate ACK: it acknowledged nothing new while `outstanding` bytes were sent but not
+    /// yet ACKed. Returns whether the sender should resend its first unACKed packet now (fast
+    /// retransmit). Algorithms without fast recovery ignore duplicate ACKs.
+    fn on_dup_ack(&mut self, _outstanding: u32) -> bool {
+        false
+    }
+    /// Count ACKed bytes, and convert rates to windows, in packets of `mss` payload bytes.
+    /// Senders call this with their flow's packet length before sending anything, so it
+    /// overrides any `mss` given to `with_params`. Algorithms which count ACKs ignore it.
+    fn set_mss(&mut self, _mss: u32) {}
+    /// For algorithms which update on timers rather than ACKs.
+    /// Called when the flow starts, then again after each returned delay; `None` stops the timer.
+    fn on_timer(&mut self, _now: Nanos) -> Option<Nanos> {
+        None
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ConstCwnd(u32);
 
 impl CongAlg for ConstCwnd {
@@ -31,3 +49,840 @@ impl CongAlg for ConstCwnd {
         self.0
     }
 }
+
+/// Duplicate ACKs which make `NewReno` presume a packet lost.
+pub const DUP_ACK_THRESHOLD: u32 = 3;
+
+/// TCP NewReno: slow start until `ssthresh`, then additive increase of one packet per RTT.
+/// On a drop, cwnd and ssthresh are both halved, once per loss: further drops reported before
+/// the next ACK of new data are the same loss.
+///
+/// The `DUP_ACK_THRESHOLD`th duplicate ACK starts fast recovery (RFC 6582): the sender
+/// retransmits, cwnd is halved (unless the loss was already reported) and inflated by a packet
+/// for each duplicate ACK, as those packets have left the network. Partial ACKs, which leave
+/// some of the data outstanding at the start of recovery unACKed, deflate cwnd by the packets
+/// they ACK; the ACK for the rest ends recovery with cwnd at `ssthresh`. Drops reported during
+/// recovery are part of the loss being recovered from.
+/// All windows are in packets; ACKed bytes are counted in units of `mss`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NewReno {
+    cwnd: u32,
+    ssthresh: u32,
+    mss: u32,
+    acked_bytes: u32, // ACKed bytes not yet counted as a full MSS
+    acked_in_window: u32, // MSSs ACKed since cwnd last grew in congestion avoidance
+    dup_acks: u32, // duplicate ACKs since the last ACK of new data
+    reduced: bool, // whether cwnd was cut since the last ACK of new data
+    recovery: Option<u32>, // in fast recovery: bytes outstanding at its start not yet ACKed
+}
+
+impl NewReno {
+    pub fn with_params(init_cwnd: u32, ssthresh: u32, mss: u32) -> Self {
+        NewReno{
+            cwnd: init_cwnd,
+            ssthresh,
+            mss,
+            acked_bytes: 0,
+            acked_in_window: 0,
+            dup_acks: 0,
+            reduced: false,
+            recovery: None,
+        }
+    }
+
+    pub fn ssthresh(&self) -> u32 {
+        self.ssthresh
+    }
+
+    pub fn in_fast_recovery(&self) -> bool {
+        self.recovery.is_some()
+    }
+}
+
+impl CongAlg for NewReno {
+    fn new() -> Self {
+        NewReno::with_params(1, u32::max_value(), DEFAULT_MTU - PACKET_HEADER_SIZE_BYTES)
+    }
+
+    fn set_mss(&mut self, mss: u32) {
+        self.mss = mss;
+    }
+
+    fn cwnd(&self) -> u32 { self.cwnd }
+
+    fn on_packet(&mut self, acked: u32, _: Nanos) -> u32 {
+        self.dup_acks = 0;
+        self.reduced = false;
+        match self.recovery {
+            Some(remaining) if acked < remaining => {
+                // partial ACK: the packets it ACKed have left the network, and so has the
+                // retransmission the sender makes for the next hole
+                self.recovery = Some(remaining - acked);
+                self.cwnd = self.cwnd.saturating_sub(acked / self.mss) + 1;
+                return self.cwnd;
+            }
+            Some(_) => {
+                // everything outstanding when recovery started has been ACKed
+                self.recovery = None;
+                self.cwnd = self.ssthresh;
+                self.acked_bytes = 0;
+                self.acked_in_window = 0;
+                return self.cwnd;
+            }
+            None => (),
+        }
+
+        self.acked_bytes += acked;
+        while self.acked_bytes >= self.mss {
+            self.acked_bytes -= self.mss;
+            if self.cwnd < self.ssthresh {
+                // slow start: one packet per ACKed packet doubles cwnd every RTT
+                self.cwnd += 1;
+            } else {
+                // congestion avoidance: one packet per window of ACKed packets
+                self.acked_in_window += 1;
+                if self.acked_in_window >= self.cwnd {
+                    self.acked_in_window = 0;
+                    self.cwnd += 1;
+                }
+            }
+        }
+
+        self.cwnd
+    }
+
+    fn reduction(&mut self, _: ReductionType) -> u32 {
+        if self.recovery.is_none() && !self.reduced {
+            self.ssthresh = ::std::cmp::max(self.cwnd / 2, 2);
+            self.cwnd = self.ssthresh;
+            self.acked_in_window = 0;
+            self.reduced = true;
+        }
+
+        self.cwnd
+    }
+
+    fn on_dup_ack(&mut self, outstanding: u32) -> bool {
+        if outstanding == 0 {
+            return false;
+        }
+
+        if self.recovery.is_some() {
+            // inflate: the packet which triggered the ACK has left the network
+            self.cwnd += 1;
+            return false;
+        }
+
+        self.dup_acks += 1;
+        if self.dup_acks < DUP_ACK_THRESHOLD {
+            return false;
+        }
+
+        if !self.reduced {
+            self.ssthresh = ::std::cmp::max(self.cwnd / 2, 2);
+            self.reduced = true;
+        }
+
+        self.cwnd = self.ssthresh + DUP_ACK_THRESHOLD;
+        self.acked_in_window = 0;
+        self.recovery = Some(outstanding);
+        true
+    }
+}
+
+/// Generic AIMD: cwnd grows by `additive_increase` packets per window of ACKed bytes, and is
+/// multiplied by `multiplicative_decrease` on congestion, never falling below one packet.
+/// (1, 0.5) is classic TCP congestion avoidance.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Aimd {
+    pub additive_increase: u32,
+    pub multiplicative_decrease: f64,
+    cwnd: f64,
+    mss: u32,
+    acked_bytes: u32, // ACKed bytes not yet counted as a full MSS
+    acked_in_window: u32, // MSSs ACKed since cwnd last grew
+}
+
+impl Aimd {
+    pub fn with_params(additive_increase: u32, multiplicative_decrease: f64) -> Self {
+        Aimd{
+            additive_increase,
+            multiplicative_decrease,
+            cwnd: 10.0,
+            mss: DEFAULT_MTU - PACKET_HEADER_SIZE_BYTES,
+            acked_bytes: 0,
+            acked_in_window: 0,
+        }
+    }
+}
+
+impl CongAlg for Aimd {
+    fn new() -> Self {
+        Aimd::with_params(1, 0.5)
+    }
+
+    fn set_mss(&mut self, mss: u32) {
+        self.mss = mss;
+    }
+
+    fn cwnd(&self) -> u32 {
+        ::std::cmp::max(self.cwnd as u32, 1)
+    }
+
+    fn on_packet(&mut self, acked: u32, _: Nanos) -> u32 {
+        self.acked_bytes += acked;
+        while self.acked_bytes >= self.mss {
+            self.acked_bytes -= self.mss;
+            self.acked_in_window += 1;
+            if self.acked_in_window >= self.cwnd() {
+                self.acked_in_window = 0;
+                self.cwnd += self.additive_increase as f64;
+            }
+        }
+
+        self.cwnd()
+    }
+
+    fn reduction(&mut self, _: ReductionType) -> u32 {
+        self.cwnd = (self.cwnd * self.multiplicative_decrease).max(1.0);
+        self.acked_in_window = 0;
+        self.cwnd()
+    }
+}
+
+/// DCTCP: keeps an EWMA `alpha` (with gain `g`) of the fraction of ACKs echoing an ECN mark,
+/// and on marks cuts cwnd by a factor of `1 - alpha/2`, at most once per window.
+/// Without marks, cwnd grows by one packet per window.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Dctcp {
+    cwnd: f64,
+    alpha: f64,
+    g: f64,
+    acks_in_window: u32,
+    marked_in_window: u32,
+    reduced_in_window: bool,
+}
+
+impl Dctcp {
+    pub fn with_params(init_cwnd: u32, g: f64) -> Self {
+        Dctcp{
+            cwnd: init_cwnd as f64,
+            alpha: 1.0,
+            g,
+            acks_in_window: 0,
+            marked_in_window: 0,
+            reduced_in_window: false,
+        }
+    }
+
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+}
+
+impl CongAlg for Dctcp {
+    fn new() -> Self {
+        Dctcp::with_params(10, 1.0 / 16.0)
+    }
+
+    fn cwnd(&self) -> u32 {
+        ::std::cmp::max(self.cwnd as u32, 1)
+    }
+
+    fn on_packet(&mut self, _: u32, _: Nanos) -> u32 {
+        // a window ends once a cwnd's worth of ACKs, and any marks they carried, are counted
+        if self.acks_in_window >= self.cwnd() {
+            let marked_fraction = self.marked_in_window as f64 / self.acks_in_window as f64;
+            self.alpha = (1.0 - self.g) * self.alpha + self.g * marked_fraction;
+            if self.marked_in_window == 0 {
+                self.cwnd += 1.0;
+            }
+
+            self.acks_in_window = 0;
+            self.marked_in_window = 0;
+            self.reduced_in_window = false;
+        }
+
+        self.acks_in_window += 1;
+        self.cwnd()
+    }
+
+    fn reduction(&mut self, reduction: ReductionType) -> u32 {
+        let factor = match reduction {
+            ReductionType::Ecn => {
+                self.marked_in_window += 1;
+                1.0 - self.alpha / 2.0
+            }
+            ReductionType::Drop => 0.5,
+        };
+
+        if !self.reduced_in_window {
+            self.reduced_in_window = true;
+            self.cwnd = (self.cwnd * factor).max(1.0);
+        }
+
+        self.cwnd()
+    }
+}
+
+/// TCP Vegas: a delay-based window controller which aims to keep between `alpha` and `beta`
+/// of its packets queued at the bottleneck.
+///
+/// Once per window, it estimates its queued packets as `cwnd * (rtt - base_rtt) / rtt`, where
+/// `base_rtt` is the minimum RTT seen. Fewer than `alpha` grows cwnd by one packet, and more than
+/// `beta` shrinks it by one. It slow starts until the estimate first exceeds `alpha`, then
+/// backs off by an eighth. A drop halves cwnd; ECN marks are ignored.
+/// All windows are in packets; ACKed bytes are counted in units of `mss`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Vegas {
+    cwnd: u32,
+    alpha: u32,
+    beta: u32,
+    mss: u32,
+    slow_start: bool,
+    base_rtt: Option<Nanos>,
+    acked_bytes: u32, // ACKed bytes not yet counted as a full MSS
+    acked_in_window: u32, // MSSs ACKed since the window started
+    window: u32, // cwnd when the window started
+}
+
+impl Vegas {
+    pub fn with_params(alpha: u32, beta: u32, mss: u32) -> Self {
+        assert!(alpha <= beta);
+        Vegas{
+            cwnd: 2,
+            alpha,
+            beta,
+            mss,
+            slow_start: true,
+            base_rtt: None,
+            acked_bytes: 0,
+            acked_in_window: 0,
+            window: 2,
+        }
+    }
+
+    fn end_of_window(&mut self, rtt: Nanos) {
+        let base_rtt = match self.base_rtt {
+            Some(b) if rtt > 0 => b,
+            _ => return,
+        };
+
+        let queued = self.cwnd as f64 * (rtt - base_rtt) as f64 / rtt as f64;
+        if self.slow_start {
+            if queued > self.alpha as f64 {
+                self.slow_start = false;
+                self.cwnd -= self.cwnd / 8;
+            }
+        } else if queued < self.alpha as f64 {
+            self.cwnd += 1;
+        } else if queued > self.beta as f64 {
+            self.cwnd = ::std::cmp::max(self.cwnd - 1, 2);
+        }
+    }
+}
+
+impl CongAlg for Vegas {
+    fn new() -> Self {
+        Vegas::with_params(2, 4, DEFAULT_MTU - PACKET_HEADER_SIZE_BYTES)
+    }
+
+    fn set_mss(&mut self, mss: u32) {
+        self.mss = mss;
+    }
+
+    fn cwnd(&self) -> u32 { self.cwnd }
+
+    fn on_packet(&mut self, acked: u32, rtt: Nanos) -> u32 {
+        if rtt > 0 {
+            self.base_rtt = Some(self.base_rtt.map_or(rtt, |b| ::std::cmp::min(b, rtt)));
+        }
+
+        self.acked_bytes += acked;
+        while self.acked_bytes >= self.mss {
+            self.acked_bytes -= self.mss;
+            if self.slow_start {
+                self.cwnd += 1;
+            }
+
+            self.acked_in_window += 1;
+            if self.acked_in_window >= self.window {
+                self.end_of_window(rtt);
+                self.acked_in_window = 0;
+                self.window = self.cwnd;
+            }
+        }
+
+        self.cwnd
+    }
+
+    fn reduction(&mut self, reduction: ReductionType) -> u32 {
+        if let ReductionType::Drop = reduction {
+            self.slow_start = false;
+            self.cwnd = ::std::cmp::max(self.cwnd / 2, 2);
+            self.acked_in_window = 0;
+            self.window = self.cwnd;
+        }
+
+        self.cwnd
+    }
+}
+
+/// TIMELY: a delay-based rate controller driven by the gradient of RTT samples.
+///
+/// Below `t_low` the rate increases additively and above `t_high` it decreases multiplicatively.
+/// In between, an EWMA (weighting new samples by `ewma_weight`) of the difference between
+/// consecutive RTTs, normalized by the minimum RTT, decides: a falling RTT increases the rate,
+/// and a rising one cuts it in proportion to the gradient.
+/// The window is the rate's bandwidth-delay product over the minimum RTT seen, in packets.
+/// Until the first RTT sample, the window is one packet.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Timely {
+    rate_bps: f64,
+    line_rate_bps: f64,
+    t_low: Nanos,
+    t_high: Nanos,
+    ewma_weight: f64,
+    mss: u32,
+    prev_rtt: Option<Nanos>,
+    min_rtt: Option<Nanos>,
+    rtt_diff: f64,
+}
+
+/// Additive increase step, as a fraction of the line rate.
+const TIMELY_ADDITIVE_STEP: f64 = 1.0 / 1000.0;
+/// Multiplicative decrease factor.
+const TIMELY_BETA: f64 = 0.8;
+
+impl Timely {
+    pub fn with_params(line_rate_bps: u64, t_low: Nanos, t_high: Nanos, ewma_weight: f64) -> Self {
+        Timely{
+            rate_bps: line_rate_bps as f64,
+            line_rate_bps: line_rate_bps as f64,
+            t_low,
+            t_high,
+            ewma_weight,
+            mss: DEFAULT_MTU - PACKET_HEADER_SIZE_BYTES,
+            prev_rtt: None,
+            min_rtt: None,
+            rtt_diff: 0.0,
+        }
+    }
+
+    pub fn rate_bps(&self) -> f64 {
+        self.rate_bps
+    }
+
+    fn update_rate(&mut self, rtt: Nanos, min_rtt: Nanos) {
+        let delta = self.line_rate_bps * TIMELY_ADDITIVE_STEP;
+        let new_rtt_diff = self.prev_rtt.map_or(0.0, |prev| rtt as f64 - prev as f64);
+        self.prev_rtt = Some(rtt);
+        self.rtt_diff = (1.0 - self.ewma_weight) * self.rtt_diff + self.ewma_weight * new_rtt_diff;
+        let gradient = self.rtt_diff / min_rtt as f64;
+
+        if rtt < self.t_low {
+            self.rate_bps += delta;
+        } else if rtt > self.t_high {
+            self.rate_bps *= 1.0 - TIMELY_BETA * (1.0 - self.t_high as f64 / rtt as f64);
+        } else if gradient <= 0.0 {
+            self.rate_bps += delta;
+        } else {
+            self.rate_bps *= 1.0 - TIMELY_BETA * gradient.min(1.0);
+        }
+
+        self.rate_bps = self.rate_bps.max(delta);
+    }
+}
+
+impl CongAlg for Timely {
+    fn new() -> Self {
+        Timely::with_params(10_000_000_000, 50_000, 500_000, 0.875)
+    }
+
+    fn set_mss(&mut self, mss: u32) {
+        self.mss = mss;
+    }
+
+    fn cwnd(&self) -> u32 {
+        match self.min_rtt {
+            Some(min_rtt) => {
+                let bdp_bytes = self.rate_bps * min_rtt as f64 / 1e9 / 8.0;
+                ::std::cmp::max((bdp_bytes / self.mss as f64) as u32, 1)
+            }
+            None => 1,
+        }
+    }
+
+    fn on_packet(&mut self, _: u32, rtt: Nanos) -> u32 {
+        if rtt > 0 {
+            let min_rtt = self.min_rtt.map_or(rtt, |m| ::std::cmp::min(m, rtt));
+            self.min_rtt = Some(min_rtt);
+            self.update_rate(rtt, min_rtt);
+        }
+
+        self.cwnd()
+    }
+
+    fn reduction(&mut self, reduction: ReductionType) -> u32 {
+        // TIMELY reacts to delay alone; a drop is still a congestion signal.
+        if let ReductionType::Drop = reduction {
+            self.rate_bps = (self.rate_bps * (1.0 - TIMELY_BETA / 2.0))
+                .max(self.line_rate_bps * TIMELY_ADDITIVE_STEP);
+        }
+
+        self.cwnd()
+    }
+}
+
+/// DCQCN's reaction point: a rate-based controller for RoCEv2.
+///
+/// A congestion notification (an ECN echo here, standing in for a CNP) sets the target rate to
+/// the current rate and cuts the current rate by `alpha / 2`, at most once per timer period.
+/// `alpha` is an EWMA with gain `g` of whether each period saw a notification.
+/// Every quiet `timer_period`, and every `DCQCN_BYTE_COUNTER` bytes ACKed, the rate recovers:
+/// first halfway to the target (fast recovery), then by raising the target additively, and
+/// faster still (hyper increase) once both the timer and byte counter have left fast recovery.
+/// The window is the rate's bandwidth-delay product over the minimum RTT seen, in packets.
+/// Until the first RTT sample, the window is one packet.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Dcqcn {
+    rate_bps: f64,
+    target_bps: f64,
+    line_rate_bps: f64,
+    alpha: f64,
+    g: f64,
+    timer_period: Nanos,
+    mss: u32,
+    min_rtt: Option<Nanos>,
+    timer_stage: u32,
+    byte_stage: u32,
+    acked_bytes: u32, // ACKed bytes since the byte counter last fired
+    notified_in_period: bool,
+}
+
+/// Rate increases in the fast recovery stage before additive increase begins.
+const DCQCN_FAST_RECOVERY_STEPS: u32 = 5;
+/// ACKed bytes per byte counter increase.
+const DCQCN_BYTE_COUNTER: u32 = 150_000;
+/// Additive increase step, as a fraction of the line rate.
+const DCQCN_ADDITIVE_STEP: f64 = 1.0 / 200.0;
+/// Hyper increase step, as a multiple of the additive step.
+const DCQCN_HYPER_STEP: f64 = 10.0;
+
+impl Dcqcn {
+    pub fn with_params(line_rate_bps: u64, timer_period: Nanos, g: f64) -> Self {
+        Dcqcn{
+            rate_bps: line_rate_bps as f64,
+            target_bps: line_rate_bps as f64,
+            line_rate_bps: line_rate_bps as f64,
+            alpha: 1.0,
+            g,
+            timer_period,
+            mss: DEFAULT_MTU - PACKET_HEADER_SIZE_BYTES,
+            min_rtt: None,
+            timer_stage: 0,
+            byte_stage: 0,
+            acked_bytes: 0,
+            notified_in_period: false,
+        }
+    }
+
+    pub fn rate_bps(&self) -> f64 {
+        self.rate_bps
+    }
+
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+
+    fn increase(&mut self) {
+        let step = self.line_rate_bps * DCQCN_ADDITIVE_STEP;
+        let slow_stage = ::std::cmp::min(self.timer_stage, self.byte_stage);
+        let fast_stage = ::std::cmp::max(self.timer_stage, self.byte_stage);
+        if slow_stage > DCQCN_FAST_RECOVERY_STEPS {
+            self.target_bps += step * DCQCN_HYPER_STEP;
+        } else if fast_stage > DCQCN_FAST_RECOVERY_STEPS {
+            self.target_bps += step;
+        }
+
+        self.target_bps = self.target_bps.min(self.line_rate_bps);
+        self.rate_bps = (self.rate_bps + self.target_bps) / 2.0;
+    }
+}
+
+impl CongAlg for Dcqcn {
+    fn new() -> Self {
+        Dcqcn::with_params(10_000_000_000, 55_000, 1.0 / 256.0)
+    }
+
+    fn set_mss(&mut self, mss: u32) {
+        self.mss = mss;
+    }
+
+    fn cwnd(&self) -> u32 {
+        match self.min_rtt {
+            Some(min_rtt) => {
+                let bdp_bytes = self.rate_bps * min_rtt as f64 / 1e9 / 8.0;
+                ::std::cmp::max((bdp_bytes / self.mss as f64) as u32, 1)
+            }
+            None => 1,
+        }
+    }
+
+    fn on_packet(&mut self, acked: u32, rtt: Nanos) -> u32 {
+        if rtt > 0 {
+            self.min_rtt = Some(self.min_rtt.map_or(rtt, |m| ::std::cmp::min(m, rtt)));
+        }
+
+        self.acked_bytes += acked;
+        while self.acked_bytes >= DCQCN_BYTE_COUNTER {
+            self.acked_bytes -= DCQCN_BYTE_COUNTER;
+            self.byte_stage += 1;
+            self.increase();
+        }
+
+        self.cwnd()
+    }
+
+    fn reduction(&mut self, _: ReductionType) -> u32 {
+        // the notification point sends at most one CNP per period
+        if !self.notified_in_period {
+            self.notified_in_period = true;
+            self.target_bps = self.rate_bps;
+            self.rate_bps = (self.rate_bps * (1.0 - self.alpha / 2.0))
+                .max(self.line_rate_bps * DCQCN_ADDITIVE_STEP);
+            self.alpha = (1.0 - self.g) * self.alpha + self.g;
+            self.timer_stage = 0;
+            self.byte_stage = 0;
+            self.acked_bytes = 0;
+        }
+
+        self.cwnd()
+    }
+
+    fn on_timer(&mut self, _now: Nanos) -> Option<Nanos> {
+        if self.notified_in_period {
+            self.notified_in_period = false;
+        } else {
+            self.alpha *= 1.0 - self.g;
+            self.timer_stage += 1;
+            self.increase();
+        }
+
+        Some(self.timer_period)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Aimd, CongAlg, Dcqcn, Dctcp, NewReno, ReductionType, Timely, Vegas};
+
+    #[test]
+    fn aimd_halving() {
+        let mut cc = Aimd::new();
+
+        // one packet per window of ACKed bytes
+        for _ in 0..10 {
+            cc.on_packet(1460, 0);
+        }
+        assert_eq!(cc.cwnd(), 11);
+
+        assert_eq!(cc.reduction(ReductionType::Drop), 5); // 5.5
+        for _ in 0..5 {
+            cc.on_packet(1460, 0);
+        }
+        assert_eq!(cc.cwnd(), 6); // 6.5
+
+        // never below one packet
+        for _ in 0..10 {
+            cc.reduction(ReductionType::Drop);
+        }
+        assert_eq!(cc.cwnd(), 1);
+    }
+
+    #[test]
+    fn aimd_gentle_decrease() {
+        let mut cc = Aimd::with_params(2, 0.875);
+
+        for _ in 0..10 {
+            cc.on_packet(1460, 0);
+        }
+        assert_eq!(cc.cwnd(), 12);
+
+        // a cut takes only an eighth of the window
+        assert_eq!(cc.reduction(ReductionType::Ecn), 10); // 10.5
+        assert_eq!(cc.reduction(ReductionType::Ecn), 9); // 9.1875
+        for _ in 0..9 {
+            cc.on_packet(1460, 0);
+        }
+        assert_eq!(cc.cwnd(), 11);
+    }
+
+    #[test]
+    fn dcqcn_cut_and_recovery() {
+        let mut cc = Dcqcn::with_params(10_000_000_000, 55_000, 0.5);
+        cc.on_packet(1460, 40_000);
+        assert_eq!(cc.cwnd(), 34); // 10 Gbps over 40us
+
+        // one cut per period, by alpha / 2
+        cc.reduction(ReductionType::Ecn);
+        cc.reduction(ReductionType::Ecn);
+        assert_eq!(cc.rate_bps(), 5_000_000_000.0);
+        assert_eq!(cc.alpha(), 1.0);
+        assert_eq!(cc.cwnd(), 17);
+
+        // the period with the cut ends; each quiet one after it decays alpha and recovers
+        // halfway to the 10 Gbps target
+        assert_eq!(cc.on_timer(55_000), Some(55_000));
+        assert_eq!(cc.rate_bps(), 5_000_000_000.0);
+        cc.on_timer(110_000);
+        assert_eq!(cc.rate_bps(), 7_500_000_000.0);
+        assert_eq!(cc.alpha(), 0.5);
+        cc.on_timer(165_000);
+        assert_eq!(cc.rate_bps(), 8_750_000_000.0);
+    }
+
+    #[test]
+    fn timely_rate_follows_rtt() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1.0;
+        let mut cc = Timely::with_params(10_000_000_000, 50_000, 500_000, 0.5);
+        assert_eq!(cc.cwnd(), 1);
+
+        // 10 Gbps over a 40us minimum RTT is 50KB, 34 packets
+        cc.on_packet(1460, 40_000);
+        assert!(close(cc.rate_bps(), 10_010_000_000.0));
+        assert_eq!(cc.cwnd(), 34);
+
+        // between the thresholds, a rising RTT cuts the rate and a falling one raises it
+        cc.on_packet(1460, 60_000); // rtt_diff = 10us, gradient = 0.25, cut by 20%
+        assert!(close(cc.rate_bps(), 10_010_000_000.0 * 0.8));
+        cc.on_packet(1460, 50_000); // rtt_diff = 0
+        assert!(close(cc.rate_bps(), 10_010_000_000.0 * 0.8 + 10_000_000.0));
+
+        // above t_high, cut in proportion to how far above
+        let rate = cc.rate_bps();
+        cc.on_packet(1460, 1_000_000);
+        assert!(close(cc.rate_bps(), rate * 0.6));
+    }
+
+    #[test]
+    fn dctcp_alpha_and_cut() {
+        let mut cc = Dctcp::with_params(10, 0.5);
+
+        // one unmarked window: alpha decays from 1 and cwnd grows
+        for _ in 0..11 {
+            cc.on_packet(1460, 0);
+        }
+        assert_eq!(cc.alpha(), 0.5);
+        assert_eq!(cc.cwnd(), 11);
+
+        // marks cut cwnd by alpha / 2, once per window
+        assert_eq!(cc.reduction(ReductionType::Ecn), 8); // 11 * 0.75
+        assert_eq!(cc.reduction(ReductionType::Ecn), 8);
+
+        // the window ends after 8 ACKs, 2 of which were marked; no growth after a cut
+        for _ in 0..8 {
+            cc.on_packet(1460, 0);
+        }
+        assert_eq!(cc.alpha(), 0.5 * 0.5 + 0.5 * (2.0 / 8.0));
+        assert_eq!(cc.cwnd(), 8);
+    }
+
+    #[test]
+    fn new_reno_sawtooth() {
+        let mut cc = NewReno::with_params(1, 8, 1000);
+
+        // slow start: cwnd doubles every window
+        let mut windows = vec![];
+        for _ in 0..3 {
+            let cwnd = cc.cwnd();
+            for _ in 0..cwnd {
+                cc.on_packet(1000, 0);
+            }
+
+            windows.push(cc.cwnd());
+        }
+        assert_eq!(windows, vec![2, 4, 8]);
+
+        // congestion avoidance: one packet per window
+        for _ in 0..8 {
+            cc.on_packet(1000, 0);
+        }
+        assert_eq!(cc.cwnd(), 9);
+
+        // partial packets accumulate
+        cc.on_packet(500, 0);
+        cc.on_packet(500, 0);
+        for _ in 0..8 {
+            cc.on_packet(1000, 0);
+        }
+        assert_eq!(cc.cwnd(), 10);
+
+        // multiplicative decrease
+        assert_eq!(cc.reduction(ReductionType::Drop), 5);
+        assert_eq!(cc.ssthresh(), 5);
+        for _ in 0..5 {
+            cc.on_packet(1000, 0);
+        }
+        assert_eq!(cc.cwnd(), 6);
+    }
+
+    #[test]
+    fn new_reno_fast_recovery() {
+        let mut cc = NewReno::with_params(10, 100, 1000);
+
+        // nothing outstanding, nothing to recover
+        assert!(!cc.on_dup_ack(0));
+
+        // the third duplicate ACK retransmits, halves ssthresh, and inflates cwnd by the three
+        assert!(!cc.on_dup_ack(10_000));
+        assert!(!cc.on_dup_ack(10_000));
+        assert!(cc.on_dup_ack(10_000));
+        assert!(cc.in_fast_recovery());
+        assert_eq!(cc.ssthresh(), 5);
+        assert_eq!(cc.cwnd(), 8);
+
+        // each further one inflates cwnd; drops are part of the loss being recovered from
+        assert!(!cc.on_dup_ack(10_000));
+        assert_eq!(cc.cwnd(), 9);
+        assert_eq!(cc.reduction(ReductionType::Drop), 9);
+
+        // a partial ACK deflates cwnd by what it ACKed, less the retransmission
+        assert_eq!(cc.on_packet(3000, 0), 7);
+        assert!(cc.in_fast_recovery());
+
+        // ACKing the rest ends recovery at ssthresh
+        assert_eq!(cc.on_packet(7000, 0), 5);
+        assert!(!cc.in_fast_recovery());
+        for _ in 0..5 {
+            cc.on_packet(1000, 0);
+        }
+        assert_eq!(cc.cwnd(), 6);
+    }
+
+    #[test]
+    fn vegas_tracks_queued_packets() {
+        let mut cc = Vegas::with_params(2, 4, 1000);
+        let window = |cc: &mut Vegas, rtt| {
+            let cwnd = cc.cwnd();
+            for _ in 0..cwnd {
+                cc.on_packet(1000, rtt);
+            }
+
+            cc.cwnd()
+        };
+
+        // slow start while nothing is queued
+        assert_eq!(window(&mut cc, 100), 4);
+        assert_eq!(window(&mut cc, 100), 8);
+        // 8 of 16 packets queued: leave slow start, backing off by an eighth
+        assert_eq!(window(&mut cc, 200), 14);
+        // 7 packets queued is more than beta
+        assert_eq!(window(&mut cc, 200), 13);
+        // about 1 packet queued is less than alpha
+        assert_eq!(window(&mut cc, 110), 14);
+
+        assert_eq!(cc.reduction(ReductionType::Ecn), 14);
+        assert_eq!(cc.reduction(ReductionType::Drop), 7);
+    }
+}
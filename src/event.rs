@@ -1,32 +1,62 @@
 use std::cmp::Ordering;
 use std::boxed::Box;
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, HashMap};
+use std::io::{Read, Write};
 
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
 use slog;
 
 use super::{Nanos, Result};
+use super::checkpoint::AsAny;
 use super::topology::Topology;
-use super::node::Node;
+use super::node::{Node, Link};
+use super::flow::{FlowSide, DEFAULT_RTO};
+use super::log_filter::{LogFilter, PACKET};
+use super::loss::{LossModel, LINK_LOSS_CONTEXT};
+use super::packet::Packet;
+use super::rng::Rng;
+use super::stats::LinkStats;
 
 /// Event driven simulator runtime model:
 /// 1. A single event covers all the computation performed by a single node in a single step of
 ///    time.
 /// 2. Calling exec() on an event can yield zero or more successive events.
 /// 3. All events are ordered by time (`impl Ord`) and executed in this order.
+/// 4. Stale events (see `Event::is_stale()`) are discarded without advancing time.
+/// 5. Events due at the same time run in the order they were scheduled.
+/// 6. Nodes are polled for new transmissions only once every event due at the current time has
+///    run, so a node sending at time `t` has already received everything delivered at `t`.
 
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum EventTime {
     Absolute(Nanos),
     Delta(Nanos),
 }
 
-pub trait Event {
+pub trait Event: AsAny {
     fn time(&self) -> EventTime; // when this should trigger
     fn affected_node_ids(&self) -> Vec<u32>;
     fn exec(&mut self, time: Nanos, affected_nodes: &mut [&mut Node], logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>>; // execute the event
+    /// Whether this event no longer has any effect, e.g. a timer for a completed flow.
+    fn is_stale(&self, _affected_nodes: &[&mut Node]) -> bool {
+        false
+    }
+    /// The packet this event puts on a link, if any, so the executor can count link usage.
+    fn transmission(&self) -> Option<(Link, Packet)> {
+        None
+    }
+    /// The packet this event delivers over a link, if any, so the executor can apply the link's
+    /// loss model (see `Executor::set_link_loss_model`).
+    fn arrival(&self) -> Option<(Link, Packet)> {
+        None
+    }
 }
 
-struct EventContainer(Box<Event>, Nanos);
+/// An event, when it is due, and its place in the order events were scheduled.
+#[derive(Serialize, Deserialize)]
+struct EventContainer(Box<Event>, Nanos, u64);
 
 impl EventContainer {
     fn abs_time(&self, now: Nanos) -> Nanos {
@@ -39,7 +69,7 @@ impl EventContainer {
 
 impl PartialEq for EventContainer {
     fn eq(&self, other: &EventContainer) -> bool {
-        self.1 == other.1
+        (self.1, self.2) == (other.1, other.2)
     }
 }
 
@@ -47,59 +77,160 @@ impl Eq for EventContainer{}
 
 impl PartialOrd for EventContainer {
     fn partial_cmp(&self, other: &EventContainer) -> Option<Ordering> {
-        other.1.partial_cmp(&self.1)
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for EventContainer {
     fn cmp(&self, other: &EventContainer) -> Ordering {
-        other.1.cmp(&self.1)
+        // BinaryHeap is a max-heap: the earliest time, then the earliest scheduled, is greatest
+        (other.1, other.2).cmp(&(self.1, self.2))
     }
 }
 
 use node::switch::Switch;
 
+/// No data packet reaching its destination for this many of the longest RTO of any incomplete
+/// sender is a stall: every sender would have timed out and retransmitted by then if it could.
+/// Retransmissions dropped or held on the way do not count. A stall with packets stuck (see
+/// `Executor::stuck_links`) is a deadlock.
+pub const STALL_RTOS: u64 = 3;
+
+/// Why `Executor::execute_with_reason` or `execute_until` returned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TerminationReason {
+    /// Nothing was left to do, and every flow had completed.
+    AllFlowsComplete,
+    /// Nothing was left to do, but some flows never completed, e.g. the receiver of a constant
+    /// bit rate flow which lost packets.
+    Idle,
+    /// The next event was due after the deadline passed to `execute_until`.
+    Deadline,
+    /// `execute_events` ran as many events as it was allowed to, and more were pending.
+    EventLimit,
+    /// Packets are stuck: nodes hold packets they cannot send, and either nothing is left to do
+    /// or no data has been delivered for `STALL_RTOS` RTOs. `Executor::stuck_links` names the links
+    /// involved.
+    Deadlock,
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Executor<S: Switch> {
     events: BinaryHeap<EventContainer>,
     current_time: Nanos,
     topology: Topology<S>,
+    #[serde(skip)]
     logger: Option<slog::Logger>,
+    rng: Rng,
+    scheduled: u64, // events pushed so far, to order events due at the same time
+    link_stats: LinkStats,
+    #[serde(with = "::checkpoint::pairs")]
+    link_loss: HashMap<(u32, u32), Box<LossModel>>, // (from, to) -> loss model
+    last_progress: Nanos, // when a data packet last reached its destination, or the run was last found idle
+    stall_window: Nanos, // how long after last_progress to look for a stall
 }
 
 impl<S: Switch> Executor<S> {
-    pub fn new(topology: Topology<S>, logger: impl Into<Option<slog::Logger>>) -> Self {
+    /// With a `filter`, only the lines it allows reach `logger`, e.g. no per-packet ones.
+    pub fn new(topology: Topology<S>, logger: impl Into<Option<slog::Logger>>, filter: impl Into<Option<LogFilter>>) -> Self {
+        Self::with_seed(topology, logger, filter, 0)
+    }
+
+    /// An executor whose `rng` is seeded with `seed`.
+    /// Each switch's random choices are seeded from it too.
+    pub fn with_seed(mut topology: Topology<S>, logger: impl Into<Option<slog::Logger>>, filter: impl Into<Option<LogFilter>>, seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        topology.switches.iter_mut().for_each(|s| s.seed_rng(rng.next_u64()));
         Executor{
             events: BinaryHeap::new(),
             current_time: 0,
             topology,
-            logger: logger.into(),
+            logger: filtered(logger.into(), filter.into()),
+            rng,
+            scheduled: 0,
+            link_stats: LinkStats::default(),
+            link_loss: HashMap::new(),
+            last_progress: 0,
+            stall_window: STALL_RTOS * DEFAULT_RTO,
         }
     }
 
+    /// The run's source of randomness. Seed each random component (loss models, RED, delay
+    /// distributions, workloads) from it, so that executors built with the same seed and given
+    /// the same topology and events produce identical runs.
+    pub fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
     pub fn components(&mut self) -> (Nanos, &mut Topology<S>, Option<&slog::Logger>) {
         (self.current_time, &mut self.topology, self.logger.as_ref())
     }
 
     pub fn push(&mut self, ev: Box<Event>) {
-        push_onto(self.current_time, ev, &mut self.events)
+        push_onto(self.current_time, ev, &mut self.events, &mut self.scheduled)
     }
 
-    fn poll_nodes(&mut self) {
+    /// Packets crossing the link from `from` to `to` are lost in flight when `model` says so.
+    /// Unlike a queue's drops, nobody hears of these: the sender only finds out when its packet
+    /// goes unacknowledged. The model sees a `LINK_LOSS_CONTEXT`, since a link has no buffer.
+    pub fn set_link_loss_model(&mut self, from: u32, to: u32, model: Box<LossModel>) {
+        self.link_loss.insert((from, to), model);
+    }
+
+    fn poll_nodes(&mut self) -> Result<()> {
         // advancing time
         // first, poll all active nodes
         let events_heap = &mut self.events;
+        let scheduled = &mut self.scheduled;
         let logger = self.logger.as_ref();
         let now = self.current_time;
         let top = &mut self.topology;
-        top
-            .active_nodes()
-            .filter_map(|n| n.exec(now, logger).ok())
-            .flat_map(|i| i)
-            .for_each(|new_ev| push_onto(now, new_ev, events_heap))
+        for n in top.active_nodes() {
+            // idle nodes return no events; an error is a real fault
+            for new_ev in n.exec(now, logger)? {
+                push_onto(now, new_ev, events_heap, scheduled);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run until nothing is left to do. A deadlock stops the run with an error (see
+    /// `execute_with_reason` to inspect it instead).
+    pub fn execute(self) -> Result<Self> {
+        let (e, reason) = self.run(None, None)?;
+        if let TerminationReason::Deadlock = reason {
+            bail!("deadlocked at {}: links {:?} hold packets they cannot send", e.current_time, e.stuck_links());
+        }
+
+        Ok(e)
     }
 
-    pub fn execute(mut self) -> Result<Self> {
+    /// Like `execute`, but also says why the run stopped.
+    pub fn execute_with_reason(self) -> Result<(Self, TerminationReason)> {
+        self.run(None, None)
+    }
+
+    /// Run until nothing is left to do or the next event is due after `deadline`, which is left
+    /// pending.
+    pub fn execute_until(self, deadline: Nanos) -> Result<(Self, TerminationReason)> {
+        self.run(Some(deadline), None)
+    }
+
+    /// Run until nothing is left to do or `max_events` events have run, leaving the rest
+    /// pending, e.g. to cut short a run which would never end.
+    pub fn execute_events(self, max_events: u64) -> Result<(Self, TerminationReason)> {
+        self.run(None, Some(max_events))
+    }
+
+    fn run(mut self, deadline: Option<Nanos>, max_events: Option<u64>) -> Result<(Self, TerminationReason)> {
+        let mut events_run = 0;
         loop {
+            if max_events.map_or(false, |max| events_run >= max) && !self.events.is_empty() {
+                return Ok(self.terminate(TerminationReason::EventLimit));
+            }
+
+
             match self.events.pop() {
                 Some(evc) => {
                     assert!(
@@ -109,17 +240,76 @@ impl<S: Switch> Executor<S> {
 
                     let evc = if evc.1 > self.current_time {
                         self.events.push(evc);
-                        self.poll_nodes();
-                        self.events.pop().unwrap() // guaranteed since we just pushed it back on
+                        self.poll_nodes()?;
+                        let next = self.events.peek().unwrap().1; // guaranteed since we just pushed it back on
+                        if deadline.map_or(false, |d| next > d) {
+                            return Ok(self.terminate(TerminationReason::Deadline));
+                        }
+
+                        if next > self.last_progress + self.stall_window {
+                            match self.longest_pending_rto() {
+                                Some(rto) if next > self.last_progress + STALL_RTOS * rto => {
+                                    if !self.stuck_links().is_empty() {
+                                        return Ok(self.terminate(TerminationReason::Deadlock));
+                                    }
+
+                                    // slow rather than stuck; check again a window from now
+                                    self.last_progress = next;
+                                }
+                                // a sender may yet time out and retransmit
+                                Some(rto) => self.stall_window = STALL_RTOS * rto,
+                                // no sender is waiting on anything
+                                None => self.last_progress = next,
+                            }
+                        }
+
+                        self.events.pop().unwrap()
                     } else {
                         evc
                     };
 
-                    self.current_time = evc.1;
+                    // the heap must yield the earliest event
+                    debug_assert!(
+                        self.events.peek().map_or(true, |next| next.1 >= evc.1),
+                        "popped event at {:?} before an earlier one", evc.1,
+                    );
 
-                    let mut ev = evc.0;
+                    let (mut ev, time) = (evc.0, evc.1);
                     let new_evs = {
                         let nds = &mut self.topology.lookup_nodes(&ev.affected_node_ids())?;
+                        if ev.is_stale(nds) {
+                            continue;
+                        }
+
+                        self.current_time = time;
+                        if let Some((l, p)) = ev.arrival() {
+                            let lost = self.link_loss.get_mut(&(l.from, l.to))
+                                .map_or(false, |m| m.should_drop(&p, &LINK_LOSS_CONTEXT));
+                            if lost {
+                                self.link_stats.record_loss(l, &p);
+                                if let Some(ref log) = self.logger {
+                                    debug!(log, #PACKET, "lost on link";
+                                        "time" => time,
+                                        "from" => l.from,
+                                        "to" => l.to,
+                                        "packet" => ?p,
+                                    );
+                                }
+
+                                events_run += 1;
+                                continue;
+                            }
+                        }
+
+                        if let Some((l, p)) = ev.transmission() {
+                            self.link_stats.record(l, &p);
+                            match p {
+                                Packet::Data{hdr, ..} if l.to == hdr.to => self.last_progress = time,
+                                _ => (),
+                            }
+                        }
+
+                        events_run += 1;
                         ev.exec(self.current_time, nds, self.logger.as_ref())?
                     };
                     for new_ev in new_evs {
@@ -127,28 +317,523 @@ impl<S: Switch> Executor<S> {
                     }
                 }
                 None => {
-                    self.poll_nodes(); // try to poll nodes one last time
+                    self.poll_nodes()?; // try to poll nodes one last time
                     if self.events.is_empty() {
-                        if let Some(ref log) = self.logger {
-                            info!(log, "exiting";
-                                "time" => self.current_time,
-                            );
-                        }
+                        // nodes may stay active with nothing to do, so only stuck packets count
+                        let reason = if !self.stuck_links().is_empty() {
+                            TerminationReason::Deadlock
+                        } else if self.topology.all_flows().all(|f| f.completion_time().is_some()) {
+                            TerminationReason::AllFlowsComplete
+                        } else {
+                            TerminationReason::Idle
+                        };
 
-                        return Ok(self);
+                        return Ok(self.terminate(reason));
                     }
                 }
             }
         }
     }
 
+    fn terminate(self, reason: TerminationReason) -> (Self, TerminationReason) {
+        if let Some(ref log) = self.logger {
+            info!(log, "exiting";
+                "time" => self.current_time,
+                "reason" => ?reason,
+                "all_flows_complete" => ?self.all_flows_complete_time(),
+            );
+
+            if let TerminationReason::Deadlock = reason {
+                warn!(log, "deadlock";
+                    "time" => self.current_time,
+                    "stuck_links" => ?self.stuck_links(),
+                );
+            }
+        }
+
+        (self, reason)
+    }
+
+    /// The `(node, neighbor)` pairs where the node holds packets it has not sent to the
+    /// neighbor, or, for a host, has an incomplete flow in a class the neighbor PAUSEd. Once the
+    /// run stops with `TerminationReason::Deadlock`, these are the links whose PAUSEs (or missing
+    /// credits) blocked each other.
+    pub fn stuck_links(&self) -> Vec<(u32, u32)> {
+        let hosts = self.topology.hosts.iter().map(|h| h as &Node);
+        let switches = self.topology.switches.iter().map(|s| s as &Node);
+        let nodes = hosts.chain(switches).collect::<Vec<&Node>>();
+        let mut stuck = nodes.iter()
+            .flat_map(|n| nodes.iter()
+                .filter(move |to| n.queue_occupancy(to.id()).map_or(false, |b| b > 0))
+                .map(move |to| (n.id(), to.id())))
+            .collect::<Vec<(u32, u32)>>();
+
+        // paused flows hold their data back rather than queueing it
+        let paused_hosts = self.topology.hosts.iter()
+            .filter(|h| h.active_flows.iter().any(|f| match f.side() {
+                FlowSide::Sender => f.completion_time().is_none() && h.paused.contains(&f.flow_info().priority),
+                FlowSide::Receiver => false,
+            }))
+            .map(|h| (h.id, h.link.to))
+            .filter(|l| !stuck.contains(l))
+            .collect::<Vec<(u32, u32)>>();
+        stuck.extend(paused_hosts);
+        stuck
+    }
+
+    /// The longest retransmission timeout of any sender yet to complete, if there is one.
+    fn longest_pending_rto(&self) -> Option<Nanos> {
+        self.topology.all_flows()
+            .filter(|f| match f.side() {
+                FlowSide::Sender => f.completion_time().is_none(),
+                FlowSide::Receiver => false,
+            })
+            .map(|f| f.flow_info().rto.unwrap_or(DEFAULT_RTO))
+            .max()
+    }
+
+    /// After `execute`, this is when the network went quiescent: when the last packet in
+    /// flight, e.g. a final ACK, arrived.
     pub fn current_time(&self) -> Nanos {
         self.current_time
     }
+
+    /// The bytes transmitted on each link so far.
+    pub fn link_stats(&self) -> &LinkStats {
+        &self.link_stats
+    }
+
+    /// When the last byte of the last flow reached its receiver, once every flow has completed.
+    /// ACKs may still be in flight then, so this can precede the quiescent `current_time`.
+    pub fn all_flows_complete_time(&self) -> Option<Nanos> {
+        let flows = self.topology.all_flows().collect::<Vec<_>>();
+        if flows.is_empty() || flows.iter().any(|f| f.completion_time().is_none()) {
+            return None;
+        }
+
+        flows.iter()
+            .filter(|f| match f.side() {
+                FlowSide::Receiver => true,
+                _ => false,
+            })
+            .filter_map(|f| Some(f.start_time()? + f.completion_time()?))
+            .max()
+    }
+}
+
+impl<S: Switch + Serialize> Executor<S> {
+    /// Save the simulation as it stands, pending events and all, to `w` as JSON, e.g. to run
+    /// several experiments from the end of one warm-up (see `execute_until` and `resume`).
+    ///
+    /// Fails on state a checkpoint cannot hold: flow completion listeners (closed-loop and DAG
+    /// workloads), pending sampling events, whose traces the resumed run could not record into,
+    /// and events, flows, queues, or loss models this crate does not provide (see `checkpoint`).
+    /// Schedule those after resuming.
+    pub fn checkpoint<W: Write>(&self, w: W) -> Result<()> {
+        if let Some(h) = self.topology.hosts.iter().find(|h| !h.completion_listeners.is_empty()) {
+            bail!("cannot checkpoint host {}'s flow completion listeners", h.id);
+        }
+
+        serde_json::to_writer(w, self)?;
+        Ok(())
+    }
+}
+
+impl<S: Switch + DeserializeOwned> Executor<S> {
+    /// Resume a simulation saved with `checkpoint`, logging as `new` does.
+    pub fn resume<R: Read>(r: R, logger: impl Into<Option<slog::Logger>>, filter: impl Into<Option<LogFilter>>) -> Result<Self> {
+        let mut e: Self = serde_json::from_reader(r)?;
+        e.logger = filtered(logger.into(), filter.into());
+        Ok(e)
+    }
 }
 
-fn push_onto(now: Nanos, ev: Box<Event>, heap: &mut BinaryHeap<EventContainer>) {
-    let mut evc = EventContainer(ev, 0);
+impl<S: Switch + Serialize + DeserializeOwned> Executor<S> {
+    /// A copy of the simulation as it stands, which runs independently of this one: a checkpoint
+    /// (see `checkpoint`, which says what it refuses to copy) resumed at once, logging to the same
+    /// logger. A queue's occupancy trace is copied along with it.
+    pub fn fork(&self) -> Result<Self> {
+        let mut saved = vec![];
+        self.checkpoint(&mut saved)?;
+        let mut e = Self::resume(&saved[..], None, None)?;
+        e.logger = self.logger.clone();
+        Ok(e)
+    }
+}
+
+fn filtered(logger: Option<slog::Logger>, filter: Option<LogFilter>) -> Option<slog::Logger> {
+    logger.map(|l| match filter {
+        Some(ref f) => f.apply(l),
+        None => l,
+    })
+}
+
+fn push_onto(now: Nanos, ev: Box<Event>, heap: &mut BinaryHeap<EventContainer>, scheduled: &mut u64) {
+    let mut evc = EventContainer(ev, 0, *scheduled);
+    *scheduled += 1;
     evc.1 = evc.abs_time(now);
     heap.push(evc);
 }
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+    use std::sync::{Arc, Mutex};
+
+    use slog;
+
+    use ::{Nanos, Result};
+    use congcontrol::{ConstCwnd, Dctcp, NewReno};
+    use flow::{FlowArrivalEvent, FlowInfo, DEFAULT_RTO, go_back_n};
+    use flow::selective_repeat::SelectiveRepeatFlowArrivalEvent;
+    use node::{LinkTransmitEvent, Node};
+    use node::switch::Queue;
+    use node::switch::lossy_switch::LossySwitch;
+    use node::switch::pfc_switch::PFCSwitch;
+    use node::switch::priority_queue::PriorityQueue;
+    use packet::{Packet, PacketHeader, PACKET_HEADER_SIZE_BYTES};
+    use topology::TopologyStrategy;
+    use topology::one_big_switch::OneBigSwitch;
+    use super::{Event, EventTime, Executor, TerminationReason, STALL_RTOS};
+
+    /// Records when it runs, then optionally schedules a follow-up `Delta` later.
+    #[derive(Clone)]
+    struct RecordEvent(EventTime, Option<Nanos>, Arc<Mutex<Vec<Nanos>>>);
+
+    impl Event for RecordEvent {
+        fn time(&self) -> EventTime {
+            match self.0 {
+                EventTime::Absolute(t) => EventTime::Absolute(t),
+                EventTime::Delta(t) => EventTime::Delta(t),
+            }
+        }
+
+        fn affected_node_ids(&self) -> Vec<u32> {
+            vec![0]
+        }
+
+        fn exec(&mut self, time: Nanos, _nodes: &mut [&mut Node], _logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+            self.2.lock().unwrap().push(time);
+            Ok(self.1.take().map(|d| {
+                Box::new(RecordEvent(EventTime::Delta(d), None, self.2.clone())) as Box<Event>
+            }).into_iter().collect())
+        }
+    }
+
+    #[test]
+    fn events_pop_in_time_order() {
+        let t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+        let times = Arc::new(Mutex::new(vec![]));
+        let ev = |time, next| Box::new(RecordEvent(time, next, times.clone()));
+
+        e.push(ev(EventTime::Absolute(50), None));
+        e.push(ev(EventTime::Delta(10), Some(35))); // schedules another at 45
+        e.push(ev(EventTime::Absolute(5), None));
+        e.push(ev(EventTime::Delta(40), Some(30))); // schedules another at 70
+        e.push(ev(EventTime::Absolute(60), None));
+        e.push(ev(EventTime::Delta(1), None));
+
+        let e = e.execute().unwrap();
+        assert_eq!(*times.lock().unwrap(), vec![1, 5, 10, 40, 45, 50, 60, 70]);
+        assert_eq!(e.current_time(), 70);
+    }
+
+    #[test]
+    fn idle_host_exec_is_ok() {
+        let t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+        let host = &mut e.components().1.hosts[0];
+        host.active = true;
+
+        assert!(host.exec(0, None).unwrap().is_empty());
+        assert!(!host.is_active());
+    }
+
+    #[test]
+    fn node_errors_stop_execution() {
+        let t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+
+        // added directly rather than by a flow arrival, so no packet length is filled in and
+        // the sender fails when the host polls it
+        let (sender, _) = go_back_n::new::<ConstCwnd>(FlowInfo::builder(1, 0, 1)
+            .length_bytes(14600)
+            .build());
+        e.components().1.hosts[0].flow_arrival(sender);
+
+        assert!(e.execute().is_err());
+    }
+
+    /// Records its label when it runs.
+    #[derive(Clone)]
+    struct LabelEvent(Nanos, u32, Arc<Mutex<Vec<u32>>>);
+
+    impl Event for LabelEvent {
+        fn time(&self) -> EventTime {
+            EventTime::Absolute(self.0)
+        }
+
+        fn affected_node_ids(&self) -> Vec<u32> {
+            vec![0]
+        }
+
+        fn exec(&mut self, _time: Nanos, _nodes: &mut [&mut Node], _logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+            self.2.lock().unwrap().push(self.1);
+            Ok(vec![])
+        }
+    }
+
+    #[test]
+    fn simultaneous_events_run_in_scheduled_order() {
+        let t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+        let labels = Arc::new(Mutex::new(vec![]));
+        for &(time, label) in &[(20, 0), (10, 1), (20, 2), (10, 3), (20, 4), (10, 5), (10, 6)] {
+            e.push(Box::new(LabelEvent(time, label, labels.clone())));
+        }
+
+        e.execute().unwrap();
+        assert_eq!(*labels.lock().unwrap(), vec![1, 3, 5, 6, 0, 2, 4]);
+    }
+
+    #[test]
+    fn deliveries_run_before_transmissions() {
+        // hosts 0, 1, 2 and the switch, whose port to host 0 serves class 0 first
+        let mut t = OneBigSwitch::<LossySwitch>::make_topology(3, 15_000, 1_000_000, 1_000_000);
+        let port = t.switches[0].rack.iter().position(|q| q.link().to == 0).unwrap();
+        let link = t.switches[0].rack[port].link();
+        t.switches[0].rack[port] = Box::new(PriorityQueue::new(2, 15_000, link)) as Box<Queue>;
+        let (from_1, from_2) = (t.hosts[1].link, t.hosts[2].link);
+        let mut e = Executor::new(t, None, None);
+
+        let pkt = |from, priority| Packet::Mirror{
+            hdr: PacketHeader{
+                flow: from,
+                from,
+                to: 0,
+                priority,
+                size_bytes: PACKET_HEADER_SIZE_BYTES,
+            },
+            orig: PacketHeader{
+                flow: from,
+                from,
+                to: 0,
+                priority,
+                size_bytes: PACKET_HEADER_SIZE_BYTES,
+            },
+            seq: 0,
+            length: 1500,
+        };
+
+        // both arrive at the switch at the same time, the low-priority one scheduled first.
+        // had the switch been polled between them, it would have started sending that one.
+        e.push(Box::new(LinkTransmitEvent(from_1, pkt(1, 1))));
+        e.push(Box::new(LinkTransmitEvent(from_2, pkt(2, 0))));
+        let mut e = e.execute().unwrap();
+
+        let received = e.components().1.hosts[0].mirrored.iter()
+            .map(|p| match *p {
+                Packet::Mirror{hdr, ..} => hdr.flow,
+                _ => unreachable!(),
+            })
+            .collect::<Vec<u32>>();
+        assert_eq!(received, vec![2, 1]);
+    }
+
+    #[test]
+    fn pause_without_resume_is_a_deadlock() {
+        let run = || {
+            let t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+            let port = t.switches[0].rack.iter().position(|q| q.link().to == 0).unwrap();
+            let link = t.switches[0].rack[port].link();
+            let mut e = Executor::new(t, None, None);
+
+            // the switch pauses host 0 before its flow starts, and never resumes it
+            e.push(Box::new(LinkTransmitEvent(link, Packet::Pause(2, 0))));
+            let flowinfo = FlowInfo::builder(1, 0, 1)
+                .length_bytes(14600)
+                .build();
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+            e
+        };
+
+        // the sender's timer keeps firing, so the events would never run out
+        let (e, reason) = run().execute_with_reason().unwrap();
+        assert_eq!(reason, TerminationReason::Deadlock);
+        assert!(e.stuck_links().contains(&(0, 2)), "{:?}", e.stuck_links());
+        assert!(e.current_time() <= 1_000_000_000 + STALL_RTOS * DEFAULT_RTO);
+
+        // a plain run cannot mistake the deadlock for completion
+        assert!(run().execute().is_err());
+    }
+
+    #[test]
+    fn cyclic_pause_is_a_deadlock() {
+        use node::switch::pfc_switch::PFCSwitch;
+        use topology::dumbbell::{Bottleneck, Dumbbell};
+
+        // hosts 0 and 1 on the left switch (4), 2 and 3 on the right switch (5), with each
+        // side sending across a slow bottleneck to the other. Naive PFC pauses every ingress
+        // once any egress fills, so the left switch's queue to the right waits on the right
+        // switch's queue to the left, and that one on the first: a buffer dependency cycle.
+        let bottleneck = Bottleneck{
+            bandwidth_bps: 1_000_000,
+            queue_length_bytes: 15_000,
+        };
+        let t = Dumbbell::<PFCSwitch>::make_dumbbell(2, 2, 15_000, 10_000_000, bottleneck, 1_000_000);
+        let (left, right) = (t.switches[0].id(), t.switches[1].id());
+        let mut e = Executor::new(t, None, None);
+        for &(id, from, to) in &[(1, 0, 2), (2, 1, 3), (3, 2, 0), (4, 3, 1)] {
+            let flowinfo = FlowInfo::builder(id, from, to)
+                .length_bytes(438000) // 300 packet flow
+                .build();
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        // both bottleneck queues fill together, each switch pauses the other, and neither
+        // can then send the RESUME queued behind its paused bottleneck
+        let (e, reason) = e.execute_with_reason().unwrap();
+        assert_eq!(reason, TerminationReason::Deadlock);
+        let stuck = e.stuck_links();
+        assert!(stuck.contains(&(left, right)), "{:?}", stuck);
+        assert!(stuck.contains(&(right, left)), "{:?}", stuck);
+        assert!(e.topology.all_flows().any(|f| f.completion_time().is_none()));
+    }
+
+    #[test]
+    fn execute_until_leaves_later_events_pending() {
+        let t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(14600) // 10 packet flow
+            .build();
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+
+        let (e, reason) = e.execute_until(1_050_000_000).unwrap();
+        assert_eq!(reason, TerminationReason::Deadline);
+        assert!(e.current_time() <= 1_050_000_000);
+
+        // picking up where it stopped
+        let (e, reason) = e.execute_with_reason().unwrap();
+        assert_eq!(reason, TerminationReason::AllFlowsComplete);
+        assert!(e.all_flows_complete_time().unwrap() > 1_050_000_000);
+    }
+
+    #[test]
+    fn execute_events_stops_at_the_cap() {
+        let t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(14600) // 10 packet flow
+            .build();
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+
+        let (e, reason) = e.execute_events(10).unwrap();
+        assert_eq!(reason, TerminationReason::EventLimit);
+        assert!(e.all_flows_complete_time().is_none());
+
+        // a cap the run never reaches
+        let (_, reason) = e.execute_events(1_000_000).unwrap();
+        assert_eq!(reason, TerminationReason::AllFlowsComplete);
+    }
+
+    #[test]
+    fn forked_run_matches_uninterrupted_run() {
+        let run = || {
+            let t = OneBigSwitch::<LossySwitch>::make_topology(3, 15_000, 1_000_000, 1_000_000);
+            let mut e = Executor::new(t, None, None);
+            for &(id, from, start) in &[(1, 1, 1_000_000_000), (2, 2, 1_020_000_000)] {
+                let flowinfo = FlowInfo::builder(id, from, 0)
+                    .length_bytes(43800) // 30 packet flow
+                    .build();
+                e.push(Box::new(FlowArrivalEvent(flowinfo, start, PhantomData::<ConstCwnd>)));
+            }
+
+            e
+        };
+
+        let whole = run().execute().unwrap();
+
+        // both flows are mid-transfer, with packets queued and in flight, at the checkpoint
+        let (warm, reason) = run().execute_until(1_100_000_000).unwrap();
+        assert_eq!(reason, TerminationReason::Deadline);
+        let mut fork = warm.fork().unwrap();
+        let mut resumed = warm.execute().unwrap();
+        assert_eq!(resumed.current_time(), whole.current_time());
+        assert_eq!(resumed.all_flows_complete_time(), whole.all_flows_complete_time());
+
+        // the fork runs the same way on its own, and changes to it do not reach the original
+        let forked = fork.fork().unwrap().execute().unwrap();
+        assert_eq!(forked.current_time(), whole.current_time());
+        let flowinfo = FlowInfo::builder(3, 1, 2)
+            .length_bytes(43800)
+            .build();
+        fork.push(Box::new(FlowArrivalEvent(flowinfo, 1_200_000_000, PhantomData::<ConstCwnd>)));
+        let mut fork = fork.execute().unwrap();
+        assert_eq!(fork.components().1.all_flows().count(), 6);
+        assert!(fork.components().1.all_flows().all(|f| f.completion_time().is_some()));
+        assert_eq!(resumed.components().1.all_flows().count(), 4);
+    }
+
+    #[test]
+    fn checkpoint_then_resume_matches_uninterrupted_run() {
+        let run = || {
+            let t = OneBigSwitch::<PFCSwitch>::make_topology(3, 15_000, 1_000_000, 1_000_000);
+            let mut e = Executor::new(t, None, None);
+            let flowinfo = FlowInfo::builder(1, 1, 0)
+                .length_bytes(43800) // 30 packet flow
+                .build();
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<NewReno>)));
+            let flowinfo = FlowInfo::builder(2, 2, 0)
+                .length_bytes(43800)
+                .build();
+            e.push(Box::new(SelectiveRepeatFlowArrivalEvent(flowinfo, 1_020_000_000, PhantomData::<Dctcp>)));
+            e
+        };
+
+        let whole = run().execute().unwrap();
+
+        let (warm, reason) = run().execute_until(1_100_000_000).unwrap();
+        assert_eq!(reason, TerminationReason::Deadline);
+        let mut saved = vec![];
+        warm.checkpoint(&mut saved).unwrap();
+        drop(warm);
+
+        let resumed = Executor::<PFCSwitch>::resume(&saved[..], None, None).unwrap().execute().unwrap();
+        assert_eq!(resumed.current_time(), whole.current_time());
+        assert_eq!(resumed.all_flows_complete_time(), whole.all_flows_complete_time());
+    }
+
+    #[test]
+    fn fork_copies_occupancy_traces_and_refuses_shared_traces() {
+        use node::switch::drop_tail_queue::DropTailQueue;
+        use stats::{OccupancyTrace, QueueSampleEvent, QueueTrace};
+
+        let mut t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let trace = OccupancyTrace::new(1_000);
+        let port = t.switches[0].rack.iter().position(|q| q.link().to == 0).unwrap();
+        let mut q = DropTailQueue::new(15_000, t.switches[0].rack[port].link());
+        q.set_occupancy_trace(Some(trace.clone()));
+        t.switches[0].rack[port] = Box::new(q) as Box<Queue>;
+        let mut e = Executor::new(t, None, None);
+        let flowinfo = FlowInfo::builder(1, 1, 0)
+            .length_bytes(43800) // 30 packet flow
+            .build();
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+
+        let (mut e, _) = e.execute_until(1_100_000_000).unwrap();
+        let samples_at_fork = trace.samples().len();
+        assert!(samples_at_fork > 0);
+
+        // the fork records into its own copy of the trace
+        let mut fork = e.fork().unwrap().execute().unwrap();
+        assert_eq!(trace.samples().len(), samples_at_fork);
+        let fork_trace = fork.components().1.switches[0].rack[port].occupancy_trace().unwrap();
+        assert!(fork_trace.samples().len() > samples_at_fork);
+
+        // a pending sampling event would record both runs into one trace
+        e.push(Box::new(QueueSampleEvent::new(1_200_000_000, 10_000_000, 2_000_000_000, 2, 0, QueueTrace::default())));
+        assert!(e.fork().is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use ::{Nanos, Result};
+use congcontrol::CongAlg;
+use event::{Event, Executor};
+use node::switch::Switch;
+use rng::Rng;
+use super::{FlowArrivalEvent, FlowCompletionListener, FlowInfo};
+
+/// One request issued by a `ClosedLoopFlow`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Request {
+    /// Which of the client's `concurrency` outstanding-request slots issued this request.
+    pub slot: u32,
+    pub issued: Nanos,
+    /// When the response completed.
+    pub responded: Option<Nanos>,
+    /// How long the client thought after the response before issuing its next request.
+    pub think_time: Option<Nanos>,
+}
+
+/// A closed-loop client: each of `concurrency` slots sends a `request_bytes` request flow from
+/// `client` to `server`, waits for the `response_bytes` response flow back, thinks for an
+/// exponentially distributed time with mean `mean_think_time`, then repeats, until
+/// `num_requests` requests have been issued in total.
+///
+/// A request (or response) is done when its sender gets the final ACK.
+/// Flows get ids from `first_flow_id` on, which must not collide with other flows' ids.
+#[derive(Clone, Debug)]
+pub struct ClosedLoopWorkload {
+    pub client: u32,
+    pub server: u32,
+    pub request_bytes: u32,
+    pub response_bytes: u32,
+    pub concurrency: u32,
+    pub mean_think_time: Nanos,
+    pub num_requests: u32,
+    pub first_flow_id: u32,
+}
+
+impl ClosedLoopWorkload {
+    /// Start every slot at `start`, registering the client's `ClosedLoopFlow` with the client
+    /// and server `Host`s in `e`. Think times are drawn from a `Rng` seeded with `seed`.
+    pub fn schedule<CC: CongAlg + 'static, S: Switch>(
+        &self,
+        e: &mut Executor<S>,
+        start: Nanos,
+        seed: u64,
+    ) -> Result<Rc<RefCell<ClosedLoopFlow<CC>>>> {
+        let flow = Rc::new(RefCell::new(ClosedLoopFlow{
+            workload: self.clone(),
+            next_flow_id: self.first_flow_id,
+            rng: Rng::new(seed),
+            requests: vec![],
+            flows: HashMap::new(),
+            _cc: PhantomData,
+        }));
+
+        {
+            let topo = e.components().1;
+            topo.lookup_host(self.client)?.completion_listeners.push(flow.clone());
+            topo.lookup_host(self.server)?.completion_listeners.push(flow.clone());
+        }
+
+        for slot in 0..self.concurrency {
+            if let Some(ev) = flow.borrow_mut().issue(slot, start) {
+                e.push(ev);
+            }
+        }
+
+        Ok(flow)
+    }
+}
+
+/// Drives the request-response cycle of a `ClosedLoopWorkload`, and records its requests.
+#[derive(Debug)]
+pub struct ClosedLoopFlow<CC: CongAlg> {
+    workload: ClosedLoopWorkload,
+    next_flow_id: u32,
+    rng: Rng,
+    requests: Vec<Request>,
+    /// flow id -> (index into `requests`, whether the flow is the request rather than the response)
+    flows: HashMap<u32, (usize, bool)>,
+    _cc: PhantomData<CC>,
+}
+
+impl<CC: CongAlg + 'static> ClosedLoopFlow<CC> {
+    /// Every request issued so far, in order.
+    pub fn requests(&self) -> &[Request] {
+        &self.requests
+    }
+
+    /// Requests issued and not yet responded to at `time`.
+    pub fn outstanding(&self, time: Nanos) -> usize {
+        self.requests.iter()
+            .filter(|r| r.issued <= time && r.responded.map_or(true, |t| t > time))
+            .count()
+    }
+
+    fn flow(&mut self, sender_id: u32, dest_id: u32, length_bytes: u32, request: usize, is_request: bool) -> FlowInfo {
+        let flow_id = self.next_flow_id;
+        self.next_flow_id += 1;
+        self.flows.insert(flow_id, (request, is_request));
+        FlowInfo::builder(flow_id, sender_id, dest_id)
+            .length_bytes(length_bytes)
+            .build()
+    }
+
+    fn issue(&mut self, slot: u32, time: Nanos) -> Option<Box<Event>> {
+        if self.requests.len() as u32 >= self.workload.num_requests {
+            return None;
+        }
+
+        self.requests.push(Request{slot, issued: time, responded: None, think_time: None});
+        let (client, server, length) = (self.workload.client, self.workload.server, self.workload.request_bytes);
+        let idx = self.requests.len() - 1;
+        let f = self.flow(client, server, length, idx, true);
+        Some(Box::new(FlowArrivalEvent(f, time, PhantomData::<CC>)))
+    }
+}
+
+impl<CC: CongAlg + 'static> FlowCompletionListener for ClosedLoopFlow<CC> {
+    fn on_completion(&mut self, flow: FlowInfo, time: Nanos) -> Vec<Box<Event>> {
+        // the listener is shared with other flows from the client and server, so ignore them
+        let (idx, is_request) = match self.flows.remove(&flow.flow_id) {
+            Some(x) => x,
+            None => return vec![],
+        };
+
+        if is_request {
+            let (client, server, length) = (self.workload.client, self.workload.server, self.workload.response_bytes);
+            let f = self.flow(server, client, length, idx, false);
+            return vec![Box::new(FlowArrivalEvent(f, time, PhantomData::<CC>))];
+        }
+
+        // exponential think time; 1 - u is in (0, 1], so ln() is finite
+        let think_time = (-(1.0 - self.rng.next_f64()).ln() * self.workload.mean_think_time as f64) as Nanos;
+        let slot = {
+            let r = &mut self.requests[idx];
+            r.responded = Some(time);
+            r.think_time = Some(think_time);
+            r.slot
+        };
+
+        self.issue(slot, time + think_time).into_iter().collect()
+    }
+}
@@ -0,0 +1,281 @@
+use std::any::Any;
+
+use slog;
+
+use ::{Nanos, Result};
+use event::{Event, EventTime};
+use node::Node;
+use packet::{Packet, PacketHeader};
+use super::{Flow, FlowInfo, FlowSide, TransmitCounts, flow_node_ids, packet_deadline, start_flow};
+
+/// Starts an open-loop, constant bit rate flow at time `.1`, sending at `.2` bits per second.
+/// With a stop time `.3`, the flow ignores `length_bytes` and sends until then; otherwise it
+/// stops once it has sent `length_bytes`.
+#[derive(Serialize, Deserialize)]
+pub struct ConstantBitRateFlowArrivalEvent(pub FlowInfo, pub Nanos, pub u64, pub Option<Nanos>);
+
+impl Event for ConstantBitRateFlowArrivalEvent {
+    fn time(&self) -> EventTime {
+        EventTime::Absolute(self.1)
+    }
+
+    fn affected_node_ids(&self) -> Vec<u32> {
+        flow_node_ids(&self.0)
+    }
+
+    fn exec(&mut self, _time: Nanos, nodes: &mut [&mut Node], _logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        let (rate_bps, until) = (self.2, self.3);
+        if rate_bps == 0 {
+            bail!("constant bit rate flow {} has a rate of 0 bps", self.0.flow_id);
+        }
+
+        start_flow(self.0, nodes, |fi| new(fi, rate_bps, until))
+    }
+}
+
+/// A UDP-style flow: the sender puts a packet on the wire every time `rate_bps` allows,
+/// whatever happens to them, and never retransmits. The receiver just counts what arrives and
+/// sends nothing back, so the flow neither reacts to congestion nor recovers from loss.
+///
+/// The sender completes when it stops sending; the receiver completes only if every byte
+/// arrived. `rate_bps` must not be 0, which `ConstantBitRateFlowArrivalEvent` checks.
+pub fn new(fi: FlowInfo, rate_bps: u64, until: Option<Nanos>) -> (Box<ConstantBitRateSender>, Box<ConstantBitRateReceiver>) {
+    (
+        Box::new(ConstantBitRateSender{
+            flow_info: fi,
+            rate_bps,
+            until,
+            start_time: None,
+            completion_time: None,
+            transmit_completion_time: None,
+            transmit_counts: TransmitCounts::default(),
+            next_send: 0,
+            next_seq: 0,
+        }),
+        Box::new(ConstantBitRateReceiver{
+            flow_info: fi,
+            received_bytes: 0,
+            start_time: None,
+            completion_time: None,
+        }),
+    )
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConstantBitRateSender {
+    flow_info: FlowInfo,
+    rate_bps: u64,
+    until: Option<Nanos>, // absolute stop time
+    start_time: Option<Nanos>,
+    completion_time: Option<Nanos>,
+    transmit_completion_time: Option<Nanos>,
+    transmit_counts: TransmitCounts,
+    next_send: Nanos, // when the next packet is due
+    next_seq: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConstantBitRateReceiver {
+    flow_info: FlowInfo,
+    received_bytes: u32,
+    start_time: Option<Nanos>,
+    completion_time: Option<Nanos>,
+}
+
+impl ConstantBitRateSender {
+    /// Whether every packet the flow will send has been handed to the host.
+    fn finished(&self) -> bool {
+        match self.until {
+            Some(t) => self.next_send >= t,
+            None => self.next_seq >= self.flow_info.length_bytes,
+        }
+    }
+
+    /// The next packet, and the time the one after it is due.
+    fn next_packet(&self, max_packet_length: u32) -> (Packet, Nanos) {
+        let length = match self.until {
+            Some(_) => max_packet_length,
+            None => ::std::cmp::min(max_packet_length, self.flow_info.length_bytes - self.next_seq),
+        };
+
+        let pkt = Packet::Data{
+            hdr: PacketHeader{
+                flow: self.flow_info.flow_id,
+                from: self.flow_info.sender_id,
+                to: self.flow_info.dest_id,
+                priority: self.flow_info.priority,
+                size_bytes: self.flow_info.header_size_bytes(),
+            },
+            seq: self.next_seq,
+            length,
+            ect: self.flow_info.ecn_capable,
+            ce: false,
+            sent: 0, // stamped by the host when it transmits the packet
+            deadline: packet_deadline(self.flow_info, self.start_time),
+            trimmed: false,
+        };
+
+        let interval = u64::from(pkt.get_size_bytes()) * 8 * 1_000_000_000 / self.rate_bps;
+        (pkt, self.next_send + interval)
+    }
+}
+
+impl Flow for ConstantBitRateSender {
+    fn flow_info(&self) -> FlowInfo { self.flow_info }
+    fn side(&self) -> FlowSide { FlowSide::Sender }
+    fn as_any(&self) -> &Any { self }
+
+    fn start_time(&self) -> Option<Nanos> {
+        self.start_time
+    }
+
+    fn completion_time(&self) -> Option<Nanos> {
+        self.completion_time
+    }
+
+    fn transmit_completion_time(&self) -> Option<Nanos> {
+        self.transmit_completion_time
+    }
+
+    fn delivered_bytes(&self) -> u32 {
+        // nothing comes back, so the sender cannot tell
+        0
+    }
+
+    fn jitter(&self) -> Option<f64> {
+        None
+    }
+
+    fn receive(&mut self, _time: Nanos, _pkt: Packet, _logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
+        // open loop: nothing the network says changes what we send
+        Ok((vec![], false))
+    }
+
+    fn exec(&mut self, time: Nanos, logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
+        if let None = self.start_time {
+            self.start_time = Some(time);
+            self.next_send = time;
+        }
+
+        let max_packet_length = self.flow_info.max_packet_length
+            .ok_or_else(|| format_err!("flow {} has no packet length", self.flow_info.flow_id))?;
+        let mut pkts = vec![];
+        while !self.finished() && self.next_send <= time {
+            let (pkt, next_send) = self.next_packet(max_packet_length);
+            if let Packet::Data{length, ..} = pkt {
+                self.next_seq = self.next_seq.wrapping_add(length);
+            }
+
+            self.next_send = next_send;
+            pkts.push(pkt);
+        }
+
+        if let (Some(until), None) = (self.until, self.completion_time) {
+            if self.finished() {
+                self.completion_time = Some(until - self.start_time.unwrap());
+                if let Some(log) = logger {
+                    info!(log, "flow completed";
+                        "node" => self.flow_info.sender_id,
+                        "completion_time" => self.completion_time.unwrap(),
+                        "start_time" => self.start_time.unwrap(),
+                        "end_time" => until,
+                    );
+                }
+            }
+        }
+
+        Ok((pkts, false))
+    }
+
+    fn transmitted(&mut self, time: Nanos, pkt: Packet) {
+        if let Packet::Data{seq, length, ..} = pkt {
+            self.transmit_counts.record(seq, length);
+            let start = self.start_time.unwrap();
+            self.transmit_completion_time = Some(time - start);
+            if self.until.is_none() && seq + length == self.flow_info.length_bytes {
+                // there is no ACK to wait for
+                self.completion_time = Some(time - start);
+            }
+        }
+    }
+
+    fn transmit_counts(&self) -> TransmitCounts {
+        self.transmit_counts
+    }
+
+    fn on_timer(&mut self, time: Nanos) -> Option<Nanos> {
+        if self.completion_time.is_some() || (self.start_time.is_some() && self.finished() && self.until.is_none()) {
+            return None;
+        }
+
+        if self.next_send > time {
+            Some(self.next_send - time)
+        } else {
+            // the host sends what is due when it next polls, which the timer firing causes;
+            // check again a packet's time later
+            let max_packet_length = self.flow_info.max_packet_length.unwrap_or(1);
+            let (_, next_send) = self.next_packet(max_packet_length);
+            Some(::std::cmp::max(next_send - self.next_send, 1))
+        }
+    }
+}
+
+impl Flow for ConstantBitRateReceiver {
+    fn flow_info(&self) -> FlowInfo { self.flow_info }
+    fn side(&self) -> FlowSide { FlowSide::Receiver }
+    fn as_any(&self) -> &Any { self }
+
+    fn start_time(&self) -> Option<Nanos> {
+        self.start_time
+    }
+
+    fn completion_time(&self) -> Option<Nanos> {
+        self.completion_time
+    }
+
+    fn transmit_completion_time(&self) -> Option<Nanos> {
+        None
+    }
+
+    fn delivered_bytes(&self) -> u32 {
+        self.received_bytes
+    }
+
+    fn jitter(&self) -> Option<f64> {
+        None
+    }
+
+    fn receive(&mut self, time: Nanos, pkt: Packet, logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
+        if let None = self.start_time {
+            self.start_time = Some(time);
+        }
+
+        if let Packet::Data{length, ..} = pkt {
+            self.received_bytes = self.received_bytes.wrapping_add(length);
+            if self.completion_time.is_none() && self.received_bytes == self.flow_info.length_bytes {
+                self.completion_time = Some(time - self.start_time.unwrap());
+                if let Some(log) = logger {
+                    info!(log, "flow completed";
+                        "node" => self.flow_info.dest_id,
+                        "completion_time" => self.completion_time.unwrap(),
+                        "start_time" => self.start_time.unwrap(),
+                        "end_time" => time,
+                    );
+                }
+            }
+        }
+
+        // nothing to acknowledge
+        Ok((vec![], false))
+    }
+
+    fn exec(&mut self, _time: Nanos, _logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
+        Ok((vec![], false))
+    }
+
+    fn transmitted(&mut self, _time: Nanos, _pkt: Packet) {}
+
+    fn on_timer(&mut self, _time: Nanos) -> Option<Nanos> {
+        None
+    }
+}
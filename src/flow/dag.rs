@@ -0,0 +1,120 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use ::{Nanos, Result};
+use congcontrol::CongAlg;
+use event::{Event, Executor};
+use node::switch::Switch;
+use super::{FlowArrivalEvent, FlowCompletionListener, FlowInfo};
+
+/// A job made of dependent flows: each flow starts only once all of its predecessors complete.
+///
+/// Flow ids must be unique within the DAG.
+#[derive(Debug)]
+pub struct FlowDag<CC: CongAlg> {
+    flows: Vec<(FlowInfo, Vec<u32>)>,
+    job_start: Option<Nanos>,
+    start_times: HashMap<u32, Nanos>,
+    completion_times: HashMap<u32, Nanos>,
+    _cc: PhantomData<CC>,
+}
+
+impl<CC: CongAlg + 'static> FlowDag<CC> {
+    pub fn new() -> Self {
+        FlowDag{
+            flows: vec![],
+            job_start: None,
+            start_times: HashMap::new(),
+            completion_times: HashMap::new(),
+            _cc: PhantomData,
+        }
+    }
+
+    /// Add `flow`, which starts once every flow in `depends_on` has completed.
+    pub fn add_flow(&mut self, flow: FlowInfo, depends_on: &[u32]) -> Result<()> {
+        if self.flows.iter().any(|(f, _)| f.flow_id == flow.flow_id) {
+            bail!("duplicate flow id {} in DAG", flow.flow_id);
+        }
+
+        if let Some(missing) = depends_on.iter().find(|d| !self.flows.iter().any(|(f, _)| f.flow_id == **d)) {
+            // predecessors must be added first, which also rules out cycles
+            bail!("flow {} depends on unknown flow {}", flow.flow_id, missing);
+        }
+
+        self.flows.push((flow, depends_on.to_vec()));
+        Ok(())
+    }
+
+    /// Start the job at `start`, registering the DAG with every sending `Host` in `e`.
+    pub fn schedule<S: Switch>(self, e: &mut Executor<S>, start: Nanos) -> Result<Rc<RefCell<Self>>> {
+        let dag = Rc::new(RefCell::new(self));
+        {
+            let topo = e.components().1;
+            let mut senders = dag.borrow().flows.iter().map(|(f, _)| f.sender_id).collect::<Vec<u32>>();
+            senders.sort();
+            senders.dedup();
+            for sender in senders {
+                topo.lookup_host(sender)?.completion_listeners.push(dag.clone());
+            }
+        }
+
+        dag.borrow_mut().job_start = Some(start);
+        for ev in dag.borrow_mut().ready_flows(start) {
+            e.push(ev);
+        }
+
+        Ok(dag)
+    }
+
+    /// When `flow_id` started, if it has.
+    pub fn start_time(&self, flow_id: u32) -> Option<Nanos> {
+        self.start_times.get(&flow_id).cloned()
+    }
+
+    /// When `flow_id` completed, if it has.
+    pub fn completion_time(&self, flow_id: u32) -> Option<Nanos> {
+        self.completion_times.get(&flow_id).cloned()
+    }
+
+    /// Time from the job's start until its last flow completed, once every flow has.
+    pub fn job_completion_time(&self) -> Option<Nanos> {
+        if self.completion_times.len() < self.flows.len() {
+            return None;
+        }
+
+        let end = self.completion_times.values().max().cloned()?;
+        self.job_start.map(|start| end - start)
+    }
+
+    fn ready_flows(&mut self, now: Nanos) -> Vec<Box<Event>> {
+        let completion_times = &self.completion_times;
+        let start_times = &mut self.start_times;
+        self.flows.iter()
+            .filter(|(f, deps)| {
+                !start_times.contains_key(&f.flow_id)
+                    && deps.iter().all(|d| completion_times.contains_key(d))
+            })
+            .map(|(f, _)| *f)
+            .collect::<Vec<FlowInfo>>()
+            .into_iter()
+            .map(|f| {
+                start_times.insert(f.flow_id, now);
+                Box::new(FlowArrivalEvent(f, now, PhantomData::<CC>)) as Box<Event>
+            })
+            .collect()
+    }
+}
+
+impl<CC: CongAlg + 'static> FlowCompletionListener for FlowDag<CC> {
+    fn on_completion(&mut self, flow: FlowInfo, time: Nanos) -> Vec<Box<Event>> {
+        // the listener is shared by every sender, so ignore flows from other jobs
+        if !self.flows.iter().any(|(f, _)| f.flow_id == flow.flow_id) {
+            return vec![];
+        }
+
+        self.completion_times.insert(flow.flow_id, time);
+        self.ready_flows(time)
+    }
+}
@@ -1,20 +1,46 @@
+use std::any::Any;
+use std::collections::HashMap;
+
 use slog;
 
 use ::{Nanos, Result};
 use ::congcontrol::{CongAlg, ReductionType};
-use super::{Flow, FlowInfo, FlowSide};
-use ::packet::{Packet, PacketHeader};
+use ::log_filter::PACKET;
+use super::{Flow, FlowInfo, FlowSide, TransmitCounts, DEFAULT_RTO, packet_deadline};
+use ::packet::{Packet, PacketHeader, NO_SACK, UNLIMITED_RWND};
 
 pub fn new<CC: CongAlg>(fi: FlowInfo) -> (Box<GoBackNSender<CC>>, Box<GoBackNReceiver>) {
+    with_cong_alg(fi, CC::new())
+}
+
+/// A flow whose sender starts from the congestion control state `cc`, which counts in packets
+/// of the flow's `max_packet_length`.
+pub fn with_cong_alg<CC: CongAlg>(fi: FlowInfo, mut cc: CC) -> (Box<GoBackNSender<CC>>, Box<GoBackNReceiver>) {
+    if let Some(l) = fi.max_packet_length {
+        cc.set_mss(l);
+    }
+
     (
         Box::new(GoBackNSender {
             flow_info: fi,
             start_time: None,
             completion_time: None,
+            transmit_completion_time: None,
+            transmit_counts: TransmitCounts::default(),
+            send_times: HashMap::new(),
+            latest_rtt: 0,
+            retransmit_below: 0,
             next_to_send: 0,
             cumulative_acked: 0,
             retx_timeout: 0,
-            cong_control: CC::new(),
+            cc_timer: Some(0),
+            next_paced_send: 0,
+            retransmitted_bytes: 0,
+            nacks_received: 0,
+            timeouts: 0,
+            // as if learned from the receiver during the handshake
+            rwnd: fi.receive_window.unwrap_or(UNLIMITED_RWND),
+            cong_control: cc,
         }),
         Box::new(GoBackNReceiver {
             flow_info: fi,
@@ -22,39 +48,85 @@ pub fn new<CC: CongAlg>(fi: FlowInfo) -> (Box<GoBackNSender<CC>>, Box<GoBackNRec
             start_time: None,
             completion_time: None,
             nack_inflight: false,
+            trimmed_past_nack: None,
+            prev_transit: None,
+            jitter: 0.0,
+            rwnd: fi.receive_window.unwrap_or(UNLIMITED_RWND),
+            ce_pending: false,
         }),
     )
 }
 
-#[derive(Clone, Debug)]
+/// The congestion control state of `f`, if it is a `GoBackNSender<CC>`.
+/// For warm-starting later flows with `WarmStartFlowArrivalEvent`.
+pub fn cong_control_of<CC: CongAlg + 'static>(f: &Flow) -> Option<CC> {
+    f.as_any()
+        .downcast_ref::<GoBackNSender<CC>>()
+        .map(|s| s.cong_control.clone())
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GoBackNSender<CC: CongAlg> {
     flow_info: FlowInfo,
 
     start_time: Option<Nanos>,
     completion_time: Option<Nanos>,
+    transmit_completion_time: Option<Nanos>, // last byte on the wire; updated on retransmission
+    transmit_counts: TransmitCounts,
+    send_times: HashMap<u32, Nanos>, // ACK seq which acknowledges a packet -> when it was sent
+    latest_rtt: Nanos,
+    retransmit_below: u32, // packets ending at or below this have been sent before
     next_to_send: u32,
     cumulative_acked: u32,
-    retx_timeout: Nanos,
+    retx_timeout: Nanos, // when the flow started, last heard from the receiver, or last timed out
+    cc_timer: Option<Nanos>, // when the congestion control timer fires next
+    next_paced_send: Nanos, // when a paced flow may send its next packet
+    retransmitted_bytes: u32,
+    nacks_received: u32,
+    timeouts: u32,
+    rwnd: u32, // the receiver's latest advertised window
     cong_control: CC,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GoBackNReceiver {
     flow_info: FlowInfo,
     cumulative_received: u32,
     start_time: Option<Nanos>,
     completion_time: Option<Nanos>,
     nack_inflight: bool,
+    trimmed_past_nack: Option<u32>, // lowest trimmed seq seen beyond an outstanding NACK's
+    prev_transit: Option<i64>,
+    jitter: f64,
+    rwnd: u32, // advertised in every ACK
+    ce_pending: bool, // some data since the last ACK was CE marked
 }
 
-impl<CC: CongAlg> Flow for GoBackNSender<CC> {
+impl<CC: CongAlg + 'static> Flow for GoBackNSender<CC> {
     fn flow_info(&self) -> FlowInfo { self.flow_info }
     fn side(&self) -> FlowSide { FlowSide::Sender }
+    fn as_any(&self) -> &Any { self }
+
+    fn start_time(&self) -> Option<Nanos> {
+        self.start_time
+    }
 
     fn completion_time(&self) -> Option<Nanos> {
         self.completion_time
     }
 
+    fn transmit_completion_time(&self) -> Option<Nanos> {
+        self.transmit_completion_time
+    }
+
+    fn delivered_bytes(&self) -> u32 {
+        self.cumulative_acked
+    }
+
+    fn jitter(&self) -> Option<f64> {
+        None
+    }
+
     fn receive(&mut self, time: Nanos, pkt: Packet, logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
         match pkt {
             Packet::Data{..} => unreachable!(),
@@ -69,42 +141,136 @@ impl<CC: CongAlg> Flow for GoBackNSender<CC> {
     fn exec(&mut self, time: Nanos, _logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
         if let None = self.start_time {
             self.start_time = Some(time);
+            self.retx_timeout = time;
         }
 
         if self.completion_time.is_some() {
             Ok((vec![], false))
         } else if !self.check_timeout(time) {
-            self.maybe_send_more().map(|v| (v, false))
+            self.maybe_send_more(time).map(|v| (v, false))
         } else {
             let cum_ack = self.cumulative_acked;
             self.retx_timeout = time;
-            self.go_back_n(cum_ack).map(|v| (v, true))
+            self.timeouts += 1;
+            self.go_back_n(cum_ack, time).map(|v| (v, true))
+        }
+    }
+
+    fn transmitted(&mut self, time: Nanos, pkt: Packet) {
+        if let Packet::Data{seq, length, sent, ..} = pkt {
+            let retransmitted = self.transmit_counts.retransmitted;
+            self.transmit_counts.record(seq, length);
+            if self.transmit_counts.retransmitted > retransmitted {
+                self.retransmitted_bytes += length;
+            }
+
+            // Karn's algorithm: an ACK for a retransmitted packet can't be attributed to either
+            // transmission, so it yields no RTT sample
+            if seq + length > self.retransmit_below {
+                self.send_times.insert(seq + length, sent);
+            }
+
+            if seq + length == self.flow_info.length_bytes {
+                self.transmit_completion_time = Some(time - self.start_time.unwrap());
+            }
+        }
+    }
+
+    fn transmit_counts(&self) -> TransmitCounts {
+        self.transmit_counts
+    }
+
+    fn on_timer(&mut self, time: Nanos) -> Option<Nanos> {
+        if self.completion_time.is_some() {
+            return None;
+        }
+
+        // one timer serves both congestion control and the retransmission timeout
+        if self.cc_timer.map_or(false, |t| time >= t) {
+            self.cc_timer = self.cong_control.on_timer(time).map(|d| time + d);
+        }
+
+        // the timer which starts the flow fires before it first sends
+        let last_progress = if self.retx_timeout > 0 { self.retx_timeout } else { time };
+        let mut rto_deadline = last_progress + self.rto() + 1;
+        if rto_deadline <= time {
+            // due now: the host handles it when it next polls, which restarts the timeout
+            rto_deadline = time + self.rto() + 1;
+        }
+
+        let next = self.cc_timer.map_or(rto_deadline, |t| ::std::cmp::min(t, rto_deadline));
+        Some(next - time)
+    }
+
+    fn next_send_time(&self) -> Option<Nanos> {
+        let max_packet_length = self.flow_info.max_packet_length?;
+        let held_back = self.completion_time.is_none()
+            && self.pacing_gap().is_some()
+            && self.next_to_send < self.flow_info.length_bytes
+            && self.next_to_send < self.cumulative_acked.saturating_add(self.window_bytes(max_packet_length));
+        if held_back {
+            Some(self.next_paced_send)
+        } else {
+            None
         }
     }
 }
 
 impl<CC: CongAlg> GoBackNSender<CC> {
+    /// Payload bytes put on the wire more than once. Goodput excludes them.
+    pub fn retransmitted_bytes(&self) -> u32 {
+        self.retransmitted_bytes
+    }
+
+    /// NACKs which made the sender go back.
+    pub fn nacks_received(&self) -> u32 {
+        self.nacks_received
+    }
+
+    /// Retransmission timeouts which made the sender go back.
+    pub fn timeouts(&self) -> u32 {
+        self.timeouts
+    }
+
     // sending side
     fn got_ack(&mut self, ack: Packet, time: Nanos, logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
         match ack {
-            Packet::Ack{hdr, cumulative_acked_seq} => {
+            Packet::Ack{hdr, cumulative_acked_seq, ece, rwnd, ..} => {
                 assert_eq!(hdr.flow, self.flow_info.flow_id);
                 assert_eq!(hdr.from, self.flow_info.dest_id);
                 assert_eq!(hdr.to, self.flow_info.sender_id);
+                self.rwnd = rwnd;
 
                 // 2 cases
                 // in order ACK, all well
                 // out of order ACK, must go back N
                 if cumulative_acked_seq > self.cumulative_acked {
-                    self.cong_control.on_packet(cumulative_acked_seq - self.cumulative_acked, 0 /* rtt, Nanos */);
+                    // RTT from when the acknowledged packet left the sender's queue.
+                    // ACKs without a sample reuse the latest one.
+                    if let Some(sent) = self.send_times.remove(&cumulative_acked_seq) {
+                        self.latest_rtt = time - sent;
+                    }
+
+                    self.send_times.retain(|&acks, _| acks > cumulative_acked_seq);
+                    self.cong_control.on_packet(cumulative_acked_seq - self.cumulative_acked, self.latest_rtt);
+                    if ece {
+                        self.cong_control.reduction(ReductionType::Ecn);
+                    }
+
+                    if let Some(log) = logger {
+                        debug!(log, #PACKET, "cwnd";
+                            "time" => time,
+                            "cwnd" => self.cong_control.cwnd(),
+                            "rtt" => self.latest_rtt,
+                        );
+                    }
+
                     self.cumulative_acked = cumulative_acked_seq;
                     if self.cumulative_acked == self.flow_info.length_bytes {
                         self.completion_time = Some(time - self.start_time.unwrap());
                         if let Some(log) = logger {
                             info!(log, "flow completed";
-                                "flow" => self.flow_info.flow_id,
                                 "node" => self.flow_info.sender_id,
-                                "side" => ?self.side(),
                                 "completion_time" => self.completion_time.unwrap(),
                                 "start_time" => self.start_time.unwrap(),
                                 "end_time" => time,
@@ -113,8 +279,15 @@ impl<CC: CongAlg> GoBackNSender<CC> {
 
                         Ok((vec![], false))
                     } else {
-                        self.maybe_send_more().map(|v| (v, false))
+                        self.maybe_send_more(time).map(|v| (v, false))
                     }
+                } else if cumulative_acked_seq == self.cumulative_acked
+                    && self.cong_control.on_dup_ack(self.outstanding_bytes())
+                {
+                    // fast retransmit: the receiver discards what follows a lost packet, so
+                    // resend from there
+                    let go_back_to = self.cumulative_acked;
+                    self.go_back_n(go_back_to, time).map(|v| (v, true))
                 } else {
                     // old ACK, ignore
                     Ok((vec![], false))
@@ -124,37 +297,100 @@ impl<CC: CongAlg> GoBackNSender<CC> {
                 assert_eq!(hdr.flow, self.flow_info.flow_id);
                 assert_eq!(hdr.from, self.flow_info.dest_id);
                 assert_eq!(hdr.to, self.flow_info.sender_id);
+                self.nacks_received += 1;
                 self.cong_control.reduction(ReductionType::Drop);
-                self.go_back_n(nacked_seq).map(|v| (v, true))
+                self.go_back_n(nacked_seq, time).map(|v| (v, true))
             }
             _ => unreachable!(),
         }
     }
 
+    fn rto(&self) -> Nanos {
+        self.flow_info.rto.unwrap_or(DEFAULT_RTO)
+    }
+
+    /// Bytes sent but not yet ACKed, including any sent before going back.
+    fn outstanding_bytes(&self) -> u32 {
+        ::std::cmp::max(self.next_to_send, self.retransmit_below).saturating_sub(self.cumulative_acked)
+    }
+
     fn check_timeout(&mut self, now: Nanos) -> bool {
         self.retx_timeout > 0 
             && self.completion_time.is_none() 
-            && (now - self.retx_timeout) > 1_000_000_000 // 1s // TODO configurable
+            && (now - self.retx_timeout) > self.rto()
+    }
+
+    /// How many bytes past the cumulative ACK the sender may have outstanding: the smaller of
+    /// cwnd and the receiver's window.
+    /// cwnd allows at least one packet, so that the flow always makes progress; the receiver's
+    /// window, which is never 0, may allow less.
+    fn window_bytes(&self, max_packet_length: u32) -> u32 {
+        let cwnd = ::std::cmp::max(self.cong_control.cwnd(), 1).saturating_mul(max_packet_length);
+        ::std::cmp::min(cwnd, self.rwnd)
+    }
+
+    /// The spacing between a paced flow's packets: one window per RTT.
+    /// `None` if the flow is unpaced, or has no RTT sample to pace with yet.
+    fn pacing_gap(&self) -> Option<Nanos> {
+        if !self.flow_info.paced || self.latest_rtt == 0 {
+            return None;
+        }
+
+        let cwnd = ::std::cmp::max(self.cong_control.cwnd(), 1);
+        Some(::std::cmp::max(self.latest_rtt / u64::from(cwnd), 1))
     }
 
-    fn maybe_send_more(&mut self) -> Result<Vec<Packet>> {
-        let cwnd = self.cong_control.cwnd() * self.flow_info.max_packet_length;
+    /// Whether the pacer lets a packet go out at `time`. If so, the next one waits a gap.
+    fn pace(&mut self, time: Nanos) -> bool {
+        match self.pacing_gap() {
+            Some(_) if time < self.next_paced_send => false,
+            Some(gap) => {
+                self.next_paced_send = time + gap;
+                true
+            }
+            None => true,
+        }
+    }
+
+    fn maybe_send_more(&mut self, time: Nanos) -> Result<Vec<Packet>> {
+        let max_packet_length = self.flow_info.max_packet_length
+            .ok_or_else(|| format_err!("flow {} has no packet length", self.flow_info.flow_id))?;
+        let window = if self.flow_info.paced && self.latest_rtt == 0 {
+            // nothing to pace at yet: send a single packet to get an RTT sample
+            ::std::cmp::min(max_packet_length, self.window_bytes(max_packet_length))
+        } else {
+            self.window_bytes(max_packet_length)
+        };
+        let window_end = self.cumulative_acked.saturating_add(window);
         let mut pkts = vec![];
         loop {
-            if self.next_to_send < self.cumulative_acked + cwnd {
-                if self.next_to_send + self.flow_info.max_packet_length <= self.flow_info.length_bytes {
-                    // send a full size packet and continue
+            if self.next_to_send < window_end {
+                if self.next_to_send < self.flow_info.length_bytes && !self.pace(time) {
+                    break;
+                }
+
+                // never put more than the window in flight, even if that is less than a packet
+                let packet_length = ::std::cmp::min(max_packet_length, window_end - self.next_to_send);
+                if self.next_to_send + packet_length <= self.flow_info.length_bytes {
+                    // send a full size (or full window) packet and continue
                     let pkt = Packet::Data{
                         hdr: PacketHeader{
                             flow: self.flow_info.flow_id,
                             from: self.flow_info.sender_id,
                             to: self.flow_info.dest_id,
+                            priority: self.flow_info.priority,
+                            size_bytes: self.flow_info.header_size_bytes(),
                         },
                         seq: self.next_to_send,
-                        length: self.flow_info.max_packet_length,
+                        length: packet_length,
+                        ect: self.flow_info.ecn_capable,
+                        ce: false,
+                        sent: 0, // stamped by the host when it transmits the packet
+                        deadline: packet_deadline(self.flow_info, self.start_time),
+                        trimmed: false,
                     };
 
-                    self.next_to_send += self.flow_info.max_packet_length;
+                    self.next_to_send += packet_length;
                     pkts.push(pkt);
                 } else if self.next_to_send < self.flow_info.length_bytes {
                     let pkt = Packet::Data{
@@ -162,9 +398,16 @@ impl<CC: CongAlg> GoBackNSender<CC> {
                             flow: self.flow_info.flow_id,
                             from: self.flow_info.sender_id,
                             to: self.flow_info.dest_id,
+                            priority: self.flow_info.priority,
+                            size_bytes: self.flow_info.header_size_bytes(),
                         },
                         seq: self.next_to_send,
                         length: self.flow_info.length_bytes - self.next_to_send,
+                        ect: self.flow_info.ecn_capable,
+                        ce: false,
+                        sent: 0,
+                        deadline: packet_deadline(self.flow_info, self.start_time),
+                        trimmed: false,
                     };
 
                     self.next_to_send += self.flow_info.length_bytes - self.next_to_send;
@@ -181,20 +424,39 @@ impl<CC: CongAlg> GoBackNSender<CC> {
         Ok(pkts)
     }
 
-    fn go_back_n(&mut self, go_back_to: u32) -> Result<Vec<Packet>> {
+    fn go_back_n(&mut self, go_back_to: u32, time: Nanos) -> Result<Vec<Packet>> {
+        self.retransmit_below = ::std::cmp::max(self.retransmit_below, self.next_to_send);
+        self.send_times.retain(|&acks, _| acks <= go_back_to);
         self.next_to_send = go_back_to;
-        self.maybe_send_more()
+        self.maybe_send_more(time)
     }
 }
 
 impl Flow for GoBackNReceiver {
     fn flow_info(&self) -> FlowInfo { self.flow_info }
     fn side(&self) -> FlowSide { FlowSide::Receiver }
+    fn as_any(&self) -> &Any { self }
+
+    fn start_time(&self) -> Option<Nanos> {
+        self.start_time
+    }
 
     fn completion_time(&self) -> Option<Nanos> {
         self.completion_time
     }
 
+    fn transmit_completion_time(&self) -> Option<Nanos> {
+        None
+    }
+
+    fn delivered_bytes(&self) -> u32 {
+        self.cumulative_received
+    }
+
+    fn jitter(&self) -> Option<f64> {
+        self.prev_transit.map(|_| self.jitter)
+    }
+
     fn receive(&mut self, time: Nanos, pkt: Packet, logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
         match pkt {
             Packet::Data{..} => self.got_data(pkt, time, logger).map(|v| (v, false)),
@@ -205,6 +467,12 @@ impl Flow for GoBackNReceiver {
     fn exec(&mut self, _time: Nanos, _logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
         Ok((vec![], false))
     }
+
+    fn transmitted(&mut self, _time: Nanos, _pkt: Packet) {}
+
+    fn on_timer(&mut self, _time: Nanos) -> Option<Nanos> {
+        None
+    }
 }
 
 impl GoBackNReceiver {
@@ -215,21 +483,79 @@ impl GoBackNReceiver {
         }
 
         match data {
-            Packet::Data{hdr, seq, length} => {
+            Packet::Data{hdr, seq, length, ce, sent, trimmed, ..} => {
                 assert_eq!(hdr.flow, self.flow_info.flow_id);
                 assert_eq!(hdr.to, self.flow_info.dest_id);
                 assert_eq!(hdr.from, self.flow_info.sender_id);
-                if seq == self.cumulative_received {
+
+                // ACKs are cumulative, so an ACK echoes CE if any data packet since the previous
+                // ACK was marked, including ones discarded as out of order: they saw the same
+                // congestion
+                self.ce_pending |= ce;
+
+                if trimmed {
+                    // the payload was cut off in the network: ask for it right away instead of
+                    // waiting for a later packet to show the gap, or for the timeout.
+                    // A trimmed retransmission of the next expected packet is NACKed again.
+                    // Later packets trimmed while a NACK is outstanding are NACKed once the
+                    // packets before them arrive, as that NACK's retransmission may not resend
+                    // them.
+                    if seq < self.cumulative_received {
+                        return Ok(vec![]);
+                    }
+
+                    if seq > self.cumulative_received && self.nack_inflight {
+                        self.trimmed_past_nack = Some(self.trimmed_past_nack.map_or(seq, |t| ::std::cmp::min(t, seq)));
+                        return Ok(vec![]);
+                    }
+
+                    self.nack_inflight = true;
+                    return Ok(vec![Packet::Nack{
+                        hdr: PacketHeader{
+                            flow: hdr.flow,
+                            from: hdr.to,
+                            to: hdr.from,
+                            priority: hdr.priority,
+                            size_bytes: hdr.size_bytes,
+                        },
+                        nacked_seq: self.cumulative_received,
+                    }]);
+                }
+
+                // RFC 3550 interarrival jitter: a smoothed mean of the change in one-way delay
+                let transit = time as i64 - sent as i64;
+                if let Some(prev) = self.prev_transit {
+                    let d = (transit - prev).abs() as f64;
+                    self.jitter += (d - self.jitter) / 16.0;
+                }
+                self.prev_transit = Some(transit);
+                if seq < self.cumulative_received {
+                    // a retransmission of data already received, e.g. because its ACK was lost:
+                    // ACK it again, or the sender would keep resending it
+                    let ece = ::std::mem::replace(&mut self.ce_pending, false);
+                    Ok(vec![Packet::Ack{
+                        hdr: PacketHeader{
+                            flow: hdr.flow,
+                            from: hdr.to,
+                            to: hdr.from,
+                            priority: hdr.priority,
+                            size_bytes: hdr.size_bytes,
+                        },
+                        cumulative_acked_seq: self.cumulative_received,
+                        ece,
+                        sack: NO_SACK,
+                        rwnd: self.rwnd,
+                    }])
+                } else if seq == self.cumulative_received {
                     self.cumulative_received += length;
                     self.nack_inflight = false;
                     if self.cumulative_received == self.flow_info.length_bytes {
                         self.completion_time = Some(time - self.start_time.unwrap());
                         if let Some(log) = logger {
                             info!(log, "flow completed";
-                                "flow" => self.flow_info.flow_id,
                                 "node" => self.flow_info.dest_id,
-                                "side" => ?self.side(),
                                 "completion_time" => self.completion_time.unwrap(),
+                                "jitter" => self.jitter,
                                 "start_time" => self.start_time.unwrap(),
                                 "end_time" => time,
                             );
@@ -237,14 +563,43 @@ impl GoBackNReceiver {
                     }
 
                     // send ACK
-                    Ok(vec![Packet::Ack{
+                    let ece = ::std::mem::replace(&mut self.ce_pending, false);
+                    let mut pkts = vec![Packet::Ack{
                         hdr: PacketHeader{
                             flow: hdr.flow,
                             from: hdr.to,
                             to: hdr.from,
+                            priority: hdr.priority,
+                            size_bytes: hdr.size_bytes,
                         },
                         cumulative_acked_seq: self.cumulative_received,
-                    }])
+                        ece,
+                        sack: NO_SACK,
+                        rwnd: self.rwnd,
+                    }];
+
+                    match self.trimmed_past_nack {
+                        Some(t) if t == self.cumulative_received => {
+                            // the next expected packet is one we know was trimmed
+                            self.trimmed_past_nack = None;
+                            self.nack_inflight = true;
+                            pkts.push(Packet::Nack{
+                                hdr: PacketHeader{
+                                    flow: hdr.flow,
+                                    from: hdr.to,
+                                    to: hdr.from,
+                                    priority: hdr.priority,
+                                    size_bytes: hdr.size_bytes,
+                                },
+                                nacked_seq: self.cumulative_received,
+                            });
+                        }
+                        // a later copy of it got through
+                        Some(t) if t < self.cumulative_received => self.trimmed_past_nack = None,
+                        _ => {}
+                    }
+
+                    Ok(pkts)
                 } else {
                     // out of order packet
                     // send NACK
@@ -255,6 +610,8 @@ impl GoBackNReceiver {
                                 flow: hdr.flow,
                                 from: hdr.to,
                                 to: hdr.from,
+                                priority: hdr.priority,
+                                size_bytes: hdr.size_bytes,
                             },
                             nacked_seq: self.cumulative_received,
                         }])
@@ -1,12 +1,14 @@
+use std::any::Any;
 use std::fmt::Debug;
 use std::marker::PhantomData;
 use slog;
 use super::{Nanos, Result};
-use super::packet::Packet;
+use super::packet::{Packet, PACKET_HEADER_SIZE_BYTES};
 use super::event::{Event, EventTime};
 use super::node::Node;
 use congcontrol::CongAlg;
 
+#[derive(Serialize, Deserialize)]
 pub struct FlowArrivalEvent<CC: CongAlg + 'static>(pub FlowInfo, pub Nanos, pub PhantomData<CC>);
 
 impl<CC: CongAlg> Event for FlowArrivalEvent<CC> {
@@ -15,43 +17,358 @@ impl<CC: CongAlg> Event for FlowArrivalEvent<CC> {
     }
 
     fn affected_node_ids(&self) -> Vec<u32> {
-        vec![self.0.sender_id, self.0.dest_id]
+        flow_node_ids(&self.0)
     }
 
     fn exec(&mut self, _time: Nanos, nodes: &mut [&mut Node], _logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
-        let (f_send, f_recv) = go_back_n::new::<CC>(self.0);
-        nodes[0].flow_arrival(f_send);
-        nodes[1].flow_arrival(f_recv);
-        Ok(vec![])
+        start_flow(self.0, nodes, |fi| go_back_n::new::<CC>(fi))
     }
 }
 
-#[derive(Clone,Copy,Debug)]
+/// Like `FlowArrivalEvent`, but the sender starts from the congestion control state `.2`,
+/// e.g. one captured at the end of a previous run with `go_back_n::cong_control_of`, rather
+/// than re-paying convergence.
+#[derive(Serialize, Deserialize)]
+pub struct WarmStartFlowArrivalEvent<CC: CongAlg + 'static>(pub FlowInfo, pub Nanos, pub CC);
+
+impl<CC: CongAlg> Event for WarmStartFlowArrivalEvent<CC> {
+    fn time(&self) -> EventTime {
+        EventTime::Absolute(self.1)
+    }
+
+    fn affected_node_ids(&self) -> Vec<u32> {
+        flow_node_ids(&self.0)
+    }
+
+    fn exec(&mut self, _time: Nanos, nodes: &mut [&mut Node], _logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        let cc = self.2.clone();
+        start_flow(self.0, nodes, |fi| go_back_n::with_cong_alg(fi, cc))
+    }
+}
+
+/// The nodes an arrival event for flow `fi` affects: its sender, then its destination.
+/// A loopback flow's node is listed once, as looking it up twice would alias it; `start_flow`
+/// rejects such a flow.
+fn flow_node_ids(fi: &FlowInfo) -> Vec<u32> {
+    if fi.sender_id == fi.dest_id {
+        vec![fi.sender_id]
+    } else {
+        vec![fi.sender_id, fi.dest_id]
+    }
+}
+
+/// Start flow `fi` between `nodes[0]` (the sender) and `nodes[1]`, using the sender and receiver
+/// `make` builds for it.
+fn start_flow<F: Flow + 'static, R: Flow + 'static>(
+    fi: FlowInfo,
+    nodes: &mut [&mut Node],
+    make: impl FnOnce(FlowInfo) -> (Box<F>, Box<R>),
+) -> Result<Vec<Box<Event>>> {
+    if fi.sender_id == fi.dest_id {
+        bail!(
+            "flow {} is a loopback flow: sender and destination are both node {}",
+            fi.flow_id,
+            fi.sender_id,
+        );
+    }
+
+    // flows without an explicit packet length fill the sender's MTU
+    let sender_mtu = nodes[0].mtu();
+    let header_bytes = fi.header_size_bytes();
+    let max_packet_length = match fi.max_packet_length {
+        Some(l) => {
+            if l.saturating_add(header_bytes) > sender_mtu {
+                bail!(
+                    "flow {} sends {} byte packets, which do not fit in node {}'s {} byte MTU",
+                    fi.flow_id,
+                    l.saturating_add(header_bytes),
+                    fi.sender_id,
+                    sender_mtu,
+                );
+            }
+
+            l
+        }
+        None => match sender_mtu.checked_sub(header_bytes) {
+            Some(l) if l > 0 => l,
+            _ => bail!(
+                "flow {}'s {} byte headers leave no room for data in node {}'s {} byte MTU",
+                fi.flow_id,
+                header_bytes,
+                fi.sender_id,
+                sender_mtu,
+            ),
+        },
+    };
+
+    let flow_info = FlowInfo{
+        max_packet_length: Some(max_packet_length),
+        ..fi
+    };
+
+    let (f_send, f_recv) = make(flow_info);
+    nodes[0].flow_arrival(f_send);
+    nodes[1].flow_arrival(f_recv);
+    Ok(vec![Box::new(FlowTimerEvent(fi.sender_id, fi.flow_id, 0))])
+}
+
+/// The absolute deadline carried by the data packets of flow `fi`, which started at
+/// `start_time`.
+fn packet_deadline(fi: FlowInfo, start_time: Option<Nanos>) -> Option<Nanos> {
+    fi.deadline.and_then(|d| start_time.map(|t| t + d))
+}
+
+/// Fires the timer of flow `.1` at node `.0` after a delay of `.2`.
+#[derive(Serialize, Deserialize)]
+pub struct FlowTimerEvent(pub u32, pub u32, pub Nanos);
+
+impl Event for FlowTimerEvent {
+    fn time(&self) -> EventTime {
+        EventTime::Delta(self.2)
+    }
+
+    fn affected_node_ids(&self) -> Vec<u32> {
+        vec![self.0]
+    }
+
+    fn exec(&mut self, time: Nanos, nodes: &mut [&mut Node], logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        nodes[0].flow_timer(self.1, time, logger)
+    }
+
+    fn is_stale(&self, nodes: &[&mut Node]) -> bool {
+        // so that a pending timer does not extend the simulation past the flow's completion
+        nodes[0].flows().iter()
+            .find(|f| f.flow_info().flow_id == self.1)
+            .map_or(true, |f| f.completion_time().is_some())
+    }
+}
+
+#[derive(Clone,Copy,Debug,PartialEq,Serialize,Deserialize)]
 pub struct FlowInfo {
     pub flow_id: u32,
     pub sender_id: u32,
     pub dest_id: u32,
     pub length_bytes: u32,
-    pub max_packet_length: u32,
+    /// Payload bytes per packet. `None` inherits the sender's link MTU.
+    pub max_packet_length: Option<u32>,
+    /// How long the sender waits without ACK progress before going back to the last ACK.
+    /// `None` uses `DEFAULT_RTO`.
+    pub rto: Option<Nanos>,
+    /// Whether the sender's packets are ECN-capable. Marking queues drop, rather than mark,
+    /// packets which are not.
+    pub ecn_capable: bool,
+    /// Traffic class of the flow's packets (see `PacketHeader::priority`).
+    pub priority: u8,
+    /// How long after starting the flow should complete. `EdfQueue`s serve packets of flows
+    /// with earlier deadlines first. `None` for flows without one.
+    pub deadline: Option<Nanos>,
+    /// Bytes of header on each of the flow's packets, ACKs included. `None` uses
+    /// `PACKET_HEADER_SIZE_BYTES`.
+    pub header_bytes: Option<u32>,
+    /// Whether the sender spaces its packets out at `cwnd / rtt` instead of sending its window
+    /// in a burst. Only go-back-N senders pace.
+    pub paced: bool,
+    /// Bytes of receive buffer a go-back-N receiver advertises in its ACKs, which bounds the
+    /// sender's outstanding bytes along with its cwnd. A window smaller than a packet makes
+    /// the sender send smaller packets. `None` never limits the sender.
+    pub receive_window: Option<u32>,
+}
+
+impl FlowInfo {
+    /// A `FlowInfoBuilder` for a flow from `sender_id` to `dest_id`, with every optional field
+    /// at its default.
+    pub fn builder(flow_id: u32, sender_id: u32, dest_id: u32) -> FlowInfoBuilder {
+        FlowInfoBuilder(FlowInfo{
+            flow_id,
+            sender_id,
+            dest_id,
+            length_bytes: 0,
+            max_packet_length: None,
+            rto: None,
+            ecn_capable: true,
+            priority: 0,
+            deadline: None,
+            header_bytes: None,
+            paced: false,
+            receive_window: None,
+        })
+    }
+
+    pub fn header_size_bytes(&self) -> u32 {
+        self.header_bytes.unwrap_or(PACKET_HEADER_SIZE_BYTES)
+    }
+}
+
+/// Builds a `FlowInfo`, so that construction sites only name the fields they change and new
+/// optional fields need no changes to them.
+///
+/// By default a flow is empty, ECN-capable, unpaced, in traffic class 0, and leaves the packet
+/// length, RTO, deadline, header size, and receive window to their defaults.
+#[derive(Clone, Copy, Debug)]
+pub struct FlowInfoBuilder(FlowInfo);
+
+impl FlowInfoBuilder {
+    pub fn length_bytes(mut self, length_bytes: u32) -> Self {
+        self.0.length_bytes = length_bytes;
+        self
+    }
+
+    pub fn max_packet_length(mut self, max_packet_length: u32) -> Self {
+        self.0.max_packet_length = Some(max_packet_length);
+        self
+    }
+
+    pub fn rto(mut self, rto: Nanos) -> Self {
+        self.0.rto = Some(rto);
+        self
+    }
+
+    pub fn ecn_capable(mut self, ecn_capable: bool) -> Self {
+        self.0.ecn_capable = ecn_capable;
+        self
+    }
+
+    pub fn priority(mut self, priority: u8) -> Self {
+        self.0.priority = priority;
+        self
+    }
+
+    pub fn deadline(mut self, deadline: Nanos) -> Self {
+        self.0.deadline = Some(deadline);
+        self
+    }
+
+    pub fn header_bytes(mut self, header_bytes: u32) -> Self {
+        self.0.header_bytes = Some(header_bytes);
+        self
+    }
+
+    pub fn paced(mut self, paced: bool) -> Self {
+        self.0.paced = paced;
+        self
+    }
+
+    pub fn receive_window(mut self, receive_window: u32) -> Self {
+        assert!(receive_window > 0, "a flow with no receive window could never send");
+        self.0.receive_window = Some(receive_window);
+        self
+    }
+
+    pub fn build(self) -> FlowInfo {
+        self.0
+    }
 }
 
-#[derive(Clone,Copy,Debug)]
+/// Retransmission timeout for flows which do not set one.
+pub const DEFAULT_RTO: Nanos = 1_000_000_000;
+
+#[derive(Clone,Copy,Debug,Serialize,Deserialize)]
 pub enum FlowSide{
     Sender,
     Receiver,
 }
 
+/// A child of `logger` whose context carries the flow id and side of `f`, so every line logged
+/// on the flow's behalf can be filtered by flow. Hosts pass it to their flows.
+pub fn flow_logger(logger: Option<&slog::Logger>, f: &Flow) -> Option<slog::Logger> {
+    logger.map(|log| log.new(o!(
+        "flow" => f.flow_info().flow_id,
+        "side" => format!("{:?}", f.side()),
+    )))
+}
+
+/// Reacts to flows completing, e.g. to start dependent flows.
+/// Register with the sending `Host`'s `completion_listeners`.
+pub trait FlowCompletionListener: Debug {
+    /// Called when the sender of `flow` receives its final ACK at `time`.
+    fn on_completion(&mut self, flow: FlowInfo, time: Nanos) -> Vec<Box<Event>>;
+}
+
+/// A sender's data packet transmissions, and how many of them were retransmissions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TransmitCounts {
+    pub sent: u32,
+    pub retransmitted: u32,
+    sent_below: u32, // end of the highest data sent so far
+}
+
+impl TransmitCounts {
+    /// Count data packet `[seq, seq + length)` going onto the wire.
+    /// Senders send in sequence order except when they retransmit, so data below the highest
+    /// sent so far is being sent again.
+    pub fn record(&mut self, seq: u32, length: u32) {
+        self.sent += 1;
+        if seq + length <= self.sent_below {
+            self.retransmitted += 1;
+        }
+
+        self.sent_below = ::std::cmp::max(self.sent_below, seq + length);
+    }
+
+    /// The fraction of transmissions which were retransmissions: the loss rate the sender
+    /// observed. `None` before anything was sent.
+    /// A go-back-N sender resends everything after a lost packet, so this overstates its loss
+    /// rate; a selective repeat sender resends only what was lost.
+    pub fn loss_rate(&self) -> Option<f64> {
+        if self.sent == 0 {
+            None
+        } else {
+            Some(f64::from(self.retransmitted) / f64::from(self.sent))
+        }
+    }
+}
+
 pub trait Flow: Debug {
     fn flow_info(&self) -> FlowInfo;
+    /// For recovering the concrete flow type, e.g. with `go_back_n::cong_control_of`.
+    fn as_any(&self) -> &Any;
     fn side(&self) -> FlowSide;
 
+    /// When this side of the flow first sent or received a packet.
+    fn start_time(&self) -> Option<Nanos>;
     fn completion_time(&self) -> Option<Nanos>;
+    /// How long after starting the sender finished putting the flow's last byte on the wire.
+    /// Always `None` for receivers.
+    fn transmit_completion_time(&self) -> Option<Nanos>;
+    /// Bytes of this flow which have been delivered in order so far.
+    fn delivered_bytes(&self) -> u32;
+    /// RFC 3550 interarrival jitter of received data packets, in nanoseconds.
+    /// `None` for senders and before any data arrives.
+    fn jitter(&self) -> Option<f64>;
 
     /// Process an incoming packet
     /// Return reaction outgoing packets.
     fn receive(&mut self, time: Nanos, pkt: Packet, logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)>;
     /// Return proactive outgoing packets.
     fn exec(&mut self, time: Nanos, logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)>;
+    /// `pkt`, which this flow sent, finished transmitting onto the wire at `time`.
+    fn transmitted(&mut self, time: Nanos, pkt: Packet);
+    /// Data packets this side has put on the wire. Always empty for receivers.
+    fn transmit_counts(&self) -> TransmitCounts {
+        TransmitCounts::default()
+    }
+    /// The flow's timer fired (see `FlowTimerEvent`). Returns the delay until it should fire
+    /// again, if at all.
+    fn on_timer(&mut self, time: Nanos) -> Option<Nanos>;
+    /// When this side will next have a packet to send which it is holding back now, e.g. to
+    /// pace it. The host wakes itself up then (see `HostWakeEvent`).
+    fn next_send_time(&self) -> Option<Nanos> {
+        None
+    }
+
+    /// Whether the flow completed within its `FlowInfo::deadline`.
+    /// `None` if it has no deadline or has not completed yet.
+    fn met_deadline(&self) -> Option<bool> {
+        let deadline = self.flow_info().deadline?;
+        self.completion_time().map(|t| t <= deadline)
+    }
 }
 
 pub mod go_back_n;
+pub mod dag;
+pub mod workload;
+pub mod closed_loop;
+pub mod rpc;
+pub mod constant_bit_rate;
+pub mod receiver_driven;
+pub mod selective_repeat;
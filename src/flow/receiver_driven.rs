@@ -0,0 +1,321 @@
+use std::any::Any;
+use std::cmp::{max, min};
+
+use slog;
+
+use ::{Nanos, Result};
+use event::{Event, EventTime};
+use node::Node;
+use packet::{Packet, PacketHeader, NO_SACK, UNLIMITED_RWND};
+use super::{Flow, FlowInfo, FlowSide, TransmitCounts, DEFAULT_RTO, flow_node_ids, packet_deadline, start_flow};
+
+/// Starts a receiver-driven (Homa/NDP-style) flow at time `.1`: the sender sends the first `.2`
+/// bytes unscheduled, at line rate, and the rest only as the receiver grants them.
+#[derive(Serialize, Deserialize)]
+pub struct ReceiverDrivenFlowArrivalEvent(pub FlowInfo, pub Nanos, pub u32);
+
+impl Event for ReceiverDrivenFlowArrivalEvent {
+    fn time(&self) -> EventTime {
+        EventTime::Absolute(self.1)
+    }
+
+    fn affected_node_ids(&self) -> Vec<u32> {
+        flow_node_ids(&self.0)
+    }
+
+    fn exec(&mut self, _time: Nanos, nodes: &mut [&mut Node], _logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        let unscheduled_bytes = self.2;
+        start_flow(self.0, nodes, |fi| new(fi, unscheduled_bytes))
+    }
+}
+
+/// A receiver-driven flow which sends its first `unscheduled_bytes` without waiting for grants.
+///
+/// The receiver keeps up to `unscheduled_bytes` (at least one packet) granted beyond what it
+/// has received in order, granting more as each data packet arrives, so the sender never has
+/// more than that outstanding.
+/// Loss recovery is go-back-N, on a NACK or a retransmission timeout.
+pub fn new(fi: FlowInfo, unscheduled_bytes: u32) -> (Box<GrantSender>, Box<GrantReceiver>) {
+    // the first packet is always unscheduled, or the receiver would never hear of the flow
+    let unscheduled_bytes = max(unscheduled_bytes, fi.max_packet_length.unwrap_or(1));
+    let granted = min(unscheduled_bytes, fi.length_bytes);
+    (
+        Box::new(GrantSender{
+            flow_info: fi,
+            start_time: None,
+            completion_time: None,
+            transmit_completion_time: None,
+            transmit_counts: TransmitCounts::default(),
+            granted,
+            next_to_send: 0,
+            cumulative_acked: 0,
+            retx_timeout: 0,
+        }),
+        Box::new(GrantReceiver{
+            flow_info: fi,
+            grant_window: unscheduled_bytes,
+            granted,
+            grants_sent: 0,
+            cumulative_received: 0,
+            start_time: None,
+            completion_time: None,
+            nack_inflight: false,
+        }),
+    )
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GrantSender {
+    flow_info: FlowInfo,
+    start_time: Option<Nanos>,
+    completion_time: Option<Nanos>,
+    transmit_completion_time: Option<Nanos>,
+    transmit_counts: TransmitCounts,
+    granted: u32, // bytes below this may be sent
+    next_to_send: u32,
+    cumulative_acked: u32,
+    retx_timeout: Nanos, // when the flow started, last heard from the receiver, or last timed out
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GrantReceiver {
+    flow_info: FlowInfo,
+    grant_window: u32,
+    granted: u32,
+    grants_sent: u32,
+    cumulative_received: u32,
+    start_time: Option<Nanos>,
+    completion_time: Option<Nanos>,
+    nack_inflight: bool,
+}
+
+impl GrantReceiver {
+    /// How many `Grant`s the receiver has sent. Flows which fit in their unscheduled bytes
+    /// never need one.
+    pub fn grants_sent(&self) -> u32 {
+        self.grants_sent
+    }
+}
+
+impl GrantSender {
+    fn rto(&self) -> Nanos {
+        self.flow_info.rto.unwrap_or(DEFAULT_RTO)
+    }
+
+    fn send_granted(&mut self) -> Result<Vec<Packet>> {
+        let max_packet_length = self.flow_info.max_packet_length
+            .ok_or_else(|| format_err!("flow {} has no packet length", self.flow_info.flow_id))?;
+        let mut pkts = vec![];
+        while self.next_to_send < self.granted {
+            let length = min(max_packet_length, self.flow_info.length_bytes - self.next_to_send);
+            pkts.push(Packet::Data{
+                hdr: PacketHeader{
+                    flow: self.flow_info.flow_id,
+                    from: self.flow_info.sender_id,
+                    to: self.flow_info.dest_id,
+                    priority: self.flow_info.priority,
+                    size_bytes: self.flow_info.header_size_bytes(),
+                },
+                seq: self.next_to_send,
+                length,
+                ect: self.flow_info.ecn_capable,
+                ce: false,
+                sent: 0, // stamped by the host when it transmits the packet
+                deadline: packet_deadline(self.flow_info, self.start_time),
+                trimmed: false,
+            });
+
+            self.next_to_send += length;
+        }
+
+        Ok(pkts)
+    }
+}
+
+impl Flow for GrantSender {
+    fn flow_info(&self) -> FlowInfo { self.flow_info }
+    fn side(&self) -> FlowSide { FlowSide::Sender }
+    fn as_any(&self) -> &Any { self }
+
+    fn start_time(&self) -> Option<Nanos> {
+        self.start_time
+    }
+
+    fn completion_time(&self) -> Option<Nanos> {
+        self.completion_time
+    }
+
+    fn transmit_completion_time(&self) -> Option<Nanos> {
+        self.transmit_completion_time
+    }
+
+    fn delivered_bytes(&self) -> u32 {
+        self.cumulative_acked
+    }
+
+    fn jitter(&self) -> Option<f64> {
+        None
+    }
+
+    fn receive(&mut self, time: Nanos, pkt: Packet, logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
+        self.retx_timeout = time;
+        match pkt {
+            Packet::Ack{cumulative_acked_seq, ..} => {
+                self.cumulative_acked = max(self.cumulative_acked, cumulative_acked_seq);
+                if self.completion_time.is_none() && self.cumulative_acked == self.flow_info.length_bytes {
+                    self.completion_time = Some(time - self.start_time.unwrap());
+                    if let Some(log) = logger {
+                        info!(log, "flow completed";
+                            "node" => self.flow_info.sender_id,
+                            "completion_time" => self.completion_time.unwrap(),
+                            "start_time" => self.start_time.unwrap(),
+                            "end_time" => time,
+                        );
+                    }
+                }
+
+                Ok((vec![], false))
+            }
+            Packet::Grant{granted_seq, ..} => {
+                self.granted = max(self.granted, granted_seq);
+                self.send_granted().map(|v| (v, false))
+            }
+            Packet::Nack{nacked_seq, ..} => {
+                self.next_to_send = nacked_seq;
+                self.send_granted().map(|v| (v, true))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn exec(&mut self, time: Nanos, _logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
+        if let None = self.start_time {
+            self.start_time = Some(time);
+            self.retx_timeout = time;
+        }
+
+        if self.completion_time.is_some() {
+            Ok((vec![], false))
+        } else if time - self.retx_timeout > self.rto() {
+            self.retx_timeout = time;
+            self.next_to_send = self.cumulative_acked;
+            self.send_granted().map(|v| (v, true))
+        } else {
+            self.send_granted().map(|v| (v, false))
+        }
+    }
+
+    fn transmitted(&mut self, time: Nanos, pkt: Packet) {
+        if let Packet::Data{seq, length, ..} = pkt {
+            self.transmit_counts.record(seq, length);
+            if seq + length == self.flow_info.length_bytes {
+                self.transmit_completion_time = Some(time - self.start_time.unwrap());
+            }
+        }
+    }
+
+    fn transmit_counts(&self) -> TransmitCounts {
+        self.transmit_counts
+    }
+
+    fn on_timer(&mut self, time: Nanos) -> Option<Nanos> {
+        if self.completion_time.is_some() {
+            return None;
+        }
+
+        // the timer which starts the flow fires before it first sends
+        let last_progress = if self.retx_timeout > 0 { self.retx_timeout } else { time };
+        let deadline = max(last_progress + self.rto() + 1, time + 1);
+        Some(deadline - time)
+    }
+}
+
+impl Flow for GrantReceiver {
+    fn flow_info(&self) -> FlowInfo { self.flow_info }
+    fn side(&self) -> FlowSide { FlowSide::Receiver }
+    fn as_any(&self) -> &Any { self }
+
+    fn start_time(&self) -> Option<Nanos> {
+        self.start_time
+    }
+
+    fn completion_time(&self) -> Option<Nanos> {
+        self.completion_time
+    }
+
+    fn transmit_completion_time(&self) -> Option<Nanos> {
+        None
+    }
+
+    fn delivered_bytes(&self) -> u32 {
+        self.cumulative_received
+    }
+
+    fn jitter(&self) -> Option<f64> {
+        None
+    }
+
+    fn receive(&mut self, time: Nanos, pkt: Packet, logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
+        if let None = self.start_time {
+            self.start_time = Some(time);
+        }
+
+        let (hdr, seq, length) = match pkt {
+            // a trimmed header carries no data; the sender resends on its timeout
+            Packet::Data{trimmed: true, ..} => return Ok((vec![], false)),
+            Packet::Data{hdr, seq, length, ..} => (hdr, seq, length),
+            _ => unreachable!(),
+        };
+
+        let reply = PacketHeader{
+            flow: hdr.flow,
+            from: hdr.to,
+            to: hdr.from,
+            priority: hdr.priority,
+            size_bytes: hdr.size_bytes,
+        };
+
+        if seq != self.cumulative_received {
+            if self.nack_inflight || seq < self.cumulative_received {
+                return Ok((vec![], false));
+            }
+
+            self.nack_inflight = true;
+            return Ok((vec![Packet::Nack{hdr: reply, nacked_seq: self.cumulative_received}], false));
+        }
+
+        self.nack_inflight = false;
+        self.cumulative_received += length;
+        if self.cumulative_received == self.flow_info.length_bytes {
+            self.completion_time = Some(time - self.start_time.unwrap());
+            if let Some(log) = logger {
+                info!(log, "flow completed";
+                    "node" => self.flow_info.dest_id,
+                    "completion_time" => self.completion_time.unwrap(),
+                    "start_time" => self.start_time.unwrap(),
+                    "end_time" => time,
+                );
+            }
+        }
+
+        let mut pkts = vec![Packet::Ack{hdr: reply, cumulative_acked_seq: self.cumulative_received, ece: false, sack: NO_SACK, rwnd: UNLIMITED_RWND}];
+        let grant = min(self.cumulative_received + self.grant_window, self.flow_info.length_bytes);
+        if grant > self.granted {
+            self.granted = grant;
+            self.grants_sent += 1;
+            pkts.push(Packet::Grant{hdr: reply, granted_seq: grant});
+        }
+
+        Ok((pkts, false))
+    }
+
+    fn exec(&mut self, _time: Nanos, _logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
+        Ok((vec![], false))
+    }
+
+    fn transmitted(&mut self, _time: Nanos, _pkt: Packet) {}
+
+    fn on_timer(&mut self, _time: Nanos) -> Option<Nanos> {
+        None
+    }
+}
@@ -0,0 +1,242 @@
+use std::any::Any;
+use std::marker::PhantomData;
+
+use slog;
+
+use ::{Nanos, Result};
+use congcontrol::CongAlg;
+use event::{Event, EventTime};
+use node::Node;
+use packet::Packet;
+use super::{Flow, FlowInfo, FlowSide, FlowTimerEvent, TransmitCounts, DEFAULT_RTO, flow_node_ids, start_flow};
+use super::go_back_n::{self, GoBackNSender, GoBackNReceiver};
+
+/// Starts a synchronous RPC: the client `.0.sender_id` sends a `.0.length_bytes` request to the
+/// server `.0.dest_id`, which answers with a `.1` byte response once the whole request has
+/// arrived. Both directions use go-back-N with congestion control `CC`.
+///
+/// The response uses the request's packet length.
+#[derive(Serialize, Deserialize)]
+pub struct RpcFlowArrivalEvent<CC: CongAlg + 'static>(pub FlowInfo, pub u32, pub Nanos, pub PhantomData<CC>);
+
+impl<CC: CongAlg> Event for RpcFlowArrivalEvent<CC> {
+    fn time(&self) -> EventTime {
+        EventTime::Absolute(self.2)
+    }
+
+    fn affected_node_ids(&self) -> Vec<u32> {
+        flow_node_ids(&self.0)
+    }
+
+    fn exec(&mut self, _time: Nanos, nodes: &mut [&mut Node], _logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        let response_bytes = self.1;
+        let mut evs = start_flow(self.0, nodes, |fi| new::<CC>(fi, response_bytes))?;
+        // the server's timer covers its response's retransmission timeout
+        evs.push(Box::new(FlowTimerEvent(self.0.dest_id, self.0.flow_id, 0)));
+        Ok(evs)
+    }
+}
+
+pub fn new<CC: CongAlg>(fi: FlowInfo, response_bytes: u32) -> (Box<RpcClient<CC>>, Box<RpcServer<CC>>) {
+    let response_info = FlowInfo{
+        sender_id: fi.dest_id,
+        dest_id: fi.sender_id,
+        length_bytes: response_bytes,
+        ..fi
+    };
+
+    let (request_sender, request_receiver) = go_back_n::new::<CC>(fi);
+    let (response_sender, response_receiver) = go_back_n::new::<CC>(response_info);
+    (
+        Box::new(RpcClient{
+            flow_info: fi,
+            request: *request_sender,
+            response: *response_receiver,
+            completion_time: None,
+        }),
+        Box::new(RpcServer{
+            flow_info: fi,
+            request: *request_receiver,
+            response: *response_sender,
+            responding: false,
+        }),
+    )
+}
+
+/// The client side of an RPC: it sends the request, receives the response, and completes once
+/// the whole response has arrived. Its `flow_info` describes the request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RpcClient<CC: CongAlg> {
+    flow_info: FlowInfo,
+    request: GoBackNSender<CC>,
+    response: GoBackNReceiver,
+    completion_time: Option<Nanos>,
+}
+
+/// The server side of an RPC: it acknowledges the request, then sends the response once the
+/// whole request has arrived. It completes when the response is fully acknowledged.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RpcServer<CC: CongAlg> {
+    flow_info: FlowInfo,
+    request: GoBackNReceiver,
+    response: GoBackNSender<CC>,
+    responding: bool,
+}
+
+impl<CC: CongAlg + 'static> Flow for RpcClient<CC> {
+    fn flow_info(&self) -> FlowInfo { self.flow_info }
+    fn side(&self) -> FlowSide { FlowSide::Sender }
+    fn as_any(&self) -> &Any { self }
+
+    fn start_time(&self) -> Option<Nanos> {
+        self.request.start_time()
+    }
+
+    fn completion_time(&self) -> Option<Nanos> {
+        self.completion_time
+    }
+
+    fn transmit_completion_time(&self) -> Option<Nanos> {
+        self.request.transmit_completion_time()
+    }
+
+    fn delivered_bytes(&self) -> u32 {
+        self.response.delivered_bytes()
+    }
+
+    fn jitter(&self) -> Option<f64> {
+        self.response.jitter()
+    }
+
+    fn receive(&mut self, time: Nanos, pkt: Packet, logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
+        match pkt {
+            Packet::Data{..} => {
+                let reply = self.response.receive(time, pkt, logger)?;
+                if self.completion_time.is_none() && self.response.completion_time().is_some() {
+                    let start = self.request.start_time().unwrap();
+                    self.completion_time = Some(time - start);
+                    if let Some(log) = logger {
+                        info!(log, "rpc completed";
+                            "node" => self.flow_info.sender_id,
+                            "completion_time" => self.completion_time.unwrap(),
+                            "start_time" => start,
+                            "end_time" => time,
+                        );
+                    }
+                }
+
+                Ok(reply)
+            }
+            _ => self.request.receive(time, pkt, logger),
+        }
+    }
+
+    fn exec(&mut self, time: Nanos, logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
+        if self.completion_time.is_some() {
+            // the response shows the request arrived, even if its final ACK did not
+            return Ok((vec![], false));
+        }
+
+        self.request.exec(time, logger)
+    }
+
+    fn transmitted(&mut self, time: Nanos, pkt: Packet) {
+        self.request.transmitted(time, pkt)
+    }
+
+    fn transmit_counts(&self) -> TransmitCounts {
+        self.request.transmit_counts()
+    }
+
+    fn on_timer(&mut self, time: Nanos) -> Option<Nanos> {
+        if self.completion_time.is_some() {
+            return None;
+        }
+
+        self.request.on_timer(time)
+    }
+
+    fn next_send_time(&self) -> Option<Nanos> {
+        if self.completion_time.is_some() {
+            return None;
+        }
+
+        self.request.next_send_time()
+    }
+}
+
+impl<CC: CongAlg + 'static> Flow for RpcServer<CC> {
+    fn flow_info(&self) -> FlowInfo { self.flow_info }
+    fn side(&self) -> FlowSide { FlowSide::Receiver }
+    fn as_any(&self) -> &Any { self }
+
+    fn start_time(&self) -> Option<Nanos> {
+        self.request.start_time()
+    }
+
+    fn completion_time(&self) -> Option<Nanos> {
+        let start = self.request.start_time()?;
+        let response_start = self.response.start_time()?;
+        self.response.completion_time().map(|c| response_start + c - start)
+    }
+
+    fn transmit_completion_time(&self) -> Option<Nanos> {
+        None
+    }
+
+    fn delivered_bytes(&self) -> u32 {
+        self.request.delivered_bytes()
+    }
+
+    fn jitter(&self) -> Option<f64> {
+        self.request.jitter()
+    }
+
+    fn receive(&mut self, time: Nanos, pkt: Packet, logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
+        match pkt {
+            Packet::Data{..} => {
+                let (acks, _) = self.request.receive(time, pkt, logger)?;
+                if self.responding || self.request.completion_time().is_none() {
+                    return Ok((acks, false));
+                }
+
+                // turn around: the response also shows the request arrived, so it goes first
+                self.responding = true;
+                let (mut pkts, _) = self.response.exec(time, logger)?;
+                pkts.extend(acks);
+                Ok((pkts, false))
+            }
+            _ if self.responding => self.response.receive(time, pkt, logger),
+            _ => Ok((vec![], false)),
+        }
+    }
+
+    fn exec(&mut self, time: Nanos, logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
+        if !self.responding {
+            return Ok((vec![], false));
+        }
+
+        self.response.exec(time, logger)
+    }
+
+    fn transmitted(&mut self, time: Nanos, pkt: Packet) {
+        self.response.transmitted(time, pkt)
+    }
+
+    fn on_timer(&mut self, time: Nanos) -> Option<Nanos> {
+        if !self.responding {
+            // check back until the request has arrived
+            return Some(self.flow_info.rto.unwrap_or(DEFAULT_RTO));
+        }
+
+        self.response.on_timer(time)
+    }
+
+    fn next_send_time(&self) -> Option<Nanos> {
+        if !self.responding {
+            return None;
+        }
+
+        self.response.next_send_time()
+    }
+}
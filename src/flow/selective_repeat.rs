@@ -0,0 +1,516 @@
+use std::any::Any;
+use std::cmp::min;
+use std::collections::{HashMap, VecDeque};
+use std::marker::PhantomData;
+
+use slog;
+
+use ::{Nanos, Result};
+use ::congcontrol::{CongAlg, ReductionType};
+use event::{Event, EventTime};
+use log_filter::PACKET;
+use node::Node;
+use packet::{Packet, PacketHeader, SackBlocks, NO_SACK, UNLIMITED_RWND};
+use super::{Flow, FlowInfo, FlowSide, TransmitCounts, DEFAULT_RTO, flow_node_ids, packet_deadline, start_flow};
+
+/// Like `FlowArrivalEvent`, but the flow recovers losses with selective repeat rather than
+/// go-back-N.
+#[derive(Serialize, Deserialize)]
+pub struct SelectiveRepeatFlowArrivalEvent<CC: CongAlg + 'static>(pub FlowInfo, pub Nanos, pub PhantomData<CC>);
+
+impl<CC: CongAlg> Event for SelectiveRepeatFlowArrivalEvent<CC> {
+    fn time(&self) -> EventTime {
+        EventTime::Absolute(self.1)
+    }
+
+    fn affected_node_ids(&self) -> Vec<u32> {
+        flow_node_ids(&self.0)
+    }
+
+    fn exec(&mut self, _time: Nanos, nodes: &mut [&mut Node], _logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        start_flow(self.0, nodes, |fi| new::<CC>(fi))
+    }
+}
+
+/// A flow whose receiver acknowledges out-of-order data with SACK blocks, and whose sender
+/// retransmits only the segments the receiver is missing.
+///
+/// A segment is presumed lost once the receiver SACKs data beyond it, or when the sender's
+/// switch NACKs it. Each is retransmitted once that way; if the retransmission is lost too,
+/// the retransmission timeout resends everything not yet SACKed.
+pub fn new<CC: CongAlg>(fi: FlowInfo) -> (Box<SelectiveRepeatSender<CC>>, Box<SelectiveRepeatReceiver>) {
+    let segments = num_segments(fi);
+    let mut cong_control = CC::new();
+    if let Some(l) = fi.max_packet_length {
+        cong_control.set_mss(l);
+    }
+
+    (
+        Box::new(SelectiveRepeatSender{
+            flow_info: fi,
+            start_time: None,
+            completion_time: None,
+            transmit_completion_time: None,
+            transmit_counts: TransmitCounts::default(),
+            send_times: HashMap::new(),
+            latest_rtt: 0,
+            next_to_send: 0,
+            cumulative_acked: 0,
+            sacked: vec![false; segments],
+            retransmitted: vec![false; segments],
+            lost: VecDeque::new(),
+            retx_timeout: 0,
+            cc_timer: Some(0),
+            cong_control,
+        }),
+        Box::new(SelectiveRepeatReceiver{
+            flow_info: fi,
+            received: vec![false; segments],
+            cumulative_received: 0,
+            start_time: None,
+            completion_time: None,
+            prev_transit: None,
+            jitter: 0.0,
+        }),
+    )
+}
+
+fn num_segments(fi: FlowInfo) -> usize {
+    // rounding up by adding `l - 1` first would overflow for flows near `u32::MAX` bytes
+    fi.max_packet_length.map_or(0, |l| {
+        let partial = if fi.length_bytes % l > 0 { 1 } else { 0 };
+        (fi.length_bytes / l).saturating_add(partial) as usize
+    })
+}
+
+/// The index of the segment starting at byte `seq` of a flow of `max_packet_length` packets.
+fn segment(seq: u32, max_packet_length: u32) -> usize {
+    (seq / max_packet_length) as usize
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SelectiveRepeatSender<CC: CongAlg> {
+    flow_info: FlowInfo,
+    start_time: Option<Nanos>,
+    completion_time: Option<Nanos>,
+    transmit_completion_time: Option<Nanos>,
+    transmit_counts: TransmitCounts,
+    send_times: HashMap<u32, Nanos>, // ACK seq which acknowledges a packet -> when it was sent
+    latest_rtt: Nanos,
+    next_to_send: u32, // first byte never sent
+    cumulative_acked: u32,
+    sacked: Vec<bool>, // per segment, whether the receiver has it
+    retransmitted: Vec<bool>, // per segment, whether it was sent more than once
+    lost: VecDeque<u32>, // seqs of segments waiting to be retransmitted, in order
+    retx_timeout: Nanos, // when the flow started, last heard from the receiver, or last timed out
+    cc_timer: Option<Nanos>, // when the congestion control timer fires next
+    cong_control: CC,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SelectiveRepeatReceiver {
+    flow_info: FlowInfo,
+    received: Vec<bool>, // per segment, whether it has arrived
+    cumulative_received: u32,
+    start_time: Option<Nanos>,
+    completion_time: Option<Nanos>,
+    prev_transit: Option<i64>,
+    jitter: f64,
+}
+
+impl<CC: CongAlg + 'static> Flow for SelectiveRepeatSender<CC> {
+    fn flow_info(&self) -> FlowInfo { self.flow_info }
+    fn side(&self) -> FlowSide { FlowSide::Sender }
+    fn as_any(&self) -> &Any { self }
+
+    fn start_time(&self) -> Option<Nanos> {
+        self.start_time
+    }
+
+    fn completion_time(&self) -> Option<Nanos> {
+        self.completion_time
+    }
+
+    fn transmit_completion_time(&self) -> Option<Nanos> {
+        self.transmit_completion_time
+    }
+
+    fn delivered_bytes(&self) -> u32 {
+        self.cumulative_acked
+    }
+
+    fn jitter(&self) -> Option<f64> {
+        None
+    }
+
+    fn receive(&mut self, time: Nanos, pkt: Packet, logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
+        self.retx_timeout = time;
+        match pkt {
+            Packet::Ack{hdr, cumulative_acked_seq, ece, sack, ..} => {
+                assert_eq!(hdr.flow, self.flow_info.flow_id);
+                assert_eq!(hdr.from, self.flow_info.dest_id);
+                assert_eq!(hdr.to, self.flow_info.sender_id);
+                self.got_ack(cumulative_acked_seq, ece, sack, time, logger).map(|v| (v, false))
+            }
+            Packet::Nack{nacked_seq, ..} => {
+                let max_packet_length = self.flow_info.max_packet_length
+                    .ok_or_else(|| format_err!("flow {} has no packet length", self.flow_info.flow_id))?;
+                // only the NACKed segment is missing; the rest need not be sent again
+                self.cong_control.reduction(ReductionType::Drop);
+                if nacked_seq >= self.cumulative_acked && !self.is_sacked(nacked_seq, max_packet_length) && !self.lost.contains(&nacked_seq) {
+                    self.lost.push_back(nacked_seq);
+                }
+
+                self.maybe_send_more().map(|v| (v, false))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn exec(&mut self, time: Nanos, _logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
+        if let None = self.start_time {
+            self.start_time = Some(time);
+            self.retx_timeout = time;
+        }
+
+        if self.completion_time.is_some() {
+            Ok((vec![], false))
+        } else if self.retx_timeout > 0 && time - self.retx_timeout > self.rto() {
+            self.retx_timeout = time;
+            self.timed_out().map(|v| (v, true))
+        } else {
+            self.maybe_send_more().map(|v| (v, false))
+        }
+    }
+
+    fn transmitted(&mut self, time: Nanos, pkt: Packet) {
+        if let Packet::Data{seq, length, sent, ..} = pkt {
+            self.transmit_counts.record(seq, length);
+            // Karn's algorithm: no RTT samples from retransmitted segments
+            let retransmitted = self.flow_info.max_packet_length
+                .map_or(false, |l| self.retransmitted[segment(seq, l)]);
+            if !retransmitted {
+                self.send_times.insert(seq + length, sent);
+            }
+
+            if seq + length == self.flow_info.length_bytes {
+                self.transmit_completion_time = Some(time - self.start_time.unwrap());
+            }
+        }
+    }
+
+    fn transmit_counts(&self) -> TransmitCounts {
+        self.transmit_counts
+    }
+
+    fn on_timer(&mut self, time: Nanos) -> Option<Nanos> {
+        if self.completion_time.is_some() {
+            return None;
+        }
+
+        // one timer serves both congestion control and the retransmission timeout
+        if self.cc_timer.map_or(false, |t| time >= t) {
+            self.cc_timer = self.cong_control.on_timer(time).map(|d| time + d);
+        }
+
+        // the timer which starts the flow fires before it first sends
+        let last_progress = if self.retx_timeout > 0 { self.retx_timeout } else { time };
+        let mut rto_deadline = last_progress + self.rto() + 1;
+        if rto_deadline <= time {
+            // due now: the host handles it when it next polls, which restarts the timeout
+            rto_deadline = time + self.rto() + 1;
+        }
+
+        let next = self.cc_timer.map_or(rto_deadline, |t| min(t, rto_deadline));
+        Some(next - time)
+    }
+}
+
+impl<CC: CongAlg> SelectiveRepeatSender<CC> {
+    fn rto(&self) -> Nanos {
+        self.flow_info.rto.unwrap_or(DEFAULT_RTO)
+    }
+
+    fn is_sacked(&self, seq: u32, max_packet_length: u32) -> bool {
+        self.sacked.get(segment(seq, max_packet_length)).cloned().unwrap_or(false)
+    }
+
+    fn got_ack(&mut self, cumulative_acked_seq: u32, ece: bool, sack: SackBlocks, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Packet>> {
+        let max_packet_length = self.flow_info.max_packet_length
+            .ok_or_else(|| format_err!("flow {} has no packet length", self.flow_info.flow_id))?;
+
+        let mut highest_sacked = 0;
+        for &(start, end) in sack.iter().flatten() {
+            for seq in (start..end).step_by(max_packet_length as usize) {
+                let i = segment(seq, max_packet_length);
+                self.sacked[i] = true;
+            }
+
+            highest_sacked = ::std::cmp::max(highest_sacked, end);
+        }
+
+        if cumulative_acked_seq == self.cumulative_acked {
+            // fast retransmit, unless the SACKs already showed the packet missing
+            let seq = self.cumulative_acked;
+            if self.cong_control.on_dup_ack(self.next_to_send - seq)
+                && !self.is_sacked(seq, max_packet_length)
+                && !self.retransmitted[segment(seq, max_packet_length)]
+                && !self.lost.contains(&seq)
+            {
+                self.lost.push_back(seq);
+            }
+        }
+
+        if cumulative_acked_seq > self.cumulative_acked {
+            // RTT from when the acknowledged packet left the sender's queue.
+            // ACKs without a sample reuse the latest one.
+            if let Some(sent) = self.send_times.remove(&cumulative_acked_seq) {
+                self.latest_rtt = time - sent;
+            }
+
+            self.send_times.retain(|&acks, _| acks > cumulative_acked_seq);
+            self.cong_control.on_packet(cumulative_acked_seq - self.cumulative_acked, self.latest_rtt);
+            if ece {
+                self.cong_control.reduction(ReductionType::Ecn);
+            }
+
+            if let Some(log) = logger {
+                debug!(log, #PACKET, "cwnd";
+                    "time" => time,
+                    "cwnd" => self.cong_control.cwnd(),
+                    "rtt" => self.latest_rtt,
+                );
+            }
+
+            self.cumulative_acked = cumulative_acked_seq;
+            if self.cumulative_acked == self.flow_info.length_bytes {
+                self.completion_time = Some(time - self.start_time.unwrap());
+                if let Some(log) = logger {
+                    info!(log, "flow completed";
+                        "node" => self.flow_info.sender_id,
+                        "completion_time" => self.completion_time.unwrap(),
+                        "start_time" => self.start_time.unwrap(),
+                        "end_time" => time,
+                    );
+                }
+
+                return Ok(vec![]);
+            }
+        }
+
+        // segments the receiver skipped over are lost
+        let mut found_loss = false;
+        let mut seq = self.cumulative_acked;
+        while seq < highest_sacked {
+            let i = segment(seq, max_packet_length);
+            if !self.sacked[i] && !self.retransmitted[i] && !self.lost.contains(&seq) {
+                self.lost.push_back(seq);
+                found_loss = true;
+            }
+
+            seq += max_packet_length;
+        }
+
+        if found_loss {
+            self.cong_control.reduction(ReductionType::Drop);
+        }
+
+        self.maybe_send_more()
+    }
+
+    fn timed_out(&mut self) -> Result<Vec<Packet>> {
+        let max_packet_length = self.flow_info.max_packet_length
+            .ok_or_else(|| format_err!("flow {} has no packet length", self.flow_info.flow_id))?;
+        self.lost.clear();
+        let mut seq = self.cumulative_acked;
+        while seq < self.next_to_send {
+            if !self.is_sacked(seq, max_packet_length) {
+                self.lost.push_back(seq);
+            }
+
+            seq += max_packet_length;
+        }
+
+        self.maybe_send_more()
+    }
+
+    fn data(&self, seq: u32, length: u32) -> Packet {
+        Packet::Data{
+            hdr: PacketHeader{
+                flow: self.flow_info.flow_id,
+                from: self.flow_info.sender_id,
+                to: self.flow_info.dest_id,
+                priority: self.flow_info.priority,
+                size_bytes: self.flow_info.header_size_bytes(),
+            },
+            seq,
+            length,
+            ect: self.flow_info.ecn_capable,
+            ce: false,
+            sent: 0, // stamped by the host when it transmits the packet
+            deadline: packet_deadline(self.flow_info, self.start_time),
+            trimmed: false,
+        }
+    }
+
+    fn maybe_send_more(&mut self) -> Result<Vec<Packet>> {
+        let max_packet_length = self.flow_info.max_packet_length
+            .ok_or_else(|| format_err!("flow {} has no packet length", self.flow_info.flow_id))?;
+        let mut pkts = vec![];
+
+        // retransmissions replace packets which have left the network, so the window does not
+        // hold them back
+        while let Some(seq) = self.lost.pop_front() {
+            if seq < self.cumulative_acked || self.is_sacked(seq, max_packet_length) {
+                continue;
+            }
+
+            let i = segment(seq, max_packet_length);
+            self.retransmitted[i] = true;
+            let length = min(max_packet_length, self.flow_info.length_bytes - seq);
+            self.send_times.remove(&(seq + length));
+            pkts.push(self.data(seq, length));
+        }
+
+        // at least one packet in flight, so that the flow always makes progress
+        let cwnd = ::std::cmp::max(self.cong_control.cwnd(), 1) * max_packet_length;
+        while self.next_to_send < self.cumulative_acked + cwnd && self.next_to_send < self.flow_info.length_bytes {
+            let length = min(max_packet_length, self.flow_info.length_bytes - self.next_to_send);
+            let pkt = self.data(self.next_to_send, length);
+            self.next_to_send += length;
+            pkts.push(pkt);
+        }
+
+        Ok(pkts)
+    }
+}
+
+impl Flow for SelectiveRepeatReceiver {
+    fn flow_info(&self) -> FlowInfo { self.flow_info }
+    fn side(&self) -> FlowSide { FlowSide::Receiver }
+    fn as_any(&self) -> &Any { self }
+
+    fn start_time(&self) -> Option<Nanos> {
+        self.start_time
+    }
+
+    fn completion_time(&self) -> Option<Nanos> {
+        self.completion_time
+    }
+
+    fn transmit_completion_time(&self) -> Option<Nanos> {
+        None
+    }
+
+    fn delivered_bytes(&self) -> u32 {
+        self.cumulative_received
+    }
+
+    fn jitter(&self) -> Option<f64> {
+        self.prev_transit.map(|_| self.jitter)
+    }
+
+    fn receive(&mut self, time: Nanos, pkt: Packet, logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
+        match pkt {
+            // a trimmed header carries no data; later SACKs reveal the hole
+            Packet::Data{trimmed: true, ..} => Ok((vec![], false)),
+            Packet::Data{..} => self.got_data(pkt, time, logger).map(|v| (v, false)),
+            _ => unreachable!(),
+        }
+    }
+
+    fn exec(&mut self, _time: Nanos, _logger: Option<&slog::Logger>) -> Result<(Vec<Packet>, bool)> {
+        Ok((vec![], false))
+    }
+
+    fn transmitted(&mut self, _time: Nanos, _pkt: Packet) {}
+
+    fn on_timer(&mut self, _time: Nanos) -> Option<Nanos> {
+        None
+    }
+}
+
+impl SelectiveRepeatReceiver {
+    fn got_data(&mut self, data: Packet, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Packet>> {
+        let max_packet_length = self.flow_info.max_packet_length
+            .ok_or_else(|| format_err!("flow {} has no packet length", self.flow_info.flow_id))?;
+        if let None = self.start_time {
+            self.start_time = Some(time);
+        }
+
+        let (hdr, seq, ce, sent) = match data {
+            Packet::Data{hdr, seq, ce, sent, ..} => (hdr, seq, ce, sent),
+            _ => unreachable!(),
+        };
+
+        assert_eq!(hdr.flow, self.flow_info.flow_id);
+        assert_eq!(hdr.to, self.flow_info.dest_id);
+        assert_eq!(hdr.from, self.flow_info.sender_id);
+
+        // RFC 3550 interarrival jitter: a smoothed mean of the change in one-way delay
+        let transit = time as i64 - sent as i64;
+        if let Some(prev) = self.prev_transit {
+            let d = (transit - prev).abs() as f64;
+            self.jitter += (d - self.jitter) / 16.0;
+        }
+        self.prev_transit = Some(transit);
+
+        self.received[(seq / max_packet_length) as usize] = true;
+        let was_complete = self.completion_time.is_some();
+        while self.cumulative_received < self.flow_info.length_bytes
+            && self.received[(self.cumulative_received / max_packet_length) as usize] {
+            self.cumulative_received = min(self.cumulative_received + max_packet_length, self.flow_info.length_bytes);
+        }
+
+        if !was_complete && self.cumulative_received == self.flow_info.length_bytes {
+            self.completion_time = Some(time - self.start_time.unwrap());
+            if let Some(log) = logger {
+                info!(log, "flow completed";
+                    "node" => self.flow_info.dest_id,
+                    "completion_time" => self.completion_time.unwrap(),
+                    "jitter" => self.jitter,
+                    "start_time" => self.start_time.unwrap(),
+                    "end_time" => time,
+                );
+            }
+        }
+
+        Ok(vec![Packet::Ack{
+            hdr: PacketHeader{
+                flow: hdr.flow,
+                from: hdr.to,
+                to: hdr.from,
+                priority: hdr.priority,
+                size_bytes: hdr.size_bytes,
+            },
+            cumulative_acked_seq: self.cumulative_received,
+            ece: ce,
+            sack: self.sack_blocks(max_packet_length),
+            rwnd: UNLIMITED_RWND,
+        }])
+    }
+
+    /// The runs of received segments above the cumulative ACK, lowest first.
+    fn sack_blocks(&self, max_packet_length: u32) -> SackBlocks {
+        let mut blocks = NO_SACK;
+        let mut runs = 0;
+        let mut i = (self.cumulative_received / max_packet_length) as usize;
+        while i < self.received.len() && runs < blocks.len() {
+            if !self.received[i] {
+                i += 1;
+                continue;
+            }
+
+            let start = i;
+            while i < self.received.len() && self.received[i] {
+                i += 1;
+            }
+
+            let end = min(i as u32 * max_packet_length, self.flow_info.length_bytes);
+            blocks[runs] = Some((start as u32 * max_packet_length, end));
+            runs += 1;
+        }
+
+        blocks
+    }
+}
@@ -0,0 +1,177 @@
+use std::marker::PhantomData;
+
+use Nanos;
+use congcontrol::CongAlg;
+use event::Executor;
+use node::switch::Switch;
+use rng::Rng;
+use super::{FlowArrivalEvent, FlowInfo};
+
+/// The sizes of a workload's flows, in bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FlowSizeDist {
+    /// Every flow is the same size.
+    Constant(u32),
+    /// A piecewise-linear CDF through `(size_bytes, cumulative_prob)` points, in increasing
+    /// order and ending at probability 1. The first point's probability is the chance a flow
+    /// is exactly that size.
+    Cdf(Vec<(u32, f64)>),
+}
+
+/// Payload bytes per packet in the published CDFs, which are given in packets.
+const CDF_PACKET_BYTES: u32 = 1460;
+
+impl FlowSizeDist {
+    /// Check that `points` is a CDF and make a `FlowSizeDist::Cdf` of it.
+    pub fn from_cdf(points: Vec<(u32, f64)>) -> Self {
+        assert!(!points.is_empty(), "empty CDF");
+        assert!(
+            points.windows(2).all(|w| w[0].0 <= w[1].0 && w[0].1 <= w[1].1),
+            "CDF points are not increasing",
+        );
+        assert_eq!(points.last().unwrap().1, 1.0, "CDF does not end at probability 1");
+        FlowSizeDist::Cdf(points)
+    }
+
+    /// The web search workload of the DCTCP paper (Alizadeh et al., SIGCOMM 2010).
+    pub fn web_search() -> Self {
+        Self::from_packet_cdf(&[
+            (6, 0.0), (6, 0.15), (13, 0.2), (19, 0.3), (33, 0.4), (53, 0.53), (133, 0.6),
+            (667, 0.7), (1333, 0.8), (3333, 0.9), (6667, 0.97), (20000, 1.0),
+        ])
+    }
+
+    /// The data mining workload of the VL2 paper (Greenberg et al., SIGCOMM 2009).
+    pub fn data_mining() -> Self {
+        Self::from_packet_cdf(&[
+            (1, 0.0), (1, 0.5), (2, 0.6), (3, 0.7), (7, 0.8), (267, 0.9), (2107, 0.95),
+            (66667, 0.99), (666667, 1.0),
+        ])
+    }
+
+    fn from_packet_cdf(points: &[(u32, f64)]) -> Self {
+        Self::from_cdf(points.iter().map(|&(pkts, p)| (pkts * CDF_PACKET_BYTES, p)).collect())
+    }
+
+    /// Draw a flow size. `Constant` sizes draw nothing from `rng`.
+    pub fn sample(&self, rng: &mut Rng) -> u32 {
+        match *self {
+            FlowSizeDist::Constant(bytes) => bytes,
+            FlowSizeDist::Cdf(ref points) => {
+                let u = rng.next_f64();
+                let (first_size, first_p) = points[0];
+                if u < first_p {
+                    return first_size;
+                }
+
+                // invert the CDF, interpolating linearly within the segment u falls in
+                points.windows(2)
+                    .find(|w| u < w[1].1)
+                    .map_or(points[points.len() - 1].0, |w| {
+                        let ((s0, p0), (s1, p1)) = (w[0], w[1]);
+                        s0 + ((u - p0) / (p1 - p0) * f64::from(s1 - s0)) as u32
+                    })
+            }
+        }
+    }
+
+    /// The mean flow size, in bytes.
+    pub fn mean(&self) -> f64 {
+        match *self {
+            FlowSizeDist::Constant(bytes) => f64::from(bytes),
+            FlowSizeDist::Cdf(ref points) => {
+                let (first_size, first_p) = points[0];
+                f64::from(first_size) * first_p + points.windows(2)
+                    .map(|w| (w[1].1 - w[0].1) * (f64::from(w[0].0) + f64::from(w[1].0)) / 2.0)
+                    .sum::<f64>()
+            }
+        }
+    }
+}
+
+/// An open-loop workload of flows with Poisson arrivals, whose sizes are drawn from
+/// `flow_sizes`.
+///
+/// Each flow's sender and destination are drawn uniformly from `senders` and `dests`, redrawing
+/// loopback pairs, so at least one sender must differ from at least one destination.
+#[derive(Clone, Debug)]
+pub struct PoissonWorkload {
+    pub senders: Vec<u32>,
+    pub dests: Vec<u32>,
+    pub mean_interarrival: Nanos,
+    pub flow_sizes: FlowSizeDist,
+}
+
+impl PoissonWorkload {
+    /// `num_flows` flows, with ids `0..num_flows`, and when each arrives (from `start` on).
+    /// Seed it from the `Executor`'s `rng` to make the whole run reproducible from one seed.
+    pub fn generate(&self, num_flows: u32, start: Nanos, seed: u64) -> Vec<(FlowInfo, Nanos)> {
+        self.generate_with_load(num_flows, start, seed, |_| 1.0)
+    }
+
+    /// Like `generate`, but with a time-varying arrival rate: at time `t` flows arrive at
+    /// `load(t)` (above 0, and at most 1) times the rate `mean_interarrival` gives, e.g. to ramp
+    /// the offered load up over a run.
+    /// Arrivals are drawn at the full rate and each is kept with probability `load(t)`.
+    ///
+    /// Panics if `load` is 0 or less at a drawn arrival: a load which stays there would never
+    /// produce the next flow.
+    pub fn generate_with_load(
+        &self,
+        num_flows: u32,
+        start: Nanos,
+        seed: u64,
+        load: impl Fn(Nanos) -> f64,
+    ) -> Vec<(FlowInfo, Nanos)> {
+        assert!(
+            self.senders.iter().any(|s| self.dests.iter().any(|d| s != d)),
+            "workload has only loopback flows",
+        );
+
+        let mut rng = Rng::new(seed);
+        let pick = |nodes: &[u32], rng: &mut Rng| nodes[(rng.next_f64() * nodes.len() as f64) as usize];
+        let mut time = start;
+        (0..num_flows).map(|flow_id| {
+            // exponential interarrival times; 1 - u is in (0, 1], so ln() is finite
+            loop {
+                time += (-(1.0 - rng.next_f64()).ln() * self.mean_interarrival as f64) as Nanos;
+                let l = load(time);
+                assert!(l > 0.0, "load {} at time {} is not positive", l, time);
+                if l >= 1.0 || rng.next_f64() < l {
+                    break;
+                }
+            }
+
+            let (sender_id, dest_id) = loop {
+                let (s, d) = (pick(&self.senders, &mut rng), pick(&self.dests, &mut rng));
+                if s != d {
+                    break (s, d);
+                }
+            };
+            let length_bytes = self.flow_sizes.sample(&mut rng);
+
+            (FlowInfo::builder(flow_id, sender_id, dest_id).length_bytes(length_bytes).build(), time)
+        }).collect()
+    }
+
+    /// Push the arrival of each `generate`d flow onto `e`, using `CC`.
+    pub fn schedule<CC: CongAlg + 'static, S: Switch>(&self, e: &mut Executor<S>, num_flows: u32, start: Nanos, seed: u64) {
+        for (flow, arrival) in self.generate(num_flows, start, seed) {
+            e.push(Box::new(FlowArrivalEvent(flow, arrival, PhantomData::<CC>)));
+        }
+    }
+
+    /// Push the arrival of each `generate_with_load`ed flow onto `e`, using `CC`.
+    pub fn schedule_with_load<CC: CongAlg + 'static, S: Switch>(
+        &self,
+        e: &mut Executor<S>,
+        num_flows: u32,
+        start: Nanos,
+        seed: u64,
+        load: impl Fn(Nanos) -> f64,
+    ) {
+        for (flow, arrival) in self.generate_with_load(num_flows, start, seed, load) {
+            e.push(Box::new(FlowArrivalEvent(flow, arrival, PhantomData::<CC>)));
+        }
+    }
+}
@@ -7,227 +7,3615 @@ extern crate itertools;
 extern crate slog;
 extern crate slog_bunyan;
 extern crate slog_term;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+use failure::Error;
+type Result<T> = std::result::Result<T, Error>;
+pub type Nanos = u64;
+
+/// Default maximum frame size (in bytes, including headers) carried by a `Link`.
+pub const DEFAULT_MTU: u32 = 1500;
+
+pub mod event;
+pub mod topology;
+pub mod packet;
+pub mod node;
+pub mod flow;
+pub mod congcontrol;
+pub mod stats;
+pub mod rng;
+pub mod loss;
+pub mod log_filter;
+pub mod checkpoint;
+
+use std::marker::PhantomData;
+
+use congcontrol::{CongAlg, ConstCwnd};
+use event::Executor;
+use flow::{FlowArrivalEvent, FlowInfo};
+use node::switch::Switch;
+use stats::{ThroughputSampleEvent, ThroughputTrace};
+use topology::{TopologyStrategy, one_big_switch::OneBigSwitch};
+
+pub trait Scenario {
+    fn make<S: Switch>(logger: Option<slog::Logger>) -> Executor<S>;
+}
+
+/// `independent_victim_flow_scenario()` is the difference between `IngressPFCSwitch` and `PFCSwitch`.
+/// (See `shared_ingress_victim_flow_scenario()`)
+/// `PFCSwitch` does not consider ingress queue occupancy when doing PFC; it is a "naive" switch
+/// implementation.
+/// Consider this simple topology:
+///
+/// `Host` 0 -------
+///                 \
+/// `Host` 1 --------\
+///                   ---- 4 (`Switch`)
+/// `Host` 2 --------/
+///                 /
+/// `Host` 3 -------
+///
+/// `Host` 3 and 2 send to `Host` 0, creating congestion. 
+/// A "victim flow" goes from `Host` *0* to `Host` 1.
+/// When the queue to `Host` 0 fills, `Switch` 4, a `PFCSwitch`, will PAUSE *all* incoming links, so the "victim flow"
+/// will also be paused.
+///
+/// `Host` 3 and 2 send to `Host` 0, creating congestion. 
+/// A "victim flow" goes from `Host` *2* to `Host` 1.
+/// When the queue to `Host` 0 fills, `Switch` 4, an `IngressPFCSwitch` will PAUSE *only* the
+/// congested incoming queues, but the "victim flow" is sending from `Host` 2 and will still get
+/// caught.
+pub struct IndependentVictimFlowScenario;
+
+impl Scenario for IndependentVictimFlowScenario {
+    fn make<S: Switch>(logger: Option<slog::Logger>) -> Executor<S> {
+        let t = OneBigSwitch::<S>::make_topology(4, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, logger, None);
+
+        let flow = FlowInfo::builder(0, 0, 1)
+            .length_bytes(43800) // 30 packet flow
+            .build();
+
+        // starts at t = 1.1s
+        let flow_arrival = Box::new(FlowArrivalEvent(flow, 1_100_000_000, PhantomData::<ConstCwnd>));
+        e.push(flow_arrival);
+
+        let flow = FlowInfo::builder(1, 2, 0)
+            .length_bytes(438000) // 300 packet flow
+            .build();
+
+        // starts at t = 1.0s
+        let flow_arrival = Box::new(FlowArrivalEvent(flow, 1_000_000_000, PhantomData::<ConstCwnd>));
+        e.push(flow_arrival);
+
+        let flow = FlowInfo::builder(2, 3, 0)
+            .length_bytes(438000) // 300 packet flow
+            .build();
+
+        // starts at t = 1.0s
+        let flow_arrival = Box::new(FlowArrivalEvent(flow, 1_000_000_000, PhantomData::<ConstCwnd>));
+        e.push(flow_arrival);
+
+        e
+    }
+}
+
+/// `shared_ingress_victim_flow_scenario()` is the difference between `IngressPFCSwitch` and `PFCSwitch`.
+/// (See `independent_victim_flow_scenario()`)
+pub struct SharedIngressVictimFlowScenario;
+
+impl Scenario for SharedIngressVictimFlowScenario {
+    fn make<S: Switch>(logger: Option<slog::Logger>) -> Executor<S> {
+        let t = OneBigSwitch::<S>::make_topology(4, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, logger, None);
+
+        let flow = FlowInfo::builder(0, 2, 1)
+            .length_bytes(43800) // 30 packet flow
+            .build();
+
+        // starts at t = 1.1s
+        let flow_arrival = Box::new(FlowArrivalEvent(flow, 1_100_000_000, PhantomData::<ConstCwnd>));
+        e.push(flow_arrival);
+
+        let flow = FlowInfo::builder(1, 2, 0)
+            .length_bytes(438000) // 300 packet flow
+            .build();
+
+        // starts at t = 1.0s
+        let flow_arrival = Box::new(FlowArrivalEvent(flow, 1_000_000_000, PhantomData::<ConstCwnd>));
+        e.push(flow_arrival);
+
+        let flow = FlowInfo::builder(2, 3, 0)
+            .length_bytes(438000) // 300 packet flow
+            .build();
+
+        // starts at t = 1.0s
+        let flow_arrival = Box::new(FlowArrivalEvent(flow, 1_000_000_000, PhantomData::<ConstCwnd>));
+        e.push(flow_arrival);
+
+        e
+    }
+}
+
+/// `ConvergenceScenario` measures how quickly flows using `CC` converge to their fair share.
+///
+/// `Host`s 1, 2, and 3 each send a long flow to `Host` 0, starting 500ms apart, so the
+/// `Switch` -> `Host` 0 link is the bottleneck.
+/// Every 100ms, each flow's throughput is logged (`"throughput"` lines) and recorded in the
+/// returned `ThroughputTrace`, yielding the classic "convergence to fair share" plot.
+pub struct ConvergenceScenario<CC: CongAlg>(PhantomData<CC>);
+
+impl<CC: CongAlg + 'static> ConvergenceScenario<CC> {
+    pub const NUM_FLOWS: u32 = 3;
+    pub const FIRST_START: Nanos = 1_000_000_000;
+    pub const START_GAP: Nanos = 500_000_000;
+    pub const SAMPLE_INTERVAL: Nanos = 100_000_000;
+    /// Sampling stops by now even if a flow never completes.
+    pub const SAMPLE_END: Nanos = 30_000_000_000;
+
+    pub fn make_with_trace<S: Switch>(logger: Option<slog::Logger>) -> (Executor<S>, ThroughputTrace) {
+        // deep buffer so that the only constraint on each flow is its congestion control
+        let t = OneBigSwitch::<S>::make_topology(Self::NUM_FLOWS + 1, 150_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, logger, None);
+
+        for i in 0..Self::NUM_FLOWS {
+            let flow = FlowInfo::builder(i, i + 1, 0)
+                .length_bytes(438000) // 300 packet flow
+                .build();
+
+            let start = Self::FIRST_START + i as Nanos * Self::START_GAP;
+            e.push(Box::new(FlowArrivalEvent(flow, start, PhantomData::<CC>)));
+        }
+
+        let trace = ThroughputTrace::default();
+        e.push(Box::new(ThroughputSampleEvent::new(
+            Self::FIRST_START,
+            Self::SAMPLE_INTERVAL,
+            Self::SAMPLE_END,
+            vec![0],
+            trace.clone(),
+        )));
+
+        (e, trace)
+    }
+}
+
+impl<CC: CongAlg + 'static> Scenario for ConvergenceScenario<CC> {
+    fn make<S: Switch>(logger: Option<slog::Logger>) -> Executor<S> {
+        Self::make_with_trace(logger).0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+    use slog;
+    use super::Scenario;
+    use super::topology::{Topology, TopologyStrategy};
+    use super::topology::one_big_switch::OneBigSwitch;
+    use super::event::Executor;
+    use super::node::switch::{Switch, lossy_switch::LossySwitch, nack_switch::NackSwitch, pfc_switch::{PFCSwitch, IngressPFCSwitch}, credit_switch::CreditSwitch, trimming_switch::TrimmingSwitch};
+    use super::packet::{Packet, PacketHeader, NO_SACK, PACKET_HEADER_SIZE_BYTES, UNLIMITED_RWND};
+    use super::flow::{FlowArrivalEvent, FlowInfo, FlowSide, TransmitCounts};
+    use super::Nanos;
+    use super::congcontrol::{CongAlg, ConstCwnd};
+    use super::stats::flow_slowdowns;
+    use super::loss::{LossContext, LossModel};
+    use std::collections::{HashMap, HashSet};
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+
+    /// Make a standard instance of `slog::Logger`.
+    fn make_logger(logfile: Option<&str>) -> slog::Logger {
+        use std::sync::Mutex;
+        use std::fs::File;
+        use slog::Drain;
+        use slog_bunyan;
+        use slog_term;
+
+        if let Some(fln) = logfile {
+            let f = File::create(fln).unwrap();
+            let json_drain = Mutex::new(slog_bunyan::default(f)).fuse();
+            slog::Logger::root(json_drain, o!())
+        } else {
+            let decorator = slog_term::PlainSyncDecorator::new(slog_term::TestStdoutWriter);
+            let human_drain = slog_term::FullFormat::new(decorator).build().filter_level(slog::Level::Debug).fuse();
+            slog::Logger::root(human_drain, o!())
+        }
+    }
+
+    /// Records every value a sender logs under `key` in its "cwnd" message, in order.
+    struct KeyRecorder(&'static str, Arc<Mutex<Vec<u64>>>);
+    impl slog::Serializer for KeyRecorder {
+        fn emit_arguments(&mut self, key: slog::Key, val: &fmt::Arguments) -> slog::Result {
+            if key == self.0 {
+                self.1.lock().unwrap().push(format!("{}", val).parse().unwrap());
+            }
+
+            Ok(())
+        }
+    }
+
+    impl slog::Drain for KeyRecorder {
+        type Ok = ();
+        type Err = slog::Never;
+        fn log(&self, record: &slog::Record, _: &slog::OwnedKVList) -> ::std::result::Result<(), slog::Never> {
+            use slog::KV;
+            if format!("{}", record.msg()) == "cwnd" {
+                record.kv().serialize(record, &mut KeyRecorder(self.0, self.1.clone())).unwrap();
+            }
+
+            Ok(())
+        }
+    }
+
+    fn cwnd_message_logger(key: &'static str) -> (slog::Logger, Arc<Mutex<Vec<u64>>>) {
+        let values = Arc::new(Mutex::new(vec![]));
+        (slog::Logger::root(KeyRecorder(key, values.clone()), o!()), values)
+    }
+
+    /// A logger which records every cwnd logged by a sender, in order.
+    fn cwnd_logger() -> (slog::Logger, Arc<Mutex<Vec<u64>>>) {
+        cwnd_message_logger("cwnd")
+    }
+
+    /// Records the message and every structured field, including the logger's context, of
+    /// each line logged.
+    struct LineRecorder(Arc<Mutex<Vec<(String, HashMap<String, String>)>>>);
+    struct FieldRecorder(HashMap<String, String>);
+    impl slog::Serializer for FieldRecorder {
+        fn emit_arguments(&mut self, key: slog::Key, val: &fmt::Arguments) -> slog::Result {
+            self.0.insert(key.to_string(), format!("{}", val));
+            Ok(())
+        }
+    }
+
+    impl slog::Drain for LineRecorder {
+        type Ok = ();
+        type Err = slog::Never;
+        fn log(&self, record: &slog::Record, values: &slog::OwnedKVList) -> ::std::result::Result<(), slog::Never> {
+            use slog::KV;
+            let mut fields = FieldRecorder(HashMap::new());
+            record.kv().serialize(record, &mut fields).unwrap();
+            values.serialize(record, &mut fields).unwrap();
+            self.0.lock().unwrap().push((format!("{}", record.msg()), fields.0));
+            Ok(())
+        }
+    }
+
+    /// Drops the first `Data` packet with this sequence number.
+    #[derive(Debug)]
+    struct DropOnce(Option<u32>);
+    impl LossModel for DropOnce {
+        fn should_drop(&mut self, pkt: &Packet, _: &LossContext) -> bool {
+            match *pkt {
+                Packet::Data{seq, ..} if Some(seq) == self.0 => {
+                    self.0 = None;
+                    true
+                }
+                _ => false,
+            }
+        }
+    }
+
+    fn setup_test() -> Executor<LossySwitch> {
+        let t = OneBigSwitch::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        Executor::new(t, None, None)
+    }
+    
+    #[test]
+    fn send_one_packet() {
+        let mut e = setup_test();
+
+        {
+            let pkt = Packet::Data{
+                hdr: PacketHeader{
+                    flow: 0,
+                    from: 0,
+                    to: 1,
+                    priority: 0,
+                    size_bytes: PACKET_HEADER_SIZE_BYTES,
+                },
+                seq: 0,
+                length: 1460,
+                ect: true,
+                ce: false,
+                sent: 0,
+                deadline: None,
+                trimmed: false,
+            };
+
+            let topo = e.components().1;
+            topo.lookup_host(0).unwrap().push_pkt(pkt);
+        }
+
+        let e = e.execute().unwrap();
+        assert_eq!(e.current_time(), 26000000);
+    }
+
+    fn ack_latency(size_bytes: u32) -> Nanos {
+        let mut e = setup_test();
+
+        {
+            let pkt = Packet::Ack{
+                hdr: PacketHeader{
+                    flow: 0,
+                    from: 1,
+                    to: 0,
+                    priority: 0,
+                    size_bytes,
+                },
+                cumulative_acked_seq: 0,
+                ece: false,
+                sack: NO_SACK,
+                rwnd: UNLIMITED_RWND,
+            };
+
+            let topo = e.components().1;
+            topo.lookup_host(1).unwrap().push_pkt(pkt);
+        }
+
+        e.execute().unwrap().current_time()
+    }
+
+    #[test]
+    fn ack_transmission_delay_scales_with_header_size() {
+        // two 1 Mbit/s hops with 1 ms propagation delay each
+        assert_eq!(ack_latency(PACKET_HEADER_SIZE_BYTES), 2 * (320_000 + 1_000_000));
+        assert_eq!(ack_latency(400), 2 * (3_200_000 + 1_000_000));
+
+        // a flow's packets carry its own header size, or the default
+        let flowinfo = FlowInfo::builder(0, 0, 1)
+            .length_bytes(14_000)
+            .header_bytes(100)
+            .build();
+        assert_eq!(flowinfo.header_size_bytes(), 100);
+        assert_eq!(FlowInfo{header_bytes: None, ..flowinfo}.header_size_bytes(), PACKET_HEADER_SIZE_BYTES);
+    }
+
+    #[test]
+    fn flow_info_builder_defaults() {
+        let built = FlowInfo::builder(0, 0, 1)
+            .length_bytes(14600)
+            .build();
+        assert_eq!(built, FlowInfo{
+            flow_id: 0,
+            sender_id: 0,
+            dest_id: 1,
+            length_bytes: 14600,
+            max_packet_length: None,
+            rto: None,
+            ecn_capable: true,
+            priority: 0,
+            deadline: None,
+            header_bytes: None,
+            paced: false,
+            receive_window: None,
+        });
+
+        let built = FlowInfo::builder(3, 1, 2)
+            .length_bytes(14600)
+            .max_packet_length(1000)
+            .rto(5_000_000)
+            .ecn_capable(false)
+            .priority(2)
+            .deadline(100_000_000)
+            .header_bytes(64)
+            .build();
+        assert_eq!(built, FlowInfo{
+            flow_id: 3,
+            sender_id: 1,
+            dest_id: 2,
+            length_bytes: 14600,
+            max_packet_length: Some(1000),
+            rto: Some(5_000_000),
+            ecn_capable: false,
+            priority: 2,
+            deadline: Some(100_000_000),
+            header_bytes: Some(64),
+            paced: false,
+            receive_window: None,
+        });
+    }
+
+    #[test]
+    fn flow_log_lines_carry_flow_id() {
+        let lines = Arc::new(Mutex::new(vec![]));
+        let logger = slog::Logger::root(LineRecorder(lines.clone()), o!());
+        let t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, logger, None);
+        let flowinfo = FlowInfo::builder(7, 0, 1)
+            .length_bytes(14600) // 10 packet flow
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        e.execute().unwrap();
+
+        // everything the hosts (0 and 1) log is on behalf of the flow; the switch's lines are not
+        let lines = lines.lock().unwrap();
+        let flow_lines = lines.iter()
+            .filter(|(msg, fields)| match msg.as_str() {
+                "cwnd" | "flow completed" => true,
+                "tx" | "rx" => fields.get("node").map_or(false, |n| n == "0" || n == "1"),
+                _ => false,
+            })
+            .collect::<Vec<_>>();
+        assert!(flow_lines.iter().any(|(msg, _)| msg == "cwnd"));
+        assert_eq!(flow_lines.iter().filter(|(msg, _)| msg == "flow completed").count(), 2);
+        for (msg, fields) in flow_lines {
+            assert_eq!(fields.get("flow").map(|f| f.as_str()), Some("7"), "{} line {:?}", msg, fields);
+        }
+
+        let sides = lines.iter().filter_map(|(_, fields)| fields.get("side")).collect::<HashSet<_>>();
+        assert_eq!(sides.len(), 2);
+    }
+
+    /// The fields of every "tx" line switch 2 of a `OneBigSwitch<S>` logs while a flow crosses it.
+    fn switch_tx_lines<S: Switch>() -> Vec<HashMap<String, String>> {
+        let lines = Arc::new(Mutex::new(vec![]));
+        let logger = slog::Logger::root(LineRecorder(lines.clone()), o!());
+        let t = OneBigSwitch::<S>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, logger, None);
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(14600) // 10 packet flow
+            .build();
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        e.execute().unwrap();
+
+        let lines = lines.lock().unwrap();
+        lines.iter()
+            .filter(|(msg, fields)| msg == "tx" && fields.get("node").map_or(false, |n| n == "2"))
+            .map(|(_, fields)| fields.clone())
+            .collect()
+    }
+
+    #[test]
+    fn switch_tx_lines_carry_occupancy() {
+        for lines in vec![
+            switch_tx_lines::<LossySwitch>(),
+            switch_tx_lines::<PFCSwitch>(),
+            switch_tx_lines::<NackSwitch>(),
+        ] {
+            assert!(!lines.is_empty());
+            for fields in lines {
+                assert!(fields.contains_key("port"), "{:?}", fields);
+                assert!(fields.contains_key("headroom"), "{:?}", fields);
+                let occupancy = fields.get("occupancy").and_then(|o| o.parse::<u32>().ok());
+                assert!(occupancy.map_or(false, |o| o <= 15_000), "{:?}", fields);
+            }
+        }
+    }
+
+    /// The lines logged while flows 1 (host 0 to 1) and 2 (host 1 to 0) run through `filter`.
+    fn filtered_log_lines(filter: super::log_filter::LogFilter) -> Vec<(String, HashMap<String, String>)> {
+        let lines = Arc::new(Mutex::new(vec![]));
+        let logger = slog::Logger::root(LineRecorder(lines.clone()), o!());
+        let t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, logger, filter);
+        for &(id, from, to) in &[(1, 0, 1), (2, 1, 0)] {
+            let flowinfo = FlowInfo::builder(id, from, to)
+                .length_bytes(14600) // 10 packet flow
+                .build();
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        e.execute().unwrap();
+        let lines = lines.lock().unwrap();
+        lines.clone()
+    }
+
+    #[test]
+    fn log_filter_drops_packet_lines() {
+        use super::log_filter::LogFilter;
+
+        let unfiltered = filtered_log_lines(LogFilter::default());
+        assert!(unfiltered.iter().any(|(msg, _)| msg == "rx"));
+
+        let lines = filtered_log_lines(LogFilter::without_packet_lines());
+        assert!(!lines.is_empty());
+        assert!(lines.len() < unfiltered.len() / 4, "{} of {} lines", lines.len(), unfiltered.len());
+
+        // without the executor's own lines, just the completions of both sides of both flows
+        let lines = filtered_log_lines(LogFilter{
+            packets: false,
+            modules: vec![("event".to_string(), slog::Level::Warning)],
+            ..Default::default()
+        });
+        assert_eq!(lines.len(), 4, "{:?}", lines);
+        assert!(lines.iter().all(|(msg, _)| msg == "flow completed"), "{:?}", lines);
+
+        let lines = filtered_log_lines(LogFilter{
+            only_messages: Some(vec!["flow completed".to_string()]),
+            ..Default::default()
+        });
+        assert_eq!(lines.len(), 4);
+        assert!(lines.iter().all(|(msg, _)| msg == "flow completed"));
+    }
+
+    #[test]
+    fn log_filter_by_module() {
+        use super::log_filter::LogFilter;
+
+        // the hosts' per-packet lines, but none of the switch's (node 2)
+        let lines = filtered_log_lines(LogFilter{
+            modules: vec![("node::switch".to_string(), slog::Level::Info)],
+            ..Default::default()
+        });
+        assert!(lines.iter().any(|(msg, _)| msg == "rx"));
+        assert!(lines.iter().all(|(_, fields)| fields.get("node").map(|n| n.as_str()) != Some("2")), "{:?}", lines);
+    }
+
+    #[test]
+    fn log_filter_selects_flows() {
+        use super::log_filter::LogFilter;
+
+        let lines = filtered_log_lines(LogFilter{
+            only_flows: Some(vec![2]),
+            ..Default::default()
+        });
+        assert!(lines.iter().any(|(msg, _)| msg == "flow completed"));
+        for (msg, fields) in lines {
+            assert_eq!(fields.get("flow").map(|f| f.as_str()), Some("2"), "{} line {:?}", msg, fields);
+        }
+    }
+
+    /// When a single packet of `length` payload bytes arrives at host 1.
+    fn one_packet_latency(length: u32, cut_through_threshold: Option<u32>) -> Nanos {
+        let mut e = setup_test();
+
+        {
+            let pkt = Packet::Data{
+                hdr: PacketHeader{
+                    flow: 0,
+                    from: 0,
+                    to: 1,
+                    priority: 0,
+                    size_bytes: PACKET_HEADER_SIZE_BYTES,
+                },
+                seq: 0,
+                length,
+                ect: true,
+                ce: false,
+                sent: 0,
+                deadline: None,
+                trimmed: false,
+            };
+
+            let topo = e.components().1;
+            topo.set_cut_through_threshold(2, cut_through_threshold);
+            topo.lookup_host(0).unwrap().push_pkt(pkt);
+        }
+
+        e.execute().unwrap().current_time()
+    }
+
+    #[test]
+    fn hybrid_cut_through() {
+        // store-and-forward: 2 * (transmission + propagation)
+        assert_eq!(one_packet_latency(1460, None), 26_000_000);
+        assert_eq!(one_packet_latency(100, None), 2 * (1_120_000 + 1_000_000));
+
+        // large packets only wait for their 40 byte header at the switch
+        assert_eq!(one_packet_latency(1460, Some(1000)), 320_000 + 1_000_000 + 12_000_000 + 1_000_000);
+        assert_eq!(one_packet_latency(100, Some(1000)), 2 * (1_120_000 + 1_000_000));
+    }
+
+    /// When a single packet of `length` payload bytes from host 0 arrives at host 1 over the
+    /// three hops of a dumbbell, with both switches (2 and 3) cutting through at
+    /// `cut_through_threshold`.
+    fn three_hop_latency(length: u32, cut_through_threshold: Option<u32>) -> Nanos {
+        use super::topology::dumbbell::{Bottleneck, Dumbbell};
+
+        let bottleneck = Bottleneck{
+            bandwidth_bps: 1_000_000,
+            queue_length_bytes: 15_000,
+        };
+        let mut t = Dumbbell::<LossySwitch>::make_dumbbell(1, 1, 15_000, 1_000_000, bottleneck, 1_000_000);
+        t.set_cut_through_threshold(2, cut_through_threshold);
+        t.set_cut_through_threshold(3, cut_through_threshold);
+        let mut e = Executor::new(t, None, None);
+        {
+            let pkt = Packet::Data{
+                hdr: PacketHeader{
+                    flow: 0,
+                    from: 0,
+                    to: 1,
+                    priority: 0,
+                    size_bytes: PACKET_HEADER_SIZE_BYTES,
+                },
+                seq: 0,
+                length,
+                ect: true,
+                ce: false,
+                sent: 0,
+                deadline: None,
+                trimmed: false,
+            };
+
+            e.components().1.lookup_host(0).unwrap().push_pkt(pkt);
+        }
+
+        e.execute().unwrap().current_time()
+    }
+
+    #[test]
+    fn cut_through_across_switches() {
+        // store-and-forward: 3 * (transmission + propagation)
+        assert_eq!(three_hop_latency(1460, None), 3 * (12_000_000 + 1_000_000));
+
+        // each switch forwards once the 40 byte header is in, so only the last hop waits for
+        // the whole packet
+        let cut_through = three_hop_latency(1460, Some(1000));
+        assert_eq!(cut_through, 2 * (320_000 + 1_000_000) + 12_000_000 + 1_000_000);
+        assert!(cut_through < three_hop_latency(1460, None));
+    }
+
+    #[test]
+    fn switch_processing_delay() {
+        use super::node::switch::delay_switch::DelaySwitch;
+        use super::rng::Delay;
+
+        let t = OneBigSwitch::<DelaySwitch<LossySwitch>>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+
+        {
+            let pkt = Packet::Data{
+                hdr: PacketHeader{
+                    flow: 0,
+                    from: 0,
+                    to: 1,
+                    priority: 0,
+                    size_bytes: PACKET_HEADER_SIZE_BYTES,
+                },
+                seq: 0,
+                length: 1460,
+                ect: true,
+                ce: false,
+                sent: 0,
+                deadline: None,
+                trimmed: false,
+            };
+
+            let topo = e.components().1;
+            topo.switches[0].set_processing_delay(Delay::Constant(500_000));
+            topo.lookup_host(0).unwrap().push_pkt(pkt);
+        }
+
+        // same as send_one_packet, plus one processing delay at the switch
+        let e = e.execute().unwrap();
+        assert_eq!(e.current_time(), 26000000 + 500_000);
+    }
+
+    /// The processing delays a `DelaySwitch` in an executor seeded with `seed` gives 1000 packets
+    /// with uniformly distributed delays.
+    fn uniform_processing_delays(seed: u64) -> Vec<Nanos> {
+        use super::event::EventTime;
+        use super::node::switch::delay_switch::DelaySwitch;
+        use super::rng::Delay;
+
+        let t = OneBigSwitch::<DelaySwitch<LossySwitch>>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::with_seed(t, None, None, seed);
+        let topo = e.components().1;
+        topo.switches[0].set_processing_delay(Delay::Uniform{min: 100_000, max: 900_000});
+        let in_link = topo.hosts[0].link;
+        (0..1000)
+            .map(|seq| {
+                let pkt = Packet::Data{
+                    hdr: PacketHeader{
+                        flow: 0,
+                        from: 0,
+                        to: 1,
+                        priority: 0,
+                        size_bytes: PACKET_HEADER_SIZE_BYTES,
+                    },
+                    seq,
+                    length: 1460,
+                    ect: true,
+                    ce: false,
+                    sent: 0,
+                    deadline: None,
+                    trimmed: false,
+                };
+
+                let evs = Switch::receive(&mut topo.switches[0], pkt, in_link, 0, None).unwrap();
+                assert_eq!(evs.len(), 1);
+                match evs[0].time() {
+                    EventTime::Delta(d) => d,
+                    EventTime::Absolute(_) => panic!("expected a delay"),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn switch_random_processing_delay() {
+        let delays = uniform_processing_delays(7);
+        assert!(delays.iter().all(|&d| d >= 100_000 && d <= 900_000), "{:?}", delays);
+        let mean = delays.iter().sum::<Nanos>() / delays.len() as Nanos;
+        assert!(mean >= 475_000 && mean <= 525_000, "mean {}", mean);
+        // delays differ from packet to packet, so the random path is exercised
+        assert!(delays.iter().any(|&d| d != delays[0]));
+
+        // the executor's seed alone decides the delays
+        assert_eq!(uniform_processing_delays(7), delays);
+        assert!(uniform_processing_delays(8) != delays);
+    }
+
+    /// Every host in a ring of 4 sends a 300 packet flow to the next, so every port of the
+    /// switch (node 4) is busy. Returns the switch's aggregate forwarding rate in bps.
+    fn all_ports_busy_throughput(forwarding_bps: Option<u64>) -> f64 {
+        use super::node::switch::fabric_switch::FabricSwitch;
+
+        let mut t = OneBigSwitch::<FabricSwitch<LossySwitch>>::make_topology(4, 150_000, 1_000_000, 1_000_000);
+        t.switches[0].set_forwarding_bandwidth(forwarding_bps);
+        let mut e = Executor::new(t, None, None);
+        for i in 0..4 {
+            let flowinfo = FlowInfo::builder(i, i, (i + 1) % 4)
+                .length_bytes(438000) // 300 packet flow
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        let e = e.execute().unwrap();
+        let duration = e.all_flows_complete_time().unwrap() - 1_000_000_000;
+        let bytes = (0..4)
+            .filter_map(|host| e.link_stats().link(4, host))
+            .map(|b| b.transmitted_bytes)
+            .sum::<u64>();
+        bytes as f64 * 8e9 / duration as f64
+    }
+
+    #[test]
+    fn fabric_bandwidth_caps_aggregate_throughput() {
+        // four 1Mbps ports. Each host's ACKs wait behind its own data on its link, so the
+        // 10 packet windows stall now and then
+        let uncapped = all_ports_busy_throughput(None);
+        assert!(uncapped > 3_600_000.0, "{} bps without a cap", uncapped);
+
+        let capped = all_ports_busy_throughput(Some(2_000_000));
+        assert!((1_900_000.0..=2_020_000.0).contains(&capped), "{} bps with a 2Mbps fabric", capped);
+    }
+
+    #[test]
+    fn drop_signals_upstream() {
+        use super::event::EventTime;
+
+        let mut e = setup_test();
+        let pkt = |seq| Packet::Data{
+            hdr: PacketHeader{
+                flow: 0,
+                from: 0,
+                to: 1,
+                priority: 0,
+                size_bytes: PACKET_HEADER_SIZE_BYTES,
+            },
+            seq,
+            length: 1460,
+            ect: true,
+            ce: false,
+            sent: 0,
+            deadline: None,
+            trimmed: false,
+        };
+
+        let evs = {
+            let topo = e.components().1;
+            let in_link = topo.hosts[0].link;
+            // the 15_000 byte queue holds 10 packets
+            for seq in 0..10 {
+                assert!(Switch::receive(&mut topo.switches[0], pkt(seq), in_link, 0, None).unwrap().is_empty());
+            }
+
+            Switch::receive(&mut topo.switches[0], pkt(10), in_link, 0, None).unwrap()
+        };
+
+        assert_eq!(evs.len(), 1);
+        assert!(evs[0].time() == EventTime::Delta(0));
+        assert_eq!(evs[0].affected_node_ids(), vec![0]);
+
+        for ev in evs {
+            e.push(ev);
+        }
+
+        let mut e = e.execute().unwrap();
+        assert_eq!(e.components().1.hosts[0].downstream_drops, vec![(0, pkt(10))]);
+    }
+
+    #[test]
+    fn send_one_flow() {
+        let mut e = setup_test();
+
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(4380) // 3 packet flow
+            .build();
+
+        // starts at t = 1.0s
+        let flow_arrival = Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)); 
+        e.push(flow_arrival);
+        let e = e.execute().unwrap();
+        assert_eq!(e.current_time(), 1052640000);
+    }
+
+    /// Run `e` to completion, failing if `flow_id` did not complete within `budget` times its
+    /// ideal FCT.
+    fn run_within_budget<S: Switch>(e: Executor<S>, flow_id: u32, budget: f64) -> Executor<S> {
+        let mut e = e.execute().unwrap();
+        let slowdown = flow_slowdowns(e.components().1).unwrap()
+            .into_iter()
+            .find(|&(id, _)| id == flow_id)
+            .map(|(_, s)| s)
+            .unwrap_or_else(|| panic!("flow {} did not complete", flow_id));
+        assert!(slowdown <= budget, "flow {} took {}x its ideal FCT, budget {}x", flow_id, slowdown, budget);
+        e
+    }
+
+    #[test]
+    fn send_one_flow_within_budget() {
+        let mut e = setup_test();
+
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(4380) // 3 packet flow
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        run_within_budget(e, 1, 1.01);
+    }
+
+    #[test]
+    fn zero_cwnd_makes_progress() {
+        use super::congcontrol::ReductionType;
+
+        #[derive(Clone, Debug)]
+        struct ZeroCwnd;
+        impl CongAlg for ZeroCwnd {
+            fn new() -> Self { ZeroCwnd }
+            fn cwnd(&self) -> u32 { 0 }
+            fn on_packet(&mut self, _: u32, _: Nanos) -> u32 { 0 }
+            fn reduction(&mut self, _: ReductionType) -> u32 { 0 }
+        }
+
+        let mut e = setup_test();
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(4380) // 3 packet flow
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ZeroCwnd>)));
+        let mut e = e.execute().unwrap();
+        assert!(e.components().1.all_flows().all(|f| f.completion_time().is_some()));
+    }
+
+    #[test]
+    fn short_flow_tail_latency() {
+        use super::flow::workload::{FlowSizeDist, PoissonWorkload};
+        use super::stats::{percentile, short_flow_fcts};
+
+        // 4 senders incast to host 0; each 3 packet flow needs 36ms of the bottleneck, so the
+        // offered load is 80%
+        let t = OneBigSwitch::<LossySwitch>::make_topology(5, 150_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+        let workload = PoissonWorkload{
+            senders: vec![1, 2, 3, 4],
+            dests: vec![0],
+            mean_interarrival: 45_000_000,
+            flow_sizes: FlowSizeDist::Constant(4380), // 3 packet flow
+        };
+
+        workload.schedule::<ConstCwnd, _>(&mut e, 500, 1_000_000_000, 42);
+        let mut e = e.execute().unwrap();
+        let fcts = short_flow_fcts(e.components().1, 4380);
+        assert_eq!(fcts.len(), 500);
+
+        let p50 = percentile(&fcts, 50.0).unwrap();
+        let p99 = percentile(&fcts, 99.0).unwrap();
+        let p999 = percentile(&fcts, 99.9).unwrap();
+        assert!(p99 > 2 * p50, "p50 {} p99 {}", p50, p99);
+        assert!(p999 >= p99);
+    }
+
+    #[test]
+    fn fct_histogram_counts_buckets() {
+        use super::stats::FctHistogram;
+
+        let fcts = [0, 999, 1_000, 2_500, 2_999, 5_000];
+        let h = FctHistogram::new(&fcts, 1_000);
+        assert_eq!(h.counts.iter().map(|(&i, &c)| (i, c)).collect::<Vec<_>>(), vec![(0, 2), (1, 1), (2, 2), (5, 1)]);
+        assert_eq!(h.buckets()[2], (2_000, 3_000, 2));
+        assert!(FctHistogram::new(&[], 1_000).counts.is_empty());
+        // empty buckets take no space, however far out an FCT is
+        assert_eq!(FctHistogram::new(&[1, ::std::u64::MAX], 1).buckets().len(), 2);
+
+        let mut csv = vec![];
+        h.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines = csv.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], "bucket_start_ns,bucket_end_ns,count");
+        assert_eq!(lines[1], "0,1000,2");
+        assert_eq!(lines[4], "5000,6000,1");
+    }
+
+    #[test]
+    fn fct_summary_of_incast() {
+        use super::stats::{fct_summary, FctSummary};
+
+        // hosts 1 through 8 each send a 30 packet flow to host 0 at once
+        let t = OneBigSwitch::<LossySwitch>::make_topology(9, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+        for i in 1..9 {
+            let flowinfo = FlowInfo::builder(i, i, 0)
+                .length_bytes(43800) // 30 packet flow
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        let mut e = e.execute().unwrap();
+        let summary = fct_summary(e.components().1);
+        assert_eq!(summary.completed, 8);
+        assert_eq!(summary.incomplete, 0);
+        assert!(summary.min <= summary.median);
+        assert!(summary.p99 > summary.median, "{:?}", summary);
+        assert!(summary.p99 <= summary.max);
+        assert!(summary.min as f64 <= summary.mean && summary.mean <= summary.max as f64);
+
+        // flows which never completed are only counted
+        let partial = FctSummary::new(&[300, 100, 200], 2);
+        assert_eq!((partial.completed, partial.incomplete), (3, 2));
+        assert_eq!((partial.min, partial.median, partial.p99, partial.max), (100, 200, 300, 300));
+        assert_eq!(partial.mean, 200.0);
+        assert_eq!(FctSummary::new(&[], 4).incomplete, 4);
+    }
+
+    #[test]
+    fn arrival_rate_follows_load_ramp() {
+        use super::flow::workload::{FlowSizeDist, PoissonWorkload};
+
+        // 10,000 flows/s at full load, ramping from 20% to 90% load over 10s
+        let workload = PoissonWorkload{
+            senders: vec![1, 2, 3],
+            dests: vec![0],
+            mean_interarrival: 100_000,
+            flow_sizes: FlowSizeDist::Constant(4380),
+        };
+
+        let start = 1_000_000_000;
+        let ramp = |t: Nanos| 0.2 + 0.7 * (t - start) as f64 / 10e9;
+        let arrivals = workload.generate_with_load(50_000, start, 42, ramp)
+            .into_iter()
+            .map(|(_, t)| t)
+            .collect::<Vec<Nanos>>();
+        assert!(arrivals.windows(2).all(|w| w[0] <= w[1]));
+
+        // the flows arrive over about 9.4s; count arrivals in each whole second
+        for second in 0..9 {
+            let from = start + second * 1_000_000_000;
+            let count = arrivals.iter().filter(|&&t| t >= from && t < from + 1_000_000_000).count() as f64;
+            let expected = 10_000.0 * ramp(from + 500_000_000);
+            assert!((count - expected).abs() < 0.1 * expected, "second {}: {} arrivals, expected {}", second, count, expected);
+        }
+    }
+
+    #[test]
+    fn web_search_flow_sizes_match_cdf_mean() {
+        use super::flow::workload::{FlowSizeDist, PoissonWorkload};
+
+        let workload = PoissonWorkload{
+            senders: vec![0, 1],
+            dests: vec![0, 1],
+            mean_interarrival: 1_000_000,
+            flow_sizes: FlowSizeDist::web_search(),
+        };
+
+        let flows = workload.generate(200_000, 0, 7);
+        let sizes = flows.iter().map(|&(f, _)| f.length_bytes).collect::<Vec<u32>>();
+        assert!(sizes.iter().all(|s| (6 * 1460..=20000 * 1460).contains(s)));
+        // 15% of flows are exactly the smallest size
+        let smallest = sizes.iter().filter(|&&s| s == 6 * 1460).count() as f64 / sizes.len() as f64;
+        assert!((smallest - 0.15).abs() < 0.01, "{} of flows are the smallest size", smallest);
+
+        let expected = workload.flow_sizes.mean();
+        let mean = sizes.iter().map(|&s| f64::from(s)).sum::<f64>() / sizes.len() as f64;
+        assert!((mean - expected).abs() < 0.02 * expected, "mean {} expected {}", mean, expected);
+    }
+
+    #[test]
+    fn short_rto_recovers_tail_drop() {
+        use super::node::switch::Queue;
+
+        let mut t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        t.switches[0].rack.iter_mut().for_each(|q| q.set_loss_model(Box::new(DropOnce(Some(2920)))));
+        let mut e = Executor::new(t, None, None);
+
+        // no later packet arrives out of order to trigger a NACK, so only the RTO recovers it
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(4380) // 3 packet flow
+            .rto(100_000_000)
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert_eq!(topo.hosts[0].downstream_drops.len(), 1);
+
+        // the second ACK returns at 40.64ms, the RTO fires 100ms later, and the retransmission
+        // takes 28.64ms
+        let sender = topo.all_flows().find(|f| f.flow_info().sender_id == 0).unwrap();
+        assert_eq!(sender.completion_time(), Some(169_280_001));
+    }
+
+    #[test]
+    fn link_loss_recovers_by_rto() {
+        let t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+        // the switch is node 2; lose the second data packet on its way to the receiver
+        e.set_link_loss_model(2, 1, Box::new(DropOnce(Some(1460))));
+
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(4380) // 3 packet flow
+            .rto(100_000_000)
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        let mut e = e.execute().unwrap();
+        assert_eq!(e.link_stats().link(2, 1).unwrap().lost_bytes, 1500);
+        assert_eq!(e.link_stats().link(0, 2).unwrap().lost_bytes, 0);
+
+        // nobody tells the sender, but it retransmits once the RTO fires
+        let topo = e.components().1;
+        assert!(topo.hosts[0].downstream_drops.is_empty());
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+        let sender = topo.all_flows().find(|f| f.flow_info().sender_id == 0).unwrap();
+        assert!(sender.transmit_counts().retransmitted > 0);
+    }
+
+    #[test]
+    fn long_rto_is_not_a_deadlock() {
+        use super::event::TerminationReason;
+        use super::node::switch::Queue;
+
+        let mut t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        t.switches[0].rack.iter_mut().for_each(|q| q.set_loss_model(Box::new(DropOnce(Some(2920)))));
+        let mut e = Executor::new(t, None, None);
+
+        // nothing moves for the whole 20s RTO, but nothing is stuck either
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(4380) // 3 packet flow
+            .rto(20_000_000_000)
+            .build();
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        let (mut e, reason) = e.execute_with_reason().unwrap();
+        assert_eq!(reason, TerminationReason::AllFlowsComplete);
+        let sender = e.components().1.all_flows().find(|f| f.flow_info().sender_id == 0).unwrap();
+        assert!(sender.completion_time().unwrap() > 20_000_000_000);
+    }
+
+    #[test]
+    fn host_flows_share_nic_rate() {
+        let t = OneBigSwitch::<LossySwitch>::make_topology(3, 150_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+
+        // both flows start together and always have a full window to send, so host 0's NIC is
+        // their bottleneck
+        for dest_id in 1..3 {
+            let flowinfo = FlowInfo::builder(dest_id, 0, dest_id)
+                .length_bytes(146000) // 100 packet flow
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+
+        // 200 1500-byte packets take 12ms each to serialize at 1 Mbit/s, one at a time
+        let last_transmit = topo.all_flows()
+            .filter_map(|f| f.transmit_completion_time().map(|t| f.start_time().unwrap() + t))
+            .max()
+            .unwrap();
+        let elapsed = last_transmit - 1_000_000_000;
+        assert!(elapsed >= 200 * 12_000_000, "elapsed {}", elapsed);
+        let rate_bps = (200 * 1500 * 8) as f64 / (elapsed as f64 / 1e9);
+        assert!(rate_bps > 0.95e6, "rate {}", rate_bps);
+    }
+
+    /// The completion time of a 100-packet flow through a switch dropping `loss_rate` of data
+    /// packets at random, and how many it dropped.
+    fn random_loss_fct(loss_rate: f64) -> (Nanos, u32) {
+        use super::node::switch::random_loss_switch::RandomLossSwitch;
+
+        let t = OneBigSwitch::<RandomLossSwitch<LossySwitch>>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::with_seed(t, None, None, 7);
+        e.components().1.switches[0].set_loss_rate(loss_rate);
+
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(146000) // 100 packet flow
+            .rto(100_000_000)
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        let sender = topo.all_flows().find(|f| f.flow_info().sender_id == 0).unwrap();
+        (sender.completion_time().unwrap(), topo.switches[0].random_drops)
+    }
+
+    #[test]
+    fn random_loss_recovered_by_retransmission() {
+        let (lossless_fct, no_drops) = random_loss_fct(0.0);
+        assert_eq!(no_drops, 0);
+
+        // each loss costs at least a round trip (28.64ms) to detect and retransmit
+        let (lossy_fct, drops) = random_loss_fct(0.1);
+        assert!(drops > 0);
+        assert!(lossy_fct > lossless_fct + 28_640_000, "lossy {} lossless {}", lossy_fct, lossless_fct);
+    }
+
+    /// Completion times of two flows through a switch dropping 5% of packets at random, with
+    /// every random choice seeded from `seed`, and the number of drops.
+    fn seeded_random_drop_fcts(seed: u64) -> (Vec<Nanos>, usize) {
+        use super::loss::FixedRateLoss;
+        use super::node::switch::Queue;
+
+        let t = OneBigSwitch::<LossySwitch>::make_topology(3, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::with_seed(t, None, None, seed);
+        let seeds = (0..3).map(|_| e.rng().next_u64()).collect::<Vec<u64>>();
+        e.components().1.switches[0].rack.iter_mut()
+            .zip(seeds)
+            .for_each(|(q, seed)| q.set_loss_model(Box::new(FixedRateLoss::new(0.05, seed))));
+
+        for i in 1..3 {
+            let flowinfo = FlowInfo::builder(i, i, 0)
+                .length_bytes(146000) // 100 packet flow
+                .rto(100_000_000)
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        let fcts = topo.all_flows().map(|f| f.completion_time().unwrap()).collect();
+        let drops = topo.hosts.iter().map(|h| h.downstream_drop_count as usize).sum();
+        (fcts, drops)
+    }
+
+    #[test]
+    fn same_seed_same_run() {
+        let (fcts, drops) = seeded_random_drop_fcts(42);
+        assert!(drops > 0);
+        assert_eq!(seeded_random_drop_fcts(42), (fcts, drops));
+    }
+
+    #[test]
+    fn loss_rate_matches_link_loss() {
+        use super::loss::FixedRateLoss;
+        use super::node::switch::Queue;
+        use super::flow::selective_repeat::SelectiveRepeatFlowArrivalEvent;
+        use super::stats::flow_loss_rates;
+
+        let mut t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        // only the data direction is lossy
+        t.switches[0].rack.iter_mut()
+            .filter(|q| q.link().to == 1)
+            .for_each(|q| q.set_loss_model(Box::new(FixedRateLoss::new(0.01, 7))));
+        let mut e = Executor::new(t, None, None);
+
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(29_200_000) // 20000 packet flow
+            .build();
+
+        // selective repeat resends only what was lost, so its retransmissions count the drops
+        e.push(Box::new(SelectiveRepeatFlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+        let rates = flow_loss_rates(topo);
+        assert_eq!(rates.len(), 1);
+        assert_eq!(rates[0].0, 1);
+        assert!((rates[0].1 - 0.01).abs() < 0.002, "loss rate {}", rates[0].1);
+    }
+
+    fn nack_switch_single_drop_fct(local_retransmission: bool) -> Nanos {
+        use super::node::switch::Queue;
+
+        let mut t = OneBigSwitch::<NackSwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        t.switches[0].local_retransmission = local_retransmission;
+        t.switches[0].rack.iter_mut().for_each(|q| q.set_loss_model(Box::new(DropOnce(Some(1460)))));
+        let mut e = Executor::new(t, None, None);
+
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(43800) // 30 packet flow
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert_eq!(topo.switches[0].nacks_sent, if local_retransmission { 0 } else { 1 });
+        assert!(topo.switches[0].retransmit_buffer.is_empty());
+        let sender = topo.all_flows().find(|f| f.flow_info().sender_id == 0).unwrap();
+        sender.completion_time().unwrap()
+    }
+
+    #[test]
+    fn nack_switch_local_retransmission() {
+        let nack_fct = nack_switch_single_drop_fct(false);
+        let local_fct = nack_switch_single_drop_fct(true);
+        assert!(local_fct < nack_fct, "local {} nack {}", local_fct, nack_fct);
+    }
+
+    #[test]
+    fn nack_switch_retransmit_buffer_overflow() {
+        // no room to buffer the dropped packet, so the receiver has to NACK it
+        let mut t = OneBigSwitch::<NackSwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        t.switches[0].local_retransmission = true;
+        t.switches[0].max_retransmit_buffer = Some(0);
+        t.switches[0].rack.iter_mut().for_each(|q| q.set_loss_model(Box::new(DropOnce(Some(1460)))));
+        let mut e = Executor::new(t, None, None);
+
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(43800) // 30 packet flow
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert_eq!(topo.switches[0].retransmit_buffer_overflows, 1);
+        assert!(topo.switches[0].retransmit_buffer.is_empty());
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+    }
+
+    #[test]
+    fn nack_switch_recovers_from_dropped_retransmission() {
+        use super::node::switch::Queue;
+
+        /// Drops the first `.1` `Data` packets with sequence number `.0`.
+        #[derive(Debug)]
+        struct DropRepeatedly(u32, u32);
+        impl LossModel for DropRepeatedly {
+            fn should_drop(&mut self, pkt: &Packet, _: &LossContext) -> bool {
+                match *pkt {
+                    Packet::Data{seq, ..} if seq == self.0 && self.1 > 0 => {
+                        self.1 -= 1;
+                        true
+                    }
+                    _ => false,
+                }
+            }
+        }
+
+        // the packet the NACK asks for is dropped again when it is retransmitted
+        let mut t = OneBigSwitch::<NackSwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        t.switches[0].rack.iter_mut().for_each(|q| q.set_loss_model(Box::new(DropRepeatedly(1460, 2))));
+        let mut e = Executor::new(t, None, None);
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(43800) // 30 packet flow
+            .build();
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+        assert_eq!(topo.switches[0].nacks_sent, 2);
+        assert!(topo.switches[0].blocked_flows.is_empty());
+    }
+
+    #[test]
+    fn nack_switch_unblocks_on_earlier_retransmission() {
+        let t = OneBigSwitch::<NackSwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+        let topo = e.components().1;
+        let l = topo.hosts[0].link;
+        let s = &mut topo.switches[0];
+
+        let pkt = |seq| Packet::Data{
+            hdr: PacketHeader{
+                flow: 1,
+                from: 0,
+                to: 1,
+                priority: 0,
+                size_bytes: PACKET_HEADER_SIZE_BYTES,
+            },
+            seq,
+            length: 1460,
+            ect: true,
+            ce: false,
+            sent: 0,
+            deadline: None,
+            trimmed: false,
+        };
+
+        // blocked until 2920, but the source timed out and went back to its cumulative ACK
+        s.blocked_flows.insert(1, 2920);
+        Switch::receive(s, pkt(4380), l, 0, None).unwrap();
+        assert_eq!(Switch::queue_occupancy(s, 1), Some(0));
+        Switch::receive(s, pkt(1460), l, 0, None).unwrap();
+        assert!(s.blocked_flows.is_empty());
+        assert!(Switch::queue_occupancy(s, 1).unwrap() > 0);
+    }
+
+    fn lossy_single_drop_fct(selective_repeat: bool) -> Nanos {
+        use super::node::switch::Queue;
+        use super::flow::selective_repeat::SelectiveRepeatFlowArrivalEvent;
+
+        let mut t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        t.switches[0].rack.iter_mut().for_each(|q| q.set_loss_model(Box::new(DropOnce(Some(1460)))));
+        let mut e = Executor::new(t, None, None);
+
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(43800) // 30 packet flow
+            .build();
+
+        if selective_repeat {
+            e.push(Box::new(SelectiveRepeatFlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        } else {
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert_eq!(topo.hosts[0].downstream_drops.len(), 1);
+        let sender = topo.all_flows().find(|f| f.flow_info().sender_id == 0).unwrap();
+        sender.completion_time().unwrap()
+    }
+
+    #[test]
+    fn selective_repeat_beats_go_back_n() {
+        let gbn_fct = lossy_single_drop_fct(false);
+        let sr_fct = lossy_single_drop_fct(true);
+        assert!(sr_fct < gbn_fct, "selective repeat {} go-back-N {}", sr_fct, gbn_fct);
+    }
+
+    #[test]
+    fn go_back_n_counts_retransmissions() {
+        use super::node::switch::Queue;
+        use super::flow::Flow;
+        use super::flow::go_back_n::GoBackNSender;
+
+        let mut t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        t.switches[0].rack.iter_mut().for_each(|q| q.set_loss_model(Box::new(DropOnce(Some(1460)))));
+        let mut e = Executor::new(t, None, None);
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(43800) // 30 packet flow
+            .build();
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        let sender = topo.hosts[0].active_flows[0].as_any()
+            .downcast_ref::<GoBackNSender<ConstCwnd>>()
+            .unwrap();
+
+        // the packets behind the drop arrive out of order, and the first of them is NACKed
+        assert_eq!(sender.nacks_received(), 1);
+        assert_eq!(sender.timeouts(), 0);
+        // every byte sent beyond the flow's own was sent again after the drop
+        let counts = sender.transmit_counts();
+        assert!(sender.retransmitted_bytes() > 0);
+        assert_eq!(sender.retransmitted_bytes(), counts.sent * 1460 - 43800);
+        assert_eq!(sender.retransmitted_bytes(), counts.retransmitted * 1460);
+    }
+
+    /// The error a switch of type `S` returns for a packet to a host it has no route to.
+    fn misrouted_packet_error<S: Switch>() -> String {
+        let t = OneBigSwitch::<S>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+        let topo = e.components().1;
+        let l = topo.hosts[0].link;
+        let pkt = Packet::Data{
+            hdr: PacketHeader{
+                flow: 1,
+                from: 0,
+                to: 7, // no such host
+                priority: 0,
+                size_bytes: PACKET_HEADER_SIZE_BYTES,
+            },
+            seq: 0,
+            length: 1460,
+            ect: false,
+            ce: false,
+            sent: 0,
+            deadline: None,
+            trimmed: false,
+        };
+
+        match Switch::receive(&mut topo.switches[0], pkt, l, 0, None) {
+            Ok(_) => panic!("misrouted packet was accepted"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn misrouted_packets_are_errors() {
+        for err in vec![
+            misrouted_packet_error::<LossySwitch>(),
+            misrouted_packet_error::<NackSwitch>(),
+            misrouted_packet_error::<PFCSwitch>(),
+            misrouted_packet_error::<IngressPFCSwitch>(),
+        ] {
+            assert!(err.contains("switch 2 has no link to node 7"), "{}", err);
+            assert!(err.contains("Data"), "{}", err);
+        }
+    }
+
+    /// The error a switch of type `S` returns when a link it does not have frees up.
+    fn reactivate_missing_link_error<S: Switch>() -> String {
+        let mut t = OneBigSwitch::<S>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let l = super::node::Link{from: 2, to: 7, ..t.hosts[0].link}; // no such neighbor
+        match Switch::reactivate(&mut t.switches[0], l) {
+            Ok(_) => panic!("reactivated a link the switch does not have"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn reactivating_a_missing_link_is_an_error() {
+        use super::node::switch::shared_buffer_switch::SharedBufferSwitch;
+        use super::node::switch::shared_medium::SharedMedium;
+
+        for err in vec![
+            reactivate_missing_link_error::<LossySwitch>(),
+            reactivate_missing_link_error::<NackSwitch>(),
+            reactivate_missing_link_error::<PFCSwitch>(),
+            reactivate_missing_link_error::<SharedBufferSwitch>(),
+            reactivate_missing_link_error::<SharedMedium>(),
+        ] {
+            assert_eq!(err, "switch 2 has no link to node 7");
+        }
+    }
+
+    #[test]
+    fn nack_switch_bounds_blocked_flows() {
+        let t = OneBigSwitch::<NackSwitch>::make_topology(2, 3_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+        let topo = e.components().1;
+        let l = topo.hosts[0].link;
+        let s = &mut topo.switches[0];
+        s.max_blocked_flows = Some(4);
+
+        let pkt = |flow| Packet::Data{
+            hdr: PacketHeader{
+                flow,
+                from: 0,
+                to: 1,
+                priority: 0,
+                size_bytes: PACKET_HEADER_SIZE_BYTES,
+            },
+            seq: 0,
+            length: 1460,
+            ect: true,
+            ce: false,
+            sent: 0,
+            deadline: None,
+            trimmed: false,
+        };
+
+        // fill the queue towards host 1 (nothing drains it), so every other flow's first
+        // packet is dropped and blocks that flow
+        Switch::receive(s, pkt(100), l, 0, None).unwrap();
+        Switch::receive(s, pkt(101), l, 0, None).unwrap();
+        for flow in 0..20 {
+            Switch::receive(s, pkt(flow), l, 0, None).unwrap();
+            assert!(s.blocked_flows.len() <= 4, "{} blocked flows", s.blocked_flows.len());
+        }
+
+        assert_eq!(s.nacks_sent, 20);
+        assert_eq!(s.blocked_flows_evicted, 16);
+        let mut blocked = s.blocked_flows.keys().cloned().collect::<Vec<u32>>();
+        blocked.sort();
+        assert_eq!(blocked, vec![16, 17, 18, 19]);
+    }
+
+    #[test]
+    fn warm_start_skips_slow_start() {
+        use super::congcontrol::NewReno;
+        use super::flow::{WarmStartFlowArrivalEvent, go_back_n};
+
+        // as if a previous run had converged in congestion avoidance at 20 packets
+        let converged = NewReno::with_params(20, 20, 1460);
+        let (logger, cwnds) = cwnd_logger();
+        let t = OneBigSwitch::<LossySwitch>::make_topology(2, 150_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, logger, None);
+
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(146000) // 100 packet flow
+            .build();
+
+        e.push(Box::new(WarmStartFlowArrivalEvent(flowinfo, 1_000_000_000, converged)));
+        let mut e = e.execute().unwrap();
+
+        // one packet per window of ACKs: 100 ACKs grow 20 packets by at most 5
+        let cwnds = cwnds.lock().unwrap();
+        assert_eq!(cwnds[0], 20);
+        assert!(cwnds.iter().all(|&c| c >= 20 && c <= 25), "{:?}", *cwnds);
+
+        // the learned state can be captured to warm-start the next run
+        let topo = e.components().1;
+        let sender = topo.all_flows().find(|f| f.flow_info().sender_id == 0).unwrap();
+        let learned = go_back_n::cong_control_of::<NewReno>(&**sender).unwrap();
+        assert_eq!(learned.cwnd() as u64, *cwnds.last().unwrap());
+        assert_eq!(learned.ssthresh(), 20);
+        assert!(go_back_n::cong_control_of::<ConstCwnd>(&**sender).is_none());
+    }
+
+    #[test]
+    fn receiver_echoes_ce() {
+        use super::flow::{Flow, go_back_n};
+
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(4380) // 3 packet flow
+            .max_packet_length(1460)
+            .build();
+
+        let (_, mut receiver) = go_back_n::new::<ConstCwnd>(flowinfo);
+        let data = |seq, ce| Packet::Data{
+            hdr: PacketHeader{
+                flow: 1,
+                from: 0,
+                to: 1,
+                priority: 0,
+                size_bytes: PACKET_HEADER_SIZE_BYTES,
+            },
+            seq,
+            length: 1460,
+            ect: true,
+            ce,
+            sent: 0,
+            deadline: None,
+            trimmed: false,
+        };
+
+        let ece = |pkts: Vec<Packet>| match pkts[..] {
+            [Packet::Ack{ece, ..}] => ece,
+            _ => panic!("expected one ACK, got {:?}", pkts),
+        };
+
+        assert!(!ece(receiver.receive(1_000, data(0, false), None).unwrap().0));
+        assert!(ece(receiver.receive(2_000, data(1460, true), None).unwrap().0));
+        assert!(!ece(receiver.receive(3_000, data(2920, false), None).unwrap().0));
+    }
+
+    #[test]
+    fn receive_window_bounds_bytes_in_flight() {
+        use super::flow::{Flow, go_back_n};
+
+        // a 2 packet receive buffer, then one smaller than a packet, but a 10 packet cwnd
+        for &(rwnd, packets) in &[(2920, 10), (1000, 15)] {
+            let flowinfo = FlowInfo::builder(1, 0, 1)
+                .length_bytes(14600) // 10 packet flow
+                .max_packet_length(1460)
+                .receive_window(rwnd)
+                .build();
+            let (mut sender, mut receiver) = go_back_n::new::<ConstCwnd>(flowinfo);
+
+            // deliver the oldest outstanding packet each step, and hand its ACK straight back
+            let mut in_flight = sender.exec(0, None).unwrap().0;
+            let mut time = 0;
+            while !in_flight.is_empty() {
+                let bytes = in_flight.iter()
+                    .map(|p| match *p {
+                        Packet::Data{length, ..} => length,
+                        _ => panic!("expected data, got {:?}", p),
+                    })
+                    .sum::<u32>();
+                assert!(bytes <= rwnd, "{} bytes in flight", bytes);
+
+                time += 1_000;
+                let pkt = in_flight.remove(0);
+                for ack in receiver.receive(time, pkt, None).unwrap().0 {
+                    match ack {
+                        Packet::Ack{rwnd: advertised, ..} => assert_eq!(advertised, rwnd),
+                        _ => panic!("expected an ACK, got {:?}", ack),
+                    }
+
+                    in_flight.extend(sender.receive(time, ack, None).unwrap().0);
+                }
+            }
+
+            assert_eq!(sender.completion_time(), Some(packets * 1_000));
+            assert_eq!(receiver.delivered_bytes(), 14600);
+        }
+    }
+
+    #[test]
+    fn ecn_echo_reduces_cwnd() {
+        use super::congcontrol::ReductionType;
+        use super::flow::{Flow, go_back_n};
+
+        /// Counts the ECN reductions it is asked for.
+        #[derive(Clone, Debug)]
+        struct CountEcn(u32);
+        impl CongAlg for CountEcn {
+            fn new() -> Self { CountEcn(0) }
+            fn cwnd(&self) -> u32 { 10 }
+            fn on_packet(&mut self, _: u32, _: Nanos) -> u32 { 10 }
+            fn reduction(&mut self, r: ReductionType) -> u32 {
+                if let ReductionType::Ecn = r {
+                    self.0 += 1;
+                }
+
+                10
+            }
+        }
+
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(14600) // 10 packet flow
+            .max_packet_length(1460)
+            .build();
+        let (mut sender, mut receiver) = go_back_n::new::<CountEcn>(flowinfo);
+        let mark = |p: &Packet| match *p {
+            Packet::Data{hdr, seq, length, ect, sent, deadline, trimmed, ..} =>
+                Packet::Data{hdr, seq, length, ect, ce: true, sent, deadline, trimmed},
+            _ => panic!("expected data, got {:?}", p),
+        };
+        let ece = |p: &Packet| match *p {
+            Packet::Ack{ece, ..} => ece,
+            _ => panic!("expected an ACK, got {:?}", p),
+        };
+
+        let data = sender.exec(0, None).unwrap().0;
+        assert_eq!(data.len(), 10);
+
+        // a marked packet is echoed, and the sender reduces its window
+        let acks = receiver.receive(1_000, mark(&data[0]), None).unwrap().0;
+        assert!(ece(&acks[0]));
+        sender.receive(2_000, acks[0], None).unwrap();
+        assert_eq!(go_back_n::cong_control_of::<CountEcn>(&*sender).unwrap().0, 1);
+
+        // the mark on a packet discarded as out of order carries over to the next ACK
+        let nack = receiver.receive(3_000, mark(&data[2]), None).unwrap().0;
+        match nack[0] {
+            Packet::Nack{..} => (),
+            _ => panic!("expected a NACK, got {:?}", nack[0]),
+        }
+        let acks = receiver.receive(4_000, data[1], None).unwrap().0;
+        assert!(ece(&acks[0]));
+        sender.receive(5_000, acks[0], None).unwrap();
+        assert_eq!(go_back_n::cong_control_of::<CountEcn>(&*sender).unwrap().0, 2);
+
+        // and is then cleared
+        let acks = receiver.receive(6_000, data[2], None).unwrap().0;
+        assert!(!ece(&acks[0]));
+        sender.receive(7_000, acks[0], None).unwrap();
+        assert_eq!(go_back_n::cong_control_of::<CountEcn>(&*sender).unwrap().0, 2);
+    }
+
+    #[test]
+    fn new_reno_cwnd_grows() {
+        use super::congcontrol::NewReno;
+
+        let (logger, cwnds) = cwnd_logger();
+
+        let t = OneBigSwitch::<LossySwitch>::make_topology(2, 150_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, logger, None);
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(43800) // 30 packet flow
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<NewReno>)));
+        let mut e = e.execute().unwrap();
+        assert!(e.components().1.all_flows().all(|f| f.completion_time().is_some()));
+
+        // slow start from a window of 1, instead of ConstCwnd's fixed 10
+        let cwnds = cwnds.lock().unwrap();
+        assert_eq!(cwnds[0], 2);
+        assert!(cwnds.windows(2).all(|w| w[0] < w[1]), "{:?}", *cwnds);
+        assert!(*cwnds.last().unwrap() > 10, "{:?}", *cwnds);
+    }
+
+    #[test]
+    fn new_reno_counts_jumbo_packets_whole() {
+        use super::congcontrol::NewReno;
+        use super::flow::WarmStartFlowArrivalEvent;
+
+        let (logger, cwnds) = cwnd_logger();
+
+        let t = OneBigSwitch::<LossySwitch>::make_topology_with_mtu(2, 900_000, 1_000_000, 1_000_000, 9000);
+        let mut e = Executor::new(t, logger, None);
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(50 * 8960) // 50 jumbo packet flow
+            .build();
+
+        // in congestion avoidance from the start; the sender replaces the 1500 byte frames' MSS
+        // with its own packet length
+        let cc = NewReno::with_params(10, 10, 1460);
+        e.push(Box::new(WarmStartFlowArrivalEvent(flowinfo, 1_000_000_000, cc)));
+        let mut e = e.execute().unwrap();
+        assert!(e.components().1.all_flows().all(|f| f.completion_time().is_some()));
+
+        // one packet more per window of ACKed packets: 9 ACKs at 10, 11 at 11, 12 at 12...
+        let cwnds = cwnds.lock().unwrap();
+        assert_eq!(cwnds.len(), 50);
+        assert!(cwnds.windows(2).all(|w| w[1] - w[0] <= 1), "{:?}", *cwnds);
+        assert_eq!(cwnds.iter().filter(|&&c| c == 11).count(), 11, "{:?}", *cwnds);
+        assert_eq!(*cwnds.last().unwrap(), 14, "{:?}", *cwnds);
+    }
+
+    #[test]
+    fn new_reno_cuts_once_per_window_of_losses() {
+        use super::congcontrol::NewReno;
+        use super::flow::selective_repeat::SelectiveRepeatFlowArrivalEvent;
+        use super::loss::AnyLoss;
+        use super::node::switch::Queue;
+
+        let (logger, cwnds) = cwnd_logger();
+
+        // two packets of the same window are lost
+        let mut t = OneBigSwitch::<LossySwitch>::make_topology(2, 150_000, 1_000_000, 1_000_000);
+        t.switches[0].rack.iter_mut()
+            .filter(|q| q.link().to == 1)
+            .for_each(|q| q.set_loss_model(Box::new(AnyLoss(vec![
+                Box::new(DropOnce(Some(30 * 1460))),
+                Box::new(DropOnce(Some(35 * 1460))),
+            ]))));
+        let mut e = Executor::new(t, logger, None);
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(100 * 1460) // 100 packet flow
+            .build();
+
+        e.push(Box::new(SelectiveRepeatFlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<NewReno>)));
+        let mut e = e.execute().unwrap();
+        assert!(e.components().1.all_flows().all(|f| f.completion_time().is_some()));
+
+        // slow start until the losses
+        let cwnds = cwnds.lock().unwrap();
+        let before_loss = cwnds.windows(2).take_while(|w| w[1] == w[0] + 1).count();
+        let peak = cwnds[before_loss];
+        // fast recovery inflates cwnd by the packets SACKed behind the losses...
+        assert!(cwnds[before_loss + 1] > peak, "{:?}", *cwnds);
+        // ...and recovers from the second loss too, so cwnd is only halved once
+        assert_eq!(*cwnds[before_loss + 1..].iter().min().unwrap(), peak / 2, "{:?}", *cwnds);
+    }
+
+    #[test]
+    fn straggler_on_slow_link() {
+        use super::topology::one_big_switch::LinkSpec;
+
+        let fast = LinkSpec{bandwidth_bps: 10_000_000, propagation_delay: 10_000};
+        let slow = LinkSpec{bandwidth_bps: 1_000_000, ..fast};
+        let t = OneBigSwitch::<LossySwitch>::make_topology_with_links(&[fast, fast, slow], 150_000);
+        assert_eq!(t.hosts[2].link.bandwidth_bps, 1_000_000);
+        assert_eq!(t.switches[0].rack[2].link().bandwidth_bps, 1_000_000);
+
+        // hosts 1 and 2 each send to host 0, one after the other
+        let mut e = Executor::new(t, None, None);
+        for i in 1..3 {
+            let flowinfo = FlowInfo::builder(i, i, 0)
+                .length_bytes(43800) // 30 packet flow
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, i as Nanos * 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        let fct = |sender| topo.all_flows()
+            .find(|f| f.flow_info().sender_id == sender && f.transmit_completion_time().is_some())
+            .and_then(|f| f.completion_time())
+            .unwrap() as f64;
+
+        // the straggler's own link is the bottleneck, so it takes about 10x as long
+        let ratio = fct(2) / fct(1);
+        assert!(ratio > 8.0 && ratio < 11.0, "ratio {}", ratio);
+    }
+
+    /// The peak occupancy of the switch queue in front of a 1Mbps receiver, and the FCT, of a
+    /// 100 packet flow from a 10Mbps sender.
+    fn peak_queue_behind_fast_sender(paced: bool) -> (u32, Nanos) {
+        use super::topology::one_big_switch::LinkSpec;
+
+        // the window (10 packets) is about one bandwidth-delay product
+        let fast = LinkSpec{bandwidth_bps: 10_000_000, propagation_delay: 1_000_000};
+        let slow = LinkSpec{bandwidth_bps: 1_000_000, propagation_delay: 50_000_000};
+        let t = OneBigSwitch::<LossySwitch>::make_topology_with_links(&[fast, slow], 150_000);
+        let mut e = Executor::new(t, None, None);
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(146000)
+            .paced(paced)
+            .build();
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        let fct = topo.hosts[0].active_flows[0].completion_time().unwrap();
+        (topo.switches[0].take_stats().high_water_bytes, fct)
+    }
+
+    #[test]
+    fn pacing_lowers_peak_queue() {
+        let (unpaced, unpaced_fct) = peak_queue_behind_fast_sender(false);
+        let (paced, paced_fct) = peak_queue_behind_fast_sender(true);
+
+        // unpaced, the first window arrives at 10x the rate the switch drains it
+        assert!(unpaced >= 12_000, "unpaced {}", unpaced);
+        // paced, it arrives at about the drain rate
+        assert!(paced < unpaced / 2, "paced {} unpaced {}", paced, unpaced);
+        // the paced flow only loses the round trip it spends getting its first RTT sample
+        assert!((paced_fct as f64) < 1.2 * unpaced_fct as f64, "paced {} unpaced {}", paced_fct, unpaced_fct);
+    }
+
+    #[test]
+    fn pod_intra_and_inter_rack_flows() {
+        use super::topology::pod::Pod;
+
+        // hosts 0, 1 under leaf 4; hosts 2, 3 under leaf 5; spine 6
+        let t = Pod::<LossySwitch>::make_pod(2, 2, 15_000, 1_000_000, 1_000_000);
+        assert_eq!(t.path(0, 1).unwrap().len(), 2);
+        assert_eq!(
+            t.path(0, 2).unwrap().iter().map(|l| (l.from, l.to)).collect::<Vec<_>>(),
+            vec![(0, 4), (4, 6), (6, 5), (5, 2)],
+        );
+
+        let mut e = Executor::new(t, None, None);
+        let intra_rack = FlowInfo::builder(1, 0, 1)
+            .length_bytes(43800) // 30 packet flow
+            .build();
+
+        let inter_rack = FlowInfo::builder(2, 3, 0)
+            .length_bytes(43800) // 30 packet flow
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(intra_rack, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        e.push(Box::new(FlowArrivalEvent(inter_rack, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        let mut e = e.execute().unwrap();
+
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+        assert_eq!(topo.path(3, 0).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn leaf_spine_inter_leaf_flows() {
+        use super::topology::leaf_spine::LeafSpine;
+
+        // hosts 0, 1 under leaf 4; hosts 2, 3 under leaf 5; spines 6, 7
+        let t = LeafSpine::<LossySwitch>::make_leaf_spine(2, 2, 2, 15_000, 1_000_000, 1_000_000);
+        assert_eq!(t.path(0, 1).unwrap().len(), 2);
+        assert_eq!(
+            t.path(0, 2).unwrap().iter().map(|l| (l.from, l.to)).collect::<Vec<_>>(),
+            vec![(0, 4), (4, 6), (6, 5), (5, 2)],
+        );
+        assert_eq!(
+            t.path(0, 3).unwrap().iter().map(|l| (l.from, l.to)).collect::<Vec<_>>(),
+            vec![(0, 4), (4, 7), (7, 5), (5, 3)],
+        );
+
+        let mut e = Executor::new(t, None, None);
+        for (flow_id, sender_id, dest_id) in vec![(1, 0, 2), (2, 1, 3), (3, 3, 0)] {
+            let flowinfo = FlowInfo::builder(flow_id, sender_id, dest_id)
+                .length_bytes(43800) // 30 packet flow
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+    }
+
+    /// Completion times of 100-packet flows from left hosts `0..num_flows` to the right side of a
+    /// dumbbell with a 1Mbps bottleneck and 10Mbps access links.
+    fn dumbbell_fcts(num_flows: u32) -> Vec<Nanos> {
+        use super::topology::dumbbell::{Bottleneck, Dumbbell};
+
+        let bottleneck = Bottleneck{
+            bandwidth_bps: 1_000_000,
+            queue_length_bytes: 60_000, // both windows fit
+        };
+
+        // hosts 0, 1 under switch 4; hosts 2, 3 under switch 5
+        let t = Dumbbell::<LossySwitch>::make_dumbbell(2, 2, 15_000, 10_000_000, bottleneck, 10_000);
+        assert_eq!(
+            t.path(0, 2).unwrap().iter().map(|l| (l.from, l.to, l.bandwidth_bps)).collect::<Vec<_>>(),
+            vec![(0, 4, 10_000_000), (4, 5, 1_000_000), (5, 2, 10_000_000)],
+        );
+
+        let mut e = Executor::new(t, None, None);
+        for i in 0..num_flows {
+            let flowinfo = FlowInfo::builder(i, i, 2 + i)
+                .length_bytes(146000) // 100 packet flow
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert!(topo.hosts.iter().all(|h| h.downstream_drops.is_empty()));
+        topo.all_flows()
+            .filter(|f| f.transmit_completion_time().is_some())
+            .map(|f| f.completion_time().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn dumbbell_flows_share_bottleneck() {
+        let alone = dumbbell_fcts(1)[0];
+        // the bottleneck, not the window, limits a flow on its own
+        assert!(alone as f64 > 0.9 * (146_000 * 8 * 1_000) as f64);
+
+        let shared = dumbbell_fcts(2);
+        assert_eq!(shared.len(), 2);
+        assert!(shared.iter().all(|&fct| fct as f64 > 1.5 * alone as f64), "alone {} shared {:?}", alone, shared);
+    }
+
+    #[test]
+    fn two_switch_line_forwards_end_to_end() {
+        use super::topology::dumbbell::{Bottleneck, Dumbbell};
+
+        // host 0 - switch 2 - switch 3 - host 1: each switch reaches the far host via its
+        // neighbor's route, not a directly attached queue
+        let bottleneck = Bottleneck{
+            bandwidth_bps: 1_000_000,
+            queue_length_bytes: 15_000,
+        };
+        let t = Dumbbell::<LossySwitch>::make_dumbbell(1, 1, 15_000, 1_000_000, bottleneck, 1_000_000);
+        assert_eq!(t.switches[0].routes.get(&1), Some(&vec![3]));
+        assert_eq!(t.switches[1].routes.get(&0), Some(&vec![2]));
+        assert_eq!(
+            t.path(0, 1).unwrap().iter().map(|l| (l.from, l.to)).collect::<Vec<_>>(),
+            vec![(0, 2), (2, 3), (3, 1)],
+        );
+
+        let mut e = Executor::new(t, None, None);
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(43800) // 30 packet flow
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+        assert!(topo.hosts.iter().all(|h| h.downstream_drops.is_empty()));
+    }
+
+    /// The standing queue at the bottleneck of a dumbbell while a 300-packet flow using `CC`
+    /// crosses it, from `settle` after the flow starts until it completes, and the flow's
+    /// completion time. The bottleneck marks ECN at `ecn_threshold` bytes, if any.
+    fn bottleneck_standing_queue<CC: CongAlg + 'static>(ecn_threshold: Option<u32>, settle: Nanos) -> (super::stats::StandingQueue, Nanos) {
+        use super::node::switch::Queue;
+        use super::stats::{QueueSampleEvent, QueueTrace};
+        use super::topology::dumbbell::{Bottleneck, Dumbbell};
+
+        let bottleneck = Bottleneck{
+            bandwidth_bps: 1_000_000,
+            queue_length_bytes: 60_000,
+        };
+
+        // hosts 0, 1 under switch 4; hosts 2, 3 under switch 5
+        let mut t = Dumbbell::<LossySwitch>::make_dumbbell(2, 2, 60_000, 10_000_000, bottleneck, 1_000_000);
+        t.switches[0].rack.iter_mut()
+            .filter(|q| q.link().to == 5)
+            .for_each(|q| q.set_ecn_threshold(ecn_threshold));
+        let mut e = Executor::new(t, None, None);
+        let flowinfo = FlowInfo::builder(1, 0, 2)
+            .length_bytes(438000) // 300 packet flow
+            .build();
+
+        let start = 1_000_000_000;
+        let trace = QueueTrace::default();
+        e.push(Box::new(FlowArrivalEvent(flowinfo, start, PhantomData::<CC>)));
+        e.push(Box::new(QueueSampleEvent::new(start, 1_000_000, start + 10_000_000_000, 4, 5, trace.clone())));
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        let fct = topo.all_flows()
+            .find(|f| f.transmit_completion_time().is_some())
+            .and_then(|f| f.completion_time())
+            .unwrap();
+        (trace.standing_queue(start + settle, start + fct).unwrap(), fct)
+    }
+
+    #[test]
+    fn vegas_standing_queue_below_reno() {
+        use super::congcontrol::{NewReno, Vegas};
+
+        let (reno, reno_fct) = bottleneck_standing_queue::<NewReno>(None, 0);
+        let (vegas, vegas_fct) = bottleneck_standing_queue::<Vegas>(None, 0);
+
+        // both keep the bottleneck busy...
+        assert!((vegas_fct as f64) < 1.2 * reno_fct as f64, "vegas {} reno {}", vegas_fct, reno_fct);
+        // ...but Vegas does it with a few packets queued, while Reno fills the buffer
+        assert!(vegas.time_average_bytes < reno.time_average_bytes / 2.0, "vegas {:?} reno {:?}", vegas, reno);
+        assert!(vegas.peak_bytes < reno.peak_bytes, "vegas {:?} reno {:?}", vegas, reno);
+    }
+
+    #[test]
+    fn dctcp_standing_queue_near_threshold() {
+        use super::congcontrol::Dctcp;
+
+        // the DCTCP marking scheme: mark whatever arrives to find more than K bytes queued
+        let k = 15_000;
+        let (dctcp, _) = bottleneck_standing_queue::<Dctcp>(Some(k), 1_000_000_000);
+        assert!(dctcp.time_average_bytes > k as f64 / 2.0, "{:?}", dctcp);
+        assert!(dctcp.time_average_bytes < 1.5 * k as f64, "{:?}", dctcp);
+        // far from the 60KB the queue could hold
+        assert!(dctcp.peak_bytes < 2 * k, "{:?}", dctcp);
+    }
+
+    #[test]
+    fn occupancy_trace_peaks_at_limit() {
+        use super::node::switch::Queue;
+        use super::node::switch::drop_tail_queue::DropTailQueue;
+        use super::stats::OccupancyTrace;
+
+        let mut t = OneBigSwitch::<LossySwitch>::make_topology(3, 15_000, 1_000_000, 1_000_000);
+        let trace = OccupancyTrace::new(1_000);
+        let port = t.switches[0].rack.iter().position(|q| q.link().to == 0).unwrap();
+        let mut q = DropTailQueue::new(15_000, t.switches[0].rack[port].link());
+        q.set_occupancy_trace(Some(trace.clone()));
+        t.switches[0].rack[port] = Box::new(q) as Box<Queue>;
+        let mut e = Executor::new(t, None, None);
+
+        // hosts 1 and 2 each burst 20 packets at host 0, so its queue fills at 1Mbps
+        {
+            let topo = e.components().1;
+            for host in 1..3 {
+                for seq in 0..20 {
+                    topo.lookup_host(host).unwrap().push_pkt(Packet::Data{
+                        hdr: PacketHeader{
+                            flow: host,
+                            from: host,
+                            to: 0,
+                            priority: 0,
+                            size_bytes: PACKET_HEADER_SIZE_BYTES,
+                        },
+                        seq: seq * 1460,
+                        length: 1460,
+                        ect: true,
+                        ce: false,
+                        sent: 0,
+                        deadline: None,
+                        trimmed: false,
+                    });
+                }
+            }
+        }
+
+        e.execute().unwrap();
+        assert_eq!(trace.peak_bytes(), Some(15_000));
+        let samples = trace.samples();
+        assert!(samples.windows(2).all(|w| w[0].0 <= w[1].0));
+        // the queue drains once the bursts are over
+        assert_eq!(samples.last().map(|&(_, occupancy)| occupancy), Some(0));
+    }
+
+    #[test]
+    fn fat_tree_intra_and_inter_pod_flows() {
+        use super::topology::fat_tree::FatTree;
+
+        let fat_tree = FatTree::<LossySwitch>::new(4);
+        let t = fat_tree.make_fat_tree(15_000, 1_000_000, 1_000_000);
+        assert_eq!(t.hosts.len(), 16);
+        assert_eq!(t.switches.len(), 20);
+
+        let (intra_pod, inter_pod) = fat_tree.host_pairs();
+        assert!(intra_pod.iter().all(|&(from, to)| {
+            let hops = t.path(from, to).unwrap().len();
+            hops == if from / 2 == to / 2 { 2 } else { 4 }
+        }));
+        assert!(inter_pod.iter().all(|&(from, to)| t.path(from, to).unwrap().len() == 6));
+
+        // one single-packet flow within a pod (across edges) and one across pods
+        let pairs = vec![(0, 2), (1, 13)];
+        let expected_fcts = pairs.iter().map(|&(from, to)| {
+            // store-and-forward the packet out, then its ACK back
+            t.path(from, to).unwrap().iter()
+                .map(|l| 2 * l.propagation_delay + l.transmission_delay(1500) + l.transmission_delay(40))
+                .sum::<u64>()
+        }).collect::<Vec<Nanos>>();
+
+        let mut e = Executor::new(t, None, None);
+        for (flow_id, &(sender_id, dest_id)) in pairs.iter().enumerate() {
+            let flowinfo = FlowInfo::builder(flow_id as u32, sender_id, dest_id)
+                .length_bytes(1460) // 1 packet flow
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        for (flow_id, expected) in expected_fcts.into_iter().enumerate() {
+            let sender = topo.all_flows()
+                .find(|f| f.flow_info().flow_id == flow_id as u32 && f.transmit_completion_time().is_some())
+                .unwrap();
+            let fct = sender.completion_time().unwrap() as f64;
+            assert!((fct - expected as f64).abs() < 0.01 * expected as f64, "fct {} expected {}", fct, expected);
+        }
+    }
+
+    #[test]
+    fn leaf_spine_strategy_routes_all_pairs() {
+        use super::topology::leaf_spine::LeafSpine;
+
+        // the last leaf only has one host
+        let t = LeafSpine::<LossySwitch>::make_topology(5, 15_000, 1_000_000, 1_000_000);
+        assert_eq!(t.switches.len(), 5);
+        for from in 0..5 {
+            for to in (0..5).filter(|&to| to != from) {
+                let hops = t.path(from, to).unwrap().len();
+                assert_eq!(hops, if from / 2 == to / 2 { 2 } else { 4 });
+            }
+        }
+    }
+
+    #[test]
+    fn ecmp_spreads_flows_across_spines() {
+        use super::topology::leaf_spine::LeafSpine;
+
+        // hosts 0, 1 under leaf 4; hosts 2, 3 under leaf 5; spines 6, 7
+        let t = LeafSpine::<LossySwitch>::make_leaf_spine_ecmp(2, 2, 2, 15_000, 1_000_000, 1_000_000);
+        assert_eq!(t.switches[0].routes.get(&2), Some(&vec![6, 7]));
+        {
+            let spine = |flow, from| t.flow_path(flow, from, 2).unwrap()[1].to;
+            assert_eq!(spine(1, 0), 6);
+            assert_eq!(spine(2, 1), 7);
+        }
+
+        // two flows to host 2: flow 1's data (and both flows' ACKs) cross spine 6, flow 2's
+        // data crosses spine 7
+        let mut e = Executor::new(t, None, None);
+        for (flow_id, sender_id) in vec![(1, 0), (2, 1)] {
+            let flowinfo = FlowInfo::builder(flow_id, sender_id, 2)
+                .length_bytes(43800) // 30 packet flow
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+        assert!(topo.switches[2].active && topo.switches[3].active);
+    }
+
+    fn sprayed_flow(selective_repeat: bool) -> (Nanos, TransmitCounts) {
+        use super::flow::selective_repeat::SelectiveRepeatFlowArrivalEvent;
+        use super::node::Link;
+        use super::node::switch::{Queue, drop_tail_queue::DropTailQueue};
+        use super::topology::leaf_spine::LeafSpine;
+
+        // hosts 0, 1 under leaf 4; hosts 2, 3 under leaf 5; spines 6, 7
+        let mut t = LeafSpine::<LossySwitch>::make_leaf_spine_ecmp(2, 2, 2, 15_000, 1_000_000, 1_000_000);
+        t.switches.iter_mut().for_each(|s| s.packet_spraying = true);
+        // the path via spine 7 is 30ms longer, so each packet sprayed onto it arrives after
+        // the next one
+        let slow = t.switches[3].rack[1].link();
+        assert_eq!(slow.to, 5);
+        t.switches[3].rack[1] = Box::new(DropTailQueue::new(15_000, Link{propagation_delay: 31_000_000, ..slow})) as Box<Queue>;
+        let mut e = Executor::new(t, None, None);
+
+        let flowinfo = FlowInfo::builder(1, 0, 2)
+            .length_bytes(43800) // 30 packet flow
+            .build();
+
+        if selective_repeat {
+            e.push(Box::new(SelectiveRepeatFlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        } else {
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        // the single flow's data crossed both spines
+        assert!(topo.switches[2].active && topo.switches[3].active);
+        let sender = topo.all_flows().find(|f| f.flow_info().sender_id == 0).unwrap();
+        (sender.completion_time().unwrap(), sender.transmit_counts())
+    }
+
+    #[test]
+    fn packet_spraying_reorders() {
+        let (gbn_fct, gbn_counts) = sprayed_flow(false);
+        let (sr_fct, sr_counts) = sprayed_flow(true);
+        // go-back-N discards every packet which overtakes another and sends it again;
+        // selective repeat keeps them, and only resends the packets they overtook
+        assert!(gbn_counts.retransmitted > 0);
+        assert!(sr_counts.retransmitted < gbn_counts.retransmitted, "selective repeat {:?} go-back-N {:?}", sr_counts, gbn_counts);
+        assert!(sr_fct < gbn_fct, "selective repeat {} go-back-N {}", sr_fct, gbn_fct);
+    }
+
+    #[test]
+    fn flows_complete_before_final_ack_drains() {
+        let mut e = setup_test();
+        assert_eq!(e.all_flows_complete_time(), None);
+
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(4380) // 3 packet flow
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        let mut e = e.execute().unwrap();
+        let complete = e.all_flows_complete_time().unwrap();
+        let quiescent = e.current_time();
+        assert!(complete < quiescent);
+
+        // once the last data packet arrives, only its ACK is left to return
+        let ack_drain = e.components().1.path(1, 0).unwrap().iter()
+            .map(|l| l.propagation_delay + l.transmission_delay(40))
+            .sum::<u64>();
+        let gap = (quiescent - complete) as f64;
+        assert!((gap - ack_drain as f64).abs() < 0.01 * ack_drain as f64, "gap {} ACK drain {}", gap, ack_drain);
+    }
+
+    #[test]
+    fn receiver_driven_unscheduled_bytes() {
+        use super::flow::receiver_driven::{GrantReceiver, ReceiverDrivenFlowArrivalEvent};
+        use super::stats::ideal_fct;
+
+        let mut e = setup_test();
+        let flow = |flow_id, length_bytes| FlowInfo::builder(flow_id, 0, 1)
+            .length_bytes(length_bytes)
+            .build();
+
+        // a short flow within its unscheduled bytes, then a long one which needs grants
+        e.push(Box::new(ReceiverDrivenFlowArrivalEvent(flow(1, 4380), 1_000_000_000, 4380)));
+        e.push(Box::new(ReceiverDrivenFlowArrivalEvent(flow(2, 43800), 2_000_000_000, 4380)));
+        let mut e = e.execute().unwrap();
+
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+        let grants_sent = |flow_id| topo.all_flows()
+            .find(|f| f.flow_info().flow_id == flow_id && f.transmit_completion_time().is_none())
+            .and_then(|f| f.as_any().downcast_ref::<GrantReceiver>())
+            .unwrap()
+            .grants_sent();
+        assert_eq!(grants_sent(1), 0);
+        assert!(grants_sent(2) > 0);
+
+        // the short flow goes out at line rate, with no grant round trip
+        let sender = topo.all_flows()
+            .find(|f| f.flow_info().flow_id == 1 && f.transmit_completion_time().is_some())
+            .unwrap();
+        let fi = sender.flow_info();
+        let ideal = ideal_fct(&fi, &topo.path(0, 1).unwrap()).unwrap() as f64;
+        let fct = sender.completion_time().unwrap() as f64;
+        assert!((fct - ideal).abs() < 0.01 * ideal, "fct {} ideal {}", fct, ideal);
+    }
+
+    #[test]
+    fn transmit_completes_before_ack() {
+        let mut e = setup_test();
+
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(14600) // 10 packet flow, all within one window
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        let mut e = e.execute().unwrap();
+
+        let topo = e.components().1;
+        let rtt = {
+            let path = topo.path(0, 1).unwrap();
+            // the last packet still has to cross the switch, then its ACK returns
+            path.iter()
+                .map(|l| 2 * l.propagation_delay + l.transmission_delay(40))
+                .sum::<u64>()
+                + path[1].transmission_delay(1500)
+        };
+
+        let sender = topo.all_flows().find(|f| f.flow_info().sender_id == 0 && f.transmit_completion_time().is_some()).unwrap();
+        let (tx, ack) = (sender.transmit_completion_time().unwrap(), sender.completion_time().unwrap());
+        assert!(tx < ack);
+        let gap = (ack - tx) as f64;
+        assert!((gap - rtt as f64).abs() < 0.1 * rtt as f64, "gap {} rtt {}", gap, rtt);
+    }
+
+    #[test]
+    fn dctcp_incast() {
+        use super::congcontrol::Dctcp;
+        use super::node::switch::Queue;
+
+        let (logger, cwnds) = cwnd_logger();
+        let mut t = OneBigSwitch::<LossySwitch>::make_topology(3, 30_000, 1_000_000, 1_000_000);
+        t.switches[0].rack.iter_mut().for_each(|q| q.set_ecn_threshold(Some(9_000))); // 6 packets
+        let mut e = Executor::new(t, logger, None);
+
+        for i in 1..3 {
+            let flowinfo = FlowInfo::builder(i, i, 0)
+                .length_bytes(438000) // 300 packet flow
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<Dctcp>)));
+        }
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+        // the queue stays near the marking threshold, far from overflowing
+        assert!(topo.hosts.iter().all(|h| h.downstream_drops.is_empty()));
+
+        // after the initial cuts, both windows stay below their initial 10 packets
+        let cwnds = cwnds.lock().unwrap();
+        assert!(cwnds.iter().skip(cwnds.len() / 2).all(|&c| c < 10), "{:?}", *cwnds);
+    }
+
+    #[test]
+    fn dctcp_red_marking() {
+        use super::congcontrol::Dctcp;
+        use super::node::switch::lossy_switch::LossySwitch;
+        use super::node::switch::red::RedParams;
+
+        let (logger, cwnds) = cwnd_logger();
+        let mut t = OneBigSwitch::<LossySwitch>::make_topology(3, 30_000, 1_000_000, 1_000_000);
+        t.switches[0].set_red(RedParams{
+            min_threshold_bytes: 6_000, // 4 packets
+            max_threshold_bytes: 12_000,
+            max_probability: 0.5,
+        });
+        let mut e = Executor::new(t, logger, None);
+
+        for i in 1..3 {
+            let flowinfo = FlowInfo::builder(i, i, 0)
+                .length_bytes(438000) // 300 packet flow
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<Dctcp>)));
+        }
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+        assert!(topo.switches[0].marks > 0);
+        // the queue stays around the marking thresholds, far from overflowing
+        assert!(topo.hosts.iter().all(|h| h.downstream_drops.is_empty()));
+
+        let cwnds = cwnds.lock().unwrap();
+        assert!(cwnds.iter().skip(cwnds.len() / 2).all(|&c| c < 10), "{:?}", *cwnds);
+    }
+
+    #[test]
+    fn red_marks_near_thresholds() {
+        use super::congcontrol::Dctcp;
+        use super::node::switch::lossy_switch::LossySwitch;
+        use super::node::switch::red::{RedParams, MARK_OCCUPANCY_SAMPLES};
+
+        let mut t = OneBigSwitch::<LossySwitch>::make_topology(3, 30_000, 1_000_000, 1_000_000);
+        t.switches[0].set_red(RedParams{
+            min_threshold_bytes: 6_000, // 4 packets
+            max_threshold_bytes: 12_000,
+            max_probability: 0.5,
+        });
+        let mut e = Executor::new(t, None, None);
+
+        for i in 1..3 {
+            let flowinfo = FlowInfo::builder(i, i, 0)
+                .length_bytes(438000) // 300 packet flow
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<Dctcp>)));
+        }
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        let occupancies = &topo.switches[0].mark_occupancies;
+        // every packet is ECN-capable, so none is dropped early
+        assert_eq!(topo.switches[0].early_drops, 0);
+        assert_eq!(occupancies.len(), ::std::cmp::min(topo.switches[0].marks as usize, MARK_OCCUPANCY_SAMPLES));
+        // nothing is marked below the minimum threshold, and the queue hovers between the
+        // thresholds rather than building up past them
+        assert!(occupancies.iter().all(|&o| o >= 6_000), "{:?}", occupancies);
+        let mean = occupancies.iter().map(|&o| o as usize).sum::<usize>() / occupancies.len();
+        assert!(mean <= 13_500, "{:?}", occupancies);
+    }
+
+    #[test]
+    fn red_marking_routes_over_core_links() {
+        use super::node::switch::Queue;
+        use super::node::switch::lossy_switch::LossySwitch;
+        use super::node::switch::red::RedParams;
+
+        // the queue toward host 0 is a core link, so the switch must look past its rack
+        let mut t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let to_receiver = t.switches[0].rack.remove(0);
+        assert_eq!(to_receiver.link().to, 0);
+        t.switches[0].core.push(to_receiver);
+        t.switches[0].set_red(RedParams{
+            min_threshold_bytes: 3_000,
+            max_threshold_bytes: 9_000,
+            max_probability: 0.5,
+        });
+        let mut e = Executor::new(t, None, None);
+
+        let flowinfo = FlowInfo::builder(1, 1, 0)
+            .length_bytes(14600) // 10 packet flow
+            .build();
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+    }
+
+    #[test]
+    fn shared_medium_collisions() {
+        use super::node::switch::shared_medium::SharedMedium;
+
+        let t = OneBigSwitch::<SharedMedium>::make_topology(3, 150_000, 1_000_000, 1_000_000);
+        let mut e = Executor::with_seed(t, None, None, 7);
+
+        // both senders start transmitting at the same moment, so their first packets collide
+        for i in 1..3 {
+            let flowinfo = FlowInfo::builder(i, i, 0)
+                .length_bytes(14600) // 10 packet flow
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        let medium = &topo.switches[0];
+        assert!(medium.collisions > 0);
+        assert!(medium.backoffs > 0);
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+        // a station's later packets wait for the one it is retrying, so none arrive out of
+        // order and go-back-N never has to resend
+        assert!(topo.all_flows().all(|f| f.transmit_counts().retransmitted == 0));
+    }
+
+    fn victim_fct_behind_bulk(victim_priority: u8) -> Nanos {
+        use super::node::switch::{Queue, priority_queue::PriorityQueue};
+
+        let mut t = OneBigSwitch::<LossySwitch>::make_topology(3, 150_000, 1_000_000, 1_000_000);
+        let queues = t.switches[0].rack.iter()
+            .map(|q| Box::new(PriorityQueue::new(2, 150_000, q.link())) as Box<Queue>)
+            .collect();
+        t.switches[0].rack = queues;
+        let mut e = Executor::new(t, None, None);
+
+        let bulk = FlowInfo::builder(1, 1, 0)
+            .length_bytes(438000) // 300 packet flow
+            .priority(1)
+            .build();
+
+        let victim = FlowInfo::builder(2, 2, 0)
+            .length_bytes(14600) // 10 packet flow
+            .priority(victim_priority)
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(bulk, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        // once the bulk flow has built a standing queue
+        e.push(Box::new(FlowArrivalEvent(victim, 1_500_000_000, PhantomData::<ConstCwnd>)));
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+        let sender = topo.all_flows().find(|f| f.flow_info().sender_id == 2).unwrap();
+        sender.completion_time().unwrap()
+    }
+
+    #[test]
+    fn strict_priority_victim_flow() {
+        let prioritized = victim_fct_behind_bulk(0);
+        let shared = victim_fct_behind_bulk(1);
+        assert!(prioritized < shared, "prioritized {} shared {}", prioritized, shared);
+    }
+
+    fn deadline_flow_behind_bulk(edf: bool) -> Option<bool> {
+        use super::node::switch::{Queue, edf_queue::EdfQueue};
+
+        let mut t = OneBigSwitch::<LossySwitch>::make_topology(3, 150_000, 1_000_000, 1_000_000);
+        if edf {
+            let queues = t.switches[0].rack.iter()
+                .map(|q| Box::new(EdfQueue::new(150_000, q.link())) as Box<Queue>)
+                .collect();
+            t.switches[0].rack = queues;
+        }
+
+        let mut e = Executor::new(t, None, None);
+
+        let bulk = FlowInfo::builder(1, 1, 0)
+            .length_bytes(438000) // 300 packet flow
+            .build();
+
+        // alone, the flow takes about 135ms; sharing the link with the bulk flow, twice that
+        let small = FlowInfo::builder(2, 2, 0)
+            .length_bytes(14600) // 10 packet flow
+            .deadline(180_000_000)
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(bulk, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        e.push(Box::new(FlowArrivalEvent(small, 1_500_000_000, PhantomData::<ConstCwnd>)));
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+        let bulk_sender = topo.all_flows().find(|f| f.flow_info().sender_id == 1).unwrap();
+        assert_eq!(bulk_sender.met_deadline(), None);
+        let sender = topo.all_flows().find(|f| f.flow_info().sender_id == 2).unwrap();
+        sender.met_deadline()
+    }
+
+    #[test]
+    fn edf_meets_tight_deadline() {
+        assert_eq!(deadline_flow_behind_bulk(true), Some(true));
+        assert_eq!(deadline_flow_behind_bulk(false), Some(false));
+    }
+
+    #[test]
+    fn ecn_and_non_ecn_flows() {
+        use super::congcontrol::Dctcp;
+        use super::node::switch::Queue;
+
+        let mut t = OneBigSwitch::<LossySwitch>::make_topology(3, 30_000, 1_000_000, 1_000_000);
+        t.switches[0].rack.iter_mut().for_each(|q| q.set_ecn_threshold(Some(9_000))); // 6 packets
+        let mut e = Executor::new(t, None, None);
+
+        for i in 1..3 {
+            let flowinfo = FlowInfo::builder(i, i, 0)
+                .length_bytes(438000) // 300 packet flow
+                .ecn_capable(i == 1)
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<Dctcp>)));
+        }
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+        // the ECN-capable flow is marked at the threshold, the other is dropped there
+        assert!(topo.hosts[1].downstream_drops.is_empty());
+        assert!(!topo.hosts[2].downstream_drops.is_empty());
+    }
+
+    #[test]
+    fn per_interval_switch_drops_sum_to_total() {
+        use super::congcontrol::NewReno;
+        use super::stats::{SwitchStatsSampleEvent, SwitchStatsTrace};
+
+        // three senders into host 0 overflow the switch's 10-packet queue towards it
+        let t = OneBigSwitch::<LossySwitch>::make_topology(4, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+        for i in 1..4 {
+            let flowinfo = FlowInfo::builder(i, i, 0)
+                .length_bytes(438000) // 300 packet flow
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<NewReno>)));
+        }
+
+        let trace = SwitchStatsTrace::default();
+        e.push(Box::new(SwitchStatsSampleEvent::new(1_000_000_000, 100_000_000, 6_000_000_000, 4, trace.clone())));
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+
+        // the senders hear of every drop, and the last interval runs until the switch goes idle
+        let total = topo.hosts.iter().map(|h| h.downstream_drop_count).sum::<u32>();
+        let tail = topo.switches[0].take_stats();
+        let samples = trace.samples();
+        assert!(samples.iter().filter(|(_, s)| s.drops > 0).count() > 1);
+        assert_eq!(samples.iter().map(|(_, s)| s.drops).sum::<u32>() + tail.drops, total);
+        assert!(samples.iter().all(|(_, s)| s.high_water_bytes <= 15_000));
+        assert_eq!(topo.switches[0].take_stats(), Default::default());
+    }
+
+    #[test]
+    fn timely_bounds_queueing_delay() {
+        use super::congcontrol::Timely;
+
+        let (logger, rtts) = cwnd_message_logger("rtt");
+        // 10 Gbps, 10us links: the minimum RTT is about 41us, and the buffer holds 1.2ms of queueing
+        let t = OneBigSwitch::<LossySwitch>::make_topology(3, 1_500_000, 10_000_000_000, 10_000);
+        let mut e = Executor::new(t, logger, None);
+
+        for i in 1..3 {
+            let flowinfo = FlowInfo::builder(i, i, 0)
+                .length_bytes(5_110_000) // 3500 packet flow
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<Timely>)));
+        }
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+        assert!(topo.hosts.iter().all(|h| h.downstream_drops.is_empty()));
+
+        // once past startup, the RTT stays near the default 500us t_high instead of filling the buffer
+        let rtts = rtts.lock().unwrap();
+        assert!(rtts.iter().skip(rtts.len() / 2).all(|&rtt| rtt < 1_000_000), "{:?}", *rtts);
+    }
+
+    /// The jitter seen by the receiver of each flow, by flow id, with `num_senders` hosts
+    /// each sending one flow to host 0.
+    fn receiver_jitter(num_senders: u32) -> Vec<(u32, f64)> {
+        let t = OneBigSwitch::<LossySwitch>::make_topology(num_senders + 1, 150_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+        for i in 1..(num_senders + 1) {
+            let flowinfo = FlowInfo::builder(i, i, 0)
+                .length_bytes(43800) // 30 packet flow
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+        topo.all_flows()
+            .filter(|f| match f.side() {
+                FlowSide::Receiver => true,
+                FlowSide::Sender => false,
+            })
+            .map(|f| (f.flow_info().flow_id, f.jitter().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn jitter() {
+        // alone, every packet sees the same one-way delay
+        let uncongested = receiver_jitter(1);
+        assert_eq!(uncongested.len(), 1);
+        assert!(uncongested[0].1 < 1.0, "{:?}", uncongested);
+
+        // two senders share the switch's egress, so their packets interleave in its queue
+        let congested = receiver_jitter(2);
+        assert_eq!(congested.len(), 2);
+        assert!(congested.iter().all(|&(_, j)| j > 1_000.0), "{:?}", congested);
+    }
+
+    #[test]
+    fn dcqcn_fair_share() {
+        use super::congcontrol::Dcqcn;
+        use super::node::switch::Queue;
+        use super::stats::{ThroughputSampleEvent, ThroughputTrace};
+
+        // 10 Gbps, 10us links, marking at 20 packets of queue
+        let mut t = OneBigSwitch::<LossySwitch>::make_topology(3, 300_000, 10_000_000_000, 10_000);
+        t.switches[0].rack.iter_mut().for_each(|q| q.set_ecn_threshold(Some(30_000)));
+        let mut e = Executor::new(t, None, None);
+
+        let start = 1_000_000_000;
+        for i in 1..3 {
+            let flowinfo = FlowInfo::builder(i, i, 0)
+                .length_bytes(51_100_000) // 35000 packet flow
+                .build();
+
+            // flow 2 joins once flow 1 has the link to itself
+            let arrival = start + (i as Nanos - 1) * 1_000_000;
+            e.push(Box::new(FlowArrivalEvent(flowinfo, arrival, PhantomData::<Dcqcn>)));
+        }
+
+        let trace = ThroughputTrace::default();
+        e.push(Box::new(ThroughputSampleEvent::new(start, 100_000, start + 100_000_000, vec![0], trace.clone())));
+        let mut e = e.execute().unwrap();
+        assert!(e.components().1.all_flows().all(|f| f.completion_time().is_some()));
+
+        // flow 2 starts with alpha at 1, so its first cuts are deep, and the flows' alphas only
+        // even out at a gain of 1/256 per timer period. 50ms after flow 2 joins, over 20ms,
+        // each flow gets about half the link
+        let (from, to) = (start + 50_000_000, start + 70_000_000);
+        let fair_share_bytes: u64 = 10_000_000_000 / 8 * 20 / 1_000 / 2;
+        for flow_id in 1..3 {
+            let got = trace.bytes_between(flow_id, from, to);
+            assert!(
+                (got as f64 - fair_share_bytes as f64).abs() < 0.25 * fair_share_bytes as f64,
+                "flow {} got {} bytes, fair share {}", flow_id, got, fair_share_bytes,
+            );
+        }
+    }
+
+    #[test]
+    fn wfq_shares_follow_weights() {
+        use super::node::switch::Queue;
+        use super::node::switch::wfq_queue::WfqQueue;
+        use super::stats::{ThroughputSampleEvent, ThroughputTrace};
+
+        // hosts 1, 2, and 3 send to host 0 with weights 1, 2, and 3
+        let mut t = OneBigSwitch::<LossySwitch>::make_topology(4, 150_000, 1_000_000, 1_000_000);
+        let weights = [(1, 1), (2, 2), (3, 3)];
+        let port = t.switches[0].rack.iter().position(|q| q.link().to == 0).unwrap();
+        let mut q = WfqQueue::new(150_000, t.switches[0].rack[port].link());
+        for &(flow_id, weight) in &weights {
+            q.set_weight(flow_id, weight);
+        }
+
+        t.switches[0].rack[port] = Box::new(q) as Box<Queue>;
+        let mut e = Executor::new(t, None, None);
+        for &(flow_id, _) in &weights {
+            let flowinfo = FlowInfo::builder(flow_id, flow_id, 0)
+                .length_bytes(438000) // 300 packet flow
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        let trace = ThroughputTrace::default();
+        e.push(Box::new(ThroughputSampleEvent::new(1_000_000_000, 100_000_000, 30_000_000_000, vec![0], trace.clone())));
+        let mut e = e.execute().unwrap();
+        assert!(e.components().1.all_flows().all(|f| f.completion_time().is_some()));
+
+        // every flow's window keeps it backlogged at the switch until flow 3 completes, after
+        // about 7s
+        let compliance = trace.weighted_share_compliance(&weights, 1_500_000_000, 5_500_000_000).unwrap();
+        assert_eq!(compliance.len(), 3);
+        for c in compliance {
+            assert!((c.achieved_share - c.target_share).abs() < 0.02, "{:?}", c);
+        }
+
+        // nothing is delivered before the flows start
+        assert_eq!(trace.weighted_share_compliance(&weights, 0, 1_000_000_000), None);
+    }
+
+    #[test]
+    fn rtt_samples() {
+        let (logger, rtts) = cwnd_message_logger("rtt");
+
+        let t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, logger, None);
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(14600) // 10 packet flow
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        e.execute().unwrap();
+
+        // measured from when the sender dequeued the packet: 2 x 12ms transmission,
+        // 4 x 1ms propagation, and 2 x 0.32ms ACK transmission.
+        // the switch drains at the sender's rate, so no packet queues.
+        let rtts = rtts.lock().unwrap();
+        assert_eq!(rtts.len(), 10);
+        assert!(rtts.iter().all(|&rtt| rtt == 28_640_000), "{:?}", *rtts);
+    }
+
+    #[test]
+    fn flows_inherit_topology_mtu() {
+        let t = OneBigSwitch::<LossySwitch>::make_topology_with_mtu(3, 90_000, 1_000_000, 1_000_000, 9000);
+        let mut e = Executor::new(t, None, None);
+
+        let inherited = FlowInfo::builder(1, 0, 2)
+            .length_bytes(89600) // 10 packet flow
+            .build();
+
+        let explicit = FlowInfo::builder(2, 1, 2)
+            .length_bytes(14600) // 10 packet flow
+            .max_packet_length(1460)
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(inherited, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        e.push(Box::new(FlowArrivalEvent(explicit, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        let mut e = e.execute().unwrap();
+
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+        for f in topo.all_flows() {
+            let fi = f.flow_info();
+            match fi.flow_id {
+                1 => assert_eq!(fi.max_packet_length, Some(9000 - 40)),
+                2 => assert_eq!(fi.max_packet_length, Some(1460)),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn jumbo_frames() {
+        use super::node::switch::Queue;
+
+        let t = OneBigSwitch::<PFCSwitch>::make_topology_with_mtu(2, 90_000, 1_000_000, 1_000_000, 9000);
+        let mut e = Executor::new(t, None, None);
+        {
+            let pkt = Packet::Data{
+                hdr: PacketHeader{
+                    flow: 0,
+                    from: 0,
+                    to: 1,
+                    priority: 0,
+                    size_bytes: PACKET_HEADER_SIZE_BYTES,
+                },
+                seq: 0,
+                length: 8960,
+                ect: true,
+                ce: false,
+                sent: 0,
+                deadline: None,
+                trimmed: false,
+            };
+
+            let topo = e.components().1;
+            // PFC reserves the BDP plus two jumbo frames of headroom
+            assert!(topo.switches[0].rack.iter().all(|(q, _)| q.link().pfc_pause_threshold() == 125 + 2 * 9000));
+            topo.lookup_host(0).unwrap().push_pkt(pkt);
+        }
+
+        // 2 * (72ms transmission + 1ms propagation)
+        let e = e.execute().unwrap();
+        assert_eq!(e.current_time(), 2 * (72_000_000 + 1_000_000));
+
+        // packets larger than the sender's MTU are refused
+        let mut e = setup_test();
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(89600)
+            .max_packet_length(8960)
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        assert!(e.execute().is_err());
+
+        // as are flows whose headers fill the whole MTU
+        let mut e = setup_test();
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(89600)
+            .header_bytes(1500)
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        assert!(e.execute().is_err());
+    }
+
+    #[test]
+    fn uncongested_flow_slowdown() {
+        let mut e = setup_test();
+
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(43800) // 30 packet flow
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        let mut e = e.execute().unwrap();
+
+        let slowdowns = flow_slowdowns(e.components().1).unwrap();
+        assert_eq!(slowdowns.len(), 1);
+        assert!((slowdowns[0].1 - 1.0).abs() < 0.05, "slowdown {}", slowdowns[0].1);
+    }
+
+    #[test]
+    fn empty_flow_ideal_fct() {
+        use super::stats::{ideal_fct, slowdown};
+
+        let t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(0)
+            .max_packet_length(1460)
+            .build();
+
+        assert_eq!(ideal_fct(&flowinfo, &t.path(0, 1).unwrap()).unwrap(), 0);
+        assert_eq!(slowdown(1_000, 0), 1.0);
+    }
+
+    #[test]
+    fn congested_flow_slowdown() {
+        let t = OneBigSwitch::<LossySwitch>::make_topology(3, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+
+        for i in 1..3 {
+            let flowinfo = FlowInfo::builder(i, i, 0)
+                .length_bytes(43800) // 30 packet flow
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        let mut e = e.execute().unwrap();
+
+        let slowdowns = flow_slowdowns(e.components().1).unwrap();
+        assert_eq!(slowdowns.len(), 2);
+        assert!(slowdowns.iter().any(|&(_, s)| s > 1.5), "slowdowns {:?}", slowdowns);
+    }
+
+    #[test]
+    fn mirror_port_copies_matching_packets() {
+        use super::node::switch::mirror_switch::MirrorSwitch;
+
+        let t = OneBigSwitch::<MirrorSwitch<LossySwitch>>::make_topology(3, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+
+        {
+            let topo = e.components().1;
+            let analyzer_link = topo.path(0, 2).unwrap()[1];
+            topo.switches[0].mirror(analyzer_link, 15_000, |p| match p {
+                Packet::Data{hdr, ..} => hdr.flow == 1,
+                _ => false,
+            });
+        }
+
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(4380) // 3 packet flow
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+
+        // same completion time as send_one_flow, which has no mirror port
+        let sender_fct = topo.all_flows()
+            .find(|f| f.flow_info().sender_id == 0)
+            .and_then(|f| f.completion_time());
+        assert_eq!(sender_fct, Some(52640000));
+
+        let seqs = topo.hosts[2].mirrored.iter().map(|p| match p {
+            Packet::Mirror{orig, seq, ..} => {
+                assert_eq!(orig.flow, 1);
+                *seq
+            }
+            _ => unreachable!(),
+        }).collect::<Vec<u32>>();
+        assert_eq!(seqs, vec![0, 1460, 2920]);
+        assert!(topo.hosts[2].active_flows.is_empty());
+    }
+
+    #[test]
+    fn mirror_port_shares_link_with_forwarded_traffic() {
+        use super::node::switch::mirror_switch::MirrorSwitch;
+
+        let t = OneBigSwitch::<MirrorSwitch<LossySwitch>>::make_topology(3, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+
+        {
+            let topo = e.components().1;
+            let analyzer_link = topo.path(0, 2).unwrap()[1];
+            topo.switches[0].mirror(analyzer_link, 15_000, |p| match p {
+                Packet::Data{hdr, ..} => hdr.flow == 1,
+                _ => false,
+            });
+        }
+
+        // flow 2 is forwarded to the analyzer over the same link the mirrored copies take
+        for (flow_id, sender_id, dest_id) in vec![(1, 0, 1), (2, 1, 2)] {
+            let flowinfo = FlowInfo::builder(flow_id, sender_id, dest_id)
+                .length_bytes(14600) // 10 packet flow
+                .build();
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+        assert!(topo.hosts[2].mirrored.len() >= 10);
+    }
+
+    /// Counts how many times hosts resume after a PFC PAUSE.
+    fn count_resumes(resume_hysteresis_bytes: u32) -> usize {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct ResumeCounter(Arc<AtomicUsize>);
+        impl slog::Drain for ResumeCounter {
+            type Ok = ();
+            type Err = slog::Never;
+            fn log(&self, record: &slog::Record, _: &slog::OwnedKVList) -> ::std::result::Result<(), slog::Never> {
+                if format!("{}", record.msg()) == "resuming" {
+                    self.0.fetch_add(1, Ordering::SeqCst);
+                }
+
+                Ok(())
+            }
+        }
+
+        let resumes = Arc::new(AtomicUsize::new(0));
+        let logger = slog::Logger::root(ResumeCounter(resumes.clone()), o!());
+
+        let mut t = OneBigSwitch::<PFCSwitch>::make_topology(3, 15_000, 1_000_000, 1_000_000);
+        t.switches[0].resume_hysteresis_bytes = Some(resume_hysteresis_bytes);
+        let mut e = Executor::new(t, logger, None);
+
+        for i in 1..3 {
+            let flowinfo = FlowInfo::builder(i, i, 0)
+                .length_bytes(438000) // 300 packet flow
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        let mut e = e.execute().unwrap();
+        assert!(e.components().1.all_flows().all(|f| f.completion_time().is_some()));
+        resumes.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn pfc_resume_hysteresis() {
+        let flapping = count_resumes(0);
+        // must stay below the queue length minus the PAUSE threshold, or we would never resume
+        let stable = count_resumes(9_000);
+        assert!(flapping > 2 * stable, "{} resumes without hysteresis, {} with", flapping, stable);
+    }
+
+    #[test]
+    fn queue_drop_counters() {
+        use super::node::switch::Queue;
+
+        let mut e = super::IndependentVictimFlowScenario::make::<LossySwitch>(None).execute().unwrap();
+        let lossy = &mut e.components().1.switches[0];
+        let lossy_drops = lossy.rack.iter().map(|q| q.dropped_packets()).sum::<usize>();
+        assert!(lossy_drops > 0);
+        // without a loss model, every drop is an overflow
+        assert_eq!(lossy_drops, lossy.take_stats().drops as usize);
+
+        let mut e = super::IndependentVictimFlowScenario::make::<PFCSwitch>(None).execute().unwrap();
+        let pfc = &e.components().1.switches[0];
+        assert_eq!(pfc.rack.iter().map(|(q, _)| q.dropped_packets()).sum::<usize>(), 0);
+    }
+
+    /// Hosts 1, 2, and 3 each send a 300 packet flow to host 0 through a `TrimmingSwitch` with
+    /// room for `header_queue_limit` headers per port. Their timeouts are long enough that any
+    /// recovery which finishes sooner did not need one.
+    fn trimming_incast(header_queue_limit: usize) -> Executor<TrimmingSwitch> {
+        let mut t = OneBigSwitch::<TrimmingSwitch>::make_topology(4, 15_000, 1_000_000, 1_000_000);
+        t.switches[0].header_queue_limit = header_queue_limit;
+        let mut e = Executor::new(t, None, None);
+        for i in 1..4 {
+            let flowinfo = FlowInfo::builder(i, i, 0)
+                .length_bytes(438000) // 300 packet flow
+                .rto(100_000_000_000)
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        e.execute().unwrap()
+    }
+
+    #[test]
+    fn trimmed_headers_trigger_retransmission() {
+        let mut e = trimming_incast(100);
+        {
+            let switch = &mut e.components().1.switches[0];
+            assert!(switch.trimmed > 0);
+            // the header queue never filled, so no payload was lost outright
+            assert_eq!(switch.take_stats().drops, 0);
+        }
+
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+        let senders = topo.all_flows().filter(|f| match f.side() {
+            FlowSide::Sender => true,
+            FlowSide::Receiver => false,
+        });
+        for f in senders {
+            assert!(f.transmit_counts().retransmitted > 0);
+            // NACKed on the trimmed headers, well before the 100s timeout
+            assert!(f.completion_time().unwrap() < 100_000_000_000, "{:?}", f.completion_time());
+        }
+
+        // with room for a single header, trimming cannot keep up with three senders
+        let mut e = trimming_incast(1);
+        let switch = &mut e.components().1.switches[0];
+        assert!(switch.trimmed > 0);
+        assert!(switch.take_stats().drops > 0);
+    }
+
+    #[test]
+    fn pfc_control_overhead() {
+        // PAUSE and RESUME frames the switch (node 4) put on its links to the 4 hosts
+        fn pfc_frames_on_wire<S: Switch>(e: &Executor<S>) -> u32 {
+            let frame_bytes = u64::from(Packet::Pause(4, 0).get_size_bytes());
+            (0..4)
+                .filter_map(|host| e.link_stats().link(4, host))
+                .map(|b| (b.pfc_bytes / frame_bytes) as u32)
+                .sum()
+        }
+
+        let mut e = super::IndependentVictimFlowScenario::make::<PFCSwitch>(None).execute().unwrap();
+        let pfc_frames = pfc_frames_on_wire(&e);
+        let pfc = &e.components().1.switches[0];
+        assert!(pfc.pauses_sent > 0);
+        assert!(pfc.resumes_sent > 0);
+        // the counters count frames, one per paused or resumed link
+        assert_eq!(pfc.pauses_sent + pfc.resumes_sent, pfc_frames);
+        let pfc_pauses = pfc.pauses_sent;
+
+        // only the congested ingresses are paused, not every incoming link
+        let mut e = super::IndependentVictimFlowScenario::make::<IngressPFCSwitch>(None).execute().unwrap();
+        let ingress_frames = pfc_frames_on_wire(&e);
+        let ingress = &e.components().1.switches[0];
+        assert!(ingress.pauses_sent() > 0);
+        assert_eq!(ingress.pauses_sent() + ingress.resumes_sent(), ingress_frames);
+        assert!(ingress.pauses_sent() < pfc_pauses, "{} ingress pauses, {} pauses", ingress.pauses_sent(), pfc_pauses);
+        assert!(ingress_frames < pfc_frames, "{} ingress PFC frames, {} PFC frames", ingress_frames, pfc_frames);
+    }
+
+    /// The completion time of flow 0, the victim flow of `IndependentVictimFlowScenario`.
+    fn independent_victim_fct<S: Switch>() -> (Executor<S>, Nanos) {
+        let mut e = super::IndependentVictimFlowScenario::make::<S>(None).execute().unwrap();
+        let fct = e.components().1.all_flows()
+            .find(|f| f.flow_info().flow_id == 0 && match f.side() {
+                FlowSide::Sender => true,
+                FlowSide::Receiver => false,
+            })
+            .and_then(|f| f.completion_time())
+            .unwrap();
+        (e, fct)
+    }
+
+    #[test]
+    fn credit_flow_control_victim_flow() {
+        use super::node::switch::Queue;
+
+        let (mut e, credit_fct) = independent_victim_fct::<CreditSwitch>();
+        {
+            let topo = e.components().1;
+            assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+            let switch = &mut topo.switches[0];
+            assert_eq!(switch.credit_per_ingress, 15_000 / 4);
+            assert!(switch.credits_sent > 0);
+            // credits keep every ingress within its share of the buffer, so nothing overflows
+            assert_eq!(switch.rack.iter().map(|q| q.dropped_packets()).sum::<usize>(), 0);
+            assert_eq!(switch.take_stats().drops, 0);
+            for host in 0..4 {
+                assert_eq!(switch.ingress_occupancy(host), 0);
+            }
+        }
+
+        // the congested port to host 0 only holds back hosts 2 and 3, while PFC PAUSEs the
+        // victim's sender too
+        let (_, pfc_fct) = independent_victim_fct::<PFCSwitch>();
+        assert!(credit_fct < pfc_fct, "victim fct {} with credits, {} with PFC", credit_fct, pfc_fct);
+    }
+
+    #[test]
+    fn credit_switch_waits_for_credit_towards_switches() {
+        use super::topology::pod::Pod;
+
+        // hosts 0 and 1 in racks under leaves 2 and 3, joined by spine 4
+        let mut t = Pod::<CreditSwitch>::make_pod(1, 2, 15_000, 1_000_000, 1_000);
+        let host_link = t.hosts[0].link;
+        let leaf = &mut t.switches[0];
+        let pkt = Packet::Data{
+            hdr: PacketHeader{
+                flow: 0,
+                from: 0,
+                to: 1,
+                priority: 0,
+                size_bytes: PACKET_HEADER_SIZE_BYTES,
+            },
+            seq: 0,
+            length: 1460,
+            ect: true,
+            ce: false,
+            sent: 0,
+            deadline: None,
+            trimmed: false,
+        };
+
+        leaf.receive(pkt, host_link, 0, None).unwrap();
+        // the initial credits go out first
+        let credits = leaf.exec(0, None).unwrap();
+        assert_eq!(credits.len(), 2);
+        for ev in credits {
+            leaf.reactivate(ev.transmission().unwrap().0).unwrap();
+        }
+
+        // the spine has not granted any credit yet
+        assert!(leaf.exec(1, None).unwrap().is_empty());
+        assert_eq!(leaf.queue_occupancy(4), Some(pkt.get_size_bytes()));
+
+        let spine_link = super::node::Link{from: 4, to: 2, ..host_link};
+        leaf.receive(Packet::Credit(4, 15_000 / 2), spine_link, 2, None).unwrap();
+        let sent = leaf.exec(3, None).unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].transmission().unwrap().1, pkt);
+    }
 
-use failure::Error;
-type Result<T> = std::result::Result<T, Error>;
-pub type Nanos = u64;
+    /// Offer a 20 packet burst to each of `ports` of a 4 port `SharedBufferSwitch` with 30 KB of
+    /// buffer and an alpha of 1, interleaved, and return the bytes each port admitted.
+    fn shared_buffer_admitted(ports: &[u32]) -> Vec<u32> {
+        use super::node::Link;
+        use super::node::switch::{Queue, drop_tail_queue::DropTailQueue, shared_buffer_switch::SharedBufferSwitch};
 
-pub mod event;
-pub mod topology;
-pub mod packet;
-pub mod node;
-pub mod flow;
-pub mod congcontrol;
+        let link = |from, to| Link{
+            propagation_delay: 1_000_000,
+            bandwidth_bps: 1_000_000,
+            mtu: 1500,
+            pfc_enabled: false,
+            cut_through_threshold: None,
+            from,
+            to,
+        };
 
-use std::marker::PhantomData;
+        // each queue alone could take the whole buffer
+        let queues = (0..4).map(|to| Box::new(DropTailQueue::new(30_000, link(4, to))) as Box<Queue>);
+        let mut switch = SharedBufferSwitch::with_shared_buffer(4, queues, 30_000, 1.0);
+        for seq in 0..20 {
+            for &to in ports {
+                let pkt = Packet::Data{
+                    hdr: PacketHeader{
+                        flow: to,
+                        from: 5,
+                        to,
+                        priority: 0,
+                        size_bytes: PACKET_HEADER_SIZE_BYTES,
+                    },
+                    seq,
+                    length: 1460,
+                    ect: true,
+                    ce: false,
+                    sent: 0,
+                    deadline: None,
+                    trimmed: false,
+                };
 
-use congcontrol::ConstCwnd;
-use event::Executor;
-use flow::{FlowArrivalEvent, FlowInfo};
-use node::switch::Switch;
-use topology::{TopologyStrategy, one_big_switch::OneBigSwitch};
+                switch.receive(pkt, link(5, 4), 0, None).unwrap();
+            }
+        }
 
-pub trait Scenario {
-    fn make<S: Switch>(logger: Option<slog::Logger>) -> Executor<S>;
-}
+        assert!(switch.shared_occupancy() <= 30_000);
+        (0..4).map(|to| switch.queue_occupancy(to).unwrap()).collect()
+    }
 
-/// `independent_victim_flow_scenario()` is the difference between `IngressPFCSwitch` and `PFCSwitch`.
-/// (See `shared_ingress_victim_flow_scenario()`)
-/// `PFCSwitch` does not consider ingress queue occupancy when doing PFC; it is a "naive" switch
-/// implementation.
-/// Consider this simple topology:
-///
-/// `Host` 0 -------
-///                 \
-/// `Host` 1 --------\
-///                   ---- 4 (`Switch`)
-/// `Host` 2 --------/
-///                 /
-/// `Host` 3 -------
-///
-/// `Host` 3 and 2 send to `Host` 0, creating congestion. 
-/// A "victim flow" goes from `Host` *0* to `Host` 1.
-/// When the queue to `Host` 0 fills, `Switch` 4, a `PFCSwitch`, will PAUSE *all* incoming links, so the "victim flow"
-/// will also be paused.
-///
-/// `Host` 3 and 2 send to `Host` 0, creating congestion. 
-/// A "victim flow" goes from `Host` *2* to `Host` 1.
-/// When the queue to `Host` 0 fills, `Switch` 4, an `IngressPFCSwitch` will PAUSE *only* the
-/// congested incoming queues, but the "victim flow" is sending from `Host` 2 and will still get
-/// caught.
-pub struct IndependentVictimFlowScenario;
+    #[test]
+    fn shared_buffer_dynamic_thresholds() {
+        // a lone bursty port takes twice its 7.5 KB even split of the buffer
+        assert_eq!(shared_buffer_admitted(&[0]), vec![15_000, 0, 0, 0]);
+        // with three ports busy, each is held to alpha * free buffer = 30 KB / 4
+        assert_eq!(shared_buffer_admitted(&[0, 1, 2]), vec![7_500, 7_500, 7_500, 0]);
+        // with every port busy, each gets less than an even split, leaving some buffer free
+        assert_eq!(shared_buffer_admitted(&[0, 1, 2, 3]), vec![6_000; 4]);
+    }
 
-impl Scenario for IndependentVictimFlowScenario {
-    fn make<S: Switch>(logger: Option<slog::Logger>) -> Executor<S> {
-        let t = OneBigSwitch::<S>::make_topology(4, 15_000, 1_000_000, 1_000_000);
-        let mut e = Executor::new(t, logger);
+    #[test]
+    fn saturating_flow_uses_bottleneck() {
+        let t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+        let flowinfo = FlowInfo::builder(0, 0, 1)
+            .length_bytes(438000) // 300 packet flow
+            .build();
 
-        let flow = FlowInfo{
-            flow_id: 0,
-            sender_id: 0,
-            dest_id: 1,
-            length_bytes: 43800, // 30 packet flow
-            max_packet_length: 1460,
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        let mut e = e.execute().unwrap();
+        let fct = e.components().1.all_flows()
+            .find(|f| match f.side() {
+                FlowSide::Sender => true,
+                _ => false,
+            })
+            .and_then(|f| f.completion_time())
+            .unwrap();
+
+        // the switch (node 2) sends every data packet to host 1 back to back; only the first
+        // packet's trip to the switch and the last ACK's return leave it idle
+        let stats = e.link_stats();
+        let bottleneck = stats.utilization(2, 1, fct).unwrap();
+        assert!((0.98..=1.0).contains(&bottleneck), "bottleneck utilization {}", bottleneck);
+        // the reverse path only carries ACKs
+        let acks = stats.utilization(1, 2, fct).unwrap();
+        assert!(acks < 0.05, "ACK path utilization {}", acks);
+        assert_eq!(stats.link(2, 1).unwrap().pfc_bytes, 0);
+
+        // PAUSE and RESUME frames take link capacity too
+        let e = super::IndependentVictimFlowScenario::make::<PFCSwitch>(None).execute().unwrap();
+        let pfc_bytes = (0..4)
+            .filter_map(|host| e.link_stats().link(4, host))
+            .map(|b| b.pfc_bytes)
+            .sum::<u64>();
+        assert!(pfc_bytes > 0);
+        assert_eq!(pfc_bytes % 9, 0);
+    }
+
+    /// `Host`s 2 and 3 send bulk traffic in class 1 to `Host` 0, so the `PFCSwitch` PAUSEs class 1
+    /// on every incoming link. A victim flow goes from `Host` 2 to `Host` 1 in class
+    /// `victim_priority`.
+    fn per_class_pfc_victim_fct(victim_priority: u8) -> (Nanos, u32) {
+        use super::node::switch::{Queue, priority_queue::PriorityQueue};
+
+        let mut t = OneBigSwitch::<PFCSwitch>::make_topology(4, 15_000, 1_000_000, 1_000_000);
+        let queues = t.switches[0].rack.iter()
+            .map(|(q, _)| (Box::new(PriorityQueue::new(2, 15_000, q.link())) as Box<Queue>, HashSet::new()))
+            .collect();
+        t.switches[0].rack = queues;
+        let mut e = Executor::new(t, None, None);
+
+        for sender_id in 2..4 {
+            let bulk = FlowInfo::builder(sender_id, sender_id, 0)
+                .length_bytes(438000) // 300 packet flow
+                .priority(1)
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(bulk, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        }
+
+        let victim = FlowInfo::builder(0, 2, 1)
+            .length_bytes(43800) // 30 packet flow
+            .priority(victim_priority)
+            .build();
+
+        e.push(Box::new(FlowArrivalEvent(victim, 1_100_000_000, PhantomData::<ConstCwnd>)));
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+        let sender = topo.all_flows().find(|f| f.flow_info().flow_id == 0 && f.transmit_completion_time().is_some()).unwrap();
+        (sender.completion_time().unwrap(), topo.switches[0].pauses_sent)
+    }
+
+    #[test]
+    fn per_class_pfc() {
+        let (separate_fct, separate_pauses) = per_class_pfc_victim_fct(0);
+        let (shared_fct, shared_pauses) = per_class_pfc_victim_fct(1);
+        assert!(separate_pauses > 0 && shared_pauses > 0);
+        // in its own class, the victim keeps flowing while the bulk class is paused
+        assert!(separate_fct < shared_fct, "separate class {} shared class {}", separate_fct, shared_fct);
+    }
+
+    #[test]
+    fn pfc_frames_carry_switch_and_class() {
+        use super::node::switch::Queue;
+
+        let mut t = OneBigSwitch::<PFCSwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let switch_id = t.switches[0].id;
+        let data = |seq| Packet::Data{
+            hdr: PacketHeader{
+                flow: 0,
+                from: 1,
+                to: 0,
+                priority: 1,
+                size_bytes: PACKET_HEADER_SIZE_BYTES,
+            },
+            seq,
+            length: 1460,
+            ect: true,
+            ce: false,
+            sent: 0,
+            deadline: None,
+            trimmed: false,
         };
 
-        // starts at t = 1.1s
-        let flow_arrival = Box::new(FlowArrivalEvent(flow, 1_100_000_000, PhantomData::<ConstCwnd>));
-        e.push(flow_arrival);
+        // fill the queue towards host 0 past the PAUSE threshold
+        let in_link = t.hosts[1].link;
+        for i in 0..10 {
+            t.switches[0].receive(data(i * 1460), in_link, 0, None).unwrap();
+        }
 
-        let flow = FlowInfo{
-            flow_id: 1,
-            sender_id: 2,
-            dest_id: 0,
-            length_bytes: 438000, // 300 packet flow
-            max_packet_length: 1460,
+        let pause = {
+            let (q, paused_classes) = t.switches[0].rack.iter_mut().find(|(q, _)| q.link().to == 1).unwrap();
+            assert!(paused_classes.contains(&1));
+            q.dequeue().unwrap()
         };
+        assert_eq!(pause, Packet::Pause(switch_id, 1));
 
-        // starts at t = 1.0s
-        let flow_arrival = Box::new(FlowArrivalEvent(flow, 1_000_000_000, PhantomData::<ConstCwnd>));
-        e.push(flow_arrival);
+        // the host stops sending only that class
+        super::node::Node::receive(&mut t.hosts[1], pause, in_link, 0, None).unwrap();
+        assert!(t.hosts[1].paused.contains(&1));
+        super::node::Node::receive(&mut t.hosts[1], Packet::Resume(switch_id, 1), in_link, 0, None).unwrap();
+        assert!(t.hosts[1].paused.is_empty());
+
+        // a neighbor's PAUSE pauses the class on the link back to it
+        let (host_id, host_link) = (t.hosts[0].id, t.hosts[0].link);
+        t.switches[0].receive(Packet::Pause(host_id, 1), host_link, 0, None).unwrap();
+        assert!(t.switches[0].rack.iter().find(|(q, _)| q.link().to == 0).unwrap().0.is_paused());
+        t.switches[0].receive(Packet::Resume(host_id, 1), host_link, 0, None).unwrap();
+        assert!(!t.switches[0].rack.iter().find(|(q, _)| q.link().to == 0).unwrap().0.is_paused());
+    }
+
+    #[test]
+    fn pfc_resumes_several_classes_at_once() {
+        use super::node::switch::Queue;
 
-        let flow = FlowInfo{
-            flow_id: 2,
-            sender_id: 3,
-            dest_id: 0,
-            length_bytes: 438000, // 300 packet flow
-            max_packet_length: 1460,
+        let mut t = OneBigSwitch::<PFCSwitch>::make_topology(3, 15_000, 1_000_000, 1_000_000);
+        let switch_id = t.switches[0].id;
+        let data = |to, priority| Packet::Data{
+            hdr: PacketHeader{
+                flow: to,
+                from: 2,
+                to,
+                priority,
+                size_bytes: PACKET_HEADER_SIZE_BYTES,
+            },
+            seq: 0,
+            length: 1460,
+            ect: true,
+            ce: false,
+            sent: 0,
+            deadline: None,
+            trimmed: false,
         };
 
-        // starts at t = 1.0s
-        let flow_arrival = Box::new(FlowArrivalEvent(flow, 1_000_000_000, PhantomData::<ConstCwnd>));
-        e.push(flow_arrival);
+        // both classes are paused towards host 2, and one packet of each is queued elsewhere
+        let in_link = t.hosts[2].link;
+        t.switches[0].rack.iter_mut().find(|(q, _)| q.link().to == 2).unwrap().1 = vec![0, 1].into_iter().collect();
+        t.switches[0].receive(data(0, 0), in_link, 0, None).unwrap();
+        t.switches[0].receive(data(1, 1), in_link, 0, None).unwrap();
 
-        e
+        // sending them drains both classes in the same step
+        assert_eq!(t.switches[0].exec(0, None).unwrap().len(), 2);
+        let q = &mut t.switches[0].rack.iter_mut().find(|(q, _)| q.link().to == 2).unwrap().0;
+        let resumes = (0..2).map(|_| q.dequeue().unwrap()).collect::<HashSet<Packet>>();
+        assert_eq!(resumes, vec![Packet::Resume(switch_id, 0), Packet::Resume(switch_id, 1)].into_iter().collect());
     }
-}
 
-/// `shared_ingress_victim_flow_scenario()` is the difference between `IngressPFCSwitch` and `PFCSwitch`.
-/// (See `independent_victim_flow_scenario()`)
-pub struct SharedIngressVictimFlowScenario;
+    #[test]
+    fn two_stage_flow_dag() {
+        use super::flow::dag::FlowDag;
 
-impl Scenario for SharedIngressVictimFlowScenario {
-    fn make<S: Switch>(logger: Option<slog::Logger>) -> Executor<S> {
-        let t = OneBigSwitch::<S>::make_topology(4, 15_000, 1_000_000, 1_000_000);
-        let mut e = Executor::new(t, logger);
+        let t = OneBigSwitch::<LossySwitch>::make_topology(4, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
 
-        let flow = FlowInfo{
-            flow_id: 0,
-            sender_id: 2,
-            dest_id: 1,
-            length_bytes: 43800, // 30 packet flow
-            max_packet_length: 1460,
-        };
+        let flow = |flow_id, sender_id, dest_id| FlowInfo::builder(flow_id, sender_id, dest_id)
+            .length_bytes(14600) // 10 packet flow
+            .build();
 
-        // starts at t = 1.1s
-        let flow_arrival = Box::new(FlowArrivalEvent(flow, 1_100_000_000, PhantomData::<ConstCwnd>));
-        e.push(flow_arrival);
+        // stage 1 ("map"): hosts 1 and 2 send to host 0
+        // stage 2 ("shuffle"): host 0 sends to host 3 once both have finished
+        let mut dag = FlowDag::<ConstCwnd>::new();
+        dag.add_flow(flow(1, 1, 0), &[]).unwrap();
+        dag.add_flow(flow(2, 2, 0), &[]).unwrap();
+        dag.add_flow(flow(3, 0, 3), &[1, 2]).unwrap();
+        let dag = dag.schedule(&mut e, 1_000_000_000).unwrap();
 
-        let flow = FlowInfo{
-            flow_id: 1,
-            sender_id: 2,
-            dest_id: 0,
-            length_bytes: 438000, // 300 packet flow
-            max_packet_length: 1460,
-        };
+        e.execute().unwrap();
+        let dag = dag.borrow();
 
-        // starts at t = 1.0s
-        let flow_arrival = Box::new(FlowArrivalEvent(flow, 1_000_000_000, PhantomData::<ConstCwnd>));
-        e.push(flow_arrival);
+        let stage1_done = dag.completion_time(1).unwrap().max(dag.completion_time(2).unwrap());
+        assert_eq!(dag.start_time(3), Some(stage1_done));
+        let jct = dag.job_completion_time().unwrap();
+        assert_eq!(jct, dag.completion_time(3).unwrap() - 1_000_000_000);
+    }
+
+    #[test]
+    fn closed_loop_client_waits_for_response_and_think_time() {
+        use super::flow::closed_loop::ClosedLoopWorkload;
 
-        let flow = FlowInfo{
-            flow_id: 2,
-            sender_id: 3,
-            dest_id: 0,
-            length_bytes: 438000, // 300 packet flow
-            max_packet_length: 1460,
+        let t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+        let workload = ClosedLoopWorkload{
+            client: 0,
+            server: 1,
+            request_bytes: 1460, // 1 packet
+            response_bytes: 14600, // 10 packets
+            concurrency: 1,
+            mean_think_time: 50_000_000,
+            num_requests: 5,
+            first_flow_id: 0,
         };
 
-        // starts at t = 1.0s
-        let flow_arrival = Box::new(FlowArrivalEvent(flow, 1_000_000_000, PhantomData::<ConstCwnd>));
-        e.push(flow_arrival);
+        let client = workload.schedule::<ConstCwnd, LossySwitch>(&mut e, 1_000_000_000, 42).unwrap();
+        e.execute().unwrap();
+        let client = client.borrow();
+        let requests = client.requests();
 
-        e
+        assert_eq!(requests.len(), 5);
+        assert!(requests.iter().all(|r| r.responded.is_some()));
+        for r in requests {
+            assert_eq!(client.outstanding(r.issued), 1);
+        }
+
+        // each request is issued once the previous response is in and the client has thought
+        for w in requests.windows(2) {
+            let (prev, next) = (w[0], w[1]);
+            assert_eq!(next.issued, prev.responded.unwrap() + prev.think_time.unwrap());
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::marker::PhantomData;
-    use slog;
-    use super::Scenario;
-    use super::topology::{Topology, TopologyStrategy};
-    use super::topology::one_big_switch::OneBigSwitch;
-    use super::event::Executor;
-    use super::node::switch::{Switch, lossy_switch::LossySwitch, nack_switch::NackSwitch, pfc_switch::{PFCSwitch, IngressPFCSwitch}};
-    use super::packet::{Packet, PacketHeader};
-    use super::flow::{FlowArrivalEvent, FlowInfo};
-    use super::congcontrol::ConstCwnd;
+    #[test]
+    fn rpc_latency_on_idle_network() {
+        use super::flow::rpc::RpcFlowArrivalEvent;
 
-    /// Make a standard instance of `slog::Logger`.
-    fn make_logger(logfile: Option<&str>) -> slog::Logger {
-        use std::sync::Mutex;
-        use std::fs::File;
-        use slog::Drain;
-        use slog_bunyan;
-        use slog_term;
+        let t = OneBigSwitch::<LossySwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+        let request = FlowInfo::builder(0, 0, 1)
+            .length_bytes(1460) // 1 packet
+            .build();
+        e.push(Box::new(RpcFlowArrivalEvent(request, 2920, 1_000_000_000, PhantomData::<ConstCwnd>))); // 2 packet response
 
-        if let Some(fln) = logfile {
-            let f = File::create(fln).unwrap();
-            let json_drain = Mutex::new(slog_bunyan::default(f)).fuse();
-            slog::Logger::root(json_drain, o!())
-        } else {
-            let decorator = slog_term::PlainSyncDecorator::new(slog_term::TestStdoutWriter);
-            let human_drain = slog_term::FullFormat::new(decorator).build().filter_level(slog::Level::Debug).fuse();
-            slog::Logger::root(human_drain, o!())
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        assert!(topo.all_flows().all(|f| f.completion_time().is_some()));
+        let client = topo.hosts[0].active_flows.iter()
+            .find(|f| f.flow_info().flow_id == 0)
+            .unwrap();
+        match client.side() {
+            FlowSide::Sender => (),
+            FlowSide::Receiver => panic!("host 0 should be the client"),
         }
+
+        // each 1500 byte packet takes 12ms to serialize and 1ms to propagate on each of the two
+        // hops. The request is a single packet; the second response packet follows 12ms
+        // behind the first.
+        let hop = 12_000_000 + 1_000_000;
+        let request_trip = 2 * hop;
+        let response_trip = 2 * hop + 12_000_000;
+        assert_eq!(client.completion_time(), Some(request_trip + response_trip));
+        assert_eq!(client.delivered_bytes(), 2920);
     }
 
-    fn setup_test() -> Executor<LossySwitch> {
-        let t = OneBigSwitch::make_topology(2, 15_000, 1_000_000, 1_000_000);
-        Executor::new(t, None)
+    #[test]
+    fn constant_bit_rate_flow_crowds_out_tcp() {
+        use super::congcontrol::NewReno;
+        use super::flow::constant_bit_rate::ConstantBitRateFlowArrivalEvent;
+
+        let tcp = FlowInfo::builder(0, 2, 0)
+            .length_bytes(438000) // 300 packet flow
+            .build();
+        let run = |with_cbr: bool| {
+            let t = OneBigSwitch::<LossySwitch>::make_topology(3, 15_000, 1_000_000, 1_000_000);
+            let mut e = Executor::new(t, None, None);
+            e.push(Box::new(FlowArrivalEvent(tcp, 1_000_000_000, PhantomData::<NewReno>)));
+            if with_cbr {
+                // three quarters of the bottleneck, for 5 seconds
+                let cbr = FlowInfo::builder(1, 1, 0).build();
+                e.push(Box::new(ConstantBitRateFlowArrivalEvent(cbr, 1_000_000_000, 750_000, Some(6_000_000_000))));
+            }
+
+            e.execute().unwrap()
+        };
+
+        let mut alone = run(false);
+        let alone_fct = alone.components().1.hosts[2].active_flows[0].completion_time().unwrap();
+
+        let mut crowded = run(true);
+        let topo = crowded.components().1;
+        let tcp_sender = &topo.hosts[2].active_flows[0];
+        let cbr_sender = &topo.hosts[1].active_flows[0];
+        let cbr_receiver = topo.hosts[0].active_flows.iter()
+            .find(|f| f.flow_info().flow_id == 1)
+            .unwrap();
+
+        // the CBR flow keeps the bottleneck queue full, so the TCP flow loses packets and backs
+        // off...
+        let crowded_fct = tcp_sender.completion_time().unwrap();
+        assert!(tcp_sender.transmit_counts().retransmitted > 0);
+        assert!(crowded_fct > alone_fct * 3 / 2, "alone {} crowded {}", alone_fct, crowded_fct);
+
+        // ...while the CBR flow sends one 1500 byte packet every 16ms for 5 seconds regardless,
+        // and never retransmits what it loses
+        assert_eq!(cbr_sender.completion_time(), Some(5_000_000_000));
+        let counts = cbr_sender.transmit_counts();
+        assert_eq!((counts.sent, counts.retransmitted), (313, 0));
+        assert!(cbr_receiver.delivered_bytes() >= counts.sent * 1460 / 2);
     }
-    
+
     #[test]
-    fn send_one_packet() {
-        let mut e = setup_test();
+    fn coflow_completion_time() {
+        use super::stats::Coflow;
 
-        {
-            let pkt = Packet::Data{
-                hdr: PacketHeader{
-                    flow: 0,
-                    from: 0,
-                    to: 1,
-                },
-                seq: 0,
-                length: 1460,
-            };
+        let t = OneBigSwitch::<LossySwitch>::make_topology(4, 15_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
 
-            let topo = e.components().1;
-            topo.lookup_host(0).unwrap().push_pkt(pkt);
+        for (i, &len) in [4380, 14600, 43800].iter().enumerate() {
+            let flowinfo = FlowInfo::builder(i as u32, i as u32 + 1, 0)
+                .length_bytes(len)
+                .build();
+
+            e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
         }
 
-        let e = e.execute().unwrap();
-        assert_eq!(e.current_time(), 26000000);
+        let coflow = Coflow{coflow_id: 0, flow_ids: vec![0, 1, 2]};
+        assert_eq!(coflow.completion_time(e.components().1), None);
+
+        let mut e = e.execute().unwrap();
+        let topo = e.components().1;
+        let max_fct = topo.all_flows()
+            .filter(|f| f.flow_info().dest_id == 0 && f.flow_info().sender_id != 0)
+            .filter_map(|f| match f.side() {
+                FlowSide::Sender => f.completion_time(),
+                _ => None,
+            })
+            .max();
+        assert_eq!(coflow.completion_time(topo), max_fct);
     }
 
     #[test]
-    fn send_one_flow() {
+    fn reject_loopback_flow() {
         let mut e = setup_test();
 
-        let flowinfo = FlowInfo{
-            flow_id: 1,
-            sender_id: 0,
-            dest_id: 1,
-            length_bytes: 4380, // 3 packet flow
-            max_packet_length: 1460,
-        };
+        let flowinfo = FlowInfo::builder(1, 1, 1)
+            .length_bytes(4380) // 3 packet flow
+            .build();
 
-        // starts at t = 1.0s
-        let flow_arrival = Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)); 
-        e.push(flow_arrival);
-        let e = e.execute().unwrap();
-        assert_eq!(e.current_time(), 1052640000);
+        e.push(Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)));
+        match e.execute() {
+            Ok(_) => panic!("loopback flow should be rejected"),
+            Err(err) => assert!(err.to_string().contains("loopback"), "unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn reject_zero_rate_constant_bit_rate_flow() {
+        use super::flow::constant_bit_rate::ConstantBitRateFlowArrivalEvent;
+
+        let mut e = setup_test();
+
+        let flowinfo = FlowInfo::builder(1, 1, 0)
+            .length_bytes(4380) // 3 packet flow
+            .build();
+
+        e.push(Box::new(ConstantBitRateFlowArrivalEvent(flowinfo, 1_000_000_000, 0, None)));
+        match e.execute() {
+            Ok(_) => panic!("zero rate flow should be rejected"),
+            Err(err) => assert!(err.to_string().contains("rate of 0 bps"), "unexpected error: {}", err),
+        }
     }
 
     mod nack_test_switch {
@@ -235,7 +3623,7 @@ mod tests {
         use event::Event;
         use node::{Link};
         use packet::{Packet, PacketHeader};
-        use node::switch::{Switch, Queue, nack_switch::NackSwitch};
+        use node::switch::{Switch, SwitchStats, Queue, nack_switch::NackSwitch};
         use slog;
 
         #[derive(Default, Debug)]
@@ -273,6 +3661,8 @@ mod tests {
                                     flow: hdr.flow,
                                     from: hdr.to,
                                     to: hdr.from,
+                                    priority: hdr.priority,
+                                    size_bytes: hdr.size_bytes,
                                 },
                                 nacked_seq: seq,
                             };
@@ -303,28 +3693,44 @@ mod tests {
                 self.0.exec(time, logger)
             }
             
-            fn reactivate(&mut self, l: Link) {
+            fn reactivate(&mut self, l: Link) -> Result<()> {
                 self.0.reactivate(l)
             }
 
             fn is_active(&self) -> bool {
                 self.0.is_active()
             }
+
+            fn add_route(&mut self, dest: u32, next_hop: u32) {
+                self.0.add_route(dest, next_hop)
+            }
+
+            fn egress_link(&self, hdr: PacketHeader) -> Option<Link> {
+                self.0.egress_link(hdr)
+            }
+
+            fn queue_occupancy(&self, to: u32) -> Option<u32> {
+                self.0.queue_occupancy(to)
+            }
+
+            fn set_cut_through_threshold(&mut self, to: u32, threshold: Option<u32>) {
+                self.0.set_cut_through_threshold(to, threshold)
+            }
+
+            fn take_stats(&mut self) -> SwitchStats {
+                self.0.take_stats()
+            }
         }
     }
 
     #[test]
     fn one_flow_with_nack() {
         let t = OneBigSwitch::<nack_test_switch::NackTestSwitch>::make_topology(2, 15_000, 1_000_000, 1_000_000);
-        let mut e = Executor::new(t, make_logger(None));
+        let mut e = Executor::new(t, make_logger(None), None);
 
-        let flowinfo = FlowInfo{
-            flow_id: 1,
-            sender_id: 0,
-            dest_id: 1,
-            length_bytes: 14600, // 10 packet flow
-            max_packet_length: 1460,
-        };
+        let flowinfo = FlowInfo::builder(1, 0, 1)
+            .length_bytes(14600) // 10 packet flow
+            .build();
 
         // starts at t = 1.0s
         let flow_arrival = Box::new(FlowArrivalEvent(flowinfo, 1_000_000_000, PhantomData::<ConstCwnd>)); 
@@ -370,23 +3776,15 @@ mod tests {
     }
 
     fn two_flows_scenario<S: Switch>(t: Topology<S>) {
-        let mut e = Executor::new(t, make_logger(None));
+        let mut e = Executor::new(t, make_logger(None), None);
 
-        let flow1 = FlowInfo{
-            flow_id: 1,
-            sender_id: 1,
-            dest_id: 0,
-            length_bytes: 43800, // 30 packet flow
-            max_packet_length: 1460,
-        };
+        let flow1 = FlowInfo::builder(1, 1, 0)
+            .length_bytes(43800) // 30 packet flow
+            .build();
         
-        let flow2 = FlowInfo{
-            flow_id: 2,
-            sender_id: 2,
-            dest_id: 0,
-            length_bytes: 43800, // 30 packet flow
-            max_packet_length: 1460,
-        };
+        let flow2 = FlowInfo::builder(2, 2, 0)
+            .length_bytes(43800) // 30 packet flow
+            .build();
 
         // starts at t = 1.0s
         let flow_arrival = Box::new(FlowArrivalEvent(flow1, 1_000_000_000, PhantomData::<ConstCwnd>)); 
@@ -429,9 +3827,172 @@ mod tests {
         assert!(e.components().1.all_flows().all(|f| f.completion_time().is_some()));
     }
 
+    #[test]
+    fn convergence_to_fair_share() {
+        type C = super::ConvergenceScenario<ConstCwnd>;
+        let (e, trace) = C::make_with_trace::<LossySwitch>(None);
+        let mut e = e.execute().unwrap();
+        assert!(e.components().1.all_flows().all(|f| f.completion_time().is_some()));
+
+        // one second after the last flow starts, all three share the 1Mbps bottleneck
+        let from = C::FIRST_START + 3 * C::START_GAP;
+        let to = from + 1_000_000_000;
+        let fair_share_bytes = 1_000_000 / 8 / C::NUM_FLOWS as u64;
+        for flow_id in 0..C::NUM_FLOWS {
+            let got = trace.bytes_between(flow_id, from, to);
+            assert!(
+                (got as f64 - fair_share_bytes as f64).abs() < 0.1 * fair_share_bytes as f64,
+                "flow {} got {} bytes, fair share {}", flow_id, got, fair_share_bytes,
+            );
+        }
+    }
+
+    /// Two 300 packet flows from hosts 1 and 2 to host 0, the second starting 200ms after the
+    /// first. Returns their FCTs, and, with `sample`, their throughput every 100ms.
+    fn two_flow_throughput(sample: bool) -> (Vec<Nanos>, super::stats::ThroughputTrace) {
+        use super::stats::{ThroughputSampleEvent, ThroughputTrace};
+
+        let t = OneBigSwitch::<LossySwitch>::make_topology(3, 150_000, 1_000_000, 1_000_000);
+        let mut e = Executor::new(t, None, None);
+        for i in 1..3 {
+            let flowinfo = FlowInfo::builder(i, i, 0)
+                .length_bytes(438000) // 300 packet flow
+                .build();
+
+            let arrival = 1_000_000_000 + (i as Nanos - 1) * 200_000_000;
+            e.push(Box::new(FlowArrivalEvent(flowinfo, arrival, PhantomData::<ConstCwnd>)));
+        }
+
+        let trace = ThroughputTrace::default();
+        if sample {
+            e.push(Box::new(ThroughputSampleEvent::new(1_000_000_000, 100_000_000, 30_000_000_000, vec![0], trace.clone())));
+        }
+
+        let mut e = e.execute().unwrap();
+        let fcts = e.components().1.all_flows()
+            .map(|f| f.completion_time().unwrap())
+            .collect();
+        (fcts, trace)
+    }
+
+    #[test]
+    fn aggregate_throughput_fills_link() {
+        let (fcts, trace) = two_flow_throughput(true);
+        // sampling only reads flow state
+        assert_eq!(fcts, two_flow_throughput(false).0);
+
+        // both flows are active from 1.2s until the first completes, after about 7s; each 100ms
+        // delivers 8 or 9 packets' worth of the 1Mbps link's 1460/1500 goodput
+        let goodput = 1_000_000.0 * 1460.0 / 1500.0;
+        let both_active = trace.aggregate_samples()
+            .into_iter()
+            .filter(|&(t, _)| t > 1_300_000_000 && t <= 6_000_000_000)
+            .map(|(_, bps)| bps)
+            .collect::<Vec<f64>>();
+        assert_eq!(both_active.len(), 47);
+        for bps in &both_active {
+            assert!((bps - goodput).abs() < 0.1 * goodput, "{} bps", bps);
+        }
+
+        let mean = both_active.iter().sum::<f64>() / both_active.len() as f64;
+        assert!((mean - goodput).abs() < 0.02 * goodput, "mean {} bps", mean);
+    }
+
     fn victim_flow_scenario<S: Switch>(logger: Option<slog::Logger>) {
         let e = super::IndependentVictimFlowScenario::make::<S>(logger);
         let mut e = e.execute().unwrap();
         assert!(e.components().1.all_flows().all(|f| f.completion_time().is_some()));
     }
+
+    /// Build a random topology of `S`s, each passed to `configure` along with the queue length,
+    /// and a random workload from `seed`, and run it for at most `FUZZ_MAX_EVENTS` events.
+    /// Returns why it stopped, or `None` if it stopped with an error, which is a pass too.
+    fn run_random_scenario<S: Switch>(
+        seed: u64,
+        configure: impl Fn(&mut S, u32),
+    ) -> Option<super::event::TerminationReason> {
+        use super::congcontrol::{Dctcp, NewReno};
+        use super::flow::workload::{FlowSizeDist, PoissonWorkload};
+        use super::rng::Rng;
+        use super::topology::pod::Pod;
+
+        let mut rng = Rng::new(seed);
+        let mut uniform = |lo: u64, hi: u64| lo + (rng.next_f64() * (hi - lo) as f64) as u64;
+
+        let queue_length_bytes = uniform(15_000, 150_000) as u32;
+        let bandwidth_bps = [1_000_000, 1_000_000_000, 10_000_000_000][uniform(0, 3) as usize];
+        let propagation_delay = uniform(1_000, 1_000_000);
+        let mut t = if uniform(0, 2) == 0 {
+            OneBigSwitch::<S>::make_topology(uniform(2, 9) as u32, queue_length_bytes, bandwidth_bps, propagation_delay)
+        } else {
+            Pod::<S>::make_pod(uniform(1, 5) as u32, uniform(2, 5) as u32, queue_length_bytes, bandwidth_bps, propagation_delay)
+        };
+
+        for s in &mut t.switches {
+            configure(s, queue_length_bytes);
+        }
+
+        let hosts = t.hosts.iter().map(|h| h.id).collect::<Vec<u32>>();
+        let mut e = Executor::new(t, None, None);
+        let workload = PoissonWorkload{
+            senders: hosts.clone(),
+            dests: hosts,
+            mean_interarrival: uniform(1_000, 10_000_000),
+            flow_sizes: FlowSizeDist::Constant(uniform(1, 100_000) as u32),
+        };
+
+        let num_flows = uniform(1, 20) as u32;
+        match uniform(0, 3) {
+            0 => workload.schedule::<ConstCwnd, S>(&mut e, num_flows, 1_000_000_000, seed),
+            1 => workload.schedule::<NewReno, S>(&mut e, num_flows, 1_000_000_000, seed),
+            _ => workload.schedule::<Dctcp, S>(&mut e, num_flows, 1_000_000_000, seed),
+        }
+
+        e.execute_events(FUZZ_MAX_EVENTS).ok().map(|(_, reason)| reason)
+    }
+
+    /// More events than any of `run_random_scenario`'s workloads needs to finish.
+    const FUZZ_MAX_EVENTS: u64 = 1_000_000;
+
+    #[test]
+    fn random_scenarios_never_panic_or_hang() {
+        use super::event::TerminationReason;
+        use super::node::switch::Queue;
+        use super::node::switch::edf_queue::EdfQueue;
+        use super::node::switch::red::RedParams;
+        use super::node::switch::shared_buffer_switch::SharedBufferSwitch;
+        use super::node::switch::shared_medium::SharedMedium;
+        use super::node::switch::wfq_queue::WfqQueue;
+
+        fn nothing<S>(_: &mut S, _: u32) {}
+
+        for seed in 1..41 {
+            let reason = match seed % 10 {
+                0 => run_random_scenario::<LossySwitch>(seed, nothing),
+                1 => run_random_scenario::<NackSwitch>(seed, nothing),
+                2 => run_random_scenario::<PFCSwitch>(seed, nothing),
+                3 => run_random_scenario::<CreditSwitch>(seed, nothing),
+                4 => run_random_scenario::<SharedBufferSwitch>(seed, nothing),
+                5 => run_random_scenario::<TrimmingSwitch>(seed, nothing),
+                6 => run_random_scenario::<SharedMedium>(seed, nothing),
+                7 => run_random_scenario::<LossySwitch>(seed, |s, queue_length_bytes| s.set_red(RedParams{
+                    min_threshold_bytes: queue_length_bytes / 4,
+                    max_threshold_bytes: queue_length_bytes / 2,
+                    max_probability: 0.5,
+                })),
+                8 => run_random_scenario::<LossySwitch>(seed, |s, queue_length_bytes| {
+                    s.rack = s.rack.iter()
+                        .map(|q| Box::new(EdfQueue::new(queue_length_bytes, q.link())) as Box<Queue>)
+                        .collect();
+                }),
+                _ => run_random_scenario::<LossySwitch>(seed, |s, queue_length_bytes| {
+                    s.rack = s.rack.iter()
+                        .map(|q| Box::new(WfqQueue::new(queue_length_bytes, q.link())) as Box<Queue>)
+                        .collect();
+                }),
+            };
+
+            assert!(reason != Some(TerminationReason::EventLimit), "seed {} did not finish", seed);
+        }
+    }
 }
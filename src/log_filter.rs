@@ -0,0 +1,160 @@
+use std::fmt;
+
+use slog;
+
+/// Tag of the per-packet lines nodes, switches and flows log: arrivals, transmissions, queue
+/// changes, drops, marks, window updates. They make up nearly all of a large run's trace.
+/// Log such lines with `debug!(log, #PACKET, "rx"; ...)` so `LogFilter::packets` can drop them.
+pub const PACKET: &str = "packet";
+
+/// Picks which lines reach the logger by what kind of line they are, which module logged them,
+/// and which flow they are about, rather than by level alone. Pass one to `Executor::new`.
+///
+/// The default passes every line.
+#[derive(Clone, Debug)]
+pub struct LogFilter {
+    /// Pass lines at or above this level from modules without an entry in `modules`.
+    pub level: slog::Level,
+    /// Levels for particular modules, by path within the crate, e.g.
+    /// `("node::switch".to_string(), Level::Info)` for every switch. The longest matching path
+    /// applies.
+    pub modules: Vec<(String, slog::Level)>,
+    /// Whether to pass per-packet lines (those tagged `PACKET`).
+    pub packets: bool,
+    /// Only pass lines with these messages, e.g. "flow completed". `None` passes every message.
+    pub only_messages: Option<Vec<String>>,
+    /// Only pass lines about these flows: those logged with a flow's logger (see
+    /// `flow::flow_logger`) or with a "flow" field. `None` passes every line.
+    pub only_flows: Option<Vec<u32>>,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        LogFilter{
+            level: slog::Level::Trace,
+            modules: vec![],
+            packets: true,
+            only_messages: None,
+            only_flows: None,
+        }
+    }
+}
+
+impl LogFilter {
+    /// Everything but the per-packet lines.
+    pub fn without_packet_lines() -> Self {
+        LogFilter{
+            packets: false,
+            ..Default::default()
+        }
+    }
+
+    /// A logger which passes the lines this filter allows on to `logger`.
+    pub fn apply(&self, logger: slog::Logger) -> slog::Logger {
+        slog::Logger::root(FilterDrain{
+            filter: self.clone(),
+            inner: logger,
+        }, o!())
+    }
+
+    fn level_of(&self, module: &'static str) -> slog::Level {
+        // module paths start with the crate's name
+        let path = module.splitn(2, "::").nth(1).unwrap_or("");
+        self.modules.iter()
+            .filter(|&&(ref m, _)| path == m.as_str() || (path.starts_with(m.as_str()) && path[m.len()..].starts_with("::")))
+            .max_by_key(|&(ref m, _)| m.len())
+            .map_or(self.level, |&(_, level)| level)
+    }
+}
+
+struct FilterDrain {
+    filter: LogFilter,
+    inner: slog::Logger,
+}
+
+impl FilterDrain {
+    fn allows(&self, record: &slog::Record, values: &slog::OwnedKVList) -> bool {
+        let filter = &self.filter;
+        if !filter.packets && record.tag() == PACKET {
+            return false;
+        }
+
+        if !record.level().is_at_least(filter.level_of(record.module())) {
+            return false;
+        }
+
+        if let Some(ref only) = filter.only_messages {
+            if !only.iter().any(|m| formats_to(record.msg(), m)) {
+                return false;
+            }
+        }
+
+        match filter.only_flows {
+            Some(ref flows) => flow_of(record, values).map_or(false, |f| flows.contains(&f)),
+            None => true,
+        }
+    }
+}
+
+impl slog::Drain for FilterDrain {
+    type Ok = ();
+    type Err = slog::Never;
+
+    fn log(&self, record: &slog::Record, values: &slog::OwnedKVList) -> ::std::result::Result<(), slog::Never> {
+        if self.allows(record, values) {
+            slog::Drain::log(&self.inner, record, values)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether `args` formats to exactly `s`, compared piece by piece as it is formatted rather than
+/// by formatting it into a new `String`.
+fn formats_to(args: &fmt::Arguments, s: &str) -> bool {
+    struct Rest<'a>(&'a str);
+
+    impl<'a> fmt::Write for Rest<'a> {
+        fn write_str(&mut self, piece: &str) -> fmt::Result {
+            if !self.0.starts_with(piece) {
+                return Err(fmt::Error);
+            }
+
+            self.0 = &self.0[piece.len()..];
+            Ok(())
+        }
+    }
+
+    let mut rest = Rest(s);
+    fmt::write(&mut rest, *args).is_ok() && rest.0.is_empty()
+}
+
+/// The flow a line is about, from its fields or its logger's context.
+fn flow_of(record: &slog::Record, values: &slog::OwnedKVList) -> Option<u32> {
+    use slog::KV;
+
+    let mut flow = FlowField(None);
+    record.kv().serialize(record, &mut flow).ok()?;
+    if flow.0.is_none() {
+        values.serialize(record, &mut flow).ok()?;
+    }
+
+    flow.0
+}
+
+/// Picks out a `u32` "flow" field; flow ids are always logged as one.
+struct FlowField(Option<u32>);
+
+impl slog::Serializer for FlowField {
+    fn emit_u32(&mut self, key: slog::Key, val: u32) -> slog::Result {
+        if self.0.is_none() && key == "flow" {
+            self.0 = Some(val);
+        }
+
+        Ok(())
+    }
+
+    fn emit_arguments(&mut self, _: slog::Key, _: &fmt::Arguments) -> slog::Result {
+        Ok(())
+    }
+}
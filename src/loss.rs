@@ -0,0 +1,177 @@
+use std::fmt::Debug;
+
+use checkpoint::AsAny;
+use packet::Packet;
+use rng::Rng;
+
+/// What a `LossModel` knows about where a packet is about to be enqueued.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LossContext {
+    pub occupancy_bytes: u32,
+    pub limit_bytes: u32,
+}
+
+/// What a link's `LossModel` sees: a link has no buffer, so nothing overflows.
+pub const LINK_LOSS_CONTEXT: LossContext = LossContext{occupancy_bytes: 0, limit_bytes: ::std::u32::MAX};
+
+/// Decides, packet by packet, whether a queue drops an arriving packet, or a link loses one in
+/// flight.
+pub trait LossModel: Debug + AsAny {
+    fn should_drop(&mut self, pkt: &Packet, ctx: &LossContext) -> bool;
+}
+
+/// Never drops.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct NoLoss;
+
+impl LossModel for NoLoss {
+    fn should_drop(&mut self, _: &Packet, _: &LossContext) -> bool {
+        false
+    }
+}
+
+/// Drops packets which do not fit in the queue's remaining buffer.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct QueueOverflow;
+
+impl LossModel for QueueOverflow {
+    fn should_drop(&mut self, pkt: &Packet, ctx: &LossContext) -> bool {
+        ctx.occupancy_bytes + pkt.get_size_bytes() > ctx.limit_bytes
+    }
+}
+
+/// Drops each packet independently with probability `rate`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FixedRateLoss {
+    rate: f64,
+    rng: Rng,
+}
+
+impl FixedRateLoss {
+    pub fn new(rate: f64, seed: u64) -> Self {
+        FixedRateLoss{
+            rate,
+            rng: Rng::new(seed),
+        }
+    }
+}
+
+impl LossModel for FixedRateLoss {
+    fn should_drop(&mut self, _: &Packet, _: &LossContext) -> bool {
+        self.rng.next_f64() < self.rate
+    }
+}
+
+/// Gilbert-Elliott bursty loss: a two-state Markov chain, stepped once per packet, which moves
+/// from the good to the bad state with probability `p` and back with probability `r`.
+/// Packets are dropped with probability `loss_good` or `loss_bad` depending on the state.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GilbertElliott {
+    p: f64,
+    r: f64,
+    loss_good: f64,
+    loss_bad: f64,
+    bad: bool,
+    rng: Rng,
+}
+
+impl GilbertElliott {
+    pub fn new(p: f64, r: f64, loss_good: f64, loss_bad: f64, seed: u64) -> Self {
+        GilbertElliott{
+            p,
+            r,
+            loss_good,
+            loss_bad,
+            bad: false,
+            rng: Rng::new(seed),
+        }
+    }
+
+    pub fn in_bad_state(&self) -> bool {
+        self.bad
+    }
+}
+
+impl LossModel for GilbertElliott {
+    fn should_drop(&mut self, _: &Packet, _: &LossContext) -> bool {
+        let transition = if self.bad { self.r } else { self.p };
+        if self.rng.next_f64() < transition {
+            self.bad = !self.bad;
+        }
+
+        let loss = if self.bad { self.loss_bad } else { self.loss_good };
+        self.rng.next_f64() < loss
+    }
+}
+
+/// Drops a packet if any of its models does. Every model sees every packet, so stateful models
+/// keep stepping.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AnyLoss(pub Vec<Box<LossModel>>);
+
+impl LossModel for AnyLoss {
+    fn should_drop(&mut self, pkt: &Packet, ctx: &LossContext) -> bool {
+        self.0.iter_mut()
+            .map(|m| m.should_drop(pkt, ctx))
+            .fold(false, |dropped, d| dropped || d)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use packet::{Packet, PacketHeader, PACKET_HEADER_SIZE_BYTES};
+    use super::{AnyLoss, FixedRateLoss, GilbertElliott, LossContext, LossModel, QueueOverflow};
+
+    fn pkt() -> Packet {
+        Packet::Data{
+            hdr: PacketHeader{
+                flow: 0,
+                from: 0,
+                to: 1,
+                priority: 0,
+                size_bytes: PACKET_HEADER_SIZE_BYTES,
+            },
+            seq: 0,
+            length: 1460,
+            ect: true,
+            ce: false,
+            sent: 0,
+            deadline: None,
+            trimmed: false,
+        }
+    }
+
+    const CTX: LossContext = LossContext{occupancy_bytes: 0, limit_bytes: 15_000};
+
+    #[test]
+    fn gilbert_elliott_bursts() {
+        let mut m = GilbertElliott::new(0.01, 0.3, 0.0, 1.0, 42);
+        let losses = (0..100_000).map(|_| m.should_drop(&pkt(), &CTX)).collect::<Vec<bool>>();
+
+        // stationary loss rate is p / (p + r), about 3%
+        let loss_rate = losses.iter().filter(|&&l| l).count() as f64 / losses.len() as f64;
+        assert!(loss_rate > 0.02 && loss_rate < 0.05, "{}", loss_rate);
+
+        // but a loss is usually followed by another: P(loss | loss) = 1 - r
+        let after_loss = losses.windows(2).filter(|w| w[0]).collect::<Vec<_>>();
+        let repeat_rate = after_loss.iter().filter(|w| w[1]).count() as f64 / after_loss.len() as f64;
+        assert!(repeat_rate > 0.6 && repeat_rate < 0.8, "{}", repeat_rate);
+    }
+
+    #[test]
+    fn fixed_rate_is_independent() {
+        let mut m = FixedRateLoss::new(0.1, 42);
+        let losses = (0..100_000).map(|_| m.should_drop(&pkt(), &CTX)).collect::<Vec<bool>>();
+
+        let after_loss = losses.windows(2).filter(|w| w[0]).collect::<Vec<_>>();
+        let repeat_rate = after_loss.iter().filter(|w| w[1]).count() as f64 / after_loss.len() as f64;
+        assert!(repeat_rate > 0.08 && repeat_rate < 0.12, "{}", repeat_rate);
+    }
+
+    #[test]
+    fn composed_overflow() {
+        let mut m = AnyLoss(vec![Box::new(QueueOverflow), Box::new(FixedRateLoss::new(0.0, 42))]);
+        assert!(!m.should_drop(&pkt(), &CTX));
+        assert!(m.should_drop(&pkt(), &LossContext{occupancy_bytes: 14_000, limit_bytes: 15_000}));
+    }
+}
@@ -1,14 +1,17 @@
 use std::vec::Vec;
-use std::collections::VecDeque;
+use std::cell::RefCell;
+use std::collections::{HashSet, VecDeque};
 use std::fmt::Debug;
+use std::rc::Rc;
 
 use slog;
 
 use super::{Nanos, Result};
-use super::packet::Packet;
+use super::packet::{Packet, PACKET_HEADER_SIZE_BYTES};
 use super::event::{Event, EventTime};
+use super::log_filter::PACKET;
 
-use super::flow::Flow;
+use super::flow::{Flow, FlowCompletionListener, FlowSide, FlowTimerEvent, flow_logger};
 
 pub mod switch;
 
@@ -16,18 +19,37 @@ pub mod switch;
 pub trait Node : Debug {
     fn id(&self) -> u32;
     fn receive(&mut self, p: Packet, l: Link, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>>;
+    /// Handle a packet whose processing delay has elapsed (see `ProcessingDoneEvent`).
+    fn receive_processed(&mut self, p: Packet, l: Link, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>>;
     fn exec(&mut self, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>>;
-    fn reactivate(&mut self, l: Link);
+    fn reactivate(&mut self, l: Link) -> Result<()>;
+    /// Node `at` dropped packet `p`, which this node sent to it.
+    fn packet_dropped(&mut self, at: u32, p: Packet, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>>;
     fn flow_arrival(&mut self, f: Box<Flow>);
+    /// The timer of `flow_id`, which has an endpoint at this node, fired.
+    fn flow_timer(&mut self, flow_id: u32, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>>;
     fn is_active(&self) -> bool;
+    /// The largest frame this node can transmit.
+    fn mtu(&self) -> u32;
+    /// Bytes waiting to be sent towards neighbor `to`, if this node links to it.
+    fn queue_occupancy(&self, to: u32) -> Option<u32>;
+    /// A switch's statistics since the last call, which are reset (see `Switch::take_stats`).
+    /// `None` for hosts.
+    fn take_switch_stats(&mut self) -> Option<switch::SwitchStats>;
+    /// The flows with an endpoint at this node.
+    fn flows(&self) -> &[Box<Flow>];
 }
 
 /// Links are unidirectional
-#[derive(Clone, Copy, Default, Debug, PartialEq)]
+#[derive(Clone, Copy, Default, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Link {
     pub propagation_delay: Nanos,
     pub bandwidth_bps: u64,
+    pub mtu: u32,
     pub pfc_enabled: bool,
+    /// If set, the node at `to` cuts through packets of at least this many bytes: it starts
+    /// forwarding once their header arrives instead of storing the whole packet first.
+    pub cut_through_threshold: Option<u32>,
     pub from: u32,
     pub to: u32,
 }
@@ -41,34 +63,86 @@ impl Link {
     //  = propagation_delay * link_bw
     // 2. Delay in transmitting PAUSE packet (just transmitted first byte of packet before
     //    PAUSE)
-    //  = 1 MTU = 1500 Bytes
+    //  = 1 MTU
     // 3. Extra transmission at sender (just transmitted first byte as PAUSE received)
-    //  = 1 MTU = 1500 Bytes
+    //  = 1 MTU
     //
     // to disable PFC (allow drops): return 0
-    fn pfc_pause_threshold(&self) -> u32 {
+    pub fn pfc_pause_threshold(&self) -> u32 {
         if !self.pfc_enabled {
             0
         } else {
             let bdp = self.propagation_delay * self.bandwidth_bps / 1_000_000_000 / 8; // bytes
-            bdp as u32 + 1500 + 1500
+            bdp as u32 + self.mtu + self.mtu
         }
     }
 
+    /// How many bytes of `p` the next hop must receive before it can start forwarding it.
+    fn bytes_before_forwarding(&self, p: &Packet) -> u32 {
+        let size = p.get_size_bytes();
+        let header = match *p {
+            Packet::Data{hdr, ..} |
+            Packet::Ack{hdr, ..} |
+            Packet::Nack{hdr, ..} |
+            Packet::Grant{hdr, ..} => hdr.get_size_bytes(),
+            _ => PACKET_HEADER_SIZE_BYTES,
+        };
+
+        match self.cut_through_threshold {
+            Some(threshold) if size >= threshold => ::std::cmp::min(header, size),
+            _ => size,
+        }
+    }
+
+    /// How long it takes to put `bytes` onto this link.
+    pub fn transmission_delay(&self, bytes: u32) -> Nanos {
+        bytes as u64
+            * 8 // to bits
+            * 1_000_000_000 // to ns * bits / sec
+            / self.bandwidth_bps // to ns
+    }
+
     // resume once there are 2 MTUs of space before the PFC threshold 
     fn pfc_resume_threshold(&self) -> u32 {
-        self.pfc_pause_threshold() + 2 * 1500
+        self.pfc_resume_threshold_with_hysteresis(2 * self.mtu)
+    }
+
+    // resume once there are `hysteresis` bytes of space before the PFC threshold.
+    // too little hysteresis causes rapid PAUSE/RESUME flapping.
+    fn pfc_resume_threshold_with_hysteresis(&self, hysteresis: u32) -> u32 {
+        self.pfc_pause_threshold().saturating_add(hysteresis)
     }
 }
 
-#[derive(Default, Debug)]
+/// How many of the latest drops of its packets a `Host` keeps in `downstream_drops`.
+pub const DOWNSTREAM_DROP_SAMPLES: usize = 1024;
+
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct Host {
     pub id: u32,
     pub active: bool,
-    pub paused: bool,
+    pub paused: HashSet<u8>, // traffic classes the next hop has PAUSEd
+    /// Bytes the next hop has granted with `Credit`s and the host has not yet used.
+    /// `None` until the first `Credit` arrives, i.e. unless the next hop is a `CreditSwitch`.
+    pub credit: Option<u32>,
     pub link: Link, // host does not need a Queue locally since it controls its own packet transmissions
+    /// The link is still serializing the last packet sent; `reactivate` frees it.
+    /// All of the host's flows share the link, so none may send until then.
+    pub transmitting: bool,
     pub active_flows: Vec<Box<Flow>>,
     pub to_send: VecDeque<Packet>,
+    /// `Mirror` copies received from a mirror port, if this host is an analyzer.
+    pub mirrored: Vec<Packet>,
+    /// The latest `DOWNSTREAM_DROP_SAMPLES` packets this host sent which were dropped by the next
+    /// hop, and when.
+    pub downstream_drops: VecDeque<(Nanos, Packet)>,
+    /// How many packets this host sent were dropped by the next hop over the whole run.
+    pub downstream_drop_count: u32,
+    /// Notified whenever a flow sent from this host completes.
+    #[serde(skip)]
+    pub completion_listeners: Vec<Rc<RefCell<FlowCompletionListener>>>,
+    /// When the earliest pending `HostWakeEvent` fires, if any.
+    pub next_wakeup: Option<Nanos>,
 }
 
 impl Host {
@@ -85,24 +159,30 @@ impl Node for Host {
     fn receive(
         &mut self, 
         p: Packet, 
-        _l: Link, 
+        l: Link, 
         time: Nanos, 
         logger: Option<&slog::Logger>,
     ) -> Result<Vec<Box<Event>>> {
+        // log on behalf of the packet's flow, if it has an endpoint here
+        let flow_log = packet_flow_logger(&self.active_flows, &p, logger);
+        let logger = flow_log.as_ref().or(logger);
         if let Some(log) = logger {
-            debug!(log, "rx";
+            debug!(log, #PACKET, "rx";
                 "time" => time,
                 "node" => self.id,
+                "from" => l.from,
                 "packet" => ?p,
             );
         }
         let active_flows = &mut self.active_flows;
         let pkts_to_send = &mut self.to_send;
         let was_empty = pkts_to_send.is_empty();
+        let mut completed = None;
         match p.clone() {
-            Packet::Data{hdr, ..} | Packet::Ack{hdr, ..} | Packet::Nack{hdr, ..} => {
+            Packet::Data{hdr, ..} | Packet::Ack{hdr, ..} | Packet::Nack{hdr, ..} | Packet::Grant{hdr, ..} => {
                 let flow_id = hdr.flow;
                 if let Some(f) = active_flows.iter_mut().find(|f| f.flow_info().flow_id == flow_id) {
+                    let was_complete = f.completion_time().is_some();
                     f.receive(time, p, logger).map(|(pkts, should_clear)| { 
                         if should_clear {
                             pkts_to_send.retain(|p| match p {
@@ -114,6 +194,10 @@ impl Node for Host {
                         pkts_to_send.extend(pkts); 
                     })?;
 
+                    if let (FlowSide::Sender, false, Some(_)) = (f.side(), was_complete, f.completion_time()) {
+                        completed = Some(f.flow_info());
+                    }
+
                     if was_empty {
                         self.active = true;
                     }
@@ -123,43 +207,85 @@ impl Node for Host {
                     );
                 }
             }
-            Packet::Pause(_, _) => {
-                self.paused = true;
+            Packet::Mirror{..} => {
+                // mirrored copies are only recorded; they must not touch flow state
+                self.mirrored.push(p);
+            }
+            Packet::Pause(_, class) => {
+                self.paused.insert(class as u8);
                 if let Some(log) = logger {
-                    debug!(log, "pausing";
+                    debug!(log, #PACKET, "pausing";
                         "node" => self.id,
+                        "class" => class,
                     );
                 }
             }
-            Packet::Resume(_, _) => {
-                self.paused = false;
+            Packet::Resume(_, class) => {
+                self.paused.remove(&(class as u8));
                 if let Some(log) = logger {
-                    debug!(log, "resuming";
+                    debug!(log, #PACKET, "resuming";
                         "node" => self.id,
+                        "class" => class,
+                    );
+                }
+            }
+            Packet::Credit(_, bytes) => {
+                let credit = self.credit.unwrap_or(0) + bytes;
+                self.credit = Some(credit);
+                // packets may be waiting for this credit
+                self.active = true;
+                if let Some(log) = logger {
+                    debug!(log, #PACKET, "credit";
+                        "node" => self.id,
+                        "bytes" => bytes,
+                        "credit" => credit,
                     );
                 }
             }
         }
 
-        Ok(vec![])
+        Ok(completed.map_or_else(Vec::new, |fi| {
+            self.completion_listeners.iter()
+                .flat_map(|l| l.borrow_mut().on_completion(fi, time))
+                .collect()
+        }))
+    }
+
+    fn receive_processed(
+        &mut self, 
+        p: Packet, 
+        l: Link, 
+        time: Nanos, 
+        logger: Option<&slog::Logger>,
+    ) -> Result<Vec<Box<Event>>> {
+        // hosts have no processing delay
+        self.receive(p, l, time, logger)
     }
 
     fn exec(&mut self, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        if self.transmitting {
+            // woken up by a packet, flow, or timer while the link is busy; reactivate() will
+            // wake the host again once it is free
+            self.active = false;
+            return Ok(vec![]);
+        }
+
         let flows = &mut self.active_flows;
         let active = &mut self.active;
         let link = self.link;
         let id = self.id;
 
-        if self.paused { 
-            return Ok(vec![]);
-        }
-
+        let paused = &self.paused;
         let pkts = &mut self.to_send;
         let (new_pkts, flows_to_clear): (Vec<_>, Vec<_>) = flows.iter_mut()
+            // flows in paused classes could not send anyway
+            .filter(|f| !paused.contains(&f.flow_info().priority))
             .map(|f| {
-                let (ps, should_clear) = f.exec(time, logger).unwrap();
-                (ps, (f.flow_info().flow_id, should_clear))
+                let (ps, should_clear) = f.exec(time, flow_logger(logger, &**f).as_ref())?;
+                Ok((ps, (f.flow_info().flow_id, should_clear)))
             })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
             .unzip();
         for fid in flows_to_clear.into_iter()
             .filter(|(_, clr)| *clr)
@@ -172,25 +298,97 @@ impl Node for Host {
 
         let new_pkts = new_pkts.into_iter().flat_map(|ps| ps);
         pkts.extend(new_pkts);
+
+        // flows holding packets back, e.g. paced ones, need the host to poll them again later
+        let next_wakeup = &mut self.next_wakeup;
+        let wakeup = flows.iter()
+            .filter_map(|f| f.next_send_time())
+            .filter(|&t| t > time)
+            .min()
+            .and_then(|t| if next_wakeup.map_or(true, |w| w <= time || t < w) {
+                *next_wakeup = Some(t);
+                Some(Box::new(HostWakeEvent(id, t - time)) as Box<Event>)
+            } else {
+                // an earlier wakeup is already pending
+                None
+            });
+
+        let credit = &mut self.credit;
+        let next = match pkts.iter().position(|p| !paused.contains(&p.priority())) {
+            // wait for the next hop to grant enough credit; its next `Credit` wakes the host
+            Some(i) if credit.map_or(false, |c| pkts[i].get_size_bytes() > c) => {
+                *active = false;
+                return Ok(wakeup.into_iter().collect());
+            }
+            Some(i) => pkts.remove(i),
+            // stay active to send the waiting packets once resumed
+            None if !paused.is_empty() => return Ok(wakeup.into_iter().collect()),
+            None => None,
+        };
+
         *active = false;
-        pkts.pop_front().map_or_else(|| {
-            Err(format_err!("no more pending outgoing packets"))
-        }, |pkt| {
-            if let Some(log) = logger {
-                debug!(log, "tx";
+        let transmitting = &mut self.transmitting;
+        next.map_or_else(|| {
+            // nothing to send: idle until a packet, flow, or timer wakes the host again
+            Ok(vec![])
+        }, |mut pkt| {
+            if let Packet::Data{ref mut sent, ..} = pkt {
+                *sent = time;
+            }
+
+            if let Some(ref mut c) = *credit {
+                *c -= pkt.get_size_bytes();
+            }
+
+            let flow_log = packet_flow_logger(flows, &pkt, logger);
+            if let Some(log) = flow_log.as_ref().or(logger) {
+                debug!(log, #PACKET, "tx";
                     "time" => time,
                     "node" => id,
                     "packet" => ?pkt,
                 );
             }
 
+            if let Packet::Data{hdr, ..} = pkt {
+                let done = time + link.transmission_delay(pkt.get_size_bytes());
+                flows.iter_mut()
+                    .find(|f| f.flow_info().flow_id == hdr.flow)
+                    .map(|f| f.transmitted(done, pkt));
+            }
+
+            *transmitting = true;
             Ok(vec![Box::new(NodeTransmitEvent(link, pkt)) as Box<Event>])
+        }).map(|mut evs| {
+            evs.extend(wakeup);
+            evs
         })
     }
 
-    fn reactivate(&mut self, l: Link) {
+    fn reactivate(&mut self, l: Link) -> Result<()> {
         assert_eq!(self.link, l);
+        self.transmitting = false;
         self.active = true;
+        Ok(())
+    }
+
+    fn packet_dropped(&mut self, at: u32, p: Packet, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        let flow_log = packet_flow_logger(&self.active_flows, &p, logger);
+        if let Some(log) = flow_log.as_ref().or(logger) {
+            debug!(log, #PACKET, "dropped downstream";
+                "time" => time,
+                "node" => self.id,
+                "at" => at,
+                "packet" => ?p,
+            );
+        }
+
+        if self.downstream_drops.len() == DOWNSTREAM_DROP_SAMPLES {
+            self.downstream_drops.pop_front();
+        }
+
+        self.downstream_drops.push_back((time, p));
+        self.downstream_drop_count += 1;
+        Ok(vec![])
     }
 
     fn flow_arrival(&mut self, f: Box<Flow>) {
@@ -198,12 +396,66 @@ impl Node for Host {
         self.active = true;
     }
 
+    fn flow_timer(&mut self, flow_id: u32, time: Nanos, _logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        let id = self.id;
+        let next = self.active_flows.iter_mut()
+            .find(|f| f.flow_info().flow_id == flow_id)
+            .ok_or_else(|| format_err!("node {} has no flow {}", id, flow_id))?
+            .on_timer(time);
+
+        // the timer may have opened the window or expired the RTO.
+        // as in receive(), only wake up if no packets are already waiting for the link.
+        if self.to_send.is_empty() {
+            self.active = true;
+        }
+
+        Ok(next.map_or_else(Vec::new, |delay| {
+            vec![Box::new(FlowTimerEvent(id, flow_id, delay)) as Box<Event>]
+        }))
+    }
+
     fn is_active(&self) -> bool {
         self.active
     }
+
+    fn mtu(&self) -> u32 {
+        self.link.mtu
+    }
+
+    fn queue_occupancy(&self, to: u32) -> Option<u32> {
+        if to == self.link.to {
+            Some(self.to_send.iter().map(|p| p.get_size_bytes()).sum())
+        } else {
+            None
+        }
+    }
+
+    fn take_switch_stats(&mut self) -> Option<switch::SwitchStats> {
+        None
+    }
+
+    fn flows(&self) -> &[Box<Flow>] {
+        &self.active_flows
+    }
 }
 
-#[derive(Debug)]
+/// A child of `logger` carrying the context of the flow `p` belongs to (see `flow_logger`), if
+/// that flow is one of `flows`.
+fn packet_flow_logger(flows: &[Box<Flow>], p: &Packet, logger: Option<&slog::Logger>) -> Option<slog::Logger> {
+    let flow_id = match *p {
+        Packet::Data{hdr, ..} |
+        Packet::Ack{hdr, ..} |
+        Packet::Nack{hdr, ..} |
+        Packet::Grant{hdr, ..} => hdr.flow,
+        Packet::Mirror{..} | Packet::Pause(_, _) | Packet::Resume(_, _) | Packet::Credit(_, _) => return None,
+    };
+
+    flows.iter()
+        .find(|f| f.flow_info().flow_id == flow_id)
+        .and_then(|f| flow_logger(logger, &**f))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct LinkTransmitEvent(pub Link, pub Packet);
 
 impl Event for LinkTransmitEvent {
@@ -215,29 +467,100 @@ impl Event for LinkTransmitEvent {
         vec![self.0.to]
     }
 
+    fn arrival(&self) -> Option<(Link, Packet)> {
+        Some((self.0, self.1))
+    }
+
     fn exec(&mut self, time: Nanos, nodes: &mut [&mut Node], logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
         nodes[0].receive(self.1.clone(), self.0, time, logger)
     }
 }
 
-#[derive(Debug)]
+/// Polls host `0` after `1`, when one of its flows will next have a packet it held back (see
+/// `Flow::next_send_time`).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HostWakeEvent(pub u32, pub Nanos);
+
+impl Event for HostWakeEvent {
+    fn time(&self) -> EventTime {
+        EventTime::Delta(self.1)
+    }
+
+    fn affected_node_ids(&self) -> Vec<u32> {
+        vec![self.0]
+    }
+
+    fn exec(&mut self, time: Nanos, nodes: &mut [&mut Node], logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        nodes[0].exec(time, logger)
+    }
+}
+
+/// A packet which arrived at node `0` over link `1` has finished processing after delay `3`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProcessingDoneEvent(pub u32, pub Link, pub Packet, pub Nanos);
+
+impl Event for ProcessingDoneEvent {
+    fn time(&self) -> EventTime {
+        EventTime::Delta(self.3)
+    }
+
+    fn affected_node_ids(&self) -> Vec<u32> {
+        vec![self.0]
+    }
+
+    fn exec(&mut self, time: Nanos, nodes: &mut [&mut Node], logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        nodes[0].receive_processed(self.2, self.1, time, logger)
+    }
+}
+
+/// Node `0` dropped packet `2`, which arrived over link `1`.
+/// Delivered immediately to the upstream node (the link's source), so it can react to the drop
+/// without inferring it from the logs.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PacketDropEvent(pub u32, pub Link, pub Packet);
+
+impl Event for PacketDropEvent {
+    fn time(&self) -> EventTime {
+        EventTime::Delta(0)
+    }
+
+    fn affected_node_ids(&self) -> Vec<u32> {
+        vec![self.1.from]
+    }
+
+    fn exec(&mut self, time: Nanos, nodes: &mut [&mut Node], logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        nodes[0].packet_dropped(self.0, self.2, time, logger)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct NodeTransmitEvent(pub Link, pub Packet);
 
 impl Event for NodeTransmitEvent {
     fn time(&self) -> EventTime {
-        let transmission_delay = self.1.get_size_bytes() as u64
-            * 8 // to bits
-            * 1_000_000_000 // to ns * bits / sec
-            / self.0.bandwidth_bps; // to ns
-        EventTime::Delta(transmission_delay)
+        // with cut-through, the packet is handed to the next hop as soon as its header is out
+        EventTime::Delta(self.0.transmission_delay(self.0.bytes_before_forwarding(&self.1)))
     }
 
     fn affected_node_ids(&self) -> Vec<u32> {
         vec![self.0.from]
     }
 
+    fn transmission(&self) -> Option<(Link, Packet)> {
+        Some((self.0, self.1))
+    }
+
     fn exec(&mut self, _time: Nanos, nodes: &mut [&mut Node], _logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
-        nodes[0].reactivate(self.0);
+        let remaining = self.1.get_size_bytes() - self.0.bytes_before_forwarding(&self.1);
+        if remaining > 0 {
+            // the rest of the packet is still being transmitted
+            return Ok(vec![
+                Box::new(LinkTransmitEvent(self.0, self.1)),
+                Box::new(LinkFreeEvent(self.0, self.0.transmission_delay(remaining))),
+            ]);
+        }
+
+        nodes[0].reactivate(self.0)?;
         Ok(vec![
             Box::new(
                 LinkTransmitEvent(self.0, self.1)
@@ -245,3 +568,79 @@ impl Event for NodeTransmitEvent {
         ])
     }
 }
+
+/// Link `0` finishes transmitting a cut-through packet after `1` more nanoseconds.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LinkFreeEvent(pub Link, pub Nanos);
+
+impl Event for LinkFreeEvent {
+    fn time(&self) -> EventTime {
+        EventTime::Delta(self.1)
+    }
+
+    fn affected_node_ids(&self) -> Vec<u32> {
+        vec![self.0.from]
+    }
+
+    fn exec(&mut self, _time: Nanos, nodes: &mut [&mut Node], _logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        nodes[0].reactivate(self.0)?;
+        Ok(vec![])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Host, Link, Node, DOWNSTREAM_DROP_SAMPLES};
+    use packet::{Packet, PacketHeader, PACKET_HEADER_SIZE_BYTES};
+
+    #[test]
+    fn pfc_headroom_scales_with_mtu() {
+        let link = |mtu| Link{
+            propagation_delay: 1_000_000,
+            bandwidth_bps: 1_000_000,
+            mtu,
+            pfc_enabled: true,
+            cut_through_threshold: None,
+            from: 0,
+            to: 1,
+        };
+
+        let (std, jumbo) = (link(1500), link(9000));
+        let bdp = 125;
+        assert_eq!(std.pfc_pause_threshold(), bdp + 2 * 1500);
+        assert_eq!(jumbo.pfc_pause_threshold(), bdp + 2 * 9000);
+        assert_eq!(std.pfc_resume_threshold(), std.pfc_pause_threshold() + 2 * 1500);
+        assert_eq!(jumbo.pfc_resume_threshold(), jumbo.pfc_pause_threshold() + 2 * 9000);
+    }
+
+    #[test]
+    fn downstream_drops_keep_the_latest() {
+        let pkt = |seq| Packet::Data{
+            hdr: PacketHeader{
+                flow: 0,
+                from: 0,
+                to: 1,
+                priority: 0,
+                size_bytes: PACKET_HEADER_SIZE_BYTES,
+            },
+            seq,
+            length: 1460,
+            ect: true,
+            ce: false,
+            sent: 0,
+            deadline: None,
+            trimmed: false,
+        };
+
+        let mut h = Host::default();
+        let drops = DOWNSTREAM_DROP_SAMPLES as u32 + 10;
+        for seq in 0..drops {
+            h.packet_dropped(2, pkt(seq), seq as u64, None).unwrap();
+        }
+
+        assert_eq!(h.downstream_drop_count, drops);
+        assert_eq!(h.downstream_drops.len(), DOWNSTREAM_DROP_SAMPLES);
+        assert_eq!(h.downstream_drops.front(), Some(&(10, pkt(10))));
+        assert_eq!(h.downstream_drops.back(), Some(&(drops as u64 - 1, pkt(drops - 1))));
+    }
+}
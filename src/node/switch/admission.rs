@@ -0,0 +1,60 @@
+use loss::{LossContext, LossModel, NoLoss, QueueOverflow};
+use packet::Packet;
+
+/// What a single-buffer output port checks when a packet arrives, shared by `DropTailQueue`,
+/// `EdfQueue` and `WfqQueue`.
+///
+/// In order: packets which do not fit in `limit_bytes` are dropped and counted in `dropped`,
+/// then `loss_model` may drop the rest, then `Data` packets which arrive to find at least
+/// `ecn_threshold` bytes queued are marked, or dropped if they are not ECN-capable.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Admission {
+    pub limit_bytes: u32,
+    pub ecn_threshold: Option<u32>,
+    pub loss_model: Box<LossModel>,
+    pub dropped: usize, // overflow drops only
+}
+
+impl Admission {
+    pub fn new(limit_bytes: u32) -> Self {
+        Admission{
+            limit_bytes,
+            ecn_threshold: None,
+            loss_model: Box::new(NoLoss),
+            dropped: 0,
+        }
+    }
+
+    /// Whether `p` may join a queue holding `occupancy_bytes`, marking it CE if it should be.
+    pub fn admit(&mut self, p: &mut Packet, occupancy_bytes: u32) -> Option<()> {
+        let ctx = LossContext{
+            occupancy_bytes,
+            limit_bytes: self.limit_bytes,
+        };
+
+        if QueueOverflow.should_drop(p, &ctx) {
+            // we have to drop this packet
+            self.dropped += 1;
+            return None;
+        }
+
+        if self.loss_model.should_drop(p, &ctx) {
+            return None;
+        }
+
+        if let Some(threshold) = self.ecn_threshold {
+            if let Packet::Data{ect, ref mut ce, ..} = *p {
+                if occupancy_bytes >= threshold {
+                    if !ect {
+                        // packets which cannot carry the mark are dropped instead
+                        return None;
+                    }
+
+                    *ce = true;
+                }
+            }
+        }
+
+        Some(())
+    }
+}
@@ -0,0 +1,287 @@
+use std::vec::Vec;
+use std::collections::{HashMap, HashSet};
+
+use slog;
+
+use ::{Nanos, Result};
+use event::Event;
+use log_filter::PACKET;
+use node::{NodeTransmitEvent, Link};
+use packet::{Packet, PacketHeader};
+use super::{Switch, SwitchStats, Queue, IngressAccounting, add_next_hop, drop_packet, next_hop, no_route, log_queue_length};
+
+/// A lossless switch which uses credit-based flow control, as in InfiniBand and some HPC
+/// fabrics, rather than PFC: each ingress link gets `credit_per_ingress` bytes of buffer, and
+/// its upstream node may only send as many bytes as the switch has granted it with `Credit`s.
+///
+/// Like `IngressPFCSwitch`, the switch accounts for buffered bytes per ingress link; once a
+/// packet leaves, its bytes are granted back to the ingress it arrived on. Each ingress gets a
+/// `1 / (number of ports)` share of the smallest output queue, so no queue overflows even when
+/// every ingress sends to the same port, and a congested port only holds back the ingresses
+/// whose packets wait in it.
+///
+/// The initial credits go out when the simulation starts. Neighboring switches must be
+/// `CreditSwitch`es too; their credits likewise limit what this switch sends to them. A port
+/// leads to a switch if it is in `core` or a route's next hop is not the destination itself;
+/// nothing goes out on it until that switch grants credit. Hosts need no credit.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreditSwitch {
+    pub id: u32,
+    pub active: bool,
+    pub rack: Vec<Box<Queue>>,
+    pub core: Vec<Box<Queue>>,
+    pub routes: HashMap<u32, Vec<u32>>, // dest host id -> equal-cost next hop node ids
+    /// Bytes of buffer each ingress link may fill; set it before the simulation starts.
+    /// Upstream nodes can never send a packet larger than this.
+    pub credit_per_ingress: u32,
+    pub credits_sent: u32,
+    started: bool,
+    ingress: IngressAccounting<u32>, // by ingress node
+    owed: HashMap<u32, u32>, // port -> bytes of credit to grant it
+    credit: HashMap<u32, u32>, // switch-facing port -> bytes the downstream switch has granted us
+    blocked: HashMap<u32, Packet>, // port -> packet dequeued, but waiting for credit
+    transmitting: HashSet<u32>, // ports whose link is still serializing a packet
+    stats: SwitchStats,
+}
+
+impl CreditSwitch {
+    /// Bytes from neighbor `from` currently buffered in the switch.
+    pub fn ingress_occupancy(&self, from: u32) -> u32 {
+        self.ingress.occupancy(from)
+    }
+}
+
+impl Switch for CreditSwitch {
+    fn new(
+        switch_id: u32,
+        links: impl Iterator<Item=Box<Queue>>,
+    ) -> Self {
+        let rack = links.collect::<Vec<Box<Queue>>>();
+        let credit_per_ingress = rack.iter()
+            .map(|q| q.headroom())
+            .min()
+            .map_or(0, |h| h / rack.len() as u32);
+        CreditSwitch{
+            id: switch_id,
+            // grant the initial credits as soon as the simulation starts
+            active: true,
+            rack,
+            core: vec![],
+            routes: HashMap::new(),
+            credit_per_ingress,
+            credits_sent: 0,
+            started: false,
+            ingress: IngressAccounting::default(),
+            owed: HashMap::new(),
+            credit: HashMap::new(),
+            blocked: HashMap::new(),
+            transmitting: HashSet::new(),
+            stats: SwitchStats::default(),
+        }
+    }
+
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn receive(
+        &mut self,
+        p: Packet,
+        l: Link,
+        time: Nanos,
+        logger: Option<&slog::Logger>,
+    ) -> Result<Vec<Box<Event>>> {
+        self.active = true;
+        let id = self.id;
+        if let Some(log) = logger {
+            debug!(log, #PACKET, "rx";
+                "time" => time,
+                "node" => self.id,
+                "from" => l.from,
+                "packet" => ?p,
+            );
+        }
+
+        let hdr = match p {
+            Packet::Credit(_, bytes) => {
+                // the downstream switch has room for `bytes` more from us
+                *self.credit.entry(l.from).or_insert(0) += bytes;
+                return Ok(vec![]);
+            }
+            Packet::Nack{hdr, ..} |
+            Packet::Grant{hdr, ..} |
+            Packet::Ack{hdr, ..} |
+            Packet::Data{hdr, ..} |
+            Packet::Mirror{hdr, ..} => hdr,
+            Packet::Pause(_, _) | Packet::Resume(_, _) => return Ok(vec![]),
+        };
+
+        // switches are output queued
+        let hop = next_hop(&self.routes, hdr);
+        let q = self.rack.iter_mut().chain(self.core.iter_mut())
+            .find(|q| q.link().to == hop)
+            .ok_or_else(|| no_route(id, hop, p))?;
+
+        if let None = q.enqueue(p) {
+            // only possible if the upstream node sent without credit, or with a loss model.
+            // the upstream node spent credit on the packet, so give it back
+            *self.owed.entry(l.from).or_insert(0) += p.get_size_bytes();
+            return Ok(vec![drop_packet(id, l, p, time, &mut self.stats, logger)]);
+        }
+
+        q.trace_occupancy(time);
+        self.stats.record_occupancy(&**q);
+        let ingress_occupancy = self.ingress.arrive(p, l.from);
+        if let Some(log) = logger {
+            debug!(log, #PACKET, "enqueue";
+                "time" => time,
+                "node" => id,
+                "ingress" => l.from,
+                "headroom" => q.headroom(),
+                "ingress-occupancy" => ingress_occupancy,
+            );
+        }
+
+        Ok(vec![])
+    }
+
+    fn exec(&mut self, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        if !self.started {
+            self.started = true;
+            for q in self.rack.iter().chain(self.core.iter()) {
+                self.owed.insert(q.link().to, self.credit_per_ingress);
+            }
+
+            for q in &self.core {
+                self.credit.entry(q.link().to).or_insert(0);
+            }
+        }
+
+        // each free port grants the credit it owes, or else sends its next packet if the next
+        // hop has granted room for it
+        let id = self.id;
+        let owed = &mut self.owed;
+        let credit = &mut self.credit;
+        let blocked = &mut self.blocked;
+        let transmitting = &mut self.transmitting;
+        let ingress_accounting = &mut self.ingress;
+        let credits_sent = &mut self.credits_sent;
+        let mut evs = vec![];
+        for q in self.rack.iter_mut().chain(self.core.iter_mut()) {
+            let port = q.link().to;
+            if transmitting.contains(&port) {
+                continue;
+            }
+
+            let pkt = match owed.remove(&port) {
+                Some(bytes) if bytes > 0 => {
+                    *credits_sent += 1;
+                    Packet::Credit(id, bytes)
+                }
+                _ => {
+                    let next = match blocked.remove(&port) {
+                        Some(p) => p,
+                        None if q.is_active() => match q.dequeue() {
+                            Some(p) => p,
+                            None => {
+                                q.set_active(false);
+                                continue;
+                            }
+                        },
+                        None => continue,
+                    };
+
+                    // ports towards hosts have no entry: hosts take whatever they are sent
+                    if let Some(c) = credit.get_mut(&port) {
+                        if next.get_size_bytes() > *c {
+                            // wait for the downstream switch to grant more credit
+                            blocked.insert(port, next);
+                            continue;
+                        }
+
+                        *c -= next.get_size_bytes();
+                    }
+
+                    // the packet has left the buffer: grant its bytes back to its ingress
+                    let (ingress, ingress_occupancy) = ingress_accounting.depart(next)
+                        .ok_or_else(|| format_err!("switch {} dequeued a packet it never received: {:?}", id, next))?;
+                    *owed.entry(ingress).or_insert(0) += next.get_size_bytes();
+                    if let Some(log) = logger {
+                        debug!(log, #PACKET, "dequeue";
+                            "time" => time,
+                            "node" => id,
+                            "ingress" => ingress,
+                            "headroom" => q.headroom(),
+                            "ingress-occupancy" => ingress_occupancy,
+                        );
+                    }
+
+                    next
+                }
+            };
+
+            if let Some(log) = logger {
+                debug!(log, #PACKET, "tx";
+                    "time" => time,
+                    "node" => id,
+                    "packet" => ?pkt,
+                    "port" => q.link().to,
+                    "occupancy" => q.occupancy_bytes(),
+                    "headroom" => q.headroom(),
+                );
+            }
+
+            q.trace_occupancy(time);
+            log_queue_length(logger, time, id, &**q);
+            transmitting.insert(port);
+            evs.push(Box::new(NodeTransmitEvent(q.link(), pkt)) as Box<Event>);
+        }
+
+        Ok(evs)
+    }
+
+    fn reactivate(&mut self, l: Link) -> Result<()> {
+        assert_eq!(l.from, self.id);
+        self.transmitting.remove(&l.to);
+        Ok(())
+    }
+
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn add_route(&mut self, dest: u32, next_hop: u32) {
+        add_next_hop(&mut self.routes, dest, next_hop);
+        if next_hop != dest {
+            // a switch: send it nothing until it grants credit
+            self.credit.entry(next_hop).or_insert(0);
+        }
+    }
+
+    fn egress_link(&self, hdr: PacketHeader) -> Option<Link> {
+        let hop = next_hop(&self.routes, hdr);
+        self.rack.iter()
+            .chain(self.core.iter())
+            .map(|q| q.link())
+            .find(|l| l.to == hop)
+    }
+
+    fn queue_occupancy(&self, to: u32) -> Option<u32> {
+        let blocked = self.blocked.get(&to).map_or(0, |p| p.get_size_bytes());
+        self.rack.iter()
+            .chain(self.core.iter())
+            .find(|q| q.link().to == to)
+            .map(|q| q.occupancy_bytes() + blocked)
+    }
+
+    fn set_cut_through_threshold(&mut self, to: u32, threshold: Option<u32>) {
+        self.rack.iter_mut()
+            .chain(self.core.iter_mut())
+            .filter(|q| q.link().to == to)
+            .for_each(|q| q.set_cut_through_threshold(threshold));
+    }
+
+    fn take_stats(&mut self) -> SwitchStats {
+        ::std::mem::replace(&mut self.stats, SwitchStats::default())
+    }
+}
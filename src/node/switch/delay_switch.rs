@@ -0,0 +1,110 @@
+use std::vec::Vec;
+
+use slog;
+
+use ::{Nanos, Result};
+use event::Event;
+use node::{ProcessingDoneEvent, Link};
+use packet::{Packet, PacketHeader};
+use rng::{Delay, Rng};
+use super::{Switch, SwitchStats, PFCSwitchFamily, Queue};
+
+/// Wraps a `Switch` with a per-packet processing delay before forwarding, sampled from an
+/// `Rng` seeded by the executor, to model store-and-forward latency jitter under load.
+///
+/// Each packet's delay is independent, so jitter can reorder packets.
+/// PAUSE/RESUME frames are handled by the MAC and are never delayed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DelaySwitch<S: Switch> {
+    pub inner: S,
+    delay: Delay,
+    rng: Rng,
+}
+
+impl<S: Switch> DelaySwitch<S> {
+    pub fn set_processing_delay(&mut self, delay: Delay) {
+        self.delay = delay;
+    }
+}
+
+impl<S: PFCSwitchFamily> PFCSwitchFamily for DelaySwitch<S> {}
+
+impl<S: Switch> Switch for DelaySwitch<S> {
+    fn new(
+        switch_id: u32,
+        links: impl Iterator<Item=Box<Queue>>,
+    ) -> Self {
+        DelaySwitch{
+            inner: S::new(switch_id, links),
+            delay: Delay::default(),
+            rng: Rng::new(0),
+        }
+    }
+
+    fn id(&self) -> u32 {
+        self.inner.id()
+    }
+
+    fn receive(
+        &mut self,
+        p: Packet,
+        l: Link,
+        time: Nanos,
+        logger: Option<&slog::Logger>,
+    ) -> Result<Vec<Box<Event>>> {
+        match p {
+            Packet::Pause(_, _) | Packet::Resume(_, _) | Packet::Credit(_, _) => self.inner.receive(p, l, time, logger),
+            _ => {
+                let delay = self.delay.sample(&mut self.rng);
+                Ok(vec![Box::new(ProcessingDoneEvent(self.id(), l, p, delay))])
+            }
+        }
+    }
+
+    fn process(
+        &mut self,
+        p: Packet,
+        l: Link,
+        time: Nanos,
+        logger: Option<&slog::Logger>,
+    ) -> Result<Vec<Box<Event>>> {
+        self.inner.process(p, l, time, logger)
+    }
+
+    fn exec(&mut self, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        self.inner.exec(time, logger)
+    }
+
+    fn reactivate(&mut self, l: Link) -> Result<()> {
+        self.inner.reactivate(l)
+    }
+
+    fn is_active(&self) -> bool {
+        self.inner.is_active()
+    }
+
+    fn add_route(&mut self, dest: u32, next_hop: u32) {
+        self.inner.add_route(dest, next_hop)
+    }
+
+    fn egress_link(&self, hdr: PacketHeader) -> Option<Link> {
+        self.inner.egress_link(hdr)
+    }
+
+    fn queue_occupancy(&self, to: u32) -> Option<u32> {
+        self.inner.queue_occupancy(to)
+    }
+
+    fn set_cut_through_threshold(&mut self, to: u32, threshold: Option<u32>) {
+        self.inner.set_cut_through_threshold(to, threshold)
+    }
+
+    fn take_stats(&mut self) -> SwitchStats {
+        self.inner.take_stats()
+    }
+
+    fn seed_rng(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+        self.inner.seed_rng(self.rng.next_u64())
+    }
+}
@@ -1,33 +1,41 @@
 use std::collections::VecDeque;
 
+use loss::LossModel;
 use node::Link;
 use node::switch::Queue;
+use node::switch::admission::Admission;
 use packet::Packet;
+use stats::OccupancyTrace;
+use Nanos;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DropTailQueue{
-    limit_bytes: u32,
     link: Link,
+    admission: Admission,
     pkts: VecDeque<Packet>,
-    forced_next: Option<Packet>,
+    forced_next: VecDeque<Packet>, // control frames (e.g. PAUSE/RESUME) to send before `pkts`, in order
     active: bool,
     paused: bool,
+    occupancy_trace: Option<OccupancyTrace>,
 }
 
 impl DropTailQueue {
     pub fn new(limit_bytes: u32, link: Link) -> Self {
         DropTailQueue{
-            limit_bytes,
             link,
+            admission: Admission::new(limit_bytes),
             pkts: VecDeque::new(),
-            forced_next: None,
+            forced_next: VecDeque::new(),
             active: false,
             paused: false,
+            occupancy_trace: None,
         }
     }
 
-    fn occupancy_bytes(&self) -> u32 {
-        self.pkts.iter().map(|p| p.get_size_bytes()).sum()
+    /// Record the occupancy after every enqueue and dequeue in `trace`, e.g. to plot
+    /// bufferbloat. `None` (the default) records nothing.
+    pub fn set_occupancy_trace(&mut self, trace: Option<OccupancyTrace>) {
+        self.occupancy_trace = trace;
     }
 }
 
@@ -37,15 +45,20 @@ impl Queue for DropTailQueue {
     }
 
     fn headroom(&self) -> u32 {
-        self.limit_bytes - self.occupancy_bytes()
+        self.admission.limit_bytes - self.occupancy_bytes()
+    }
+
+    fn occupancy_bytes(&self) -> u32 {
+        self.pkts.iter().map(|p| p.get_size_bytes()).sum()
+    }
+
+    fn dropped_packets(&self) -> usize {
+        self.admission.dropped
     }
     
-    fn enqueue(&mut self, p: Packet) -> Option<()> {
+    fn enqueue(&mut self, mut p: Packet) -> Option<()> {
         let occupancy_bytes = self.occupancy_bytes();
-        if occupancy_bytes + p.get_size_bytes() > self.limit_bytes {
-            // we have to drop this packet
-            return None;
-        }
+        self.admission.admit(&mut p, occupancy_bytes)?;
 
         self.pkts.push_back(p);
         self.set_active(true);
@@ -53,21 +66,21 @@ impl Queue for DropTailQueue {
     }
     
     fn force_tx_next(&mut self, p: Packet) -> Option<()> {
-        self.forced_next = Some(p);
+        self.forced_next.push_back(p);
         self.set_active(true);
         Some(())
     }
 
     fn dequeue(&mut self) -> Option<Packet> {
-        if let None = self.forced_next {
-            if self.pkts.len() == 1 {
-                self.set_active(false);
-            }
+        if let Some(p) = self.forced_next.pop_front() {
+            return Some(p);
+        }
 
-            self.pkts.pop_front()
-        } else {
-            self.forced_next.take()
+        if self.pkts.len() == 1 {
+            self.set_active(false);
         }
+
+        self.pkts.pop_front()
     }
 
     fn discard_matching(&mut self, mut should_discard: Box<FnMut(Packet) -> bool>) -> usize {
@@ -97,26 +110,55 @@ impl Queue for DropTailQueue {
     fn set_paused(&mut self, a: bool) {
         self.paused = a;
     }
+
+    fn set_ecn_threshold(&mut self, threshold: Option<u32>) {
+        self.admission.ecn_threshold = threshold;
+    }
+
+    fn set_loss_model(&mut self, model: Box<LossModel>) {
+        self.admission.loss_model = model;
+    }
+
+    fn set_cut_through_threshold(&mut self, threshold: Option<u32>) {
+        self.link.cut_through_threshold = threshold;
+    }
+
+    fn occupancy_trace(&self) -> Option<OccupancyTrace> {
+        self.occupancy_trace.clone()
+    }
+
+    fn trace_occupancy(&self, time: Nanos) {
+        if let Some(ref trace) = self.occupancy_trace {
+            trace.record(time, self.occupancy_bytes());
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use node::{Link, switch::Queue};
-    use packet::{Packet, PacketHeader};
+    use packet::{Packet, PacketHeader, PACKET_HEADER_SIZE_BYTES};
     use super::DropTailQueue;
 
     #[test]
     fn check_discard_matching() {
-        let mut q = DropTailQueue::new(15_000, Link{propagation_delay: 0, bandwidth_bps: 0, pfc_enabled: false, from: 0, to: 1});
+        let mut q = DropTailQueue::new(15_000, Link{propagation_delay: 0, bandwidth_bps: 0, mtu: 1500, pfc_enabled: false, cut_through_threshold: None, from: 0, to: 1});
         let mut pkts = (0..).map(|seq| {
             Packet::Data{
                 hdr: PacketHeader{
                     flow: 0,
                     from: 0,
                     to: 1,
+                    priority: 0,
+                    size_bytes: PACKET_HEADER_SIZE_BYTES,
                 },
                 seq,
                 length: 1460,
+                ect: true,
+                ce: false,
+                sent: 0,
+                deadline: None,
+                trimmed: false,
             }
         });
 
@@ -139,4 +181,83 @@ mod tests {
         assert_eq!(dropped, 2);
         assert_eq!(q.headroom(), 1500 * 4);
     }
+
+    #[test]
+    fn check_ecn_marking() {
+        let mut q = DropTailQueue::new(15_000, Link{propagation_delay: 0, bandwidth_bps: 0, mtu: 1500, pfc_enabled: false, cut_through_threshold: None, from: 0, to: 1});
+        q.set_ecn_threshold(Some(3_000));
+        let pkt = |seq, ect| Packet::Data{
+            hdr: PacketHeader{
+                flow: 0,
+                from: 0,
+                to: 1,
+                priority: 0,
+                size_bytes: PACKET_HEADER_SIZE_BYTES,
+            },
+            seq,
+            length: 1460,
+            ect,
+            ce: false,
+            sent: 0,
+            deadline: None,
+            trimmed: false,
+        };
+
+        q.enqueue(pkt(0, false)).unwrap();
+        q.enqueue(pkt(1, true)).unwrap();
+        // at the threshold, ECN-capable packets are marked and the rest are dropped
+        assert!(q.enqueue(pkt(2, false)).is_none());
+        q.enqueue(pkt(3, true)).unwrap();
+
+        let marks = (0..3).map(|_| match q.dequeue() {
+            Some(Packet::Data{ce, ..}) => ce,
+            p => panic!("expected a data packet, got {:?}", p),
+        }).collect::<Vec<bool>>();
+        assert_eq!(marks, vec![false, false, true]);
+    }
+
+    #[test]
+    fn ecn_delay_threshold_scales_with_bandwidth() {
+        let link = |bandwidth_bps| Link{propagation_delay: 0, bandwidth_bps, mtu: 1500, pfc_enabled: false, cut_through_threshold: None, from: 0, to: 1};
+        let pkt = |seq| Packet::Data{
+            hdr: PacketHeader{
+                flow: 0,
+                from: 0,
+                to: 1,
+                priority: 0,
+                size_bytes: PACKET_HEADER_SIZE_BYTES,
+            },
+            seq,
+            length: 1460,
+            ect: true,
+            ce: false,
+            sent: 0,
+            deadline: None,
+            trimmed: false,
+        };
+
+        // 24ms is 3,000 bytes at 1 Mbit/s and 30,000 bytes at 10 Mbit/s
+        let first_marked_occupancy = |bandwidth_bps| {
+            let mut q = DropTailQueue::new(60_000, link(bandwidth_bps));
+            q.set_ecn_delay_threshold(Some(24_000_000));
+            let occupancies = (0..30).map(|seq| {
+                let occupancy = q.occupancy_bytes();
+                q.enqueue(pkt(seq)).unwrap();
+                occupancy
+            }).collect::<Vec<u32>>();
+
+            occupancies.into_iter().find(|_| match q.dequeue() {
+                Some(Packet::Data{ce, ..}) => ce,
+                p => panic!("expected a data packet, got {:?}", p),
+            })
+        };
+
+        assert_eq!(first_marked_occupancy(1_000_000), Some(3_000));
+        assert_eq!(first_marked_occupancy(10_000_000), Some(30_000));
+
+        // 10s at 10 Gbit/s is more bytes than a u32 holds
+        let mut q = DropTailQueue::new(60_000, link(10_000_000_000));
+        q.set_ecn_delay_threshold(Some(10_000_000_000));
+        assert_eq!(q.admission.ecn_threshold, Some(::std::u32::MAX));
+    }
 }
@@ -0,0 +1,172 @@
+use std::collections::VecDeque;
+
+use loss::LossModel;
+use node::Link;
+use node::switch::Queue;
+use node::switch::admission::Admission;
+use packet::Packet;
+use Nanos;
+
+/// An earliest-deadline-first output port: it sends the queued `Data` packet with the earliest
+/// deadline next, as deadline-aware transports like D3 and PDQ assume.
+///
+/// Packets without a deadline, including all non-`Data` packets, come after every packet with
+/// one. Packets with equal deadlines leave in arrival order.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EdfQueue {
+    link: Link,
+    admission: Admission,
+    pkts: Vec<Packet>, // in arrival order
+    forced_next: VecDeque<Packet>,
+    active: bool,
+    paused: bool,
+}
+
+impl EdfQueue {
+    pub fn new(limit_bytes: u32, link: Link) -> Self {
+        EdfQueue{
+            link,
+            admission: Admission::new(limit_bytes),
+            pkts: vec![],
+            forced_next: VecDeque::new(),
+            active: false,
+            paused: false,
+        }
+    }
+}
+
+fn deadline(p: &Packet) -> Nanos {
+    match *p {
+        Packet::Data{deadline: Some(d), ..} => d,
+        _ => Nanos::max_value(),
+    }
+}
+
+impl Queue for EdfQueue {
+    fn link(&self) -> Link {
+        self.link
+    }
+
+    fn headroom(&self) -> u32 {
+        self.admission.limit_bytes - self.occupancy_bytes()
+    }
+
+    fn occupancy_bytes(&self) -> u32 {
+        self.pkts.iter().map(|p| p.get_size_bytes()).sum()
+    }
+
+    fn dropped_packets(&self) -> usize {
+        self.admission.dropped
+    }
+
+    fn enqueue(&mut self, mut p: Packet) -> Option<()> {
+        let occupancy_bytes = self.occupancy_bytes();
+        self.admission.admit(&mut p, occupancy_bytes)?;
+
+        self.pkts.push(p);
+        self.set_active(true);
+        Some(())
+    }
+
+    fn force_tx_next(&mut self, p: Packet) -> Option<()> {
+        self.forced_next.push_back(p);
+        self.set_active(true);
+        Some(())
+    }
+
+    fn dequeue(&mut self) -> Option<Packet> {
+        if let Some(p) = self.forced_next.pop_front() {
+            return Some(p);
+        }
+
+        // min_by_key keeps the first of equal deadlines, so ties leave in arrival order
+        let next = self.pkts.iter()
+            .enumerate()
+            .min_by_key(|&(_, p)| deadline(p))
+            .map(|(i, _)| i)?;
+        if self.pkts.len() == 1 {
+            self.set_active(false);
+        }
+
+        Some(self.pkts.remove(next))
+    }
+
+    fn discard_matching(&mut self, mut should_discard: Box<FnMut(Packet) -> bool>) -> usize {
+        let before = self.pkts.len();
+        self.pkts.retain(|&p| !should_discard(p));
+        before - self.pkts.len()
+    }
+
+    fn count_matching(&self, mut counter: Box<FnMut(Packet) -> bool>) -> usize {
+        self.pkts.iter().filter(|&&p| counter(p)).count()
+    }
+
+    fn is_active(&self) -> bool {
+        self.active && !self.paused
+    }
+
+    fn set_active(&mut self, a: bool) {
+        self.active = a;
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn set_paused(&mut self, a: bool) {
+        self.paused = a;
+    }
+
+    fn set_ecn_threshold(&mut self, threshold: Option<u32>) {
+        self.admission.ecn_threshold = threshold;
+    }
+
+    fn set_loss_model(&mut self, model: Box<LossModel>) {
+        self.admission.loss_model = model;
+    }
+
+    fn set_cut_through_threshold(&mut self, threshold: Option<u32>) {
+        self.link.cut_through_threshold = threshold;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use node::{Link, switch::Queue};
+    use packet::{Packet, PacketHeader, PACKET_HEADER_SIZE_BYTES};
+    use super::EdfQueue;
+
+    #[test]
+    fn earliest_deadline_first() {
+        let mut q = EdfQueue::new(15_000, Link{propagation_delay: 0, bandwidth_bps: 0, mtu: 1500, pfc_enabled: false, cut_through_threshold: None, from: 0, to: 1});
+        let pkt = |flow, deadline| Packet::Data{
+            hdr: PacketHeader{
+                flow,
+                from: 0,
+                to: 1,
+                priority: 0,
+                size_bytes: PACKET_HEADER_SIZE_BYTES,
+            },
+            seq: 0,
+            length: 1460,
+            ect: true,
+            ce: false,
+            sent: 0,
+            deadline,
+            trimmed: false,
+        };
+
+        q.enqueue(pkt(0, None)).unwrap();
+        q.enqueue(pkt(1, Some(300))).unwrap();
+        q.enqueue(pkt(2, Some(100))).unwrap();
+        q.enqueue(pkt(3, Some(300))).unwrap();
+        q.enqueue(pkt(4, None)).unwrap();
+
+        let order = (0..5).map(|_| match q.dequeue() {
+            Some(Packet::Data{hdr, ..}) => hdr.flow,
+            p => panic!("expected a data packet, got {:?}", p),
+        }).collect::<Vec<u32>>();
+        assert_eq!(order, vec![2, 1, 3, 0, 4]);
+        assert!(!q.is_active());
+    }
+}
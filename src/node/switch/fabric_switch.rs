@@ -0,0 +1,150 @@
+use std::vec::Vec;
+
+use slog;
+
+use ::{Nanos, Result};
+use event::{Event, EventTime};
+use node::{Node, Link};
+use packet::{Packet, PacketHeader};
+use super::{Switch, SwitchStats, PFCSwitchFamily, Queue};
+
+/// Wraps a `Switch` with a switch-wide forwarding bandwidth shared by all of its ports, below
+/// the sum of their line rates, so the fabric itself can become the bottleneck when many ports
+/// are busy.
+///
+/// Forwarding is serialized: packets the inner switch dequeues together leave together, and
+/// then the fabric is busy for as long as their total size takes at the forwarding bandwidth.
+/// Ports wait for it to free up before dequeuing again.
+/// PAUSE/RESUME frames are generated at the egress port and do not cross the fabric.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FabricSwitch<S: Switch> {
+    pub inner: S,
+    forwarding_bps: Option<u64>,
+    busy_until: Nanos,
+}
+
+impl<S: Switch> FabricSwitch<S> {
+    /// Cap the switch's aggregate forwarding rate at `bps`. `None` (the default) is unlimited.
+    pub fn set_forwarding_bandwidth(&mut self, bps: Option<u64>) {
+        self.forwarding_bps = bps;
+    }
+}
+
+/// The fabric of switch `0` frees up after `1`.
+/// It only has to happen: the executor polls the (still active) switch again once it has run.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FabricFreeEvent(pub u32, pub Nanos);
+
+impl Event for FabricFreeEvent {
+    fn time(&self) -> EventTime {
+        EventTime::Delta(self.1)
+    }
+
+    fn affected_node_ids(&self) -> Vec<u32> {
+        vec![self.0]
+    }
+
+    fn exec(&mut self, _time: Nanos, _nodes: &mut [&mut Node], _logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        Ok(vec![])
+    }
+}
+
+impl<S: PFCSwitchFamily> PFCSwitchFamily for FabricSwitch<S> {}
+
+impl<S: Switch> Switch for FabricSwitch<S> {
+    fn new(
+        switch_id: u32,
+        links: impl Iterator<Item=Box<Queue>>,
+    ) -> Self {
+        FabricSwitch{
+            inner: S::new(switch_id, links),
+            forwarding_bps: None,
+            busy_until: 0,
+        }
+    }
+
+    fn id(&self) -> u32 {
+        self.inner.id()
+    }
+
+    fn receive(
+        &mut self,
+        p: Packet,
+        l: Link,
+        time: Nanos,
+        logger: Option<&slog::Logger>,
+    ) -> Result<Vec<Box<Event>>> {
+        self.inner.receive(p, l, time, logger)
+    }
+
+    fn process(
+        &mut self,
+        p: Packet,
+        l: Link,
+        time: Nanos,
+        logger: Option<&slog::Logger>,
+    ) -> Result<Vec<Box<Event>>> {
+        self.inner.process(p, l, time, logger)
+    }
+
+    fn exec(&mut self, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        let forwarding_bps = match self.forwarding_bps {
+            Some(bps) => bps,
+            None => return self.inner.exec(time, logger),
+        };
+
+        if time < self.busy_until {
+            // the FabricFreeEvent for the packets still crossing will wake us
+            return Ok(vec![]);
+        }
+
+        let mut evs = self.inner.exec(time, logger)?;
+        let bytes = evs.iter()
+            .filter_map(|ev| ev.transmission())
+            .filter(|&(_, p)| match p {
+                Packet::Pause(_, _) | Packet::Resume(_, _) | Packet::Credit(_, _) => false,
+                _ => true,
+            })
+            .map(|(_, p)| u64::from(p.get_size_bytes()))
+            .sum::<u64>();
+        if bytes > 0 {
+            let busy = bytes * 8 * 1_000_000_000 / forwarding_bps;
+            self.busy_until = time + busy;
+            evs.push(Box::new(FabricFreeEvent(Switch::id(self), busy)));
+        }
+
+        Ok(evs)
+    }
+
+    fn reactivate(&mut self, l: Link) -> Result<()> {
+        self.inner.reactivate(l)
+    }
+
+    fn is_active(&self) -> bool {
+        self.inner.is_active()
+    }
+
+    fn add_route(&mut self, dest: u32, next_hop: u32) {
+        self.inner.add_route(dest, next_hop)
+    }
+
+    fn egress_link(&self, hdr: PacketHeader) -> Option<Link> {
+        self.inner.egress_link(hdr)
+    }
+
+    fn queue_occupancy(&self, to: u32) -> Option<u32> {
+        self.inner.queue_occupancy(to)
+    }
+
+    fn set_cut_through_threshold(&mut self, to: u32, threshold: Option<u32>) {
+        self.inner.set_cut_through_threshold(to, threshold)
+    }
+
+    fn take_stats(&mut self) -> SwitchStats {
+        self.inner.take_stats()
+    }
+
+    fn seed_rng(&mut self, seed: u64) {
+        self.inner.seed_rng(seed)
+    }
+}
@@ -1,19 +1,51 @@
 use std::vec::Vec;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use slog;
 
 use ::{Nanos, Result};
 use event::Event;
+use log_filter::PACKET;
 use node::{NodeTransmitEvent, Link};
-use packet::Packet;
-use super::{Switch, Queue};
+use packet::{Packet, PacketHeader};
+use rng::Rng;
+use super::{Switch, SwitchStats, Queue, add_next_hop, drop_packet, next_hop, spray_next_hop, no_route, log_queue_length};
+use super::red::{RedParams, MARK_OCCUPANCY_SAMPLES};
 
-#[derive(Default, Debug)]
+/// With `packet_spraying`, LossySwitch sends each packet of a flow out of the next of its
+/// equal-cost next hops in turn, rather than hashing the whole flow onto one (see
+/// `spray_next_hop`).
+///
+/// With `RedParams` (see `set_red`), it marks `Data` packets with ECN CE using RED, and drops
+/// packets which are not ECN-capable where it would mark them. Marking decisions come from an
+/// `Rng` seeded by the executor, so runs are reproducible. `marks` counts marked packets and
+/// `early_drops` the packets dropped instead. `mark_occupancies` keeps the output queue
+/// occupancy at the latest `MARK_OCCUPANCY_SAMPLES` marks and early drops, to check the
+/// operating point RED settles at.
+#[derive(Debug, Serialize, Deserialize)]
 pub struct LossySwitch {
     pub id: u32,
     pub active: bool,
     pub rack: Vec<Box<Queue>>,
     pub core: Vec<Box<Queue>>,
+    pub routes: HashMap<u32, Vec<u32>>, // dest host id -> equal-cost next hop node ids
+    pub packet_spraying: bool,
+    spray_turns: HashMap<u32, usize>, // dest host id -> next hop index
+    pub marks: u32,
+    pub early_drops: u32,
+    pub mark_occupancies: VecDeque<u32>,
+    red: Option<RedParams>,
+    rng: Rng,
+    marks_taken: u32, // marks at the last take_stats
+    transmitting: HashSet<u32>, // ports whose link is still serializing a packet
+    stats: SwitchStats,
+}
+
+impl LossySwitch {
+    pub fn set_red(&mut self, red: RedParams) {
+        assert!(red.min_threshold_bytes <= red.max_threshold_bytes);
+        self.red = Some(red);
+    }
 }
 
 impl Switch for LossySwitch {
@@ -26,6 +58,17 @@ impl Switch for LossySwitch {
             active: false,
             rack: links.collect::<Vec<Box<Queue>>>(),
             core: vec![],
+            routes: HashMap::new(),
+            packet_spraying: false,
+            spray_turns: HashMap::new(),
+            marks: 0,
+            early_drops: 0,
+            mark_occupancies: VecDeque::new(),
+            red: None,
+            rng: Rng::new(0),
+            marks_taken: 0,
+            transmitting: HashSet::new(),
+            stats: SwitchStats::default(),
         }
     }
 
@@ -35,47 +78,77 @@ impl Switch for LossySwitch {
 
     fn receive(
         &mut self, 
-        p: Packet, 
-        _l: Link, 
+        mut p: Packet, 
+        l: Link, 
         time: Nanos, 
         logger: Option<&slog::Logger>,
     ) -> Result<Vec<Box<Event>>> {
         self.active = true;
         let id = self.id;
         if let Some(log) = logger {
-            debug!(log, "rx";
+            debug!(log, #PACKET, "rx";
                 "time" => time,
                 "node" => self.id,
+                "from" => l.from,
                 "packet" => ?p,
             );
         }
         // switches are output queued
         match p {
             Packet::Nack{hdr, ..} |
+            Packet::Grant{hdr, ..} |
             Packet::Ack{hdr, ..} |
-            Packet::Data{hdr, ..} => {
-				self.rack
-                    .iter_mut()
-                    .find(|ref q| {
-                        let link_dst = q.link().to;
-                        link_dst == hdr.to
-                    })
-					.map_or_else(|| unimplemented!(), |rack_link_queue| {
-						// send packet out on rack_link_queue
-						if let None = rack_link_queue.enqueue(p) {
-                            // packet was dropped
-                            if let Some(log) = logger {
-                                debug!(log, "dropping";
-                                    "time" => time,
-                                    "node" => id,
-                                    "packet" => ?p,
-                                );
-                            }
-
-                            return;
+            Packet::Data{hdr, ..} |
+            Packet::Mirror{hdr, ..} => {
+                let hop = if self.packet_spraying {
+                    spray_next_hop(&self.routes, &mut self.spray_turns, hdr)
+                } else {
+                    next_hop(&self.routes, hdr)
+                };
+                let (red, rng, marks, early_drops) = (self.red, &mut self.rng, &mut self.marks, &mut self.early_drops);
+                let mark_occupancies = &mut self.mark_occupancies;
+                let stats = &mut self.stats;
+                let q = self.rack.iter_mut().chain(self.core.iter_mut())
+                    .find(|q| q.link().to == hop)
+                    .ok_or_else(|| no_route(id, hop, p))?;
+                let mut early_drop = false;
+                if let (Some(red), &mut Packet::Data{ect, ref mut ce, ..}) = (red, &mut p) {
+                    let occupancy = q.occupancy_bytes();
+                    let probability = red.mark_probability(occupancy);
+                    if probability > 0.0 && rng.next_f64() < probability {
+                        if mark_occupancies.len() == MARK_OCCUPANCY_SAMPLES {
+                            mark_occupancies.pop_front();
                         }
-					});
-                
+
+                        mark_occupancies.push_back(occupancy);
+                        if let Some(log) = logger {
+                            debug!(log, #PACKET, "mark";
+                                "time" => time,
+                                "node" => id,
+                                "port" => hop,
+                                "occupancy" => occupancy,
+                                "ect" => ect,
+                            );
+                        }
+
+                        if ect {
+                            *ce = true;
+                            *marks += 1;
+                        } else {
+                            early_drop = true;
+                            *early_drops += 1;
+                        }
+                    }
+                }
+
+                let dropped = early_drop || q.enqueue(p).is_none();
+                q.trace_occupancy(time);
+                stats.record_occupancy(&**q);
+
+                if dropped {
+                    return Ok(vec![drop_packet(id, l, p, time, stats, logger)]);
+                }
+
                 Ok(vec![])
             }
             _ => Ok(vec![]),
@@ -85,21 +158,31 @@ impl Switch for LossySwitch {
     fn exec(&mut self, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
         // step all queues forward
         let id = self.id;
+        let transmitting = &mut self.transmitting;
         let evs = self.rack.iter_mut().chain(self.core.iter_mut())
-            .filter(|q| {
-                q.is_active()
-            })
             .filter_map(|q| {
+                // a packet arriving while the link is busy waits for reactivate
+                if !q.is_active() || transmitting.contains(&q.link().to) {
+                    return None;
+                }
+
                 q.set_active(false);
                 if let Some(pkt) = q.dequeue() {
+                    transmitting.insert(q.link().to);
                     if let Some(log) = logger {
-                        debug!(log, "tx";
+                        debug!(log, #PACKET, "tx";
                             "time" => time,
                             "node" => id,
                             "packet" => ?pkt,
+                            "port" => q.link().to,
+                            "occupancy" => q.occupancy_bytes(),
+                            "headroom" => q.headroom(),
                         );
                     }
 
+                    q.trace_occupancy(time);
+                    log_queue_length(logger, time, id, &**q);
+
                     Some(
                         Box::new(
                             NodeTransmitEvent(q.link(), pkt)
@@ -114,19 +197,58 @@ impl Switch for LossySwitch {
         Ok(evs)
     }
     
-    fn reactivate(&mut self, l: Link) {
+    fn reactivate(&mut self, l: Link) -> Result<()> {
         assert_eq!(l.from, self.id);
-        self.rack.iter_mut()
+        self.transmitting.remove(&l.to);
+        let id = self.id;
+        let link_queue = self.rack.iter_mut()
             .chain(self.core.iter_mut())
             .find(|q| {
                 q.link().to == l.to
             })
-            .map_or_else(|| unimplemented!(), |link_queue| {
-                link_queue.set_active(true);
-            });
+            .ok_or_else(|| no_route(id, l.to, None))?;
+        link_queue.set_active(true);
+        Ok(())
     }
 
     fn is_active(&self) -> bool {
         self.active
     }
+
+    fn add_route(&mut self, dest: u32, next_hop: u32) {
+        add_next_hop(&mut self.routes, dest, next_hop);
+    }
+
+    fn egress_link(&self, hdr: PacketHeader) -> Option<Link> {
+        // a sprayed flow has no single path; this is the one its first packet takes
+        let hop = next_hop(&self.routes, hdr);
+        self.rack.iter()
+            .chain(self.core.iter())
+            .map(|q| q.link())
+            .find(|l| l.to == hop)
+    }
+
+    fn queue_occupancy(&self, to: u32) -> Option<u32> {
+        self.rack.iter()
+            .chain(self.core.iter())
+            .find(|q| q.link().to == to)
+            .map(|q| q.occupancy_bytes())
+    }
+
+    fn set_cut_through_threshold(&mut self, to: u32, threshold: Option<u32>) {
+        self.rack.iter_mut()
+            .chain(self.core.iter_mut())
+            .filter(|q| q.link().to == to)
+            .for_each(|q| q.set_cut_through_threshold(threshold));
+    }
+
+    fn take_stats(&mut self) -> SwitchStats {
+        let mut stats = ::std::mem::replace(&mut self.stats, SwitchStats::default());
+        stats.marks = SwitchStats::take_since(self.marks, &mut self.marks_taken);
+        stats
+    }
+
+    fn seed_rng(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
 }
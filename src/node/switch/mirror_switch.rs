@@ -0,0 +1,189 @@
+use std::fmt;
+use std::vec::Vec;
+
+use slog;
+
+use ::{Nanos, Result};
+use event::Event;
+use log_filter::PACKET;
+use node::{NodeTransmitEvent, Link};
+use packet::{Packet, PacketHeader};
+use super::{Switch, SwitchStats, PFCSwitchFamily, Queue};
+use super::drop_tail_queue::DropTailQueue;
+
+/// A SPAN port: copies packets matching `filter` onto a dedicated link to an analyzer node.
+struct MirrorPort {
+    queue: Box<Queue>,
+    filter: Box<Fn(&Packet) -> bool>,
+    transmitting: bool, // the link is still serializing a copy
+}
+
+impl fmt::Debug for MirrorPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MirrorPort")
+            .field("queue", &self.queue)
+            .finish()
+    }
+}
+
+/// Wraps a `Switch` with an optional mirror port.
+/// Forwarding is left entirely to the inner switch; mirrored copies use their own queue and
+/// link, so they never contend with forwarded traffic.
+#[derive(Debug)]
+pub struct MirrorSwitch<S: Switch> {
+    pub inner: S,
+    mirror: Option<MirrorPort>,
+}
+
+impl<S: Switch> MirrorSwitch<S> {
+    /// Mirror every packet matching `filter` over `link` (which must start at this switch).
+    /// Copies which do not fit in `queue_length_bytes` are dropped.
+    pub fn mirror(
+        &mut self,
+        link: Link,
+        queue_length_bytes: u32,
+        filter: impl Fn(&Packet) -> bool + 'static,
+    ) {
+        assert_eq!(link.from, self.id());
+        self.mirror = Some(MirrorPort{
+            queue: Box::new(DropTailQueue::new(queue_length_bytes, link)),
+            filter: Box::new(filter),
+            transmitting: false,
+        });
+    }
+}
+
+impl<S: PFCSwitchFamily> PFCSwitchFamily for MirrorSwitch<S> {}
+
+impl<S: Switch> Switch for MirrorSwitch<S> {
+    fn new(
+        switch_id: u32,
+        links: impl Iterator<Item=Box<Queue>>,
+    ) -> Self {
+        MirrorSwitch{
+            inner: S::new(switch_id, links),
+            mirror: None,
+        }
+    }
+
+    fn id(&self) -> u32 {
+        self.inner.id()
+    }
+
+    fn receive(
+        &mut self,
+        p: Packet,
+        l: Link,
+        time: Nanos,
+        logger: Option<&slog::Logger>,
+    ) -> Result<Vec<Box<Event>>> {
+        let id = self.id();
+        if let Some(ref mut port) = self.mirror {
+            if (port.filter)(&p) {
+                let copy = p.mirror(id, port.queue.link().to);
+                if let Some(copy) = copy {
+                    if let None = port.queue.enqueue(copy) {
+                        if let Some(log) = logger {
+                            debug!(log, #PACKET, "dropping mirrored";
+                                "time" => time,
+                                "node" => id,
+                                "packet" => ?copy,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        self.inner.receive(p, l, time, logger)
+    }
+
+    fn process(
+        &mut self,
+        p: Packet,
+        l: Link,
+        time: Nanos,
+        logger: Option<&slog::Logger>,
+    ) -> Result<Vec<Box<Event>>> {
+        self.inner.process(p, l, time, logger)
+    }
+
+    fn exec(&mut self, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        let id = self.id();
+        let mut evs = if self.inner.is_active() {
+            self.inner.exec(time, logger)?
+        } else {
+            vec![]
+        };
+
+        if let Some(ref mut port) = self.mirror {
+            if port.queue.is_active() && !port.transmitting {
+                port.queue.set_active(false);
+                if let Some(pkt) = port.queue.dequeue() {
+                    port.transmitting = true;
+                    if let Some(log) = logger {
+                        debug!(log, #PACKET, "tx";
+                            "time" => time,
+                            "node" => id,
+                            "packet" => ?pkt,
+                            "port" => port.queue.link().to,
+                            "occupancy" => port.queue.occupancy_bytes(),
+                            "headroom" => port.queue.headroom(),
+                        );
+                    }
+
+                    evs.push(Box::new(NodeTransmitEvent(port.queue.link(), pkt)));
+                }
+            }
+        }
+
+        Ok(evs)
+    }
+
+    fn reactivate(&mut self, l: Link) -> Result<()> {
+        // the mirror link may also be one of the inner switch's egress links (as when the
+        // analyzer is an ordinary host), in which case both queues wait on it
+        let mirrored = match self.mirror {
+            Some(ref mut port) if port.queue.link() == l => {
+                port.transmitting = false;
+                port.queue.set_active(true);
+                true
+            }
+            _ => false,
+        };
+
+        if !mirrored || self.inner.queue_occupancy(l.to).is_some() {
+            self.inner.reactivate(l)?;
+        }
+
+        Ok(())
+    }
+
+    fn is_active(&self) -> bool {
+        self.inner.is_active() || self.mirror.as_ref().map_or(false, |port| port.queue.is_active())
+    }
+
+    fn add_route(&mut self, dest: u32, next_hop: u32) {
+        self.inner.add_route(dest, next_hop)
+    }
+
+    fn egress_link(&self, hdr: PacketHeader) -> Option<Link> {
+        self.inner.egress_link(hdr)
+    }
+
+    fn queue_occupancy(&self, to: u32) -> Option<u32> {
+        self.inner.queue_occupancy(to)
+    }
+
+    fn set_cut_through_threshold(&mut self, to: u32, threshold: Option<u32>) {
+        self.inner.set_cut_through_threshold(to, threshold)
+    }
+
+    fn take_stats(&mut self) -> SwitchStats {
+        self.inner.take_stats()
+    }
+
+    fn seed_rng(&mut self, seed: u64) {
+        self.inner.seed_rng(seed)
+    }
+}
@@ -1,30 +1,84 @@
 use std::vec::Vec;
+use std::cmp::max;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::Debug;
+use std::hash::Hash;
 
 use slog;
 
 use ::{Nanos, Result};
+use checkpoint::AsAny;
 use event::Event;
-use node::{Node, Link};
-use packet::Packet;
+use log_filter::PACKET;
+use node::{Node, Link, PacketDropEvent};
+use packet::{Packet, PacketHeader};
 use flow::Flow;
+use loss::LossModel;
+use stats::OccupancyTrace;
    
 /// Queues are tied to a specfic link.
-pub trait Queue : Debug {
+pub trait Queue : Debug + AsAny {
     fn link(&self) -> Link;
     fn enqueue(&mut self, p: Packet) -> Option<()>;
+    /// Send `p` ahead of every queued packet, e.g. a PAUSE or RESUME frame.
+    /// Frames forced one after another leave in the order they were forced.
     fn force_tx_next(&mut self, p: Packet) -> Option<()>;
     fn dequeue(&mut self) -> Option<Packet>;
     fn discard_matching(&mut self, Box<FnMut(Packet) -> bool>) -> usize;
     fn count_matching(&self, Box<FnMut(Packet) -> bool>) -> usize;
     fn headroom(&self) -> u32;
+    /// Bytes of packets currently waiting in the queue.
+    fn occupancy_bytes(&self) -> u32;
+    /// Packets refused so far because they did not fit in the buffer.
+    /// Sum these over a switch's `rack` and `core` for its total; a PFC switch should have none.
+    fn dropped_packets(&self) -> usize;
     fn is_active(&self) -> bool;
     fn set_active(&mut self, a: bool);
     fn is_paused(&self) -> bool;
     fn set_paused(&mut self, a: bool);
+    /// Pause or resume only packets of traffic class `class` (see `Packet::priority`).
+    /// Queues without traffic classes pause or resume entirely.
+    fn set_class_paused(&mut self, _class: u8, paused: bool) {
+        self.set_paused(paused)
+    }
+    /// Headroom left for packets of traffic class `class`.
+    fn class_headroom(&self, _class: u8) -> u32 {
+        self.headroom()
+    }
+    /// Mark `Data` packets with ECN CE when they arrive to find at least `threshold` bytes
+    /// queued (RED with equal min and max thresholds, as DCTCP recommends).
+    /// Packets which are not ECN-capable are dropped instead.
+    /// `None` disables marking.
+    fn set_ecn_threshold(&mut self, threshold: Option<u32>);
+    /// Like `set_ecn_threshold`, but in queueing delay: mark packets which arrive to find at
+    /// least `threshold` worth of bytes to drain at the link's bandwidth.
+    /// The same delay is a larger byte threshold on a faster link; one beyond `u32::MAX` bytes
+    /// never marks.
+    fn set_ecn_delay_threshold(&mut self, threshold: Option<Nanos>) {
+        let bandwidth_bps = u128::from(self.link().bandwidth_bps);
+        self.set_ecn_threshold(threshold.map(|t| {
+            let bytes = u128::from(t) * bandwidth_bps / 8 / 1_000_000_000;
+            ::std::cmp::min(bytes, u128::from(::std::u32::MAX)) as u32
+        }))
+    }
+    /// Additionally drop arriving packets which fit in the buffer when `model` says to.
+    fn set_loss_model(&mut self, model: Box<LossModel>);
+    /// Set the cut-through threshold of this queue's link (see `Link::cut_through_threshold`).
+    fn set_cut_through_threshold(&mut self, threshold: Option<u32>);
+    /// Note the occupancy at `time` in the queue's occupancy trace, if it keeps one (see
+    /// `DropTailQueue::set_occupancy_trace`). Switches call this after every enqueue and dequeue.
+    fn trace_occupancy(&self, _time: Nanos) {}
+    /// The queue's occupancy trace, if it keeps one, e.g. to read a forked run's copy.
+    fn occupancy_trace(&self) -> Option<OccupancyTrace> {
+        None
+    }
 }
 
+pub mod admission;
 pub mod drop_tail_queue;
+pub mod priority_queue;
+pub mod edf_queue;
+pub mod wfq_queue;
 
 pub trait Switch: Debug {
     fn new(
@@ -39,9 +93,195 @@ pub trait Switch: Debug {
         time: Nanos, 
         logger: Option<&slog::Logger>,
     ) -> Result<Vec<Box<Event>>>;
+    /// Handle a packet once its processing delay has elapsed.
+    /// Switches without a processing delay never delay packets, so this is just `receive`.
+    fn process(
+        &mut self, 
+        p: Packet, 
+        l: Link, 
+        time: Nanos, 
+        logger: Option<&slog::Logger>,
+    ) -> Result<Vec<Box<Event>>> {
+        self.receive(p, l, time, logger)
+    }
     fn exec(&mut self, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>>;
-    fn reactivate(&mut self, l: Link);
+    /// Link `l` out of this switch finished transmitting, so its queue may send again.
+    fn reactivate(&mut self, l: Link) -> Result<()>;
     fn is_active(&self) -> bool;
+    /// Forward packets for host `dest` to the neighboring node `next_hop`.
+    /// Adding several next hops for the same `dest` makes them equal-cost paths (ECMP).
+    /// Without a route, packets go straight to `dest`, which must then be a neighbor.
+    fn add_route(&mut self, dest: u32, next_hop: u32);
+    /// The link packets with header `hdr` leave on.
+    fn egress_link(&self, hdr: PacketHeader) -> Option<Link>;
+    /// Bytes waiting in the queue towards neighbor `to`, if this switch has one.
+    fn queue_occupancy(&self, to: u32) -> Option<u32>;
+    /// Set the cut-through threshold of the link towards neighbor `to`, if this switch has one,
+    /// so that `to` cuts through large packets from this switch.
+    fn set_cut_through_threshold(&mut self, to: u32, threshold: Option<u32>);
+    /// The counters accumulated since the last call (or since the switch was built), resetting
+    /// them, so that consecutive calls give per-interval statistics.
+    fn take_stats(&mut self) -> SwitchStats;
+    /// Reseed the switch's random choices (RED marks, random drops, delays, backoffs), if it
+    /// makes any. `Executor::with_seed` calls this with a seed drawn from its own `Rng`.
+    fn seed_rng(&mut self, _seed: u64) {}
+}
+
+/// Counters a switch accumulates between calls to `Switch::take_stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SwitchStats {
+    /// Packets dropped on arrival, e.g. because they did not fit in their output queue.
+    pub drops: u32,
+    /// Packets the switch itself ECN-marked. Marks made by a queue's ECN threshold are not
+    /// counted.
+    pub marks: u32,
+    /// PFC PAUSE frames sent.
+    pub pauses: u32,
+    /// The largest occupancy, in bytes, of any output queue just after a packet arrived at it.
+    pub high_water_bytes: u32,
+}
+
+impl SwitchStats {
+    /// How much `total`, a counter the switch keeps over its whole run, grew since the previous
+    /// call, whose total `taken` remembers. For `take_stats`.
+    pub fn take_since(total: u32, taken: &mut u32) -> u32 {
+        total - ::std::mem::replace(taken, total)
+    }
+
+    /// Note the occupancy of `q` after a packet arrived at it.
+    pub fn record_occupancy(&mut self, q: &Queue) {
+        self.high_water_bytes = max(self.high_water_bytes, q.occupancy_bytes());
+    }
+}
+
+/// The bytes a switch has buffered from each ingress, keyed by `K`: the ingress node, or e.g.
+/// the ingress node and traffic class. It also remembers which ingress each buffered packet
+/// arrived on, so its bytes go back to that ingress when it leaves; identical packets (such as
+/// duplicate ACKs) buffered at once each keep their own, in arrival order.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(bound(serialize = "K: ::serde::Serialize", deserialize = "K: ::serde::Deserialize<'de>"))]
+pub struct IngressAccounting<K: Copy + Eq + Hash> {
+    #[serde(with = "::checkpoint::pairs")]
+    occupancy: HashMap<K, u32>,
+    #[serde(with = "::checkpoint::pairs")]
+    arrived_on: HashMap<Packet, VecDeque<K>>,
+}
+
+impl<K: Copy + Eq + Hash> IngressAccounting<K> {
+    /// Account for packet `p`, from `ingress`, entering the buffer. Returns the bytes now
+    /// buffered from `ingress`.
+    pub fn arrive(&mut self, p: Packet, ingress: K) -> u32 {
+        self.arrived_on.entry(p).or_insert_with(VecDeque::new).push_back(ingress);
+        let occupancy = self.occupancy.entry(ingress).or_insert(0);
+        *occupancy += p.get_size_bytes();
+        *occupancy
+    }
+
+    /// Account for packet `p` leaving the buffer. Returns the ingress it arrived on and the
+    /// bytes still buffered from that ingress, or `None` if `p` never arrived, e.g. because the
+    /// switch made it.
+    pub fn depart(&mut self, p: Packet) -> Option<(K, u32)> {
+        let ingress = {
+            let ingresses = self.arrived_on.get_mut(&p)?;
+            let ingress = ingresses.pop_front()?;
+            if ingresses.is_empty() {
+                self.arrived_on.remove(&p);
+            }
+
+            ingress
+        };
+
+        let occupancy = self.occupancy.get_mut(&ingress)
+            .expect("buffered packet's ingress has no occupancy");
+        *occupancy -= p.get_size_bytes();
+        Some((ingress, *occupancy))
+    }
+
+    /// Bytes currently buffered from `ingress`.
+    pub fn occupancy(&self, ingress: K) -> u32 {
+        self.occupancy.get(&ingress).cloned().unwrap_or(0)
+    }
+}
+
+/// Log a "qlen" line with the occupancy of `q`, the queue at `node` towards port `q.link().to`.
+/// Switches log one as each packet departs; the viz crate plots them per port.
+pub fn log_queue_length(logger: Option<&slog::Logger>, time: Nanos, node: u32, q: &Queue) {
+    if let Some(log) = logger {
+        debug!(log, #PACKET, "qlen";
+            "time" => time,
+            "node" => node,
+            "port" => q.link().to,
+            "occupancy" => q.occupancy_bytes(),
+        );
+    }
+}
+
+/// The neighbor to forward packets with header `hdr` to.
+/// When there are several equal-cost next hops, a hash of the flow and its endpoints picks one,
+/// so each flow takes a single path (and stays in order) while different flows spread out.
+pub fn next_hop(routes: &HashMap<u32, Vec<u32>>, hdr: PacketHeader) -> u32 {
+    match routes.get(&hdr.to) {
+        Some(hops) if !hops.is_empty() => hops[ecmp_hash(hdr) as usize % hops.len()],
+        _ => hdr.to,
+    }
+}
+
+/// The error for a packet `p` which switch `id` should forward to `hop` but has no link to,
+/// e.g. because of a routing bug. Without a packet, e.g. when a link to `hop` frees up, the
+/// switch has no link to `hop` at all.
+pub fn no_route(id: u32, hop: u32, p: impl Into<Option<Packet>>) -> ::failure::Error {
+    match p.into() {
+        Some(p) => format_err!("switch {} has no link to node {} for packet {:?}", id, hop, p),
+        None => format_err!("switch {} has no link to node {}", id, hop),
+    }
+}
+
+/// Count and log the drop of packet `p`, which arrived at switch `id` over link `l`, and
+/// signal the upstream node at the moment of the drop.
+pub fn drop_packet(id: u32, l: Link, p: Packet, time: Nanos, stats: &mut SwitchStats, logger: Option<&slog::Logger>) -> Box<Event> {
+    if let Some(log) = logger {
+        debug!(log, #PACKET, "dropping";
+            "time" => time,
+            "node" => id,
+            "from" => l.from,
+            "packet" => ?p,
+        );
+    }
+
+    stats.drops += 1;
+    Box::new(PacketDropEvent(id, l, p))
+}
+
+/// Like `next_hop`, but successive packets towards a destination take each of its equal-cost
+/// next hops in turn ("packet spraying"), whatever the reordering, so every flow's packets are
+/// spread over all of them. `turns` holds whose turn is next for each destination, so it grows
+/// no larger than `routes`; the first packet takes the hop `next_hop` would.
+pub fn spray_next_hop(routes: &HashMap<u32, Vec<u32>>, turns: &mut HashMap<u32, usize>, hdr: PacketHeader) -> u32 {
+    match routes.get(&hdr.to) {
+        Some(hops) if !hops.is_empty() => {
+            let turn = turns.entry(hdr.to).or_insert(ecmp_hash(hdr) as usize);
+            let hop = hops[*turn % hops.len()];
+            *turn = turn.wrapping_add(1);
+            hop
+        }
+        _ => hdr.to,
+    }
+}
+
+/// Add `next_hop` to the equal-cost next hops for `dest`.
+pub fn add_next_hop(routes: &mut HashMap<u32, Vec<u32>>, dest: u32, next_hop: u32) {
+    let hops = routes.entry(dest).or_insert_with(Vec::new);
+    if !hops.contains(&next_hop) {
+        hops.push(next_hop);
+    }
+}
+
+/// FNV-1a over the flow id, source, and destination, so path choices are the same every run.
+fn ecmp_hash(hdr: PacketHeader) -> u32 {
+    let h = [hdr.flow, hdr.from, hdr.to].iter()
+        .flat_map(|&w| (0..4).map(move |i| (w >> (8 * i)) as u8))
+        .fold(2_166_136_261u32, |h, b| (h ^ u32::from(b)).wrapping_mul(16_777_619));
+    h ^ (h >> 16)
 }
 
 /// Marker trait that indicates to `TopologyStrategy` instances that the links
@@ -63,23 +303,67 @@ impl<S: Switch> Node for S {
         self.receive(p, l, time, logger)
     }
 
+    fn receive_processed(
+        &mut self, 
+        p: Packet, 
+        l: Link, 
+        time: Nanos, 
+        logger: Option<&slog::Logger>,
+    ) -> Result<Vec<Box<Event>>> {
+        self.process(p, l, time, logger)
+    }
+
     fn exec(&mut self, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
         self.exec(time, logger)
     }
 
-    fn reactivate(&mut self, l: Link) {
+    fn reactivate(&mut self, l: Link) -> Result<()> {
         self.reactivate(l)
     }
 
+    fn packet_dropped(&mut self, _at: u32, _p: Packet, _time: Nanos, _logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        // switches do not react to downstream drops
+        Ok(vec![])
+    }
+
     fn flow_arrival(&mut self, _: Box<Flow>) {
         unreachable!()
     }
 
+    fn flow_timer(&mut self, flow_id: u32, _time: Nanos, _logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        bail!("switch {} has no flow {}", self.id(), flow_id)
+    }
+
     fn is_active(&self) -> bool {
         self.is_active()
     }
+
+    fn mtu(&self) -> u32 {
+        unreachable!()
+    }
+
+    fn queue_occupancy(&self, to: u32) -> Option<u32> {
+        self.queue_occupancy(to)
+    }
+
+    fn take_switch_stats(&mut self) -> Option<SwitchStats> {
+        Some(self.take_stats())
+    }
+
+    fn flows(&self) -> &[Box<Flow>] {
+        &[]
+    }
 }
 
 pub mod pfc_switch;
+pub mod credit_switch;
 pub mod lossy_switch;
 pub mod nack_switch;
+pub mod mirror_switch;
+pub mod delay_switch;
+pub mod fabric_switch;
+pub mod trimming_switch;
+pub mod shared_buffer_switch;
+pub mod red;
+pub mod shared_medium;
+pub mod random_loss_switch;
@@ -1,21 +1,44 @@
 use std::vec::Vec;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use slog;
 
 use ::{Nanos, Result};
 use event::Event;
+use log_filter::PACKET;
 use node::{NodeTransmitEvent, Link};
 use packet::{Packet, PacketHeader};
-use super::{Switch, Queue};
+use super::{Switch, SwitchStats, Queue, add_next_hop, drop_packet, next_hop, no_route, log_queue_length};
 
-#[derive(Default, Debug)]
+/// NackSwitch drops a flow's packets once one is dropped, and NACKs the source so it goes back
+/// to the dropped packet.
+///
+/// With `local_retransmission`, it instead keeps packets which do not fit in their output queue
+/// in a retransmission buffer, and retransmits them itself as the queue drains. The source never
+/// learns of the drop, so recovery does not cost it an RTT. With `max_retransmit_buffer`, a
+/// packet which finds the buffer full is dropped instead, and the receiver NACKs the gap.
+///
+/// With `max_blocked_flows`, it remembers at most that many blocked flows, forgetting the least
+/// recently blocked one to make room. A forgotten flow's packets are forwarded again, and its
+/// receiver NACKs the gap instead.
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct NackSwitch {
     pub id: u32,
     pub active: bool,
     pub rack: Vec<Box<Queue>>,
     pub core: Vec<Box<Queue>>,
+    pub routes: HashMap<u32, Vec<u32>>, // dest host id -> equal-cost next hop node ids
     pub blocked_flows: HashMap<u32, u32>, // flow id -> expected seqno
+    blocked_order: VecDeque<u32>, // blocked flow ids, least recently blocked first
+    pub max_blocked_flows: Option<usize>,
+    pub blocked_flows_evicted: u32,
+    pub nacks_sent: u32,
+    pub local_retransmission: bool,
+    pub retransmit_buffer: Vec<(u32, Packet)>, // next hop node id, dropped packet
+    pub max_retransmit_buffer: Option<usize>, // packets
+    pub retransmit_buffer_overflows: u32,
+    transmitting: HashSet<u32>, // ports whose link is still serializing a packet
+    stats: SwitchStats,
 }
 
 impl Switch for NackSwitch {
@@ -28,7 +51,18 @@ impl Switch for NackSwitch {
             active: false,
             rack: links.collect::<Vec<Box<Queue>>>(),
             core: vec![],
+            routes: HashMap::new(),
             blocked_flows: HashMap::new(),
+            blocked_order: VecDeque::new(),
+            max_blocked_flows: None,
+            blocked_flows_evicted: 0,
+            nacks_sent: 0,
+            local_retransmission: false,
+            retransmit_buffer: vec![],
+            max_retransmit_buffer: None,
+            retransmit_buffer_overflows: 0,
+            transmitting: HashSet::new(),
+            stats: SwitchStats::default(),
         }
     }
 
@@ -39,56 +73,105 @@ impl Switch for NackSwitch {
     fn receive(
         &mut self, 
         p: Packet, 
-        _l: Link, 
+        l: Link, 
         time: Nanos, 
         logger: Option<&slog::Logger>,
     ) -> Result<Vec<Box<Event>>> {
         self.active = true;
         let id = self.id;
         if let Some(log) = logger {
-            debug!(log, "rx";
+            debug!(log, #PACKET, "rx";
                 "time" => time,
                 "node" => self.id,
+                "from" => l.from,
                 "packet" => ?p,
             );
         }
         // switches are output queued
         match p {
             Packet::Nack{hdr, ..} |
-            Packet::Ack{hdr, ..} => {
+            Packet::Grant{hdr, ..} |
+            Packet::Ack{hdr, ..} |
+            Packet::Mirror{hdr, ..} => {
+                let mut dropped = false;
+                let hop = next_hop(&self.routes, hdr);
+                let stats = &mut self.stats;
 				self.rack
                     .iter_mut()
                     .find(|ref q| {
                         let link_dst = q.link().to;
-                        link_dst == hdr.to
+                        link_dst == hop
                     })
-					.map_or_else(|| unimplemented!(), |rack_link_queue| {
+					.map(|rack_link_queue| {
 						// send packet out on rack_link_queue
 						if let None = rack_link_queue.enqueue(p) {
-                            // packet was dropped
-                            if let Some(log) = logger {
-                                debug!(log, "dropping";
-                                    "time" => time,
-                                    "node" => id,
-                                    "packet" => ?p,
-                                );
-                            }
-
+                            dropped = true;
                             return;
                         }
-					});
-                
+
+                        rack_link_queue.trace_occupancy(time);
+                        stats.record_occupancy(&**rack_link_queue);
+					})
+                    .ok_or_else(|| no_route(id, hop, p))?;
+
+                if dropped {
+                    return Ok(vec![drop_packet(id, l, p, time, &mut self.stats, logger)]);
+                }
+
+                Ok(vec![])
+            }
+            Packet::Data{hdr, ..} if self.local_retransmission => {
+                let hop = next_hop(&self.routes, hdr);
+                let buffer = &mut self.retransmit_buffer;
+                let stats = &mut self.stats;
+                let enqueued = self.rack
+                    .iter_mut()
+                    .find(|q| q.link().to == hop)
+                    .map(|q| {
+                        retransmit_buffered(buffer, q, hop);
+                        // packets behind a buffered packet of the same flow wait too, to stay in order
+                        let behind_buffered = buffer.iter().any(|(_, b)| match b {
+                            Packet::Data{hdr: buffered, ..} => buffered.flow == hdr.flow,
+                            _ => false,
+                        });
+
+                        let enqueued = !behind_buffered && q.enqueue(p).is_some();
+                        q.trace_occupancy(time);
+                        stats.record_occupancy(&**q);
+                        enqueued
+                    })
+                    .ok_or_else(|| no_route(id, hop, p))?;
+                if !enqueued {
+                    let full = self.max_retransmit_buffer.map_or(false, |max| self.retransmit_buffer.len() >= max);
+                    if full {
+                        self.retransmit_buffer_overflows += 1;
+                        return Ok(vec![drop_packet(id, l, p, time, &mut self.stats, logger)]);
+                    }
+
+                    if let Some(log) = logger {
+                        debug!(log, #PACKET, "buffering for retransmission";
+                            "time" => time,
+                            "node" => id,
+                            "packet" => ?p,
+                        );
+                    }
+
+                    self.retransmit_buffer.push((hop, p));
+                }
+
                 Ok(vec![])
             }
             Packet::Data{hdr, seq, ..} => {
                 let mut progress_flow = false;
                 if let Some(next_expected_seq) = self.blocked_flows.get(&hdr.flow) {
-                    if seq == *next_expected_seq {
+                    // the source may have gone back further than we NACKed, e.g. after a
+                    // timeout, and will resend the expected packet in order from there
+                    if seq <= *next_expected_seq {
                         progress_flow = true;
                     } else {
                         // this packet is going to be retransmitted anyway. drop it
                         if let Some(log) = logger {
-                            debug!(log, "pre-dropping";
+                            debug!(log, #PACKET, "pre-dropping";
                                 "time" => time,
                                 "node" => id,
                                 "packet" => ?p,
@@ -100,23 +183,30 @@ impl Switch for NackSwitch {
                 }
 
                 if progress_flow {
+                    // should this packet be dropped too, the flow is blocked and NACKed again
                     self.blocked_flows.remove(&hdr.flow);
+                    self.blocked_order.retain(|&f| f != hdr.flow);
                 }
 
                 let blocked = &mut self.blocked_flows;
+                let blocked_order = &mut self.blocked_order;
+                let max_blocked = self.max_blocked_flows;
+                let evicted = &mut self.blocked_flows_evicted;
+                let hop = next_hop(&self.routes, hdr);
+                let stats = &mut self.stats;
 				let nack_pkt = self.rack
                     .iter_mut()
                     .find(|ref q| {
                         let link_dst = q.link().to;
-                        link_dst == hdr.to
+                        link_dst == hop
                     })
-					.map_or_else(|| unimplemented!(), |rack_link_queue| {
+					.map(|rack_link_queue| {
 						// send packet out on rack_link_queue
 						if let None = rack_link_queue.enqueue(p) {
                             // add this packet to the list of dropped flows
                             let flow_id_to_drop = hdr.flow;
                             let dropped_seq = seq;
-                            blocked.insert(flow_id_to_drop, seq);
+                            *evicted += block_flow(blocked, blocked_order, max_blocked, flow_id_to_drop, seq);
                             // remove all packets from this flow from this queue
                             let dropped = rack_link_queue.discard_matching(Box::new(move |p| {
                                 match p {
@@ -128,11 +218,11 @@ impl Switch for NackSwitch {
                             }));
                             
                             if let Some(log) = logger {
-                                debug!(log, "dropping";
+                                debug!(log, #PACKET, "discarding";
                                     "time" => time,
                                     "node" => id,
-                                    "packet" => ?p,
-                                    "from_flow" => dropped,
+                                    "flow" => flow_id_to_drop,
+                                    "packets" => dropped,
                                 );
                             }
                             
@@ -142,26 +232,40 @@ impl Switch for NackSwitch {
                                     flow: hdr.flow,
                                     from: hdr.to,
                                     to: hdr.from,
+                                    priority: hdr.priority,
+                                    size_bytes: hdr.size_bytes,
                                 },
                                 nacked_seq: seq,
                             })
                         } else {
+                            rack_link_queue.trace_occupancy(time);
+                            stats.record_occupancy(&**rack_link_queue);
                             None
                         }
-					});
+					})
+                    .ok_or_else(|| no_route(id, hop, p))?;
 
                 if let Some(nack) = nack_pkt {
+                    let nack_hop = match nack {
+                        Packet::Nack{hdr, ..} => next_hop(&self.routes, hdr),
+                        _ => unreachable!(),
+                    };
                     let q = self.rack
                         .iter_mut()
-                        .find(|ref q| {
-                            let link_dst = q.link().to;
-                            match nack {
-                                Packet::Nack{hdr, ..} => link_dst == hdr.to,
-                                _ => unreachable!(),
-                            }
-                        })
-                        .unwrap();
-                    q.enqueue(nack).unwrap();
+                        .find(|ref q| q.link().to == nack_hop)
+                        .ok_or_else(|| no_route(id, nack_hop, nack))?;
+                    if q.enqueue(nack).is_some() {
+                        self.nacks_sent += 1;
+                    } else if let Some(log) = logger {
+                        // the source times out and goes back to its cumulative ACK instead
+                        debug!(log, #PACKET, "dropping nack";
+                            "time" => time,
+                            "node" => id,
+                            "packet" => ?nack,
+                        );
+                    }
+
+                    return Ok(vec![drop_packet(id, l, p, time, &mut self.stats, logger)]);
                 }
                 
                 Ok(vec![])
@@ -173,21 +277,38 @@ impl Switch for NackSwitch {
     fn exec(&mut self, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
         // step all queues forward
         let id = self.id;
+        let transmitting = &mut self.transmitting;
+        let buffer = &mut self.retransmit_buffer;
         let evs = self.rack.iter_mut().chain(self.core.iter_mut())
-            .filter(|q| {
-                q.is_active()
-            })
             .filter_map(|q| {
+                // a packet arriving while the link is busy waits for reactivate
+                if transmitting.contains(&q.link().to) {
+                    return None;
+                }
+
+                // an idle link is never reactivated, so it picks up its buffered packets here
+                retransmit_buffered(buffer, q, q.link().to);
+                if !q.is_active() {
+                    return None;
+                }
+
                 q.set_active(false);
                 if let Some(pkt) = q.dequeue() {
+                    transmitting.insert(q.link().to);
                     if let Some(log) = logger {
-                        debug!(log, "tx";
+                        debug!(log, #PACKET, "tx";
                             "time" => time,
                             "node" => id,
                             "packet" => ?pkt,
+                            "port" => q.link().to,
+                            "occupancy" => q.occupancy_bytes(),
+                            "headroom" => q.headroom(),
                         );
                     }
 
+                    q.trace_occupancy(time);
+                    log_queue_length(logger, time, id, &**q);
+
                     Some(
                         Box::new(
                             NodeTransmitEvent(q.link(), pkt)
@@ -202,19 +323,90 @@ impl Switch for NackSwitch {
         Ok(evs)
     }
     
-    fn reactivate(&mut self, l: Link) {
+    fn reactivate(&mut self, l: Link) -> Result<()> {
         assert_eq!(l.from, self.id);
-        self.rack.iter_mut()
+        self.transmitting.remove(&l.to);
+        let id = self.id;
+        let link_queue = self.rack.iter_mut()
             .chain(self.core.iter_mut())
             .find(|q| {
                 q.link().to == l.to
             })
-            .map_or_else(|| unimplemented!(), |link_queue| {
-                link_queue.set_active(true);
-            });
+            .ok_or_else(|| no_route(id, l.to, None))?;
+        retransmit_buffered(&mut self.retransmit_buffer, link_queue, l.to);
+        link_queue.set_active(true);
+        Ok(())
     }
 
     fn is_active(&self) -> bool {
         self.active
     }
+
+    fn add_route(&mut self, dest: u32, next_hop: u32) {
+        add_next_hop(&mut self.routes, dest, next_hop);
+    }
+
+    fn egress_link(&self, hdr: PacketHeader) -> Option<Link> {
+        let hop = next_hop(&self.routes, hdr);
+        self.rack.iter()
+            .chain(self.core.iter())
+            .map(|q| q.link())
+            .find(|l| l.to == hop)
+    }
+
+    fn queue_occupancy(&self, to: u32) -> Option<u32> {
+        self.rack.iter()
+            .chain(self.core.iter())
+            .find(|q| q.link().to == to)
+            .map(|q| q.occupancy_bytes())
+    }
+
+    fn set_cut_through_threshold(&mut self, to: u32, threshold: Option<u32>) {
+        self.rack.iter_mut()
+            .chain(self.core.iter_mut())
+            .filter(|q| q.link().to == to)
+            .for_each(|q| q.set_cut_through_threshold(threshold));
+    }
+
+    fn take_stats(&mut self) -> SwitchStats {
+        ::std::mem::replace(&mut self.stats, SwitchStats::default())
+    }
+}
+
+/// Remember that `flow` is blocked until `seq` arrives, forgetting the least recently blocked
+/// flows beyond `max`. Returns how many were forgotten.
+fn block_flow(
+    blocked: &mut HashMap<u32, u32>,
+    order: &mut VecDeque<u32>,
+    max: Option<usize>,
+    flow: u32,
+    seq: u32,
+) -> u32 {
+    order.retain(|&f| f != flow);
+    order.push_back(flow);
+    blocked.insert(flow, seq);
+
+    let mut evicted = 0;
+    while max.map_or(false, |max| blocked.len() > max) {
+        match order.pop_front() {
+            Some(oldest) => {
+                blocked.remove(&oldest);
+                evicted += 1;
+            }
+            None => break,
+        }
+    }
+
+    evicted
+}
+
+/// Move packets buffered for `hop` into its queue, in order, as long as they fit.
+fn retransmit_buffered(buffer: &mut Vec<(u32, Packet)>, q: &mut Box<Queue>, hop: u32) {
+    while let Some(i) = buffer.iter().position(|&(h, _)| h == hop) {
+        if q.enqueue(buffer[i].1).is_none() {
+            break;
+        }
+
+        buffer.remove(i);
+    }
 }
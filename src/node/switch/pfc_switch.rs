@@ -1,37 +1,59 @@
 use std::vec::Vec;
+use std::collections::{HashMap, HashSet};
 
 use slog;
 
 use ::{Nanos, Result};
 use event::Event;
+use log_filter::PACKET;
 use node::{NodeTransmitEvent, Link};
-use packet::Packet;
-use super::{Switch, PFCSwitchFamily, Queue};
+use packet::{Packet, PacketHeader};
+use super::{Switch, SwitchStats, PFCSwitchFamily, Queue, IngressAccounting, add_next_hop, drop_packet, next_hop, no_route, log_queue_length};
 
 /// PFCSwitch uses a *static* and *queue-agnostic* PFC threshold.
 /// This means that once the queue headroom decreases below a static threshold, it PAUSEs *all*
 /// incoming queues.
 /// It resumes the incoming queues (all at once) when headroom rises above the static
 /// `pfc_resume_threshold`.
-#[derive(Default, Debug)]
+///
+/// PFC is per traffic class (802.1Qbb): only the class whose headroom ran out is PAUSEd, so
+/// with `PriorityQueue`s other classes keep flowing.
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct PFCSwitch {
     pub id: u32,
     pub active: bool,
-    pub rack: Vec<(Box<Queue>, bool)>, // a queue to send, and whether we have paused the corresponding incoming queue 
-    pub core: Vec<(Box<Queue>, bool)>, // a queue to send, and whether we have paused the corresponding incoming queue 
+    pub rack: Vec<(Box<Queue>, HashSet<u8>)>, // a queue to send, and the classes we have paused on the corresponding incoming queue
+    pub core: Vec<(Box<Queue>, HashSet<u8>)>, // a queue to send, and the classes we have paused on the corresponding incoming queue
+    pub routes: HashMap<u32, Vec<u32>>, // dest host id -> equal-cost next hop node ids
+    /// Bytes of headroom beyond the PAUSE threshold required before resuming.
+    /// `None` uses the link default of 2 MTUs.
+    pub resume_hysteresis_bytes: Option<u32>,
+    pub pauses_sent: u32,
+    pub resumes_sent: u32,
+    pauses_taken: u32, // pauses_sent at the last take_stats
+    transmitting: HashSet<u32>, // ports whose link is still serializing a packet
+    stats: SwitchStats,
 }
 
 impl PFCSwitchFamily for PFCSwitch {}
 
+fn resume_threshold(l: Link, hysteresis: Option<u32>) -> u32 {
+    hysteresis.map_or_else(
+        || l.pfc_resume_threshold(),
+        |h| l.pfc_resume_threshold_with_hysteresis(h),
+    )
+}
+
 impl PFCSwitch {
-    fn pause_incoming(&mut self, _time: Nanos, _logger: Option<&slog::Logger>) {
+    fn pause_incoming(&mut self, class: u8, _time: Nanos, _logger: Option<&slog::Logger>) {
         let id = self.id;
+        let mut sent = 0;
 
         self.rack
             .iter_mut()
             .chain(self.core.iter_mut())
-            .filter(|(_, already_paused)| !already_paused)
-            .for_each(|(q, ref mut already_paused)| {
+            .filter(|(_, paused_classes)| !paused_classes.contains(&class))
+            .for_each(|(q, ref mut paused_classes)| {
                 //   --->
                 // A      B ---> C
                 //   <---
@@ -44,24 +66,29 @@ impl PFCSwitch {
                 // 4. B receives packet P
                 // 5. B sends PAUSE again
                 // send pause to upstream queue
-                *already_paused = true;
-                let to = q.link().to;
-                q.force_tx_next(Packet::Pause(id, to)).unwrap();
+                paused_classes.insert(class);
+                q.force_tx_next(Packet::Pause(id, class as u32)).unwrap();
+                sent += 1;
             });
+
+        self.pauses_sent += sent;
     }
 
-    fn resume_incoming(&mut self, _time: Nanos, _logger: Option<&slog::Logger>) {
+    fn resume_incoming(&mut self, class: u8, _time: Nanos, _logger: Option<&slog::Logger>) {
         let id = self.id;
+        let mut sent = 0;
 
         self.rack
             .iter_mut()
             .chain(self.core.iter_mut())
-            .filter(|(_, already_paused)| *already_paused)
-            .for_each(|(q, ref mut already_paused)| {
-                *already_paused = false;
-                let to = q.link().to;
-                q.force_tx_next(Packet::Resume(id, to)).unwrap();
+            .filter(|(_, paused_classes)| paused_classes.contains(&class))
+            .for_each(|(q, ref mut paused_classes)| {
+                paused_classes.remove(&class);
+                q.force_tx_next(Packet::Resume(id, class as u32)).unwrap();
+                sent += 1;
             });
+
+        self.resumes_sent += sent;
     }
 }
 
@@ -73,8 +100,15 @@ impl Switch for PFCSwitch {
         PFCSwitch{
             id: switch_id,
             active: false,
-            rack: links.map(|q| (q, false)).collect::<Vec<(Box<Queue>, bool)>>(),
+            rack: links.map(|q| (q, HashSet::new())).collect::<Vec<(Box<Queue>, HashSet<u8>)>>(),
             core: vec![],
+            routes: HashMap::new(),
+            resume_hysteresis_bytes: None,
+            pauses_sent: 0,
+            resumes_sent: 0,
+            pauses_taken: 0,
+            transmitting: HashSet::new(),
+            stats: SwitchStats::default(),
         }
     }
 
@@ -85,81 +119,89 @@ impl Switch for PFCSwitch {
     fn receive(
         &mut self, 
         p: Packet, 
-        _l: Link, 
+        l: Link, 
         time: Nanos, 
         logger: Option<&slog::Logger>,
     ) -> Result<Vec<Box<Event>>> {
         self.active = true;
         let id = self.id;
         if let Some(log) = logger {
-            debug!(log, "rx";
+            debug!(log, #PACKET, "rx";
                 "time" => time,
                 "node" => self.id,
+                "from" => l.from,
                 "packet" => ?p,
             );
         }
 
         // switches are output queued
         match p {
-            Packet::Pause(from, _) => {
+            Packet::Pause(_, class) => {
+                // pause the class on the link back to the sender of the PAUSE
 				self.rack
 					.iter_mut()
 					.find(|(ref q, _)| {
-						let link_src = q.link().from;
-						link_src == from
+						q.link().to == l.from
 					})
-					.map_or_else(|| unimplemented!(), |(rack_link_queue, _)| {
-                        rack_link_queue.set_paused(true);
-                    });
+					.map(|(rack_link_queue, _)| {
+                        rack_link_queue.set_class_paused(class as u8, true);
+                    })
+                    .ok_or_else(|| no_route(id, l.from, p))?;
 
                 Ok(vec![])
 			}
-			Packet::Resume(from, _) => {
+			Packet::Resume(_, class) => {
 				self.rack
 					.iter_mut()
 					.find(|(ref q, _)| {
-						let link_src = q.link().from;
-						link_src == from
+						q.link().to == l.from
 					})
-					.map_or_else(|| unimplemented!(), |(rack_link_queue, _)| {
-                        rack_link_queue.set_paused(false);
-                    });
+					.map(|(rack_link_queue, _)| {
+                        rack_link_queue.set_class_paused(class as u8, false);
+                    })
+                    .ok_or_else(|| no_route(id, l.from, p))?;
 
                 Ok(vec![])
 			},
+            // PFC switches do not use credit-based flow control
+            Packet::Credit(_, _) => Ok(vec![]),
             Packet::Nack{hdr, ..} |
+            Packet::Grant{hdr, ..} |
             Packet::Ack{hdr, ..} |
-            Packet::Data{hdr, ..} => {
+            Packet::Data{hdr, ..} |
+            Packet::Mirror{hdr, ..} => {
                 let mut should_pause = false;
+                let mut dropped = false;
+                let hop = next_hop(&self.routes, hdr);
+                let stats = &mut self.stats;
 				self.rack
                     .iter_mut()
                     .find(|(ref q, _)| {
                         let link_dst = q.link().to;
-                        link_dst == hdr.to
+                        link_dst == hop
                     })
-					.map_or_else(|| unimplemented!(), |(rack_link_queue, _)| {
+					.map(|(rack_link_queue, _)| {
 						// send packet out on rack_link_queue
 						if let None = rack_link_queue.enqueue(p) {
-                            // packet was dropped
-                            if let Some(log) = logger {
-                                debug!(log, "dropping";
-                                    "time" => time,
-                                    "node" => id,
-                                    "packet" => ?p,
-                                );
-                            }
-
+                            dropped = true;
                             return;
                         }
 
-                        if rack_link_queue.headroom() <= rack_link_queue.link().pfc_pause_threshold() {
+                        rack_link_queue.trace_occupancy(time);
+                        stats.record_occupancy(&**rack_link_queue);
+                        if rack_link_queue.class_headroom(p.priority()) <= rack_link_queue.link().pfc_pause_threshold() {
                             // outgoing queue has filled up
                             should_pause = true;
                         }
-					});
+					})
+                    .ok_or_else(|| no_route(id, hop, p))?;
                 
                 if should_pause {
-                    self.pause_incoming(time, logger);
+                    self.pause_incoming(p.priority(), time, logger);
+                }
+
+                if dropped {
+                    return Ok(vec![drop_packet(id, l, p, time, &mut self.stats, logger)]);
                 }
 
                 Ok(vec![])
@@ -169,28 +211,44 @@ impl Switch for PFCSwitch {
 
     fn exec(&mut self, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
         // step all queues forward
-        let mut should_resume = false;
+        let mut classes_sent = HashSet::new();
         let id = self.id;
+        let hysteresis = self.resume_hysteresis_bytes;
+        let transmitting = &mut self.transmitting;
         let evs = self.rack.iter_mut().chain(self.core.iter_mut())
-            .filter(|(q, _)| {
-                q.is_active()
-            })
             .filter_map(|(q, _)| {
+                // a packet arriving while the link is busy waits for reactivate
+                if !q.is_active() || transmitting.contains(&q.link().to) {
+                    return None;
+                }
+
                 q.set_active(false);
                 if let Some(pkt) = q.dequeue() {
-                    // check if queue is sufficiently empty
-                    if q.headroom() > q.link().pfc_resume_threshold() {
-                        should_resume = true;
+                    transmitting.insert(q.link().to);
+                    // PAUSE and RESUME frames the switch made itself never took up buffer, so
+                    // sending one drains nothing.
+                    let is_control = match pkt {
+                        Packet::Pause(_, _) | Packet::Resume(_, _) | Packet::Credit(_, _) => true,
+                        _ => false,
+                    };
+                    if !is_control {
+                        classes_sent.insert(pkt.priority());
                     }
 
                     if let Some(log) = logger {
-                        debug!(log, "tx";
+                        debug!(log, #PACKET, "tx";
                             "time" => time,
                             "node" => id,
                             "packet" => ?pkt,
+                            "port" => q.link().to,
+                            "occupancy" => q.occupancy_bytes(),
+                            "headroom" => q.headroom(),
                         );
                     }
 
+                    q.trace_occupancy(time);
+                    log_queue_length(logger, time, id, &**q);
+
                     Some(
                         Box::new(
                             NodeTransmitEvent(q.link(), pkt)
@@ -202,43 +260,96 @@ impl Switch for PFCSwitch {
             })
             .collect::<Vec<Box<Event>>>();
 
-        if should_resume {
-            self.resume_incoming(time, logger);
+        // a class is paused on every ingress, so resume it only once every queue is
+        // sufficiently empty, not as soon as some uncongested port sends one of its packets
+        let classes_to_resume = classes_sent.into_iter()
+            .filter(|&class| {
+                self.rack.iter()
+                    .chain(self.core.iter())
+                    .all(|(q, _)| q.class_headroom(class) > resume_threshold(q.link(), hysteresis))
+            })
+            .collect::<Vec<u8>>();
+        for class in classes_to_resume {
+            self.resume_incoming(class, time, logger);
         }
 
         Ok(evs)
     }
     
-    fn reactivate(&mut self, l: Link) {
+    fn reactivate(&mut self, l: Link) -> Result<()> {
         assert_eq!(l.from, self.id);
-        self.rack.iter_mut()
+        self.transmitting.remove(&l.to);
+        let id = self.id;
+        let (link_queue, _) = self.rack.iter_mut()
             .chain(self.core.iter_mut())
             .find(|(ref q, _)| {
                 q.link().to == l.to
             })
-            .map_or_else(|| unimplemented!(), |(link_queue, _)| {
-                link_queue.set_active(true);
-            });
+            .ok_or_else(|| no_route(id, l.to, None))?;
+        link_queue.set_active(true);
+        Ok(())
     }
 
     fn is_active(&self) -> bool {
         self.active
     }
-}
 
-use std::collections::HashMap;
+    fn add_route(&mut self, dest: u32, next_hop: u32) {
+        add_next_hop(&mut self.routes, dest, next_hop);
+    }
+
+    fn egress_link(&self, hdr: PacketHeader) -> Option<Link> {
+        let hop = next_hop(&self.routes, hdr);
+        self.rack.iter()
+            .chain(self.core.iter())
+            .map(|(q, _)| q.link())
+            .find(|l| l.to == hop)
+    }
+
+    fn queue_occupancy(&self, to: u32) -> Option<u32> {
+        self.rack.iter()
+            .chain(self.core.iter())
+            .find(|(q, _)| q.link().to == to)
+            .map(|(q, _)| q.occupancy_bytes())
+    }
+
+    fn set_cut_through_threshold(&mut self, to: u32, threshold: Option<u32>) {
+        self.rack.iter_mut()
+            .chain(self.core.iter_mut())
+            .filter(|(q, _)| q.link().to == to)
+            .for_each(|(q, _)| q.set_cut_through_threshold(threshold));
+    }
+
+    fn take_stats(&mut self) -> SwitchStats {
+        let mut stats = ::std::mem::replace(&mut self.stats, SwitchStats::default());
+        stats.pauses = SwitchStats::take_since(self.pauses_sent, &mut self.pauses_taken);
+        stats
+    }
+}
 
-#[derive(Default, Debug)]
-pub struct IngressPFCSwitch(PFCSwitch, HashMap<u32, u32>, HashMap<Packet, u32>);
+/// IngressPFCSwitch accounts for egress occupancy per ingress link and traffic class, and
+/// PAUSEs only the class on the ingress link responsible for it.
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct IngressPFCSwitch(PFCSwitch, IngressAccounting<(u32, u8)>); // by ingress node and class
 
 impl PFCSwitchFamily for IngressPFCSwitch {}
 
+impl IngressPFCSwitch {
+    pub fn pauses_sent(&self) -> u32 {
+        self.0.pauses_sent
+    }
+
+    pub fn resumes_sent(&self) -> u32 {
+        self.0.resumes_sent
+    }
+}
+
 impl Switch for IngressPFCSwitch {
     fn new(
         switch_id: u32,
         links: impl Iterator<Item=Box<Queue>>,
     ) -> Self {
-        IngressPFCSwitch(PFCSwitch::new(switch_id, links), HashMap::new(), HashMap::new())
+        IngressPFCSwitch(PFCSwitch::new(switch_id, links), IngressAccounting::default())
     }
     
     fn id(&self) -> u32 {
@@ -254,78 +365,87 @@ impl Switch for IngressPFCSwitch {
     ) -> Result<Vec<Box<Event>>> {
         match p {
             Packet::Pause(_, _) |
-			Packet::Resume(_, _) => {
+			Packet::Resume(_, _) |
+            Packet::Credit(_, _) => {
                 self.0.receive(p, l, time, logger)
 			},
             Packet::Nack{hdr, ..} |
+            Packet::Grant{hdr, ..} |
             Packet::Ack{hdr, ..} |
-            Packet::Data{hdr, ..} => {
+            Packet::Data{hdr, ..} |
+            Packet::Mirror{hdr, ..} => {
                 let id = self.id();
                 self.0.active = true;
-                let ingress_queues = &mut self.1;
-                let ingress_queue_mapping = &mut self.2;
+                let ingress_accounting = &mut self.1;
                 let num_links = self.0.rack.len();
+                let class = p.priority();
                 let mut queue_to_pause: Option<u32> = None;
+                let mut dropped = false;
 
+                let hop = next_hop(&self.0.routes, hdr);
+                let stats = &mut self.0.stats;
 				self.0.rack
                     .iter_mut()
                     .find(|(ref q, _)| {
                         let link_dst = q.link().to;
-                        link_dst == hdr.to
+                        link_dst == hop
                     })
-					.map_or_else(|| unimplemented!(), |(out_queue, _)| {
-                        // already_paused corresponds to the other-direction incoming queue on this
+					.map(|(out_queue, _)| {
+                        // the paused classes correspond to the other-direction incoming queue on this
                         // link
                         //
 						// send packet out on out_queue
 						if let None = out_queue.enqueue(p) {
-                            // packet was dropped
-                            if let Some(log) = logger {
-                                debug!(log, "dropping";
-                                    "time" => time,
-                                    "node" => id,
-                                    "packet" => ?p,
-                                );
-                            }
-
+                            dropped = true;
                             return;
                         } else {
-                            ingress_queue_mapping.entry(p).or_insert(l.from);
-                            let virtual_ingress_queue_occupancy = ingress_queues
-                                .entry(l.from)
-                                .and_modify(|occ| { *occ += p.get_size_bytes(); })
-                                .or_insert(p.get_size_bytes());
-
-                            let per_ingress_static_pfc_thresh = ((out_queue.headroom() - out_queue.link().pfc_pause_threshold()) as f64 / num_links as f64) as u32;
-                            if *virtual_ingress_queue_occupancy > per_ingress_static_pfc_thresh {
+                            out_queue.trace_occupancy(time);
+                            stats.record_occupancy(&**out_queue);
+                            let virtual_ingress_queue_occupancy = ingress_accounting.arrive(p, (l.from, class));
+
+                            let per_ingress_static_pfc_thresh = (out_queue.class_headroom(class).saturating_sub(out_queue.link().pfc_pause_threshold()) as f64 / num_links as f64) as u32;
+                            if virtual_ingress_queue_occupancy > per_ingress_static_pfc_thresh {
                                 // PAUSE this ingress queue
                                 queue_to_pause = Some(l.from);
                             }
 
                             if let Some(log) = logger {
-                                trace!(log, "enqueue";
+                                debug!(log, #PACKET, "enqueue";
+                                    "time" => time,
+                                    "node" => id,
+                                    "ingress" => l.from,
                                     "headroom" => out_queue.headroom(),
-                                    "ingress-occupancy" => *virtual_ingress_queue_occupancy,
+                                    "ingress-occupancy" => virtual_ingress_queue_occupancy,
                                     "in_link" => ?l,
                                     "out_link" => ?out_queue.link(),
                                 );
                             }
                         }
-					});
+					})
+                    .ok_or_else(|| no_route(id, hop, p))?;
 
                 if let Some(to_pause) = queue_to_pause {
+                    let mut sent = false;
                     self.0.rack
                         .iter_mut()
                         .chain(self.0.core.iter_mut())
                         .find(|(q, _)| {
                             q.link().to == to_pause
                         })
-                        .map(|(q, ref mut already_paused)| {
-                            if !*already_paused {
-                                *already_paused = true;
-                                q.force_tx_next(Packet::Pause(id, to_pause)).unwrap();
+                        .map(|(q, ref mut paused_classes)| {
+                            if paused_classes.insert(class) {
+                                q.force_tx_next(Packet::Pause(id, class as u32)).unwrap();
+                                sent = true;
                             }
                         });
+
+                    if sent {
+                        self.0.pauses_sent += 1;
+                    }
+                }
+
+                if dropped {
+                    return Ok(vec![drop_packet(id, l, p, time, &mut self.0.stats, logger)]);
                 }
 
                 Ok(vec![])
@@ -336,49 +456,57 @@ impl Switch for IngressPFCSwitch {
     fn exec(&mut self, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
         // step all queues forward
         let id = self.0.id;
-        let ingress_queues = &mut self.1;
-        let ingress_queue_mapping = &mut self.2;
+        let ingress_accounting = &mut self.1;
         let num_links = self.0.rack.len();
-        let mut queue_to_resume: Option<u32> = None;
+        let hysteresis = self.0.resume_hysteresis_bytes;
+        let mut queue_to_resume: Option<(u32, u8)> = None;
+        let transmitting = &mut self.0.transmitting;
         let evs = self.0.rack.iter_mut().chain(self.0.core.iter_mut())
-            .filter(|(q, _)| {
-                q.is_active()
-            })
             .filter_map(|(q, _)| {
+                // a packet arriving while the link is busy waits for reactivate
+                if !q.is_active() || transmitting.contains(&q.link().to) {
+                    return None;
+                }
+
                 q.set_active(false);
                 if let Some(pkt) = q.dequeue() {
+                    transmitting.insert(q.link().to);
                     if let Some(log) = logger {
-                        debug!(log, "tx";
+                        debug!(log, #PACKET, "tx";
                             "time" => time,
                             "node" => id,
                             "packet" => ?pkt,
+                            "port" => q.link().to,
+                            "occupancy" => q.occupancy_bytes(),
+                            "headroom" => q.headroom(),
                         );
                     }
 
-                    match pkt {
-                        Packet::Data{..} | Packet::Ack{..} | Packet::Nack{..} => {
-                            let ingress_queue = ingress_queue_mapping.remove(&pkt).unwrap();
+                    q.trace_occupancy(time);
+                    log_queue_length(logger, time, id, &**q);
 
-                            let virtual_ingress_queue_occupancy = ingress_queues.entry(ingress_queue)
-                                .and_modify(|occ| { *occ -= pkt.get_size_bytes() })
-                                .or_insert_with(|| unreachable!());
-
-                            let per_ingress_static_pfc_thresh = ((q.headroom() - q.link().pfc_resume_threshold()) as f64 / num_links as f64) as u32;
-                            if *virtual_ingress_queue_occupancy < per_ingress_static_pfc_thresh {
+                    // PAUSE and RESUME frames the switch made itself never arrived
+                    match ingress_accounting.depart(pkt) {
+                        Some((ingress_queue, virtual_ingress_queue_occupancy)) => {
+                            let per_ingress_static_pfc_thresh = (q.class_headroom(pkt.priority()).saturating_sub(resume_threshold(q.link(), hysteresis)) as f64 / num_links as f64) as u32;
+                            if virtual_ingress_queue_occupancy < per_ingress_static_pfc_thresh {
                                 queue_to_resume = Some(ingress_queue);
                             }
 
                             if let Some(log) = logger {
-                                trace!(log, "dequeue";
+                                debug!(log, #PACKET, "dequeue";
+                                    "time" => time,
+                                    "node" => id,
+                                    "ingress" => ingress_queue.0,
                                     "headroom" => q.headroom(),
-                                    "ingress-occupancy" => *virtual_ingress_queue_occupancy,
+                                    "ingress-occupancy" => virtual_ingress_queue_occupancy,
                                     "resume" => ?queue_to_resume,
-                                    "resume-head" => q.link().pfc_resume_threshold(),
+                                    "resume-head" => resume_threshold(q.link(), hysteresis),
                                     "resume-thresh" => per_ingress_static_pfc_thresh,
                                 );
                             }
                         }
-                        _ => {}
+                        None => {}
                     };
                     
                     Some(
@@ -392,29 +520,54 @@ impl Switch for IngressPFCSwitch {
             })
             .collect::<Vec<Box<Event>>>();
 
-        if let Some(to_resume) = queue_to_resume {
+        if let Some((to_resume, class)) = queue_to_resume {
+            let mut sent = false;
             self.0.rack
                 .iter_mut()
                 .chain(self.0.core.iter_mut())
                 .find(|(q, _)| {
                     q.link().to == to_resume
                 })
-                .map(|(q, ref mut already_paused)| {
-                    if *already_paused {
-                        *already_paused = false;
-                        q.force_tx_next(Packet::Resume(id, to_resume)).unwrap();
+                .map(|(q, ref mut paused_classes)| {
+                    if paused_classes.remove(&class) {
+                        q.force_tx_next(Packet::Resume(id, class as u32)).unwrap();
+                        sent = true;
                     }
                 });
+
+            if sent {
+                self.0.resumes_sent += 1;
+            }
         }
 
         Ok(evs)
     }
 
-    fn reactivate(&mut self, l: Link) {
+    fn reactivate(&mut self, l: Link) -> Result<()> {
         self.0.reactivate(l)
     }
 
     fn is_active(&self) -> bool {
         self.0.is_active()
     }
+
+    fn add_route(&mut self, dest: u32, next_hop: u32) {
+        self.0.add_route(dest, next_hop)
+    }
+
+    fn egress_link(&self, hdr: PacketHeader) -> Option<Link> {
+        self.0.egress_link(hdr)
+    }
+
+    fn queue_occupancy(&self, to: u32) -> Option<u32> {
+        self.0.queue_occupancy(to)
+    }
+
+    fn set_cut_through_threshold(&mut self, to: u32, threshold: Option<u32>) {
+        self.0.set_cut_through_threshold(to, threshold)
+    }
+
+    fn take_stats(&mut self) -> SwitchStats {
+        self.0.take_stats()
+    }
 }
@@ -0,0 +1,248 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use loss::{LossContext, LossModel, NoLoss};
+use node::Link;
+use node::switch::Queue;
+use node::switch::drop_tail_queue::DropTailQueue;
+use packet::Packet;
+
+/// A strict-priority output port: one `DropTailQueue` per traffic class, always serving the
+/// highest-priority (lowest-numbered) non-empty class first.
+///
+/// Packets whose priority is beyond the last class go in the last class.
+/// Each class has its own `limit_bytes` buffer, and can be PAUSEd on its own.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PriorityQueue {
+    link: Link,
+    classes: Vec<DropTailQueue>,
+    paused_classes: Vec<bool>,
+    forced_next: VecDeque<Packet>,
+    active: bool,
+    paused: bool,
+    loss_model: Box<LossModel>,
+}
+
+impl PriorityQueue {
+    pub fn new(num_classes: usize, limit_bytes: u32, link: Link) -> Self {
+        assert!(num_classes > 0);
+        PriorityQueue{
+            link,
+            classes: (0..num_classes).map(|_| DropTailQueue::new(limit_bytes, link)).collect(),
+            paused_classes: vec![false; num_classes],
+            forced_next: VecDeque::new(),
+            active: false,
+            paused: false,
+            loss_model: Box::new(NoLoss),
+        }
+    }
+
+    fn class_index(&self, priority: u8) -> usize {
+        ::std::cmp::min(priority as usize, self.classes.len() - 1)
+    }
+
+    fn class(&mut self, p: &Packet) -> &mut DropTailQueue {
+        let i = self.class_index(p.priority());
+        &mut self.classes[i]
+    }
+
+    /// Whether a packet can be dequeued, ignoring paused classes.
+    fn has_sendable(&self) -> bool {
+        !self.forced_next.is_empty() || self.classes.iter()
+            .zip(self.paused_classes.iter())
+            .any(|(q, &paused)| !paused && q.occupancy_bytes() > 0)
+    }
+}
+
+impl Queue for PriorityQueue {
+    fn link(&self) -> Link {
+        self.link
+    }
+
+    fn headroom(&self) -> u32 {
+        self.classes.iter().map(|q| q.headroom()).sum()
+    }
+
+    fn occupancy_bytes(&self) -> u32 {
+        self.classes.iter().map(|q| q.occupancy_bytes()).sum()
+    }
+
+    fn dropped_packets(&self) -> usize {
+        // each class's buffer overflows on its own
+        self.classes.iter().map(|q| q.dropped_packets()).sum()
+    }
+
+    fn enqueue(&mut self, p: Packet) -> Option<()> {
+        let ctx = {
+            let q = self.class(&p);
+            LossContext{
+                occupancy_bytes: q.occupancy_bytes(),
+                limit_bytes: q.occupancy_bytes() + q.headroom(),
+            }
+        };
+
+        if self.loss_model.should_drop(&p, &ctx) {
+            return None;
+        }
+
+        self.class(&p).enqueue(p)?;
+        self.set_active(true);
+        Some(())
+    }
+
+    fn force_tx_next(&mut self, p: Packet) -> Option<()> {
+        self.forced_next.push_back(p);
+        self.set_active(true);
+        Some(())
+    }
+
+    fn dequeue(&mut self) -> Option<Packet> {
+        if let Some(p) = self.forced_next.pop_front() {
+            return Some(p);
+        }
+
+        let p = self.classes.iter_mut()
+            .zip(self.paused_classes.iter())
+            .find(|&(ref q, &paused)| !paused && q.occupancy_bytes() > 0)
+            .and_then(|(q, _)| q.dequeue());
+        if self.occupancy_bytes() == 0 {
+            self.set_active(false);
+        }
+
+        p
+    }
+
+    fn discard_matching(&mut self, should_discard: Box<FnMut(Packet) -> bool>) -> usize {
+        // every class shares the one predicate
+        let should_discard = Rc::new(RefCell::new(should_discard));
+        self.classes.iter_mut()
+            .map(|q| {
+                let should_discard = should_discard.clone();
+                q.discard_matching(Box::new(move |p| (&mut *should_discard.borrow_mut())(p)))
+            })
+            .sum()
+    }
+
+    fn count_matching(&self, counter: Box<FnMut(Packet) -> bool>) -> usize {
+        let counter = Rc::new(RefCell::new(counter));
+        self.classes.iter()
+            .map(|q| {
+                let counter = counter.clone();
+                q.count_matching(Box::new(move |p| (&mut *counter.borrow_mut())(p)))
+            })
+            .sum()
+    }
+
+    fn is_active(&self) -> bool {
+        // stay active while only paused classes have packets, to send them once resumed
+        self.active && !self.paused && self.has_sendable()
+    }
+
+    fn set_active(&mut self, a: bool) {
+        self.active = a;
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn set_paused(&mut self, a: bool) {
+        self.paused = a;
+    }
+
+    fn set_class_paused(&mut self, class: u8, paused: bool) {
+        let i = self.class_index(class);
+        self.paused_classes[i] = paused;
+    }
+
+    fn class_headroom(&self, class: u8) -> u32 {
+        self.classes[self.class_index(class)].headroom()
+    }
+
+    fn set_ecn_threshold(&mut self, threshold: Option<u32>) {
+        self.classes.iter_mut().for_each(|q| q.set_ecn_threshold(threshold));
+    }
+
+    fn set_loss_model(&mut self, model: Box<LossModel>) {
+        self.loss_model = model;
+    }
+
+    fn set_cut_through_threshold(&mut self, threshold: Option<u32>) {
+        self.link.cut_through_threshold = threshold;
+        for q in self.classes.iter_mut() {
+            q.set_cut_through_threshold(threshold);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use node::{Link, switch::Queue};
+    use packet::{Packet, PacketHeader, NO_SACK, PACKET_HEADER_SIZE_BYTES, UNLIMITED_RWND};
+    use super::PriorityQueue;
+
+    #[test]
+    fn strict_priority() {
+        let mut q = PriorityQueue::new(2, 15_000, Link{propagation_delay: 0, bandwidth_bps: 0, mtu: 1500, pfc_enabled: false, cut_through_threshold: None, from: 0, to: 1});
+        let pkt = |seq, priority| Packet::Data{
+            hdr: PacketHeader{
+                flow: priority as u32,
+                from: 0,
+                to: 1,
+                priority,
+                size_bytes: PACKET_HEADER_SIZE_BYTES,
+            },
+            seq,
+            length: 1460,
+            ect: true,
+            ce: false,
+            sent: 0,
+            deadline: None,
+            trimmed: false,
+        };
+
+        q.enqueue(pkt(0, 1)).unwrap();
+        q.enqueue(pkt(1, 1)).unwrap();
+        q.enqueue(pkt(0, 0)).unwrap();
+        q.enqueue(pkt(0, 7)).unwrap(); // beyond the last class
+        assert_eq!(q.headroom(), 15_000 * 2 - 1500 * 4);
+
+        let order = (0..4).map(|_| match q.dequeue() {
+            Some(Packet::Data{hdr, seq, ..}) => (hdr.priority, seq),
+            p => panic!("expected a data packet, got {:?}", p),
+        }).collect::<Vec<(u8, u32)>>();
+        assert_eq!(order, vec![(0, 0), (1, 0), (1, 1), (7, 0)]);
+        assert!(!q.is_active());
+    }
+
+    #[test]
+    fn per_class_pause() {
+        let mut q = PriorityQueue::new(2, 15_000, Link{propagation_delay: 0, bandwidth_bps: 0, mtu: 1500, pfc_enabled: false, cut_through_threshold: None, from: 0, to: 1});
+        let pkt = |priority| Packet::Ack{
+            hdr: PacketHeader{
+                flow: 0,
+                from: 0,
+                to: 1,
+                priority,
+                size_bytes: PACKET_HEADER_SIZE_BYTES,
+            },
+            cumulative_acked_seq: 0,
+            ece: false,
+            sack: NO_SACK,
+            rwnd: UNLIMITED_RWND,
+        };
+
+        q.set_class_paused(0, true);
+        q.enqueue(pkt(0)).unwrap();
+        q.enqueue(pkt(1)).unwrap();
+        assert_eq!(q.class_headroom(0), 15_000 - 40);
+
+        // the paused class is skipped, and waits without deactivating the queue
+        assert_eq!(q.dequeue().map(|p| p.priority()), Some(1));
+        assert!(!q.is_active());
+        q.set_class_paused(0, false);
+        assert!(q.is_active());
+        assert_eq!(q.dequeue().map(|p| p.priority()), Some(0));
+    }
+}
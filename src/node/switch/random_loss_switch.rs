@@ -0,0 +1,128 @@
+use std::vec::Vec;
+
+use slog;
+
+use ::{Nanos, Result};
+use event::Event;
+use log_filter::PACKET;
+use node::{PacketDropEvent, Link};
+use packet::{Packet, PacketHeader};
+use rng::Rng;
+use super::{Switch, SwitchStats, PFCSwitchFamily, Queue};
+
+/// Wraps a `Switch` to drop each arriving `Data` packet with probability `loss_rate`,
+/// independent of queue occupancy, to model bit-error loss rather than congestion loss.
+///
+/// Drops are drawn from an `Rng` seeded by the executor, so runs are reproducible.
+/// ACKs, NACKs, and other control packets are always forwarded.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RandomLossSwitch<S: Switch> {
+    pub inner: S,
+    pub random_drops: u32,
+    loss_rate: f64,
+    rng: Rng,
+    random_drops_taken: u32, // random_drops at the last take_stats
+}
+
+impl<S: Switch> RandomLossSwitch<S> {
+    pub fn set_loss_rate(&mut self, loss_rate: f64) {
+        self.loss_rate = loss_rate;
+    }
+}
+
+impl<S: PFCSwitchFamily> PFCSwitchFamily for RandomLossSwitch<S> {}
+
+impl<S: Switch> Switch for RandomLossSwitch<S> {
+    fn new(
+        switch_id: u32,
+        links: impl Iterator<Item=Box<Queue>>,
+    ) -> Self {
+        RandomLossSwitch{
+            inner: S::new(switch_id, links),
+            random_drops: 0,
+            loss_rate: 0.0,
+            rng: Rng::new(0),
+            random_drops_taken: 0,
+        }
+    }
+
+    fn id(&self) -> u32 {
+        self.inner.id()
+    }
+
+    fn receive(
+        &mut self,
+        p: Packet,
+        l: Link,
+        time: Nanos,
+        logger: Option<&slog::Logger>,
+    ) -> Result<Vec<Box<Event>>> {
+        if let Packet::Data{..} = p {
+            if self.loss_rate > 0.0 && self.rng.next_f64() < self.loss_rate {
+                let id = self.id();
+                if let Some(log) = logger {
+                    debug!(log, #PACKET, "random drop";
+                        "time" => time,
+                        "node" => id,
+                        "from" => l.from,
+                        "packet" => ?p,
+                    );
+                }
+
+                self.random_drops += 1;
+                return Ok(vec![Box::new(PacketDropEvent(id, l, p))]);
+            }
+        }
+
+        self.inner.receive(p, l, time, logger)
+    }
+
+    fn process(
+        &mut self,
+        p: Packet,
+        l: Link,
+        time: Nanos,
+        logger: Option<&slog::Logger>,
+    ) -> Result<Vec<Box<Event>>> {
+        self.inner.process(p, l, time, logger)
+    }
+
+    fn exec(&mut self, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        self.inner.exec(time, logger)
+    }
+
+    fn reactivate(&mut self, l: Link) -> Result<()> {
+        self.inner.reactivate(l)
+    }
+
+    fn is_active(&self) -> bool {
+        self.inner.is_active()
+    }
+
+    fn add_route(&mut self, dest: u32, next_hop: u32) {
+        self.inner.add_route(dest, next_hop)
+    }
+
+    fn egress_link(&self, hdr: PacketHeader) -> Option<Link> {
+        self.inner.egress_link(hdr)
+    }
+
+    fn queue_occupancy(&self, to: u32) -> Option<u32> {
+        self.inner.queue_occupancy(to)
+    }
+
+    fn set_cut_through_threshold(&mut self, to: u32, threshold: Option<u32>) {
+        self.inner.set_cut_through_threshold(to, threshold)
+    }
+
+    fn take_stats(&mut self) -> SwitchStats {
+        let mut stats = self.inner.take_stats();
+        stats.drops += SwitchStats::take_since(self.random_drops, &mut self.random_drops_taken);
+        stats
+    }
+
+    fn seed_rng(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+        self.inner.seed_rng(self.rng.next_u64())
+    }
+}
@@ -0,0 +1,48 @@
+/// How many of the latest marks' and early drops' occupancies `LossySwitch` keeps with RED.
+pub const MARK_OCCUPANCY_SAMPLES: usize = 1024;
+
+/// RED marking thresholds, in bytes of output queue occupancy.
+///
+/// Below `min_threshold_bytes` nothing is marked. Between the thresholds, the marking probability
+/// grows linearly up to `max_probability`, and at or above `max_threshold_bytes` every packet
+/// is marked.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RedParams {
+    pub min_threshold_bytes: u32,
+    pub max_threshold_bytes: u32,
+    pub max_probability: f64,
+}
+
+impl RedParams {
+    /// The probability of marking a packet which arrives to find `occupancy_bytes` queued.
+    pub fn mark_probability(&self, occupancy_bytes: u32) -> f64 {
+        if occupancy_bytes < self.min_threshold_bytes {
+            0.0
+        } else if occupancy_bytes >= self.max_threshold_bytes {
+            1.0
+        } else {
+            let span = (self.max_threshold_bytes - self.min_threshold_bytes) as f64;
+            self.max_probability * (occupancy_bytes - self.min_threshold_bytes) as f64 / span
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RedParams;
+
+    #[test]
+    fn mark_probability_is_linear() {
+        let red = RedParams{
+            min_threshold_bytes: 3_000,
+            max_threshold_bytes: 9_000,
+            max_probability: 0.5,
+        };
+
+        assert_eq!(red.mark_probability(0), 0.0);
+        assert_eq!(red.mark_probability(2_999), 0.0);
+        assert_eq!(red.mark_probability(3_000), 0.0);
+        assert_eq!(red.mark_probability(6_000), 0.25);
+        assert_eq!(red.mark_probability(9_000), 1.0);
+    }
+}
@@ -0,0 +1,212 @@
+use std::vec::Vec;
+use std::collections::{HashMap, HashSet};
+
+use slog;
+
+use ::{Nanos, Result};
+use event::Event;
+use log_filter::PACKET;
+use node::{NodeTransmitEvent, Link};
+use packet::{Packet, PacketHeader};
+use super::{Switch, SwitchStats, Queue, add_next_hop, drop_packet, next_hop, no_route, log_queue_length};
+
+/// A lossy switch whose output queues share one pool of `buffer_bytes`, with dynamic
+/// thresholds (Choudhury and Hahne): a packet is admitted to its output queue only if the
+/// queue would then hold at most `alpha` times the shared buffer still free.
+///
+/// A lone busy port can so take more than an even split of the buffer, and ports share it
+/// more evenly as others fill up; with `n` busy ports, each settles at
+/// `alpha * buffer_bytes / (1 + n * alpha)`. Each queue's own `limit_bytes` still applies too.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SharedBufferSwitch {
+    pub id: u32,
+    pub active: bool,
+    pub rack: Vec<Box<Queue>>,
+    pub core: Vec<Box<Queue>>,
+    pub routes: HashMap<u32, Vec<u32>>, // dest host id -> equal-cost next hop node ids
+    /// Bytes in the pool all output queues draw from.
+    pub buffer_bytes: u32,
+    /// How much of the free shared buffer one queue may grow to.
+    pub alpha: f64,
+    transmitting: HashSet<u32>, // ports whose link is still serializing a packet
+    stats: SwitchStats,
+}
+
+impl SharedBufferSwitch {
+    /// A switch whose queues on `links` share `buffer_bytes` with threshold factor `alpha`.
+    /// `Switch::new` pools the queues' limits, with an `alpha` of 1.
+    pub fn with_shared_buffer(
+        switch_id: u32,
+        links: impl Iterator<Item=Box<Queue>>,
+        buffer_bytes: u32,
+        alpha: f64,
+    ) -> Self {
+        assert!(alpha > 0.0, "alpha must be positive");
+        SharedBufferSwitch{
+            id: switch_id,
+            active: false,
+            rack: links.collect::<Vec<Box<Queue>>>(),
+            core: vec![],
+            routes: HashMap::new(),
+            buffer_bytes,
+            alpha,
+            transmitting: HashSet::new(),
+            stats: SwitchStats::default(),
+        }
+    }
+
+    /// Bytes queued at all output ports.
+    pub fn shared_occupancy(&self) -> u32 {
+        self.rack.iter()
+            .chain(self.core.iter())
+            .map(|q| q.occupancy_bytes())
+            .sum()
+    }
+}
+
+impl Switch for SharedBufferSwitch {
+    fn new(
+        switch_id: u32,
+        links: impl Iterator<Item=Box<Queue>>,
+    ) -> Self {
+        let queues = links.collect::<Vec<Box<Queue>>>();
+        let buffer_bytes = queues.iter().map(|q| q.headroom()).sum();
+        SharedBufferSwitch::with_shared_buffer(switch_id, queues.into_iter(), buffer_bytes, 1.0)
+    }
+
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn receive(
+        &mut self,
+        p: Packet,
+        l: Link,
+        time: Nanos,
+        logger: Option<&slog::Logger>,
+    ) -> Result<Vec<Box<Event>>> {
+        self.active = true;
+        let id = self.id;
+        if let Some(log) = logger {
+            debug!(log, #PACKET, "rx";
+                "time" => time,
+                "node" => self.id,
+                "from" => l.from,
+                "packet" => ?p,
+            );
+        }
+
+        let hdr = match p {
+            Packet::Nack{hdr, ..} |
+            Packet::Grant{hdr, ..} |
+            Packet::Ack{hdr, ..} |
+            Packet::Data{hdr, ..} |
+            Packet::Mirror{hdr, ..} => hdr,
+            _ => return Ok(vec![]),
+        };
+
+        let free = self.buffer_bytes.saturating_sub(self.shared_occupancy());
+        let threshold = self.alpha * f64::from(free);
+
+        // switches are output queued
+        let hop = next_hop(&self.routes, hdr);
+        let q = self.rack.iter_mut().chain(self.core.iter_mut())
+            .find(|q| q.link().to == hop)
+            .ok_or_else(|| no_route(id, hop, p))?;
+
+        let size = p.get_size_bytes();
+        let admitted = size <= free
+            && f64::from(q.occupancy_bytes() + size) <= threshold
+            && q.enqueue(p).is_some();
+        if !admitted {
+            return Ok(vec![drop_packet(id, l, p, time, &mut self.stats, logger)]);
+        }
+
+        q.trace_occupancy(time);
+        self.stats.record_occupancy(&**q);
+        Ok(vec![])
+    }
+
+    fn exec(&mut self, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        // step all queues forward
+        let id = self.id;
+        let transmitting = &mut self.transmitting;
+        let evs = self.rack.iter_mut().chain(self.core.iter_mut())
+            .filter_map(|q| {
+                // a packet arriving while the link is busy waits for reactivate
+                if !q.is_active() || transmitting.contains(&q.link().to) {
+                    return None;
+                }
+
+                q.set_active(false);
+                q.dequeue().map(|pkt| {
+                    transmitting.insert(q.link().to);
+                    if let Some(log) = logger {
+                        debug!(log, #PACKET, "tx";
+                            "time" => time,
+                            "node" => id,
+                            "packet" => ?pkt,
+                            "port" => q.link().to,
+                            "occupancy" => q.occupancy_bytes(),
+                            "headroom" => q.headroom(),
+                        );
+                    }
+
+                    q.trace_occupancy(time);
+                    log_queue_length(logger, time, id, &**q);
+                    Box::new(NodeTransmitEvent(q.link(), pkt)) as Box<Event>
+                })
+            })
+            .collect::<Vec<Box<Event>>>();
+
+        Ok(evs)
+    }
+
+    fn reactivate(&mut self, l: Link) -> Result<()> {
+        assert_eq!(l.from, self.id);
+        self.transmitting.remove(&l.to);
+        let id = self.id;
+        let link_queue = self.rack.iter_mut()
+            .chain(self.core.iter_mut())
+            .find(|q| {
+                q.link().to == l.to
+            })
+            .ok_or_else(|| no_route(id, l.to, None))?;
+        link_queue.set_active(true);
+        Ok(())
+    }
+
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn add_route(&mut self, dest: u32, next_hop: u32) {
+        add_next_hop(&mut self.routes, dest, next_hop);
+    }
+
+    fn egress_link(&self, hdr: PacketHeader) -> Option<Link> {
+        let hop = next_hop(&self.routes, hdr);
+        self.rack.iter()
+            .chain(self.core.iter())
+            .map(|q| q.link())
+            .find(|l| l.to == hop)
+    }
+
+    fn queue_occupancy(&self, to: u32) -> Option<u32> {
+        self.rack.iter()
+            .chain(self.core.iter())
+            .find(|q| q.link().to == to)
+            .map(|q| q.occupancy_bytes())
+    }
+
+    fn set_cut_through_threshold(&mut self, to: u32, threshold: Option<u32>) {
+        self.rack.iter_mut()
+            .chain(self.core.iter_mut())
+            .filter(|q| q.link().to == to)
+            .for_each(|q| q.set_cut_through_threshold(threshold));
+    }
+
+    fn take_stats(&mut self) -> SwitchStats {
+        ::std::mem::replace(&mut self.stats, SwitchStats::default())
+    }
+}
@@ -0,0 +1,336 @@
+use std::vec::Vec;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use slog;
+
+use ::{Nanos, Result};
+use event::Event;
+use log_filter::PACKET;
+use node::{NodeTransmitEvent, ProcessingDoneEvent, Link};
+use packet::{Packet, PacketHeader};
+use rng::Rng;
+use super::{Switch, SwitchStats, Queue, add_next_hop, drop_packet, next_hop, no_route, log_queue_length};
+
+/// Transmissions are abandoned after this many collisions, as in Ethernet.
+pub const MAX_TRANSMISSION_ATTEMPTS: u32 = 16;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Transmission {
+    station: u32, // the link source which sent it; each has one transmission at a time
+    start: Nanos, // when the packet started occupying the medium
+    end: Nanos,
+    collided: bool,
+}
+
+/// A station's transmitter. It has at most one packet on, or backing off from, the medium at a
+/// time; packets it sends meanwhile wait behind it in `backlog`, in order.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Station {
+    busy: bool,
+    attempts: u32, // collisions so far of the packet being transmitted
+    backlog: VecDeque<Packet>,
+}
+
+/// A shared medium (CSMA/CD-like) which the hosts connected to it contend for.
+///
+/// Every link into the medium is one station's transmitter, and only one transmission can
+/// occupy the medium at a time. A station which hears an ongoing transmission defers until
+/// the medium is idle. Transmissions starting within two propagation delays of each other
+/// cannot hear each other and collide: both are lost, and each sender retries after a binary
+/// exponential backoff of 512-bit slots, drawn from an `Rng` seeded by the executor.
+/// While a station defers or backs off, the packets it sends after the one it is retrying
+/// queue behind that one, so each station's packets reach the medium in order.
+///
+/// A transmission is only known to be collision-free once the collision window after it has
+/// passed, so successful transmissions are forwarded to their destination that much later.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SharedMedium {
+    pub id: u32,
+    pub active: bool,
+    pub rack: Vec<Box<Queue>>,
+    pub core: Vec<Box<Queue>>,
+    pub routes: HashMap<u32, Vec<u32>>, // dest host id -> equal-cost next hop node ids
+    pub collisions: u32,
+    pub backoffs: u32,
+    pub deferrals: u32,
+    in_flight: Vec<Transmission>,
+    stations: HashMap<u32, Station>, // station (link source) id -> its transmitter
+    rng: Rng,
+    transmitting: HashSet<u32>, // ports whose link is still serializing a packet
+    stats: SwitchStats,
+}
+
+impl SharedMedium {
+    /// Station `l.from` finishes putting `p` onto the medium at `time`, unless it defers.
+    /// Either way, the medium hears back about `p` (in `process`) after a delay.
+    fn transmit(&mut self, p: Packet, l: Link, time: Nanos, logger: Option<&slog::Logger>) -> Vec<Box<Event>> {
+        let tx = l.transmission_delay(p.get_size_bytes());
+        let start = time - tx;
+        let vulnerable = 2 * l.propagation_delay;
+
+        // carrier sense: the sender heard a transmission which started early enough
+        let busy_until = self.in_flight.iter()
+            .filter(|t| t.end > start && t.start + vulnerable <= start)
+            .map(|t| t.end)
+            .max();
+        if let Some(busy_until) = busy_until {
+            self.deferrals += 1;
+            return vec![Box::new(ProcessingDoneEvent(self.id, l, p, busy_until + tx - time))];
+        }
+
+        let mut collided = false;
+        for t in self.in_flight.iter_mut().filter(|t| t.end > start) {
+            t.collided = true;
+            collided = true;
+        }
+
+        if collided {
+            if let Some(log) = logger {
+                debug!(log, #PACKET, "collision";
+                    "time" => time,
+                    "node" => self.id,
+                    "packet" => ?p,
+                );
+            }
+
+            self.collisions += 1;
+        }
+
+        self.in_flight.push(Transmission{station: l.from, start, end: time, collided});
+        // wait out the collision window before forwarding
+        let window = vulnerable + l.transmission_delay(l.mtu);
+        vec![Box::new(ProcessingDoneEvent(self.id, l, p, window))]
+    }
+
+    /// Station `l.from` is done with its current packet: start on the next one it sent, if any.
+    fn next_from_station(&mut self, l: Link) -> Vec<Box<Event>> {
+        let station = self.stations.entry(l.from).or_insert_with(Station::default);
+        station.attempts = 0;
+        match station.backlog.pop_front() {
+            Some(p) => {
+                let tx = l.transmission_delay(p.get_size_bytes());
+                vec![Box::new(ProcessingDoneEvent(self.id, l, p, tx)) as Box<Event>]
+            }
+            None => {
+                station.busy = false;
+                vec![]
+            }
+        }
+    }
+}
+
+impl Switch for SharedMedium {
+    fn new(
+        switch_id: u32,
+        links: impl Iterator<Item=Box<Queue>>,
+    ) -> Self {
+        SharedMedium{
+            id: switch_id,
+            active: false,
+            rack: links.collect::<Vec<Box<Queue>>>(),
+            core: vec![],
+            routes: HashMap::new(),
+            collisions: 0,
+            backoffs: 0,
+            deferrals: 0,
+            in_flight: vec![],
+            stations: HashMap::new(),
+            rng: Rng::new(0),
+            transmitting: HashSet::new(),
+            stats: SwitchStats::default(),
+        }
+    }
+
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn receive(
+        &mut self, 
+        p: Packet, 
+        l: Link, 
+        time: Nanos, 
+        logger: Option<&slog::Logger>,
+    ) -> Result<Vec<Box<Event>>> {
+        self.active = true;
+        if let Some(log) = logger {
+            debug!(log, #PACKET, "rx";
+                "time" => time,
+                "node" => self.id,
+                "from" => l.from,
+                "packet" => ?p,
+            );
+        }
+
+        match p {
+            Packet::Pause(_, _) | Packet::Resume(_, _) | Packet::Credit(_, _) => return Ok(vec![]),
+            _ => (),
+        }
+
+        {
+            let station = self.stations.entry(l.from).or_insert_with(Station::default);
+            if station.busy {
+                station.backlog.push_back(p);
+                return Ok(vec![]);
+            }
+
+            station.busy = true;
+        }
+
+        Ok(self.transmit(p, l, time, logger))
+    }
+
+    fn process(
+        &mut self, 
+        p: Packet, 
+        l: Link, 
+        time: Nanos, 
+        logger: Option<&slog::Logger>,
+    ) -> Result<Vec<Box<Event>>> {
+        let id = self.id;
+        let i = match self.in_flight.iter().position(|t| t.station == l.from) {
+            Some(i) => i,
+            // the station's deferral or backoff is over
+            None => return Ok(self.transmit(p, l, time, logger)),
+        };
+        let transmission = self.in_flight.remove(i);
+
+        if transmission.collided {
+            let attempt = {
+                let station = self.stations.entry(l.from).or_insert_with(Station::default);
+                station.attempts += 1;
+                station.attempts
+            };
+
+            if attempt >= MAX_TRANSMISSION_ATTEMPTS {
+                let mut evs = vec![drop_packet(id, l, p, time, &mut self.stats, logger)];
+                evs.extend(self.next_from_station(l));
+                return Ok(evs);
+            }
+
+            // binary exponential backoff, capped at 2^10 slots
+            let slots = self.rng.next_u64() % (1 << ::std::cmp::min(attempt, 10));
+            let backoff = slots * l.transmission_delay(64);
+            self.backoffs += 1;
+            let tx = l.transmission_delay(p.get_size_bytes());
+            return Ok(vec![Box::new(ProcessingDoneEvent(id, l, p, backoff + tx))]);
+        }
+
+        let mut evs = self.next_from_station(l);
+        let hdr = match p {
+            Packet::Data{hdr, ..} |
+            Packet::Ack{hdr, ..} |
+            Packet::Nack{hdr, ..} |
+            Packet::Grant{hdr, ..} |
+            Packet::Mirror{hdr, ..} => hdr,
+            _ => unreachable!(),
+        };
+
+        let hop = next_hop(&self.routes, hdr);
+        let stats = &mut self.stats;
+        let q = self.rack.iter_mut().chain(self.core.iter_mut())
+            .find(|q| q.link().to == hop)
+            .ok_or_else(|| no_route(id, hop, p))?;
+        let dropped = q.enqueue(p).is_none();
+        q.trace_occupancy(time);
+        stats.record_occupancy(&**q);
+        if dropped {
+            evs.push(drop_packet(id, l, p, time, stats, logger));
+        }
+
+        Ok(evs)
+    }
+
+    fn exec(&mut self, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        // step all queues forward
+        let id = self.id;
+        let transmitting = &mut self.transmitting;
+        let evs = self.rack.iter_mut().chain(self.core.iter_mut())
+            .filter_map(|q| {
+                // a packet arriving while the link is busy waits for reactivate
+                if !q.is_active() || transmitting.contains(&q.link().to) {
+                    return None;
+                }
+
+                q.set_active(false);
+                if let Some(pkt) = q.dequeue() {
+                    transmitting.insert(q.link().to);
+                    if let Some(log) = logger {
+                        debug!(log, #PACKET, "tx";
+                            "time" => time,
+                            "node" => id,
+                            "packet" => ?pkt,
+                            "port" => q.link().to,
+                            "occupancy" => q.occupancy_bytes(),
+                            "headroom" => q.headroom(),
+                        );
+                    }
+
+                    q.trace_occupancy(time);
+                    log_queue_length(logger, time, id, &**q);
+                    Some(
+                        Box::new(
+                            NodeTransmitEvent(q.link(), pkt)
+                        ) as Box<Event>,
+                    )
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<Box<Event>>>();
+
+        Ok(evs)
+    }
+    
+    fn reactivate(&mut self, l: Link) -> Result<()> {
+        assert_eq!(l.from, self.id);
+        self.transmitting.remove(&l.to);
+        let id = self.id;
+        let link_queue = self.rack.iter_mut()
+            .chain(self.core.iter_mut())
+            .find(|q| {
+                q.link().to == l.to
+            })
+            .ok_or_else(|| no_route(id, l.to, None))?;
+        link_queue.set_active(true);
+        Ok(())
+    }
+
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn add_route(&mut self, dest: u32, next_hop: u32) {
+        add_next_hop(&mut self.routes, dest, next_hop);
+    }
+
+    fn egress_link(&self, hdr: PacketHeader) -> Option<Link> {
+        let hop = next_hop(&self.routes, hdr);
+        self.rack.iter()
+            .chain(self.core.iter())
+            .map(|q| q.link())
+            .find(|l| l.to == hop)
+    }
+
+    fn queue_occupancy(&self, to: u32) -> Option<u32> {
+        self.rack.iter()
+            .chain(self.core.iter())
+            .find(|q| q.link().to == to)
+            .map(|q| q.occupancy_bytes())
+    }
+
+    fn set_cut_through_threshold(&mut self, to: u32, threshold: Option<u32>) {
+        self.rack.iter_mut()
+            .chain(self.core.iter_mut())
+            .filter(|q| q.link().to == to)
+            .for_each(|q| q.set_cut_through_threshold(threshold));
+    }
+
+    fn take_stats(&mut self) -> SwitchStats {
+        ::std::mem::replace(&mut self.stats, SwitchStats::default())
+    }
+
+    fn seed_rng(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+}
@@ -0,0 +1,219 @@
+use std::vec::Vec;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use slog;
+
+use ::{Nanos, Result};
+use event::Event;
+use log_filter::PACKET;
+use node::{NodeTransmitEvent, Link};
+use packet::{Packet, PacketHeader};
+use super::{Switch, SwitchStats, Queue, add_next_hop, drop_packet, next_hop, no_route, log_queue_length};
+
+/// A lossy switch which trims rather than drops (as in NDP): a `Data` packet which does not
+/// fit in its output queue loses its payload, and the header left over (`length` 0, `trimmed`
+/// set) goes out ahead of any queued data, so the receiver learns of the loss within about
+/// an RTT.
+///
+/// Headers and control packets (ACKs, NACKs, grants) share a separate per-port queue of up to
+/// `header_queue_limit` packets, served first. Packets are only dropped outright when that
+/// queue is full too.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrimmingSwitch {
+    pub id: u32,
+    pub active: bool,
+    pub rack: Vec<Box<Queue>>,
+    pub core: Vec<Box<Queue>>,
+    pub routes: HashMap<u32, Vec<u32>>, // dest host id -> equal-cost next hop node ids
+    pub header_queue_limit: usize,
+    /// Payloads cut off so far.
+    pub trimmed: u32,
+    headers: HashMap<u32, VecDeque<Packet>>, // port -> headers and control packets, in arrival order
+    transmitting: HashSet<u32>, // ports whose link is still serializing a packet
+    stats: SwitchStats,
+}
+
+impl Switch for TrimmingSwitch {
+    fn new(
+        switch_id: u32,
+        links: impl Iterator<Item=Box<Queue>>,
+    ) -> Self {
+        TrimmingSwitch{
+            id: switch_id,
+            active: false,
+            rack: links.collect::<Vec<Box<Queue>>>(),
+            core: vec![],
+            routes: HashMap::new(),
+            header_queue_limit: 100,
+            trimmed: 0,
+            headers: HashMap::new(),
+            transmitting: HashSet::new(),
+            stats: SwitchStats::default(),
+        }
+    }
+
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn receive(
+        &mut self,
+        p: Packet,
+        l: Link,
+        time: Nanos,
+        logger: Option<&slog::Logger>,
+    ) -> Result<Vec<Box<Event>>> {
+        self.active = true;
+        let id = self.id;
+        if let Some(log) = logger {
+            debug!(log, #PACKET, "rx";
+                "time" => time,
+                "node" => self.id,
+                "from" => l.from,
+                "packet" => ?p,
+            );
+        }
+
+        let hdr = match p {
+            Packet::Nack{hdr, ..} |
+            Packet::Grant{hdr, ..} |
+            Packet::Ack{hdr, ..} |
+            Packet::Data{hdr, ..} |
+            Packet::Mirror{hdr, ..} => hdr,
+            _ => return Ok(vec![]),
+        };
+
+        // switches are output queued
+        let hop = next_hop(&self.routes, hdr);
+        let q = self.rack.iter_mut().chain(self.core.iter_mut())
+            .find(|q| q.link().to == hop)
+            .ok_or_else(|| no_route(id, hop, p))?;
+        let header = match p {
+            Packet::Data{trimmed: false, ..} | Packet::Mirror{..} => {
+                if let Some(_) = q.enqueue(p) {
+                    q.trace_occupancy(time);
+                    self.stats.record_occupancy(&**q);
+                    return Ok(vec![]);
+                }
+
+                match p {
+                    Packet::Data{hdr, seq, ect, ce, sent, deadline, ..} => {
+                        self.trimmed += 1;
+                        if let Some(log) = logger {
+                            debug!(log, #PACKET, "trimming";
+                                "time" => time,
+                                "node" => id,
+                                "packet" => ?p,
+                            );
+                        }
+
+                        Some(Packet::Data{hdr, seq, length: 0, ect, ce, sent, deadline, trimmed: true})
+                    }
+                    _ => None,
+                }
+            }
+            _ => Some(p),
+        };
+
+        let limit = self.header_queue_limit;
+        let headers = self.headers.entry(hop).or_insert_with(VecDeque::new);
+        match header {
+            Some(h) if headers.len() < limit => {
+                headers.push_back(h);
+                Ok(vec![])
+            }
+            _ => {
+                // the header queue is full too (or a mirrored copy did not fit): the packet is lost
+                Ok(vec![drop_packet(id, l, p, time, &mut self.stats, logger)])
+            }
+        }
+    }
+
+    fn exec(&mut self, time: Nanos, logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        // each free port sends its next header, or else its next queued packet
+        let id = self.id;
+        let headers = &mut self.headers;
+        let transmitting = &mut self.transmitting;
+        let evs = self.rack.iter_mut().chain(self.core.iter_mut())
+            .filter_map(|q| {
+                let port = q.link().to;
+                if transmitting.contains(&port) {
+                    return None;
+                }
+
+                let pkt = match headers.get_mut(&port).and_then(|h| h.pop_front()) {
+                    Some(h) => Some(h),
+                    None if q.is_active() => {
+                        let next = q.dequeue();
+                        if next.is_none() {
+                            q.set_active(false);
+                        }
+
+                        next
+                    }
+                    None => None,
+                };
+
+                pkt.map(|pkt| {
+                    if let Some(log) = logger {
+                        debug!(log, #PACKET, "tx";
+                            "time" => time,
+                            "node" => id,
+                            "packet" => ?pkt,
+                            "port" => q.link().to,
+                            "occupancy" => q.occupancy_bytes(),
+                            "headroom" => q.headroom(),
+                        );
+                    }
+
+                    q.trace_occupancy(time);
+                    log_queue_length(logger, time, id, &**q);
+                    transmitting.insert(port);
+                    Box::new(NodeTransmitEvent(q.link(), pkt)) as Box<Event>
+                })
+            })
+            .collect::<Vec<Box<Event>>>();
+
+        Ok(evs)
+    }
+
+    fn reactivate(&mut self, l: Link) -> Result<()> {
+        assert_eq!(l.from, self.id);
+        self.transmitting.remove(&l.to);
+        Ok(())
+    }
+
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn add_route(&mut self, dest: u32, next_hop: u32) {
+        add_next_hop(&mut self.routes, dest, next_hop);
+    }
+
+    fn egress_link(&self, hdr: PacketHeader) -> Option<Link> {
+        let hop = next_hop(&self.routes, hdr);
+        self.rack.iter()
+            .chain(self.core.iter())
+            .map(|q| q.link())
+            .find(|l| l.to == hop)
+    }
+
+    fn queue_occupancy(&self, to: u32) -> Option<u32> {
+        self.rack.iter()
+            .chain(self.core.iter())
+            .find(|q| q.link().to == to)
+            .map(|q| q.occupancy_bytes())
+    }
+
+    fn set_cut_through_threshold(&mut self, to: u32, threshold: Option<u32>) {
+        self.rack.iter_mut()
+            .chain(self.core.iter_mut())
+            .filter(|q| q.link().to == to)
+            .for_each(|q| q.set_cut_through_threshold(threshold));
+    }
+
+    fn take_stats(&mut self) -> SwitchStats {
+        ::std::mem::replace(&mut self.stats, SwitchStats::default())
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::{HashMap, VecDeque};
+
+use loss::LossModel;
+use node::Link;
+use node::switch::Queue;
+use node::switch::admission::Admission;
+use packet::Packet;
+
+/// Virtual time advanced by one byte of a weight 1 flow.
+const BYTE_VIRTUAL_TIME: u64 = 1_000_000;
+
+/// A weighted fair queueing output port (self-clocked fair queueing): backlogged flows share
+/// the link in proportion to their weights, set with `set_weight`. Flows default to weight 1.
+///
+/// Each arriving packet is stamped with the virtual time at which it would finish if its flow
+/// were served at its weighted rate, and the packet with the earliest finish leaves next.
+/// Packets without a flow, e.g. PAUSE/RESUME and credit frames, count as flow 0's.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WfqQueue {
+    link: Link,
+    admission: Admission,
+    pkts: Vec<(u64, Packet)>, // (virtual finish time, packet), in arrival order
+    weights: HashMap<u32, u32>,
+    last_finish: HashMap<u32, u64>, // flow id -> virtual finish time of its last packet, for flows with packets queued
+    virtual_time: u64,
+    forced_next: VecDeque<Packet>,
+    active: bool,
+    paused: bool,
+}
+
+impl WfqQueue {
+    pub fn new(limit_bytes: u32, link: Link) -> Self {
+        WfqQueue{
+            link,
+            admission: Admission::new(limit_bytes),
+            pkts: vec![],
+            weights: HashMap::new(),
+            last_finish: HashMap::new(),
+            virtual_time: 0,
+            forced_next: VecDeque::new(),
+            active: false,
+            paused: false,
+        }
+    }
+
+    pub fn set_weight(&mut self, flow_id: u32, weight: u32) {
+        assert!(weight > 0, "flow weights must be nonzero");
+        self.weights.insert(flow_id, weight);
+    }
+
+    pub fn weight(&self, flow_id: u32) -> u32 {
+        self.weights.get(&flow_id).cloned().unwrap_or(1)
+    }
+}
+
+fn flow_of(p: &Packet) -> u32 {
+    match *p {
+        Packet::Data{hdr, ..} |
+        Packet::Ack{hdr, ..} |
+        Packet::Nack{hdr, ..} |
+        Packet::Grant{hdr, ..} |
+        Packet::Mirror{hdr, ..} => hdr.flow,
+        Packet::Pause(_, _) | Packet::Resume(_, _) | Packet::Credit(_, _) => 0,
+    }
+}
+
+impl Queue for WfqQueue {
+    fn link(&self) -> Link {
+        self.link
+    }
+
+    fn headroom(&self) -> u32 {
+        self.admission.limit_bytes - self.occupancy_bytes()
+    }
+
+    fn occupancy_bytes(&self) -> u32 {
+        self.pkts.iter().map(|&(_, p)| p.get_size_bytes()).sum()
+    }
+
+    fn dropped_packets(&self) -> usize {
+        self.admission.dropped
+    }
+
+    fn enqueue(&mut self, mut p: Packet) -> Option<()> {
+        let occupancy_bytes = self.occupancy_bytes();
+        self.admission.admit(&mut p, occupancy_bytes)?;
+
+        // a flow which went idle starts again from the current virtual time
+        let flow = flow_of(&p);
+        let start = ::std::cmp::max(self.virtual_time, self.last_finish.get(&flow).cloned().unwrap_or(0));
+        let finish = start + u64::from(p.get_size_bytes()) * BYTE_VIRTUAL_TIME / u64::from(self.weight(flow));
+        self.last_finish.insert(flow, finish);
+        self.pkts.push((finish, p));
+        self.set_active(true);
+        Some(())
+    }
+
+    fn force_tx_next(&mut self, p: Packet) -> Option<()> {
+        self.forced_next.push_back(p);
+        self.set_active(true);
+        Some(())
+    }
+
+    fn dequeue(&mut self) -> Option<Packet> {
+        if let Some(p) = self.forced_next.pop_front() {
+            return Some(p);
+        }
+
+        // min_by_key keeps the first of equal finish times, so ties leave in arrival order
+        let next = self.pkts.iter()
+            .enumerate()
+            .min_by_key(|&(_, &(finish, _))| finish)
+            .map(|(i, _)| i)?;
+        if self.pkts.len() == 1 {
+            self.set_active(false);
+        }
+
+        let (finish, p) = self.pkts.remove(next);
+        self.virtual_time = finish;
+        let flow = flow_of(&p);
+        if !self.pkts.iter().any(|&(_, q)| flow_of(&q) == flow) {
+            self.last_finish.remove(&flow);
+        }
+
+        Some(p)
+    }
+
+    fn discard_matching(&mut self, mut should_discard: Box<FnMut(Packet) -> bool>) -> usize {
+        let before = self.pkts.len();
+        self.pkts.retain(|&(_, p)| !should_discard(p));
+        let pkts = &self.pkts;
+        self.last_finish.retain(|&flow, _| pkts.iter().any(|&(_, q)| flow_of(&q) == flow));
+        before - self.pkts.len()
+    }
+
+    fn count_matching(&self, mut counter: Box<FnMut(Packet) -> bool>) -> usize {
+        self.pkts.iter().filter(|&&(_, p)| counter(p)).count()
+    }
+
+    fn is_active(&self) -> bool {
+        self.active && !self.paused
+    }
+
+    fn set_active(&mut self, a: bool) {
+        self.active = a;
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn set_paused(&mut self, a: bool) {
+        self.paused = a;
+    }
+
+    fn set_ecn_threshold(&mut self, threshold: Option<u32>) {
+        self.admission.ecn_threshold = threshold;
+    }
+
+    fn set_loss_model(&mut self, model: Box<LossModel>) {
+        self.admission.loss_model = model;
+    }
+
+    fn set_cut_through_threshold(&mut self, threshold: Option<u32>) {
+        self.link.cut_through_threshold = threshold;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use node::{Link, switch::Queue};
+    use packet::{Packet, PacketHeader, PACKET_HEADER_SIZE_BYTES};
+    use super::WfqQueue;
+
+    #[test]
+    fn weighted_service_order() {
+        let mut q = WfqQueue::new(60_000, Link{propagation_delay: 0, bandwidth_bps: 0, mtu: 1500, pfc_enabled: false, cut_through_threshold: None, from: 0, to: 1});
+        q.set_weight(2, 2);
+        let pkt = |flow, seq| Packet::Data{
+            hdr: PacketHeader{
+                flow,
+                from: 0,
+                to: 1,
+                priority: 0,
+                size_bytes: PACKET_HEADER_SIZE_BYTES,
+            },
+            seq,
+            length: 1460,
+            ect: true,
+            ce: false,
+            sent: 0,
+            deadline: None,
+            trimmed: false,
+        };
+
+        for seq in 0..6 {
+            q.enqueue(pkt(1, seq)).unwrap();
+            q.enqueue(pkt(2, seq)).unwrap();
+        }
+
+        // flow 2 has twice the weight, so it sends two packets for each of flow 1's
+        let order = (0..9).map(|_| match q.dequeue() {
+            Some(Packet::Data{hdr, ..}) => hdr.flow,
+            p => panic!("expected a data packet, got {:?}", p),
+        }).collect::<Vec<u32>>();
+        assert_eq!(order, vec![2, 1, 2, 2, 1, 2, 2, 1, 2]);
+
+        // idle flows are forgotten
+        while q.dequeue().is_some() {}
+        assert!(q.last_finish.is_empty());
+    }
+}
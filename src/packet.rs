@@ -1,33 +1,124 @@
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+use Nanos;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct PacketHeader {
     pub flow: u32,
     pub from: u32,
     pub to: u32,
+    /// Traffic class; lower values are served first by `PriorityQueue`s.
+    pub priority: u8,
+    /// Bytes of header on the wire. Flows set it from `FlowInfo::header_bytes`, and replies
+    /// carry the header size of the packet they answer.
+    pub size_bytes: u32,
 }
 
+/// Default size of the header carried by every `Data`, `Ack`, `Nack`, and `Grant` packet.
+pub const PACKET_HEADER_SIZE_BYTES: u32 = 40;
+
+/// The most SACK blocks an `Ack` carries, as with TCP's SACK option.
+pub const MAX_SACK_BLOCKS: usize = 3;
+
+/// Byte ranges `[start, end)` received above the cumulative ACK, lowest first.
+/// Unused blocks are `None`.
+pub type SackBlocks = [Option<(u32, u32)>; MAX_SACK_BLOCKS];
+
+/// An empty SACK block list, for ACKs which only acknowledge cumulatively.
+pub const NO_SACK: SackBlocks = [None; MAX_SACK_BLOCKS];
+
+/// The window advertised by receivers which never run out of buffer.
+pub const UNLIMITED_RWND: u32 = ::std::u32::MAX;
+
+/// Each SACK block adds its two sequence numbers to the ACK.
+const SACK_BLOCK_SIZE_BYTES: u32 = 8;
+
 impl PacketHeader{
     pub fn get_size_bytes(&self) -> u32 {
-        40
+        self.size_bytes
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Packet {
-    Data{hdr: PacketHeader, seq: u32, length: u32},
-    Ack{hdr: PacketHeader, cumulative_acked_seq: u32},
+    /// `ect` says the sender is ECN-capable, and `ce` is the ECN congestion-experienced mark
+    /// set by a congested queue.
+    /// `sent` is when the sending host started transmitting the packet.
+    /// `deadline` is the absolute time by which the packet's flow should complete, if it has a
+    /// deadline.
+    /// `trimmed` marks a header whose payload an overflowing `TrimmingSwitch` cut off; its
+    /// `length` is then 0.
+    Data{hdr: PacketHeader, seq: u32, length: u32, ect: bool, ce: bool, sent: Nanos, deadline: Option<Nanos>, trimmed: bool},
+    /// `ece` echoes the `ce` mark of the data packet being acknowledged. Cumulative ACKs set it
+    /// if any data packet since the previous ACK was marked.
+    /// `sack` lists data received out of order, for selective-repeat senders.
+    /// `rwnd` is the receiver's advertised window: how many bytes past `cumulative_acked_seq`
+    /// the sender may have outstanding.
+    Ack{hdr: PacketHeader, cumulative_acked_seq: u32, ece: bool, sack: SackBlocks, rwnd: u32},
     Nack{hdr: PacketHeader, nacked_seq: u32},
+    /// Sent by the receiver of a receiver-driven flow to let the sender send up to `granted_seq`.
+    Grant{hdr: PacketHeader, granted_seq: u32},
+    /// PFC (802.1Qbb) PAUSE and RESUME frames sent by switch `.0` for traffic class `.1`.
     Pause(u32, u32),
     Resume(u32, u32),
+    /// Sent by `CreditSwitch` `.0` to let the upstream node send `.1` more bytes to it.
+    Credit(u32, u32),
+    /// A copy of a `Data`, `Ack`, `Nack`, or `Grant` packet made by a mirror port.
+    /// `hdr` addresses the analyzer, `orig` is the original header, `seq` is the original's
+    /// sequence number (or acked/nacked/granted sequence number), and `length` is its size in
+    /// bytes.
+    Mirror{hdr: PacketHeader, orig: PacketHeader, seq: u32, length: u32},
 }
 
 impl Packet {
+    /// The traffic class of this packet. PAUSE/RESUME and credit frames go in the highest class.
+    pub fn priority(&self) -> u8 {
+        match self {
+            Packet::Pause(_, _) | Packet::Resume(_, _) | Packet::Credit(_, _) => 0,
+            Packet::Data{hdr, ..} |
+            Packet::Ack{hdr, ..} |
+            Packet::Nack{hdr, ..} |
+            Packet::Grant{hdr, ..} |
+            Packet::Mirror{hdr, ..} => hdr.priority,
+        }
+    }
+
     pub fn get_size_bytes(&self) -> u32 {
         match self {
             Packet::Pause(_, _) | Packet::Resume(_, _) => 9, // https://github.com/bobzhuyb/ns3-rdma/blob/master/src/point-to-point/model/pause-header.cc#L96
-            Packet::Nack{hdr, ..} | Packet::Ack{hdr, ..} => hdr.get_size_bytes(),
+            Packet::Credit(_, _) => 9, // a credit update fits in the same frame as a PAUSE
+            Packet::Nack{hdr, ..} | Packet::Grant{hdr, ..} => hdr.get_size_bytes(),
+            Packet::Ack{hdr, sack, ..} => {
+                let blocks = sack.iter().filter(|b| b.is_some()).count() as u32;
+                hdr.get_size_bytes() + blocks * SACK_BLOCK_SIZE_BYTES
+            }
             Packet::Data{hdr, length, ..} => {
                 length + hdr.get_size_bytes()
             }
+            Packet::Mirror{length, ..} => *length,
         }
     }
+
+    /// A `Mirror` copy of this packet sent from node `from` to the analyzer `to`.
+    /// PAUSE/RESUME frames are link-local and are never mirrored.
+    pub fn mirror(&self, from: u32, to: u32) -> Option<Packet> {
+        let (orig, seq) = match *self {
+            Packet::Data{hdr, seq, ..} => (hdr, seq),
+            Packet::Ack{hdr, cumulative_acked_seq, ..} => (hdr, cumulative_acked_seq),
+            Packet::Nack{hdr, nacked_seq} => (hdr, nacked_seq),
+            Packet::Grant{hdr, granted_seq} => (hdr, granted_seq),
+            _ => return None,
+        };
+
+        Some(Packet::Mirror{
+            hdr: PacketHeader{
+                flow: orig.flow,
+                from,
+                to,
+                priority: orig.priority,
+                size_bytes: orig.size_bytes,
+            },
+            orig,
+            seq,
+            length: self.get_size_bytes(),
+        })
+    }
 }
@@ -0,0 +1,89 @@
+use super::Nanos;
+
+/// A small seeded PRNG (xorshift64*), so that randomized runs are reproducible.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift never leaves the all-zero state
+        Rng(if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed })
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// A distribution of delays, sampled per packet.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Delay {
+    Constant(Nanos),
+    /// Uniform in `[min, max]`.
+    Uniform{min: Nanos, max: Nanos},
+    /// Normal, truncated at zero.
+    Normal{mean: Nanos, std_dev: Nanos},
+}
+
+impl Default for Delay {
+    fn default() -> Self {
+        Delay::Constant(0)
+    }
+}
+
+impl Delay {
+    pub fn sample(&self, rng: &mut Rng) -> Nanos {
+        match *self {
+            Delay::Constant(d) => d,
+            Delay::Uniform{min, max} => min + (rng.next_f64() * (max - min + 1) as f64) as Nanos,
+            Delay::Normal{mean, std_dev} => {
+                // Box-Muller
+                let u1 = 1.0 - rng.next_f64(); // (0, 1], so ln() is finite
+                let u2 = rng.next_f64();
+                let z = (-2.0 * u1.ln()).sqrt() * (2.0 * ::std::f64::consts::PI * u2).cos();
+                (mean as f64 + z * std_dev as f64).max(0.0) as Nanos
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Delay, Rng};
+
+    fn moments(d: Delay) -> (f64, f64) {
+        let mut rng = Rng::new(42);
+        let samples = (0..100_000).map(|_| d.sample(&mut rng) as f64).collect::<Vec<f64>>();
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        let var = samples.iter().map(|s| (s - mean) * (s - mean)).sum::<f64>() / samples.len() as f64;
+        (mean, var)
+    }
+
+    #[test]
+    fn seeded_rng_is_reproducible() {
+        let (mut a, mut b) = (Rng::new(7), Rng::new(7));
+        assert!((0..100).all(|_| a.next_u64() == b.next_u64()));
+    }
+
+    #[test]
+    fn delay_distributions() {
+        assert_eq!(moments(Delay::Constant(1_000)), (1_000.0, 0.0));
+
+        let (mean, var) = moments(Delay::Uniform{min: 0, max: 10_000});
+        assert!((mean - 5_000.0).abs() < 100.0, "mean {}", mean);
+        let expected_var = 10_000.0 * 10_000.0 / 12.0;
+        assert!((var - expected_var).abs() < 0.05 * expected_var, "variance {}", var);
+
+        let (mean, var) = moments(Delay::Normal{mean: 50_000, std_dev: 5_000});
+        assert!((mean - 50_000.0).abs() < 100.0, "mean {}", mean);
+        assert!((var.sqrt() - 5_000.0).abs() < 250.0, "std dev {}", var.sqrt());
+    }
+}
@@ -0,0 +1,687 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::Write;
+use std::rc::Rc;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use slog;
+
+use super::{Nanos, Result};
+use super::event::{Event, EventTime};
+use super::flow::{FlowInfo, FlowSide};
+use super::node::{Node, Link};
+use super::node::switch::{Switch, SwitchStats};
+use super::packet::Packet;
+use super::topology::Topology;
+
+fn serialization_delay(bytes: u64, bandwidth_bps: u64) -> Nanos {
+    bytes * 8 * 1_000_000_000 / bandwidth_bps
+}
+
+/// The fastest `flow` could possibly complete over the links in `path`: every byte crosses the
+/// bottleneck back to back, the last packet is stored and forwarded at every other hop, and the
+/// final ACK returns over the same path. Congestion control and queueing are ignored.
+/// A flow with no data has nothing to wait for, so its ideal FCT is 0.
+pub fn ideal_fct(flow: &FlowInfo, path: &[Link]) -> Result<Nanos> {
+    if flow.length_bytes == 0 {
+        return Ok(0);
+    }
+
+    let max_packet_length = flow.max_packet_length
+        .filter(|&l| l > 0)
+        .ok_or_else(|| format_err!("flow {} has no packet length", flow.flow_id))?;
+    let bottleneck = path.iter()
+        .enumerate()
+        .min_by_key(|(_, l)| l.bandwidth_bps)
+        .map(|(i, _)| i)
+        .ok_or_else(|| format_err!("flow {} has an empty path", flow.flow_id))?;
+
+    let header_bytes = flow.header_size_bytes();
+    let num_packets = (flow.length_bytes - 1) / max_packet_length + 1;
+    let wire_bytes = flow.length_bytes as u64 + num_packets as u64 * header_bytes as u64;
+    let last_packet_bytes = (flow.length_bytes - (num_packets - 1) * max_packet_length + header_bytes) as u64;
+    let ack_bytes = header_bytes as u64;
+
+    Ok(path.iter().enumerate().map(|(i, l)| {
+        let data = if i == bottleneck { wire_bytes } else { last_packet_bytes };
+        serialization_delay(data, l.bandwidth_bps)
+            + serialization_delay(ack_bytes, l.bandwidth_bps)
+            + 2 * l.propagation_delay
+    }).sum())
+}
+
+/// Measured FCT divided by the ideal FCT for the flow's path; 1.0 means the flow was unimpeded.
+/// A flow whose ideal FCT is 0 has no slowdown to speak of, so it gets 1.0.
+pub fn slowdown(measured: Nanos, ideal: Nanos) -> f64 {
+    if ideal == 0 {
+        return 1.0;
+    }
+
+    measured as f64 / ideal as f64
+}
+
+/// The `p`th percentile (0 < `p` <= 100) of `values` by nearest rank, if there are any.
+pub fn percentile(values: &[Nanos], p: f64) -> Option<Nanos> {
+    if values.is_empty() {
+        return None;
+    }
+
+    let mut sorted = values.to_vec();
+    sorted.sort();
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    Some(sorted[::std::cmp::max(rank, 1) - 1])
+}
+
+/// FCTs of the completed flows in `topo` of at most `max_length_bytes`.
+/// Tail percentiles of these are the usual latency metric for interactive traffic.
+pub fn short_flow_fcts<S: Switch>(topo: &Topology<S>, max_length_bytes: u32) -> Vec<Nanos> {
+    topo.all_flows()
+        .filter(|f| match f.side() {
+            FlowSide::Sender => f.flow_info().length_bytes <= max_length_bytes,
+            _ => false,
+        })
+        .filter_map(|f| f.completion_time())
+        .collect()
+}
+
+/// The distribution of FCTs, counted in buckets of `bucket_width`: bucket `i` holds FCTs in
+/// `[i * bucket_width, (i + 1) * bucket_width)`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FctHistogram {
+    pub bucket_width: Nanos,
+    /// Bucket index -> count, for the buckets holding any FCT, so that one outlying FCT does
+    /// not cost a bucket for every width below it.
+    pub counts: BTreeMap<Nanos, u64>,
+}
+
+impl FctHistogram {
+    pub fn new(fcts: &[Nanos], bucket_width: Nanos) -> Self {
+        assert!(bucket_width > 0, "bucket width must be nonzero");
+        let mut counts = BTreeMap::new();
+        for &fct in fcts {
+            *counts.entry(fct / bucket_width).or_insert(0) += 1;
+        }
+
+        FctHistogram{
+            bucket_width,
+            counts,
+        }
+    }
+
+    /// The histogram of every completed sender's FCT in `topo`.
+    pub fn from_topology<S: Switch>(topo: &Topology<S>, bucket_width: Nanos) -> Self {
+        let fcts = topo.all_flows()
+            .filter(|f| match f.side() {
+                FlowSide::Sender => true,
+                _ => false,
+            })
+            .filter_map(|f| f.completion_time())
+            .collect::<Vec<Nanos>>();
+        Self::new(&fcts, bucket_width)
+    }
+
+    /// `(bucket start, bucket end, count)` for every nonempty bucket, in order.
+    pub fn buckets(&self) -> Vec<(Nanos, Nanos, u64)> {
+        let w = self.bucket_width;
+        self.counts.iter()
+            .map(|(&i, &c)| (i * w, (i * w).saturating_add(w), c))
+            .collect()
+    }
+
+    /// Write the histogram as CSV, with a `bucket_start_ns,bucket_end_ns,count` header and a row
+    /// per nonempty bucket, for plotting.
+    pub fn write_csv<W: Write>(&self, mut w: W) -> Result<()> {
+        writeln!(w, "bucket_start_ns,bucket_end_ns,count")?;
+        for (start, end, count) in self.buckets() {
+            writeln!(w, "{},{},{}", start, end, count)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Summary statistics of the FCTs of a run's sender flows.
+/// The FCT fields are all 0 when no flow completed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FctSummary {
+    pub completed: usize,
+    /// Flows which had not completed, and are left out of the statistics.
+    pub incomplete: usize,
+    pub min: Nanos,
+    pub median: Nanos,
+    pub p99: Nanos,
+    pub max: Nanos,
+    pub mean: f64,
+}
+
+impl FctSummary {
+    pub fn new(fcts: &[Nanos], incomplete: usize) -> Self {
+        if fcts.is_empty() {
+            return FctSummary{
+                incomplete,
+                ..Default::default()
+            };
+        }
+
+        FctSummary{
+            completed: fcts.len(),
+            incomplete,
+            min: *fcts.iter().min().unwrap(),
+            median: percentile(fcts, 50.0).unwrap(),
+            p99: percentile(fcts, 99.0).unwrap(),
+            max: *fcts.iter().max().unwrap(),
+            mean: fcts.iter().sum::<Nanos>() as f64 / fcts.len() as f64,
+        }
+    }
+}
+
+/// Summarize the FCTs of every sender in `topo`.
+pub fn fct_summary<S: Switch>(topo: &Topology<S>) -> FctSummary {
+    let fcts = topo.all_flows()
+        .filter(|f| match f.side() {
+            FlowSide::Sender => true,
+            _ => false,
+        })
+        .map(|f| f.completion_time())
+        .collect::<Vec<Option<Nanos>>>();
+    let completed = fcts.iter().filter_map(|&fct| fct).collect::<Vec<Nanos>>();
+    FctSummary::new(&completed, fcts.len() - completed.len())
+}
+
+/// A group of flows whose collective completion matters more than any one flow's, e.g. the
+/// shuffle stage of a job.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Coflow {
+    pub coflow_id: u32,
+    pub flow_ids: Vec<u32>,
+}
+
+impl Coflow {
+    /// Coflow completion time (CCT): from the first flow starting until the last one completes.
+    /// `None` until every flow in the coflow has completed.
+    pub fn completion_time<S: Switch>(&self, topo: &Topology<S>) -> Option<Nanos> {
+        let spans = self.flow_ids.iter()
+            .map(|id| {
+                topo.all_flows()
+                    .find(|f| match f.side() {
+                        FlowSide::Sender => f.flow_info().flow_id == *id,
+                        _ => false,
+                    })
+                    .and_then(|f| f.start_time().and_then(|s| f.completion_time().map(|fct| (s, s + fct))))
+            })
+            .collect::<Option<Vec<(Nanos, Nanos)>>>()?;
+
+        let start = spans.iter().map(|&(s, _)| s).min()?;
+        let end = spans.iter().map(|&(_, e)| e).max()?;
+        Some(end - start)
+    }
+}
+
+/// The slowdown of every completed flow in `topo`, as `(flow_id, slowdown)` pairs.
+pub fn flow_slowdowns<S: Switch>(topo: &Topology<S>) -> Result<Vec<(u32, f64)>> {
+    topo.all_flows()
+        .filter(|f| match f.side() {
+            FlowSide::Sender => true,
+            _ => false,
+        })
+        .filter_map(|f| f.completion_time().map(|fct| (f.flow_info(), fct)))
+        .map(|(fi, fct)| {
+            let path = topo.flow_path(fi.flow_id, fi.sender_id, fi.dest_id)?;
+            Ok((fi.flow_id, slowdown(fct, ideal_fct(&fi, &path)?)))
+        })
+        .collect()
+}
+
+/// The loss rate each sender in `topo` observed, as `(flow_id, retransmitted / sent)` pairs.
+/// Senders which retransmit more than was lost, as go-back-N does, overestimate it.
+pub fn flow_loss_rates<S: Switch>(topo: &Topology<S>) -> Vec<(u32, f64)> {
+    topo.all_flows()
+        .filter(|f| match f.side() {
+            FlowSide::Sender => true,
+            _ => false,
+        })
+        .filter_map(|f| f.transmit_counts().loss_rate().map(|r| (f.flow_info().flow_id, r)))
+        .collect()
+}
+
+/// Bytes transmitted on one link.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LinkBytes {
+    pub link: Link,
+    /// Every packet sent on the link, including PFC frames.
+    pub transmitted_bytes: u64,
+    /// Packets the link's loss model dropped in flight (see `Executor::set_link_loss_model`).
+    pub lost_bytes: u64,
+    /// PFC PAUSE and RESUME frames, which take link capacity but carry no flow's data.
+    pub pfc_bytes: u64,
+}
+
+/// Bytes transmitted on each link, keyed by `(from, to)`.
+/// The `Executor` counts every packet a `NodeTransmitEvent` puts on a link, and every packet the
+/// link then loses.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LinkStats(#[serde(with = "::checkpoint::pairs")] HashMap<(u32, u32), LinkBytes>);
+
+impl LinkStats {
+    pub fn record(&mut self, l: Link, p: &Packet) {
+        let bytes = self.0.entry((l.from, l.to)).or_insert(LinkBytes{
+            link: l,
+            ..Default::default()
+        });
+        let size = u64::from(p.get_size_bytes());
+        bytes.transmitted_bytes += size;
+        match *p {
+            Packet::Pause(_, _) | Packet::Resume(_, _) => bytes.pfc_bytes += size,
+            _ => (),
+        }
+    }
+
+    pub fn record_loss(&mut self, l: Link, p: &Packet) {
+        let bytes = self.0.entry((l.from, l.to)).or_insert(LinkBytes{
+            link: l,
+            ..Default::default()
+        });
+        bytes.lost_bytes += u64::from(p.get_size_bytes());
+    }
+
+    /// The bytes sent from `from` to `to`, or `None` if that link has sent nothing.
+    pub fn link(&self, from: u32, to: u32) -> Option<LinkBytes> {
+        self.0.get(&(from, to)).cloned()
+    }
+
+    /// The fraction of the link from `from` to `to` used over `duration`:
+    /// `transmitted_bytes * 8 / (bandwidth_bps * duration)`.
+    pub fn utilization(&self, from: u32, to: u32, duration: Nanos) -> Option<f64> {
+        self.link(from, to).map(|b| utilization(b, duration))
+    }
+
+    /// The utilization of every link which sent anything, over `duration`.
+    pub fn utilizations(&self, duration: Nanos) -> Vec<(Link, f64)> {
+        self.0.values().map(|&b| (b.link, utilization(b, duration))).collect()
+    }
+}
+
+fn utilization(b: LinkBytes, duration: Nanos) -> f64 {
+    b.transmitted_bytes as f64 * 8e9 / (b.link.bandwidth_bps as f64 * duration as f64)
+}
+
+/// The bytes a flow delivered during one sampling interval ending at `time`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ThroughputSample {
+    pub time: Nanos,
+    pub flow_id: u32,
+    pub bytes: u32,
+    pub throughput_bps: f64,
+}
+
+/// A flow's share of the bytes delivered by a set of flows, and its weighted fair share of them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShareCompliance {
+    pub flow_id: u32,
+    pub weight: u32,
+    pub target_share: f64,
+    pub achieved_share: f64,
+}
+
+/// What a `SampleEvent` reads at each sample, and where it records what it read.
+pub trait Sampler: Clone {
+    /// The nodes `sample` reads.
+    fn node_ids(&self) -> Vec<u32>;
+    /// Read `nodes` at `time`, `interval` after the previous sample, and record the sample.
+    /// Returns whether to keep sampling.
+    fn sample(&mut self, time: Nanos, interval: Nanos, nodes: &mut [&mut Node], logger: Option<&slog::Logger>) -> Result<bool>;
+}
+
+/// Takes a sample with `S` every `interval` from `start` until `end`, or until the sampler
+/// has nothing left to sample.
+///
+/// The event only reads state, so it does not perturb the simulation.
+pub struct SampleEvent<S: Sampler> {
+    interval: Nanos,
+    next_sample: Nanos,
+    end: Nanos,
+    sampler: S,
+}
+
+impl<S: Sampler> SampleEvent<S> {
+    pub fn with_sampler(start: Nanos, interval: Nanos, end: Nanos, sampler: S) -> Self {
+        assert!(interval > 0, "sampling interval must be nonzero");
+        SampleEvent{
+            interval,
+            next_sample: start + interval,
+            end,
+            sampler,
+        }
+    }
+}
+
+impl<S: Sampler + 'static> Event for SampleEvent<S> {
+    fn time(&self) -> EventTime {
+        EventTime::Absolute(self.next_sample)
+    }
+
+    fn affected_node_ids(&self) -> Vec<u32> {
+        self.sampler.node_ids()
+    }
+
+    fn exec(&mut self, time: Nanos, nodes: &mut [&mut Node], logger: Option<&slog::Logger>) -> Result<Vec<Box<Event>>> {
+        let more = self.sampler.sample(time, self.interval, nodes, logger)?;
+        if !more || time + self.interval > self.end {
+            return Ok(vec![]);
+        }
+
+        Ok(vec![Box::new(SampleEvent{
+            interval: self.interval,
+            next_sample: time + self.interval,
+            end: self.end,
+            sampler: self.sampler.clone(),
+        })])
+    }
+}
+
+/// Shared handle to the samples recorded by a `ThroughputSampleEvent`.
+/// Keep a clone before pushing the event to read the samples after `execute()`.
+#[derive(Clone, Debug, Default)]
+pub struct ThroughputTrace(Rc<RefCell<Vec<ThroughputSample>>>);
+
+impl ThroughputTrace {
+    pub fn samples(&self) -> Vec<ThroughputSample> {
+        self.0.borrow().clone()
+    }
+
+    pub fn flow_samples(&self, flow_id: u32) -> Vec<ThroughputSample> {
+        self.0.borrow().iter().filter(|s| s.flow_id == flow_id).cloned().collect()
+    }
+
+    /// The total throughput of all flows in each sample, as `(time, bps)` pairs in time order.
+    /// Once flows share a bottleneck, this approximates its capacity.
+    pub fn aggregate_samples(&self) -> Vec<(Nanos, f64)> {
+        let mut totals = BTreeMap::new();
+        for s in self.0.borrow().iter() {
+            *totals.entry(s.time).or_insert(0.0) += s.throughput_bps;
+        }
+
+        totals.into_iter().collect()
+    }
+
+    /// Bytes delivered by `flow_id` in samples ending in `(from, to]`.
+    pub fn bytes_between(&self, flow_id: u32, from: Nanos, to: Nanos) -> u64 {
+        self.0.borrow()
+            .iter()
+            .filter(|s| s.flow_id == flow_id && s.time > from && s.time <= to)
+            .map(|s| s.bytes as u64)
+            .sum()
+    }
+
+    /// How closely the flows in `weights`, as `(flow_id, weight)` pairs, split the bytes they
+    /// delivered in samples ending in `(from, to]` in proportion to their weights, as a weighted
+    /// fair scheduler (see `WfqQueue`) should while they are all backlogged.
+    /// `None` if they delivered nothing then, since they have no shares to compare.
+    pub fn weighted_share_compliance(&self, weights: &[(u32, u32)], from: Nanos, to: Nanos) -> Option<Vec<ShareCompliance>> {
+        let total_weight = weights.iter().map(|&(_, w)| f64::from(w)).sum::<f64>();
+        let bytes = weights.iter()
+            .map(|&(flow_id, _)| self.bytes_between(flow_id, from, to))
+            .collect::<Vec<u64>>();
+        let total_bytes = bytes.iter().sum::<u64>() as f64;
+        if total_bytes == 0.0 {
+            return None;
+        }
+
+        Some(weights.iter()
+            .zip(bytes)
+            .map(|(&(flow_id, weight), b)| ShareCompliance{
+                flow_id,
+                weight,
+                target_share: f64::from(weight) / total_weight,
+                achieved_share: b as f64 / total_bytes,
+            })
+            .collect())
+    }
+
+    fn record(&self, s: ThroughputSample) {
+        self.0.borrow_mut().push(s)
+    }
+}
+
+/// Periodically records, for every flow received at `host_ids`, the bytes delivered since the
+/// previous sample, until every flow it has seen has completed.
+pub type ThroughputSampleEvent = SampleEvent<ThroughputSampler>;
+
+impl ThroughputSampleEvent {
+    pub fn new(start: Nanos, interval: Nanos, end: Nanos, host_ids: Vec<u32>, trace: ThroughputTrace) -> Self {
+        Self::with_sampler(start, interval, end, ThroughputSampler{
+            host_ids,
+            last_delivered: HashMap::new(),
+            trace,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ThroughputSampler {
+    host_ids: Vec<u32>,
+    last_delivered: HashMap<u32, u32>,
+    trace: ThroughputTrace,
+}
+
+impl Sampler for ThroughputSampler {
+    fn node_ids(&self) -> Vec<u32> {
+        self.host_ids.clone()
+    }
+
+    fn sample(&mut self, time: Nanos, interval: Nanos, nodes: &mut [&mut Node], logger: Option<&slog::Logger>) -> Result<bool> {
+        let mut any_flows = false;
+        let mut all_done = true;
+        for f in nodes.iter()
+            .flat_map(|n| n.flows().iter())
+            .filter(|f| match f.side() {
+                FlowSide::Receiver => true,
+                _ => false,
+            }) {
+
+            any_flows = true;
+            all_done &= f.completion_time().is_some();
+
+            let flow_id = f.flow_info().flow_id;
+            let delivered = f.delivered_bytes();
+            let last = self.last_delivered.insert(flow_id, delivered).unwrap_or(0);
+            let sample = ThroughputSample{
+                time,
+                flow_id,
+                bytes: delivered - last,
+                throughput_bps: (delivered - last) as f64 * 8e9 / interval as f64,
+            };
+
+            if let Some(log) = logger {
+                info!(log, "throughput";
+                    "time" => time,
+                    "flow" => flow_id,
+                    "bytes" => sample.bytes,
+                    "bps" => sample.throughput_bps,
+                );
+            }
+
+            self.trace.record(sample);
+        }
+
+        Ok(!(any_flows && all_done))
+    }
+}
+
+/// The time-average and peak of a queue's occupancy, in bytes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StandingQueue {
+    pub time_average_bytes: f64,
+    pub peak_bytes: u32,
+}
+
+/// Shared handle to the `(time, occupancy in bytes)` samples recorded by a `QueueSampleEvent`.
+/// Keep a clone before pushing the event to read the samples after `execute()`.
+#[derive(Clone, Debug, Default)]
+pub struct QueueTrace(Rc<RefCell<Vec<(Nanos, u32)>>>);
+
+impl QueueTrace {
+    pub fn samples(&self) -> Vec<(Nanos, u32)> {
+        self.0.borrow().clone()
+    }
+
+    /// The standing queue over samples in `[from, to]`, or `None` if there are none.
+    /// Samples are evenly spaced, so their mean is the time average.
+    pub fn standing_queue(&self, from: Nanos, to: Nanos) -> Option<StandingQueue> {
+        let samples = self.0.borrow();
+        let window = samples.iter()
+            .filter(|&&(t, _)| t >= from && t <= to)
+            .map(|&(_, occupancy)| occupancy)
+            .collect::<Vec<u32>>();
+        let peak_bytes = window.iter().cloned().max()?;
+        Some(StandingQueue{
+            time_average_bytes: window.iter().map(|&o| o as f64).sum::<f64>() / window.len() as f64,
+            peak_bytes,
+        })
+    }
+
+    fn record(&self, time: Nanos, occupancy: u32) {
+        self.0.borrow_mut().push((time, occupancy))
+    }
+}
+
+/// Shared handle to the last `capacity` `(time, occupancy in bytes)` samples a queue noted as
+/// packets arrived and departed (see `DropTailQueue::set_occupancy_trace`), oldest first.
+/// Keep a clone before running the simulation to read the samples after `execute()`.
+#[derive(Clone, Debug)]
+pub struct OccupancyTrace(Rc<RefCell<VecDeque<(Nanos, u32)>>>, usize);
+
+impl OccupancyTrace {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "trace capacity must be nonzero");
+        OccupancyTrace(Rc::new(RefCell::new(VecDeque::with_capacity(capacity))), capacity)
+    }
+
+    pub fn samples(&self) -> Vec<(Nanos, u32)> {
+        self.0.borrow().iter().cloned().collect()
+    }
+
+    /// The largest occupancy among the samples still in the buffer.
+    pub fn peak_bytes(&self) -> Option<u32> {
+        self.0.borrow().iter().map(|&(_, occupancy)| occupancy).max()
+    }
+
+    /// Add a sample, overwriting the oldest once the buffer is full.
+    pub fn record(&self, time: Nanos, occupancy: u32) {
+        let mut samples = self.0.borrow_mut();
+        if samples.len() == self.1 {
+            samples.pop_front();
+        }
+
+        samples.push_back((time, occupancy));
+    }
+}
+
+/// A checkpoint (see `Executor::checkpoint`) saves the samples so far. The restored queue records
+/// into a new trace of its own, read with `Queue::occupancy_trace`.
+impl Serialize for OccupancyTrace {
+    fn serialize<S: Serializer>(&self, s: S) -> ::std::result::Result<S::Ok, S::Error> {
+        (&*self.0.borrow(), self.1).serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for OccupancyTrace {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> ::std::result::Result<Self, D::Error> {
+        let (samples, capacity) = <(VecDeque<(Nanos, u32)>, usize)>::deserialize(d)?;
+        Ok(OccupancyTrace(Rc::new(RefCell::new(samples)), capacity))
+    }
+}
+
+/// Periodically records the occupancy of the queue at node `node` towards neighbor `to`,
+/// e.g. a bottleneck, from `start` until `end`.
+pub type QueueSampleEvent = SampleEvent<QueueSampler>;
+
+impl QueueSampleEvent {
+    pub fn new(start: Nanos, interval: Nanos, end: Nanos, node: u32, to: u32, trace: QueueTrace) -> Self {
+        Self::with_sampler(start, interval, end, QueueSampler{node, to, trace})
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct QueueSampler {
+    node: u32,
+    to: u32,
+    trace: QueueTrace,
+}
+
+impl Sampler for QueueSampler {
+    fn node_ids(&self) -> Vec<u32> {
+        vec![self.node]
+    }
+
+    fn sample(&mut self, time: Nanos, _interval: Nanos, nodes: &mut [&mut Node], logger: Option<&slog::Logger>) -> Result<bool> {
+        let occupancy = nodes[0].queue_occupancy(self.to)
+            .ok_or_else(|| format_err!("node {} has no queue towards {}", self.node, self.to))?;
+        if let Some(log) = logger {
+            info!(log, "standing queue";
+                "time" => time,
+                "node" => self.node,
+                "port" => self.to,
+                "occupancy" => occupancy,
+            );
+        }
+
+        self.trace.record(time, occupancy);
+        Ok(true)
+    }
+}
+
+/// Shared handle to the `(time, statistics for the interval ending then)` samples recorded by a
+/// `SwitchStatsSampleEvent`.
+#[derive(Clone, Debug, Default)]
+pub struct SwitchStatsTrace(Rc<RefCell<Vec<(Nanos, SwitchStats)>>>);
+
+impl SwitchStatsTrace {
+    pub fn samples(&self) -> Vec<(Nanos, SwitchStats)> {
+        self.0.borrow().clone()
+    }
+
+    fn record(&self, time: Nanos, stats: SwitchStats) {
+        self.0.borrow_mut().push((time, stats))
+    }
+}
+
+/// Every `interval` from `start` until `end`, takes the statistics switch `node` accumulated
+/// since the previous sample (see `Switch::take_stats`), so each sample covers one interval.
+pub type SwitchStatsSampleEvent = SampleEvent<SwitchStatsSampler>;
+
+impl SwitchStatsSampleEvent {
+    pub fn new(start: Nanos, interval: Nanos, end: Nanos, node: u32, trace: SwitchStatsTrace) -> Self {
+        Self::with_sampler(start, interval, end, SwitchStatsSampler{node, trace})
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SwitchStatsSampler {
+    node: u32,
+    trace: SwitchStatsTrace,
+}
+
+impl Sampler for SwitchStatsSampler {
+    fn node_ids(&self) -> Vec<u32> {
+        vec![self.node]
+    }
+
+    fn sample(&mut self, time: Nanos, _interval: Nanos, nodes: &mut [&mut Node], logger: Option<&slog::Logger>) -> Result<bool> {
+        let stats = nodes[0].take_switch_stats()
+            .ok_or_else(|| format_err!("node {} is not a switch", self.node))?;
+        if let Some(log) = logger {
+            info!(log, "switch stats";
+                "time" => time,
+                "node" => self.node,
+                "drops" => stats.drops,
+                "marks" => stats.marks,
+                "pauses" => stats.pauses,
+                "high_water" => stats.high_water_bytes,
+            );
+        }
+
+        self.trace.record(time, stats);
+        Ok(true)
+    }
+}
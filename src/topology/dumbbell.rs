@@ -0,0 +1,135 @@
+use std::marker::PhantomData;
+use Nanos;
+use DEFAULT_MTU;
+use node::{Host, Link};
+use node::switch::{Switch, Queue};
+use node::switch::drop_tail_queue::DropTailQueue;
+
+use super::{Topology, TopologyStrategy};
+use super::pod::PfcLinks;
+
+/// The link joining the two sides of a `Dumbbell`.
+#[derive(Clone, Copy, Debug)]
+pub struct Bottleneck {
+    pub bandwidth_bps: u64,
+    pub queue_length_bytes: u32,
+}
+
+/// Two switches joined by a single bottleneck link, with hosts on either side.
+///
+/// Ids are assigned as `lookup_node` expects:
+/// the left hosts are `0..left_hosts`, then the right hosts, then the left switch, then the
+/// right switch. Traffic between the sides crosses the bottleneck, whose bandwidth and buffer
+/// are set separately from the access links'.
+/// Every link has the same propagation delay.
+pub struct Dumbbell<S: Switch>(PhantomData<S>);
+
+impl<S: Switch> Dumbbell<S> {
+    pub fn make_dumbbell(
+        left_hosts: u32,
+        right_hosts: u32,
+        access_queue_length_bytes: u32,
+        access_link_bandwidth_bps: u64,
+        bottleneck: Bottleneck,
+        per_link_propagation_delay: Nanos,
+    ) -> Topology<S> {
+        dumbbell(
+            left_hosts,
+            right_hosts,
+            access_queue_length_bytes,
+            access_link_bandwidth_bps,
+            bottleneck,
+            per_link_propagation_delay,
+            DEFAULT_MTU,
+        )
+    }
+}
+
+/// Half of the hosts (rounded down) on the left. The bottleneck has the access links'
+/// bandwidth and buffer, so it congests as soon as several hosts send across it.
+impl<S: Switch> TopologyStrategy<S> for Dumbbell<S> {
+    fn make_topology_with_mtu(
+        num_hosts: u32,
+        queue_length_bytes: u32,
+        access_link_bandwidth_bps: u64,
+        per_link_propagation_delay: Nanos,
+        mtu: u32,
+    ) -> Topology<S> {
+        dumbbell(
+            num_hosts / 2,
+            num_hosts - num_hosts / 2,
+            queue_length_bytes,
+            access_link_bandwidth_bps,
+            Bottleneck{
+                bandwidth_bps: access_link_bandwidth_bps,
+                queue_length_bytes,
+            },
+            per_link_propagation_delay,
+            mtu,
+        )
+    }
+}
+
+fn dumbbell<S: Switch>(
+    left_hosts: u32,
+    right_hosts: u32,
+    access_queue_length_bytes: u32,
+    access_link_bandwidth_bps: u64,
+    bottleneck: Bottleneck,
+    per_link_propagation_delay: Nanos,
+    mtu: u32,
+) -> Topology<S> {
+    let num_hosts = left_hosts + right_hosts;
+    let (left_switch, right_switch) = (num_hosts, num_hosts + 1);
+    let switch_of = |host: u32| if host < left_hosts { left_switch } else { right_switch };
+    let link = |from, to, bandwidth_bps| Link{
+        propagation_delay: per_link_propagation_delay,
+        bandwidth_bps,
+        mtu,
+        pfc_enabled: S::pfc_enabled(),
+        cut_through_threshold: None,
+        from,
+        to,
+    };
+    let access_queue = |from, to| Box::new(DropTailQueue::new(
+        access_queue_length_bytes,
+        link(from, to, access_link_bandwidth_bps),
+    )) as Box<Queue>;
+    let bottleneck_queue = |from, to| Box::new(DropTailQueue::new(
+        bottleneck.queue_length_bytes,
+        link(from, to, bottleneck.bandwidth_bps),
+    )) as Box<Queue>;
+
+    let hosts = (0..num_hosts).map(|id| {
+        Host{
+            id,
+            active: true,
+            link: link(id, switch_of(id), access_link_bandwidth_bps),
+            ..Default::default()
+        }
+    }).collect();
+
+    let switches = vec![(left_switch, right_switch), (right_switch, left_switch)]
+        .into_iter()
+        .map(|(id, other)| {
+            let mut s = S::new(
+                id,
+                (0..num_hosts)
+                    .filter(|&h| switch_of(h) == id)
+                    .map(|h| access_queue(id, h))
+                    .chain(Some(bottleneck_queue(id, other))),
+            );
+
+            // hosts on the other side are reached over the bottleneck
+            (0..num_hosts)
+                .filter(|&h| switch_of(h) == other)
+                .for_each(|h| s.add_route(h, other));
+            s
+        })
+        .collect();
+
+    Topology{
+        hosts,
+        switches,
+    }
+}
@@ -0,0 +1,167 @@
+use std::marker::PhantomData;
+use Nanos;
+use DEFAULT_MTU;
+use node::{Host, Link};
+use node::switch::{Switch, Queue};
+use node::switch::drop_tail_queue::DropTailQueue;
+
+use super::{Topology, TopologyStrategy};
+use super::pod::PfcLinks;
+
+/// The standard k-ary fat tree: `k` pods of `k/2` edge and `k/2` aggregation switches, and
+/// `(k/2)^2` core switches. Each edge switch has `k/2` hosts, for `k^3/4` hosts in total.
+///
+/// Ids are assigned as `lookup_node` expects:
+/// hosts are `0..k^3/4` (edge by edge, pod by pod), then the edge switches, then the
+/// aggregation switches (both pod by pod), then the core switches.
+/// Traffic leaving an edge goes up via aggregation switch `dest % (k/2)`, and traffic leaving a
+/// pod via that aggregation switch's core `(dest / (k/2)) % (k/2)`, so each destination's
+/// traffic takes a single path.
+/// Every link has the same bandwidth and propagation delay.
+pub struct FatTree<S: Switch> {
+    pub k: u32,
+    switch: PhantomData<S>,
+}
+
+impl<S: Switch> FatTree<S> {
+    pub fn new(k: u32) -> Self {
+        assert!(k >= 2 && k % 2 == 0, "fat tree arity must be even: {}", k);
+        FatTree{
+            k,
+            switch: PhantomData,
+        }
+    }
+
+    pub fn num_hosts(&self) -> u32 {
+        self.k * self.k * self.k / 4
+    }
+
+    /// The pod host `host` is in.
+    pub fn pod(&self, host: u32) -> u32 {
+        host / (self.k * self.k / 4)
+    }
+
+    /// Pairs of distinct hosts in the same pod, and pairs of hosts in different pods.
+    pub fn host_pairs(&self) -> (Vec<(u32, u32)>, Vec<(u32, u32)>) {
+        let n = self.num_hosts();
+        (0..n)
+            .flat_map(|from| (0..n).filter(move |&to| to != from).map(move |to| (from, to)))
+            .partition(|&(from, to)| self.pod(from) == self.pod(to))
+    }
+
+    pub fn make_fat_tree(
+        &self,
+        queue_length_bytes: u32,
+        link_bandwidth_bps: u64,
+        per_link_propagation_delay: Nanos,
+    ) -> Topology<S> {
+        self.make_fat_tree_with_mtu(queue_length_bytes, link_bandwidth_bps, per_link_propagation_delay, DEFAULT_MTU)
+    }
+
+    pub fn make_fat_tree_with_mtu(
+        &self,
+        queue_length_bytes: u32,
+        link_bandwidth_bps: u64,
+        per_link_propagation_delay: Nanos,
+        mtu: u32,
+    ) -> Topology<S> {
+        let half = self.k / 2;
+        let num_hosts = self.num_hosts();
+        // edge and aggregation switches are numbered pod by pod, `half` per pod
+        let edge_id = |edge: u32| num_hosts + edge;
+        let agg_id = |agg: u32| num_hosts + self.k * half + agg;
+        let core_id = |core: u32| num_hosts + 2 * self.k * half + core;
+        let edge_of = |host: u32| host / half;
+        let pod_of = |host: u32| self.pod(host);
+        let link = |from, to| Link{
+            propagation_delay: per_link_propagation_delay,
+            bandwidth_bps: link_bandwidth_bps,
+            mtu,
+            pfc_enabled: S::pfc_enabled(),
+            cut_through_threshold: None,
+            from,
+            to,
+        };
+        let queue = |from, to| Box::new(DropTailQueue::new(queue_length_bytes, link(from, to))) as Box<Queue>;
+
+        let hosts = (0..num_hosts).map(|id| {
+            Host{
+                id,
+                active: true,
+                link: link(id, edge_id(edge_of(id))),
+                ..Default::default()
+            }
+        }).collect();
+
+        let mut switches = (0..self.k * half).map(|edge| {
+            let id = edge_id(edge);
+            let pod = edge / half;
+            let mut s = S::new(
+                id,
+                (edge * half..(edge + 1) * half)
+                    .map(|h| queue(id, h))
+                    .chain((0..half).map(|a| queue(id, agg_id(pod * half + a)))),
+            );
+
+            (0..num_hosts)
+                .filter(|&h| edge_of(h) != edge)
+                .for_each(|h| s.add_route(h, agg_id(pod * half + h % half)));
+            s
+        }).collect::<Vec<S>>();
+
+        switches.extend((0..self.k * half).map(|agg| {
+            let id = agg_id(agg);
+            let (pod, index) = (agg / half, agg % half);
+            let mut s = S::new(
+                id,
+                (0..half)
+                    .map(|e| queue(id, edge_id(pod * half + e)))
+                    .chain((0..half).map(|c| queue(id, core_id(index * half + c)))),
+            );
+
+            (0..num_hosts).for_each(|h| if pod_of(h) == pod {
+                s.add_route(h, edge_id(edge_of(h)))
+            } else {
+                s.add_route(h, core_id(index * half + (h / half) % half))
+            });
+            s
+        }));
+
+        switches.extend((0..half * half).map(|core| {
+            let id = core_id(core);
+            // core `core` connects to aggregation switch `core / half` of every pod
+            let index = core / half;
+            let mut s = S::new(id, (0..self.k).map(|pod| queue(id, agg_id(pod * half + index))));
+            (0..num_hosts).for_each(|h| s.add_route(h, agg_id(pod_of(h) * half + index)));
+            s
+        }));
+
+        Topology{
+            hosts,
+            switches,
+        }
+    }
+}
+
+/// `num_hosts` must be `k^3/4` for an even `k`.
+impl<S: Switch> TopologyStrategy<S> for FatTree<S> {
+    fn make_topology_with_mtu(
+        num_hosts: u32,
+        queue_length_bytes: u32,
+        access_link_bandwidth_bps: u64,
+        per_link_propagation_delay: Nanos,
+        mtu: u32,
+    ) -> Topology<S> {
+        let k = (2..)
+            .step_by(2)
+            .find(|k| k * k * k / 4 >= num_hosts)
+            .unwrap();
+        assert_eq!(k * k * k / 4, num_hosts, "no fat tree has {} hosts", num_hosts);
+        FatTree::new(k).make_fat_tree_with_mtu(
+            queue_length_bytes,
+            access_link_bandwidth_bps,
+            per_link_propagation_delay,
+            mtu,
+        )
+    }
+}
@@ -0,0 +1,165 @@
+use std::cmp::min;
+use std::marker::PhantomData;
+use Nanos;
+use DEFAULT_MTU;
+use node::{Host, Link};
+use node::switch::{Switch, Queue};
+use node::switch::drop_tail_queue::DropTailQueue;
+
+use super::{Topology, TopologyStrategy};
+use super::pod::PfcLinks;
+
+/// Hosts per leaf when built as a `TopologyStrategy`.
+pub const DEFAULT_HOSTS_PER_LEAF: u32 = 2;
+/// Spines when built as a `TopologyStrategy`.
+pub const DEFAULT_NUM_SPINES: u32 = 2;
+
+/// A two-tier Clos: racks of hosts under leaf switches, and every leaf connected to every spine.
+///
+/// Ids are assigned as `lookup_node` expects:
+/// hosts are `0..num_hosts` (leaf by leaf), then the leaves in order, then the spines.
+/// Traffic between leaves goes via spine `dest % num_spines`, so each destination's traffic
+/// takes a single path and stays in order; `make_leaf_spine_ecmp` instead spreads flows across
+/// all spines.
+/// Every link has the same bandwidth and propagation delay.
+pub struct LeafSpine<S: Switch>(PhantomData<S>);
+
+impl<S: Switch> LeafSpine<S> {
+    pub fn make_leaf_spine(
+        num_leaves: u32,
+        num_spines: u32,
+        hosts_per_leaf: u32,
+        queue_length_bytes: u32,
+        link_bandwidth_bps: u64,
+        per_link_propagation_delay: Nanos,
+    ) -> Topology<S> {
+        leaf_spine(
+            hosts_per_leaf * num_leaves,
+            Fabric{hosts_per_leaf, num_spines, ecmp: false},
+            queue_length_bytes,
+            link_bandwidth_bps,
+            per_link_propagation_delay,
+            DEFAULT_MTU,
+        )
+    }
+
+    /// Like `make_leaf_spine`, but every spine is an equal-cost next hop between leaves, so each
+    /// flow is hashed onto one of them (see `next_hop`).
+    pub fn make_leaf_spine_ecmp(
+        num_leaves: u32,
+        num_spines: u32,
+        hosts_per_leaf: u32,
+        queue_length_bytes: u32,
+        link_bandwidth_bps: u64,
+        per_link_propagation_delay: Nanos,
+    ) -> Topology<S> {
+        leaf_spine(
+            hosts_per_leaf * num_leaves,
+            Fabric{hosts_per_leaf, num_spines, ecmp: true},
+            queue_length_bytes,
+            link_bandwidth_bps,
+            per_link_propagation_delay,
+            DEFAULT_MTU,
+        )
+    }
+}
+
+/// `DEFAULT_NUM_SPINES` spines and `DEFAULT_HOSTS_PER_LEAF` hosts per leaf; the last leaf has
+/// fewer hosts if they do not divide evenly.
+impl<S: Switch> TopologyStrategy<S> for LeafSpine<S> {
+    fn make_topology_with_mtu(
+        num_hosts: u32,
+        queue_length_bytes: u32,
+        access_link_bandwidth_bps: u64,
+        per_link_propagation_delay: Nanos,
+        mtu: u32,
+    ) -> Topology<S> {
+        leaf_spine(
+            num_hosts,
+            Fabric{
+                hosts_per_leaf: DEFAULT_HOSTS_PER_LEAF,
+                num_spines: DEFAULT_NUM_SPINES,
+                ecmp: false,
+            },
+            queue_length_bytes,
+            access_link_bandwidth_bps,
+            per_link_propagation_delay,
+            mtu,
+        )
+    }
+}
+
+struct Fabric {
+    hosts_per_leaf: u32,
+    num_spines: u32,
+    /// Route between leaves over every spine rather than spine `dest % num_spines`.
+    ecmp: bool,
+}
+
+fn leaf_spine<S: Switch>(
+    num_hosts: u32,
+    fabric: Fabric,
+    queue_length_bytes: u32,
+    link_bandwidth_bps: u64,
+    per_link_propagation_delay: Nanos,
+    mtu: u32,
+) -> Topology<S> {
+    let Fabric{hosts_per_leaf, num_spines, ecmp} = fabric;
+    assert!(hosts_per_leaf > 0 && num_spines > 0);
+    let num_leaves = (num_hosts + hosts_per_leaf - 1) / hosts_per_leaf;
+    let leaf_id = |host: u32| num_hosts + host / hosts_per_leaf;
+    let spine_id = |spine: u32| num_hosts + num_leaves + spine;
+    let link = |from, to| Link{
+        propagation_delay: per_link_propagation_delay,
+        bandwidth_bps: link_bandwidth_bps,
+        mtu,
+        pfc_enabled: S::pfc_enabled(),
+        cut_through_threshold: None,
+        from,
+        to,
+    };
+    let queue = |from, to| Box::new(DropTailQueue::new(queue_length_bytes, link(from, to))) as Box<Queue>;
+
+    let hosts = (0..num_hosts).map(|id| {
+        Host{
+            id,
+            active: true,
+            link: link(id, leaf_id(id)),
+            ..Default::default()
+        }
+    }).collect();
+
+    let mut switches = (0..num_leaves).map(|leaf| {
+        let id = num_hosts + leaf;
+        let first_host = leaf * hosts_per_leaf;
+        let last_host = min(first_host + hosts_per_leaf, num_hosts);
+        let mut s = S::new(
+            id,
+            (first_host..last_host)
+                .map(|h| queue(id, h))
+                .chain((0..num_spines).map(|spine| queue(id, spine_id(spine)))),
+        );
+
+        // hosts under other leaves are reached via the spine for their id, or any spine
+        (0..num_hosts)
+            .filter(|&h| leaf_id(h) != id)
+            .for_each(|h| if ecmp {
+                (0..num_spines).for_each(|spine| s.add_route(h, spine_id(spine)));
+            } else {
+                s.add_route(h, spine_id(h % num_spines));
+            });
+        s
+    }).collect::<Vec<S>>();
+
+    switches.extend((0..num_spines).map(|spine| {
+        let id = spine_id(spine);
+        let mut s = S::new(id, (0..num_leaves).map(|leaf| queue(id, num_hosts + leaf)));
+        (0..num_hosts).for_each(|h| s.add_route(h, leaf_id(h)));
+        s
+    }));
+
+    Topology{
+        hosts,
+        switches,
+    }
+}
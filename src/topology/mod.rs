@@ -1,10 +1,11 @@
 use itertools::Itertools;
 use itertools::EitherOrBoth::{Left, Right, Both};
 
-use super::{Nanos, Result};
-use super::node::{Node, Host};
+use super::{Nanos, Result, DEFAULT_MTU};
+use super::node::{Node, Host, Link};
 use super::node::switch::Switch;
 use super::flow::Flow;
+use super::packet::{PacketHeader, PACKET_HEADER_SIZE_BYTES};
 
 pub trait TopologyStrategy<S: Switch> {
     fn make_topology(
@@ -12,12 +13,34 @@ pub trait TopologyStrategy<S: Switch> {
         queue_length_bytes: u32,
         access_link_bandwidth_bps: u64, 
         per_link_propagation_delay: Nanos,
+    ) -> Topology<S> {
+        Self::make_topology_with_mtu(
+            num_hosts,
+            queue_length_bytes,
+            access_link_bandwidth_bps,
+            per_link_propagation_delay,
+            DEFAULT_MTU,
+        )
+    }
+
+    /// Like `make_topology`, but every link carries frames of at most `mtu` bytes.
+    /// Flows which do not set `max_packet_length` inherit their packet size from this MTU.
+    fn make_topology_with_mtu(
+        num_hosts: u32, 
+        queue_length_bytes: u32,
+        access_link_bandwidth_bps: u64, 
+        per_link_propagation_delay: Nanos,
+        mtu: u32,
     ) -> Topology<S>;
 }
 
 pub mod one_big_switch;
+pub mod pod;
+pub mod leaf_spine;
+pub mod fat_tree;
+pub mod dumbbell;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Topology<S: Switch> {
     pub hosts: Vec<Host>,
     pub switches: Vec<S>,
@@ -36,6 +59,48 @@ impl<S: Switch> Topology<S> {
             .flat_map(|h| h.active_flows.iter())
     }
 
+    /// The links a packet from host `from` to host `to` traverses, in order.
+    /// With equal-cost paths between them this is flow 0's; see `flow_path`.
+    pub fn path(&self, from: u32, to: u32) -> Result<Vec<Link>> {
+        self.flow_path(0, from, to)
+    }
+
+    /// The links packets of flow `flow` from host `from` to host `to` traverse, in order.
+    pub fn flow_path(&self, flow: u32, from: u32, to: u32) -> Result<Vec<Link>> {
+        if (to as usize) >= self.hosts.len() {
+            bail!("Invalid host id: {:?}", to)
+        }
+
+        let mut path = vec![self.hosts.get(from as usize)
+            .ok_or_else(|| format_err!("Invalid host id: {:?}", from))?
+            .link];
+        while path[path.len() - 1].to != to {
+            if path.len() > self.switches.len() + 1 {
+                bail!("Routing loop from {:?} to {:?}", from, to)
+            }
+
+            let at = path[path.len() - 1].to;
+            let link = (at as usize).checked_sub(self.hosts.len())
+                .and_then(|idx| self.switches.get(idx))
+                .and_then(|s| s.egress_link(PacketHeader{flow, from, to, priority: 0, size_bytes: PACKET_HEADER_SIZE_BYTES}))
+                .ok_or_else(|| format_err!("No route from node {:?} to {:?}", at, to))?;
+            path.push(link);
+        }
+
+        Ok(path)
+    }
+
+    /// Make switch `switch_id` cut through packets of at least `threshold` bytes arriving from
+    /// its hosts and neighboring switches, and store-and-forward smaller ones. `None` restores
+    /// store-and-forward.
+    pub fn set_cut_through_threshold(&mut self, switch_id: u32, threshold: Option<u32>) {
+        self.hosts.iter_mut()
+            .filter(|h| h.link.to == switch_id)
+            .for_each(|h| h.link.cut_through_threshold = threshold);
+        self.switches.iter_mut()
+            .for_each(|s| s.set_cut_through_threshold(switch_id, threshold));
+    }
+
     pub fn lookup_host(&mut self, id: u32) -> Result<&mut Host> {
         if (id as usize) < self.hosts.len() {
             Ok(&mut self.hosts[id as usize])
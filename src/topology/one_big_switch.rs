@@ -1,17 +1,19 @@
-use std::collections::VecDeque;
 use std::marker::PhantomData;
 use Nanos;
+use DEFAULT_MTU;
 use node::{Host, Link};
 use node::switch::{Switch, PFCSwitchFamily, Queue};
 use node::switch::drop_tail_queue::DropTailQueue;
 
 use super::{Topology, TopologyStrategy};
+use super::pod::PfcLinks;
 
 fn switch_links(
     num_hosts: u32,
     queue_length_bytes: u32,
     access_link_bandwidth: u64,
     per_link_propagation_delay: Nanos,
+    mtu: u32,
     pfc_enabled: bool,
 ) -> impl Iterator<Item=Box<Queue + 'static>> {
     (0..num_hosts).map(move |id| {
@@ -20,7 +22,9 @@ fn switch_links(
             Link{
                 propagation_delay: per_link_propagation_delay,
                 bandwidth_bps: access_link_bandwidth,
+                mtu,
                 pfc_enabled,
+                cut_through_threshold: None,
                 from: num_hosts,
                 to: id,
             },
@@ -32,22 +36,23 @@ fn hosts(
     num_hosts: u32,
     access_link_bandwidth: u64,
     per_link_propagation_delay: Nanos,
+    mtu: u32,
     pfc_enabled: bool,
 ) -> impl Iterator<Item=Host> {
     (0..num_hosts).map(move |id| {
         Host{
             id,
             active: true,
-            paused: false,
             link: Link{
                 propagation_delay: per_link_propagation_delay,
                 bandwidth_bps: access_link_bandwidth,
+                mtu,
                 pfc_enabled,
+                cut_through_threshold: None,
                 from: id,
                 to: num_hosts,
             },
-            to_send: VecDeque::new(),
-            active_flows: vec![],
+            ..Default::default()
         }
     })
 }
@@ -56,6 +61,7 @@ fn topology<S: Switch>(
     num_hosts: u32,
     access_link_bandwidth: u64,
     per_link_propagation_delay: Nanos,
+    mtu: u32,
     pfc_enabled: bool,
     big_switch: S,
 ) -> Topology<S> {
@@ -64,6 +70,7 @@ fn topology<S: Switch>(
             num_hosts, 
             access_link_bandwidth,
             per_link_propagation_delay,
+            mtu,
             pfc_enabled,
         ).collect(),
         switches: vec![big_switch],
@@ -72,12 +79,58 @@ fn topology<S: Switch>(
 
 pub struct OneBigSwitch<S: Switch>(PhantomData<S>);
 
+/// The bandwidth and propagation delay of one host's access link, in both directions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinkSpec {
+    pub bandwidth_bps: u64,
+    pub propagation_delay: Nanos,
+}
+
+impl<S: Switch> OneBigSwitch<S> {
+    /// Like `make_topology`, but host `i`'s access link, and the switch's link back to it, follow
+    /// `specs[i]`, e.g. to put a straggler on a slow link.
+    pub fn make_topology_with_links(specs: &[LinkSpec], queue_length_bytes: u32) -> Topology<S> {
+        let num_hosts = specs.len() as u32;
+        let link = |spec: &LinkSpec, from, to| Link{
+            propagation_delay: spec.propagation_delay,
+            bandwidth_bps: spec.bandwidth_bps,
+            mtu: DEFAULT_MTU,
+            pfc_enabled: S::pfc_enabled(),
+            cut_through_threshold: None,
+            from,
+            to,
+        };
+
+        let big_switch = S::new(
+            num_hosts,
+            specs.iter().zip(0..).map(|(spec, id)| {
+                Box::new(DropTailQueue::new(queue_length_bytes, link(spec, num_hosts, id))) as Box<Queue>
+            }),
+        );
+
+        let hosts = specs.iter().zip(0..).map(|(spec, id)| {
+            Host{
+                id,
+                active: true,
+                link: link(spec, id, num_hosts),
+                ..Default::default()
+            }
+        }).collect();
+
+        Topology{
+            hosts,
+            switches: vec![big_switch],
+        }
+    }
+}
+
 impl<S: Switch> TopologyStrategy<S> for OneBigSwitch<S> {
-    default fn make_topology(
+    default fn make_topology_with_mtu(
         num_hosts: u32,
         queue_length_bytes: u32,
         access_link_bandwidth: u64,
         per_link_propagation_delay: Nanos,
+        mtu: u32,
     ) -> Topology<S> {
         let big_switch = S::new(
             num_hosts, 
@@ -86,6 +139,7 @@ impl<S: Switch> TopologyStrategy<S> for OneBigSwitch<S> {
                 queue_length_bytes,
                 access_link_bandwidth,
                 per_link_propagation_delay,
+                mtu,
                 false,
             ),
         );
@@ -94,6 +148,7 @@ impl<S: Switch> TopologyStrategy<S> for OneBigSwitch<S> {
             num_hosts, 
             access_link_bandwidth,
             per_link_propagation_delay,
+            mtu,
             false,
             big_switch,
         )
@@ -101,11 +156,12 @@ impl<S: Switch> TopologyStrategy<S> for OneBigSwitch<S> {
 }
 
 impl<S: PFCSwitchFamily> TopologyStrategy<S> for OneBigSwitch<S> {
-    fn make_topology(
+    fn make_topology_with_mtu(
         num_hosts: u32,
         queue_length_bytes: u32,
         access_link_bandwidth: u64,
         per_link_propagation_delay: Nanos,
+        mtu: u32,
     ) -> Topology<S> {
         let big_switch = S::new(
             num_hosts, 
@@ -114,6 +170,7 @@ impl<S: PFCSwitchFamily> TopologyStrategy<S> for OneBigSwitch<S> {
                 queue_length_bytes,
                 access_link_bandwidth,
                 per_link_propagation_delay,
+                mtu,
                 true,
             ),
         );
@@ -122,6 +179,7 @@ impl<S: PFCSwitchFamily> TopologyStrategy<S> for OneBigSwitch<S> {
             num_hosts, 
             access_link_bandwidth,
             per_link_propagation_delay,
+            mtu,
             true,
             big_switch,
         )
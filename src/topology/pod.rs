@@ -0,0 +1,88 @@
+use std::marker::PhantomData;
+use Nanos;
+use DEFAULT_MTU;
+use node::{Host, Link};
+use node::switch::{Switch, PFCSwitchFamily, Queue};
+use node::switch::drop_tail_queue::DropTailQueue;
+
+use super::Topology;
+
+/// Whether a switch type wants `pfc_enabled` links (see `PFCSwitchFamily`).
+pub(super) trait PfcLinks {
+    fn pfc_enabled() -> bool;
+}
+
+impl<S: Switch> PfcLinks for S {
+    default fn pfc_enabled() -> bool { false }
+}
+
+impl<S: PFCSwitchFamily> PfcLinks for S {
+    fn pfc_enabled() -> bool { true }
+}
+
+/// A two-tier datacenter pod: `num_racks` racks of `hosts_per_rack` hosts, each rack under a
+/// top-of-rack (leaf) switch, and every leaf connected to a single spine switch.
+///
+/// Ids are assigned as `lookup_node` expects:
+/// hosts are `0..num_hosts` (rack by rack), then the leaves in rack order, then the spine.
+/// Every link has the same bandwidth and propagation delay.
+pub struct Pod<S: Switch>(PhantomData<S>);
+
+impl<S: Switch> Pod<S> {
+    pub fn make_pod(
+        hosts_per_rack: u32,
+        num_racks: u32,
+        queue_length_bytes: u32,
+        link_bandwidth_bps: u64,
+        per_link_propagation_delay: Nanos,
+    ) -> Topology<S> {
+        let num_hosts = hosts_per_rack * num_racks;
+        let leaf_id = |rack: u32| num_hosts + rack;
+        let spine_id = num_hosts + num_racks;
+        let link = |from, to| Link{
+            propagation_delay: per_link_propagation_delay,
+            bandwidth_bps: link_bandwidth_bps,
+            mtu: DEFAULT_MTU,
+            pfc_enabled: S::pfc_enabled(),
+            cut_through_threshold: None,
+            from,
+            to,
+        };
+        let queue = |from, to| Box::new(DropTailQueue::new(queue_length_bytes, link(from, to))) as Box<Queue>;
+
+        let hosts = (0..num_hosts).map(|id| {
+            Host{
+                id,
+                active: true,
+                link: link(id, leaf_id(id / hosts_per_rack)),
+                ..Default::default()
+            }
+        }).collect();
+
+        let mut switches = (0..num_racks).map(|rack| {
+            let leaf = leaf_id(rack);
+            let first_host = rack * hosts_per_rack;
+            let mut s = S::new(
+                leaf,
+                (first_host..first_host + hosts_per_rack)
+                    .map(|h| queue(leaf, h))
+                    .chain(Some(queue(leaf, spine_id))),
+            );
+
+            // hosts in other racks are reached via the spine
+            (0..num_hosts)
+                .filter(|h| h / hosts_per_rack != rack)
+                .for_each(|h| s.add_route(h, spine_id));
+            s
+        }).collect::<Vec<S>>();
+
+        let mut spine = S::new(spine_id, (0..num_racks).map(|rack| queue(spine_id, leaf_id(rack))));
+        (0..num_hosts).for_each(|h| spine.add_route(h, leaf_id(h / hosts_per_rack)));
+        switches.push(spine);
+
+        Topology{
+            hosts,
+            switches,
+        }
+    }
+}